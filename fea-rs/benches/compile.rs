@@ -0,0 +1,71 @@
+//! A benchmark for compiling files that make heavy use of glyph classes
+
+use std::{ffi::OsStr, fmt::Write, sync::Arc};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use fea_rs::{parse::SourceLoadError, Compiler, GlyphIdent, GlyphMap};
+
+const N_GLYPHS: usize = 1000;
+const N_REFERENCES: usize = 100;
+
+/// A file defining a class of every glyph, which is referenced by many rules.
+fn make_class_heavy_fea() -> String {
+    let mut fea = format!("@BIG = [g000 - g{:03}];\n", N_GLYPHS - 1);
+    fea.push_str("feature test {\n");
+    for i in 0..N_REFERENCES {
+        writeln!(fea, "    lookup l{i} {{ pos [@BIG] g{i:03} -{i}; }} l{i};").unwrap();
+    }
+    fea.push_str("} test;\n");
+    fea
+}
+
+/// A file defining a large mark class, which is used by many attachment rules
+/// and referenced as a glyph class.
+fn make_mark_class_heavy_fea() -> String {
+    let n_marks = N_GLYPHS / 2;
+    let mut fea = String::new();
+    for i in 0..n_marks {
+        writeln!(fea, "markClass g{i:03} <anchor 0 {i}> @MARKS;").unwrap();
+    }
+    fea.push_str("feature test {\n");
+    for i in 0..N_REFERENCES {
+        let base = n_marks + i;
+        writeln!(
+            fea,
+            "    lookup m{i} {{ pos base g{base:03} <anchor {i} 0> mark @MARKS; }} m{i};"
+        )
+        .unwrap();
+        writeln!(
+            fea,
+            "    lookup p{i} {{ pos [@MARKS] g{base:03} -{i}; }} p{i};"
+        )
+        .unwrap();
+    }
+    fea.push_str("} test;\n");
+    fea
+}
+
+fn compile_source(source: Arc<str>, glyph_map: &GlyphMap) -> fea_rs::compile::Compilation {
+    let resolver = move |_: &OsStr| -> Result<Arc<str>, SourceLoadError> { Ok(source.clone()) };
+    Compiler::new("bench.fea", glyph_map)
+        .with_resolver(resolver)
+        .compile()
+        .unwrap()
+}
+
+fn compiling(c: &mut Criterion) {
+    let glyph_map = (0..N_GLYPHS)
+        .map(|i| GlyphIdent::from(format!("g{i:03}")))
+        .collect::<GlyphMap>();
+    let fea: Arc<str> = make_class_heavy_fea().into();
+    c.bench_function("compile class references", |b| {
+        b.iter(|| compile_source(black_box(fea.clone()), &glyph_map))
+    });
+    let fea: Arc<str> = make_mark_class_heavy_fea().into();
+    c.bench_function("compile mark class references", |b| {
+        b.iter(|| compile_source(black_box(fea.clone()), &glyph_map))
+    });
+}
+
+criterion_group!(benches, compiling);
+criterion_main!(benches);
@@ -7,7 +7,7 @@ use fea_rs::{
     compile::{
         self,
         error::{FontGlyphOrderError, GlyphOrderError, UfoGlyphOrderError},
-        Compiler, Opts,
+        Compiler, Opts, PostFormat,
     },
     GlyphMap,
 };
@@ -24,16 +24,20 @@ fn main() -> Result<(), Error> {
     if !fea.exists() {
         return Err(Error::EmptyFeatureFile);
     }
+    let post = if args.post {
+        PostFormat::Format2
+    } else {
+        PostFormat::None
+    };
     let compiled = Compiler::new(fea, &glyph_names)
-        .with_opts(Opts::new().make_post_table(args.post))
+        .with_opts(Opts::new().make_post_table(post))
         .compile()?;
 
     let path = args.out_path();
-    let opts = Opts::new().make_post_table(args.post);
+    let opts = Opts::new().make_post_table(post);
     let raw_font = compiled
-        .assemble(&glyph_names, opts)
-        .expect("ttf compile failed")
-        .build();
+        .build_binary(&glyph_names, opts)
+        .expect("ttf compile failed");
 
     log::info!("writing {} bytes to {}", raw_font.len(), path.display());
     std::fs::write(path, raw_font).map_err(Into::into)
@@ -43,8 +47,6 @@ fn main() -> Result<(), Error> {
 enum Error {
     #[error("io error: '{0}'")]
     File(#[from] std::io::Error),
-    #[error("Couldn't read UFO: '{0}'")]
-    Ufo(Box<norad::error::FontLoadError>),
     #[error("invalid glyph map: '{0}'")]
     InvalidGlyphMap(#[from] GlyphOrderError),
     #[error("Couldn't get glyph order from UFO: '{0}'")]
@@ -97,9 +99,7 @@ struct Args {
 impl Args {
     pub fn get_inputs(&self) -> Result<(PathBuf, GlyphMap), Error> {
         if self.input.extension() == Some("ufo".as_ref()) {
-            let request = norad::DataRequest::none().lib(true);
-            let font = norad::Font::load_requested_data(&self.input, request)?;
-            let glyph_order = compile::get_ufo_glyph_order(&font)?;
+            let glyph_order = GlyphMap::from_ufo(&self.input)?;
             let fea_path = self.input.join("features.fea");
             Ok((fea_path, glyph_order))
         } else {
@@ -108,7 +108,7 @@ impl Args {
                 compile::parse_glyph_order(&contents)?
             } else if let Some(path) = self.font.as_deref() {
                 let bytes = std::fs::read(path)?;
-                compile::get_post_glyph_order(&bytes)?
+                GlyphMap::from_font_bytes(&bytes)?
             } else {
                 return Err(Error::MissingGlyphOrder);
             };
@@ -126,9 +126,3 @@ impl Args {
             .unwrap_or_else(|| Path::new("compile-out.ttf"))
     }
 }
-
-impl From<norad::error::FontLoadError> for Error {
-    fn from(src: norad::error::FontLoadError) -> Error {
-        Error::Ufo(Box::new(src))
-    }
-}
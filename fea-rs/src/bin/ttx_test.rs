@@ -1,7 +1,10 @@
 //! Run the compiler against a bunch of inputs, comparing them with
 //! the results of fonttools.
 
-use std::path::{Path, PathBuf};
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use clap::Parser;
 use fea_rs::util::ttx;
@@ -12,24 +15,25 @@ static WIP_DIFF_DIR: &str = "./wip";
 fn main() {
     let args = Args::parse();
 
-    let results = ttx::run_all_tests(TEST_DATA, args.test_filter.as_ref());
+    let tables = match args.tables.as_ref() {
+        Some(tables) => tables.split(',').map(str::trim).collect(),
+        None => ttx::DEFAULT_TABLES.to_vec(),
+    };
+    let options = ttx::TestOptions {
+        keep_temp_files: args.keep_temp_files,
+        timeout: args.timeout.map(Duration::from_secs),
+    };
+    let results = ttx::run_all_tests(TEST_DATA, args.test_filter.as_ref(), &tables, options);
 
-    if let Some(to_compare) = args
-        .compare
-        .as_ref()
-        .map(std::fs::read)
-        .transpose()
-        .unwrap()
-    {
-        let old_result: ttx::Report = serde_json::from_slice(&to_compare).unwrap();
+    if let Some(to_compare) = args.compare.as_ref() {
+        let old_result = ttx::Report::from_json(to_compare).unwrap();
         eprintln!("{:?}", results.compare_printer(&old_result));
     } else {
         eprintln!("{results:?}",);
     }
 
     if let Some(path) = args.save {
-        let serialized = serde_json::to_vec(&results).unwrap();
-        std::fs::write(path, serialized).unwrap();
+        results.write_json(path).unwrap();
     }
 
     if args.write_diff {
@@ -70,6 +74,17 @@ struct Args {
     /// e.g.: -t "spec5,GPOS" matches spec5h1.fea, spec5fi2.fea, GPOS_2.fea, etc
     #[arg(short, long = "test")]
     test_filter: Option<String>,
+    /// Optional comma separated list of the tables to compare.
+    ///
+    /// e.g.: --tables "GSUB,GPOS,vmtx". Defaults to `ttx::DEFAULT_TABLES`.
+    #[arg(long)]
+    tables: Option<String>,
+    /// Keep the generated fonts and ttx files, and print their paths on failure
+    #[arg(short, long)]
+    keep_temp_files: bool,
+    /// Report tests that take longer than this many seconds as timeouts
+    #[arg(long)]
+    timeout: Option<u64>,
     /// Write diffs to a ./wip directory
     #[arg(short, long)]
     write_diff: bool,
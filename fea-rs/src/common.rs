@@ -5,11 +5,14 @@ use std::fmt::{Display, Formatter};
 use smol_str::SmolStr;
 pub use write_fonts::types::GlyphId;
 
+mod cmap;
 mod glyph_class;
 mod glyph_map;
 
+pub(crate) use cmap::read_cmap;
 pub use glyph_class::GlyphClass;
-pub use glyph_map::GlyphMap;
+pub(crate) use glyph_map::{glyph_order_from_post, glyph_order_from_ufo};
+pub use glyph_map::{FontGlyphOrderError, GlyphMap, UfoGlyphOrderError};
 
 /// A glyph name
 pub type GlyphName = SmolStr;
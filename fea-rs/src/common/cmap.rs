@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use write_fonts::read::{
+    tables::cmap::{Cmap, Cmap12, Cmap4, CmapSubtable, EncodingRecord, PlatformId},
+    ReadError,
+};
+
+use super::GlyphId;
+
+/// The largest valid unicode codepoint.
+const MAX_CODEPOINT: u32 = 0x10FFFF;
+
+/// Collect the mappings from all unicode subtables in a cmap table.
+///
+/// If a codepoint is mapped by more than one subtable, the first mapping wins.
+pub(crate) fn read_cmap(cmap: &Cmap) -> Result<BTreeMap<u32, GlyphId>, ReadError> {
+    let mut result = BTreeMap::new();
+    for record in cmap.encoding_records().iter().filter(|x| is_unicode(x)) {
+        match record.subtable(cmap.offset_data())? {
+            CmapSubtable::Format4(subtable) => read_cmap4(&subtable, &mut result),
+            CmapSubtable::Format12(subtable) => read_cmap12(&subtable, &mut result),
+            _ => (),
+        }
+    }
+    Ok(result)
+}
+
+fn is_unicode(record: &EncodingRecord) -> bool {
+    match record.platform_id() {
+        PlatformId::Unicode => true,
+        PlatformId::Windows => matches!(record.encoding_id(), 1 | 10),
+        _ => false,
+    }
+}
+
+fn read_cmap4(subtable: &Cmap4, out: &mut BTreeMap<u32, GlyphId>) {
+    let seg_count = subtable.end_code().len();
+    let glyph_ids = subtable.glyph_id_array();
+    let segments = subtable
+        .start_code()
+        .iter()
+        .zip(subtable.end_code())
+        .zip(subtable.id_delta())
+        .zip(subtable.id_range_offsets());
+    for (i, (((start, end), delta), range_offset)) in segments.enumerate() {
+        let start = start.get();
+        let delta = delta.get() as u16;
+        let range_offset = range_offset.get() as usize;
+        // 0xFFFF is used to terminate the segment list
+        for codepoint in start..=end.get().min(0xFFFE) {
+            let gid = if range_offset == 0 {
+                codepoint.wrapping_add(delta)
+            } else {
+                // the offset is from this segment's entry in idRangeOffset,
+                // which is followed by the glyph id array
+                let idx = range_offset / 2 + (codepoint - start) as usize;
+                let idx = idx.checked_sub(seg_count - i);
+                match idx.and_then(|idx| glyph_ids.get(idx)).map(|gid| gid.get()) {
+                    None | Some(0) => 0,
+                    Some(gid) => gid.wrapping_add(delta),
+                }
+            };
+            if gid != 0 {
+                out.entry(codepoint as u32).or_insert(GlyphId::new(gid));
+            }
+        }
+    }
+}
+
+fn read_cmap12(subtable: &Cmap12, out: &mut BTreeMap<u32, GlyphId>) {
+    for group in subtable.groups() {
+        let start = group.start_char_code();
+        let start_gid = group.start_glyph_id();
+        for codepoint in start..=group.end_char_code().min(MAX_CODEPOINT) {
+            // glyph ids increase through the group, so once one is out of
+            // range, so are the rest
+            let gid = (codepoint - start).checked_add(start_gid);
+            let Some(gid) = gid.and_then(|gid| u16::try_from(gid).ok()) else {
+                break;
+            };
+            out.entry(codepoint).or_insert(GlyphId::new(gid));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::read::{FontData, FontRead};
+
+    use super::*;
+
+    #[test]
+    fn cmap_format_4() {
+        // two segments: 'A'..='C' by delta, and the required 0xFFFF segment
+        #[rustfmt::skip]
+        let subtable: &[u16] = &[
+            4, 32, 0, // format, length, language
+            4, 4, 1, 0, // segCountX2, searchRange, entrySelector, rangeShift
+            0x43, 0xFFFF, // endCode
+            0, // reservedPad
+            0x41, 0xFFFF, // startCode
+            (10u16).wrapping_sub(0x41), 1, // idDelta
+            0, 0, // idRangeOffset
+        ];
+        let mut data = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12];
+        data.extend(subtable.iter().flat_map(|x| x.to_be_bytes()));
+
+        let cmap = read_cmap(&Cmap::read(FontData::new(&data)).unwrap()).unwrap();
+        assert_eq!(cmap.len(), 3);
+        assert_eq!(cmap.get(&0x41), Some(&GlyphId::new(10)));
+        assert_eq!(cmap.get(&0x43), Some(&GlyphId::new(12)));
+    }
+
+    #[test]
+    fn cmap_format_12() {
+        let mut data = vec![0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12];
+        // format, reserved, length, language, numGroups
+        data.extend([0, 12, 0, 0, 0, 0, 0, 28, 0, 0, 0, 0, 0, 0, 0, 1]);
+        // a single group
+        let group: &[u32] = &[0x1F600, 0x1F602, 7];
+        data.extend(group.iter().flat_map(|x| x.to_be_bytes()));
+
+        let cmap = read_cmap(&Cmap::read(FontData::new(&data)).unwrap()).unwrap();
+        assert_eq!(cmap.len(), 3);
+        assert_eq!(cmap.get(&0x1F601), Some(&GlyphId::new(8)));
+    }
+
+    #[test]
+    fn cmap_format_12_glyph_ids_out_of_range() {
+        let mut data = vec![0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12];
+        // format, reserved, length, language, numGroups
+        data.extend([0, 12, 0, 0, 0, 0, 0, 52, 0, 0, 0, 0, 0, 0, 0, 3]);
+        // a group that runs past the largest glyph id, one that is entirely
+        // past it, and one that ends past the largest codepoint
+        #[rustfmt::skip]
+        let groups: &[u32] = &[
+            0x100, 0x1FF, 0xFFFE,
+            0x200, 0x2FF, 0x10000,
+            0x10FFFF, 0xFFFFFFFF, 3,
+        ];
+        data.extend(groups.iter().flat_map(|x| x.to_be_bytes()));
+
+        let cmap = read_cmap(&Cmap::read(FontData::new(&data)).unwrap()).unwrap();
+        assert_eq!(cmap.len(), 3);
+        assert_eq!(cmap.get(&0x101), Some(&GlyphId::new(0xFFFF)));
+        assert_eq!(cmap.get(&0x10FFFF), Some(&GlyphId::new(3)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::rc::Rc;
+use std::sync::Arc;
 
 use write_fonts::types::GlyphId;
 
@@ -10,7 +10,7 @@ use super::GlyphOrClass;
 /// that a glyph class is sorted and deduplicated, and in other places it expects
 /// a glyph class to be an arbitrary sequence of glyphs.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct GlyphClass(Rc<[GlyphId]>);
+pub struct GlyphClass(Arc<[GlyphId]>);
 
 impl std::iter::FromIterator<GlyphId> for GlyphClass {
     fn from_iter<T: IntoIterator<Item = GlyphId>>(iter: T) -> Self {
@@ -34,7 +34,7 @@ impl GlyphClass {
     }
 
     pub fn empty() -> Self {
-        Self(Rc::new([]))
+        Self(Arc::new([]))
     }
 
     pub fn sort_and_dedupe(&self) -> GlyphClass {
@@ -1,11 +1,18 @@
-use write_fonts::tables::post::Post;
+use write_fonts::{
+    from_obj::ToOwnedTable,
+    read::{tables::post::DEFAULT_GLYPH_NAMES, FontRef, ReadError, TableProvider},
+    tables::post::Post,
+};
 
-use super::{GlyphId, GlyphIdent, GlyphName};
+use super::{read_cmap, GlyphId, GlyphIdent, GlyphName};
 use std::{
     borrow::Cow,
     collections::{BTreeMap, HashMap},
     convert::TryInto,
     iter::FromIterator,
+    ops::RangeInclusive,
+    path::Path,
+    sync::Arc,
 };
 
 /// A glyph map for mapping from raw glyph identifiers to numeral `GlyphId`s.
@@ -13,8 +20,9 @@ use std::{
 /// This is used to map from names or CIDS encountered in a FEA file to the actual
 /// GlyphIds that will be used in the final font.
 ///
-/// Currently, the only way to construct this type is by calling `collect()`
-/// on an iterator of cids or names.
+/// This can be constructed by calling `collect()` on an iterator of cids or
+/// names, or read from an existing font with [`GlyphMap::from_font_bytes`]
+/// or [`GlyphMap::from_ufo`].
 #[derive(Clone, Debug, Default)]
 pub struct GlyphMap {
     names: HashMap<GlyphName, GlyphId>,
@@ -32,6 +40,18 @@ impl GlyphMap {
         self.names.is_empty() && self.cids.is_empty()
     }
 
+    /// Returns `true` if this map contains glyphs identified by CID
+    pub(crate) fn is_cid_keyed(&self) -> bool {
+        !self.cids.is_empty()
+    }
+
+    /// The lowest and highest CIDs in this map, if it is CID-keyed.
+    pub(crate) fn cid_range(&self) -> Option<RangeInclusive<u16>> {
+        let min = self.cids.keys().min()?;
+        let max = self.cids.keys().max()?;
+        Some(*min..=*max)
+    }
+
     /// Generates a reverse map of ids -> raw identifers (names or CIDs)
     //  maybe just for testing?
     pub fn reverse_map(&self) -> BTreeMap<GlyphId, GlyphIdent> {
@@ -68,6 +88,21 @@ impl GlyphMap {
         }
     }
 
+    /// The glyph name most similar to `name`, if one is close enough to be a
+    /// plausible typo.
+    ///
+    /// This is used to suggest a fix when a glyph name is not found.
+    pub(crate) fn closest_name(&self, name: &str) -> Option<&GlyphName> {
+        let max_distance = (name.chars().count() / 3).max(1);
+        self.names
+            .keys()
+            .map(|candidate| (edit_distance(name, candidate), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            // ties are broken by name, so the result is deterministic
+            .min()
+            .map(|(_, candidate)| candidate)
+    }
+
     /// Generate a post table from this glyph map
     pub fn make_post_table(&self) -> Post {
         let reverse = self.reverse_map();
@@ -81,6 +116,151 @@ impl GlyphMap {
 
         Post::new_v2(rev_vec.iter().map(Cow::as_ref))
     }
+
+    /// Read the glyph order from a compiled TTF or OTF font.
+    ///
+    /// Glyph names are taken from the `post` table. If the font has no glyph
+    /// names (for instance if it has a version 3.0 `post` table) they are
+    /// generated from the `maxp` and `cmap` tables, following the fontTools
+    /// convention: glyph 0 is `.notdef`, encoded glyphs are named `uniXXXX`
+    /// (or `uXXXXX` outside of the BMP), and the rest are `glyphXXXXX`.
+    pub fn from_font_bytes(data: &[u8]) -> Result<GlyphMap, FontGlyphOrderError> {
+        let font = FontRef::new(data)?;
+        match font.post() {
+            Ok(post) => {
+                if let Some(map) = glyph_order_from_post(&post.to_owned_table()) {
+                    return Ok(map);
+                }
+            }
+            Err(ReadError::TableIsMissing(_)) => (),
+            Err(e) => return Err(e.into()),
+        }
+        let num_glyphs = font.maxp()?.num_glyphs();
+        let cmap = match font.cmap() {
+            Ok(cmap) => read_cmap(&cmap)?,
+            Err(ReadError::TableIsMissing(_)) => Default::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(synthesize_glyph_names(num_glyphs, &cmap))
+    }
+
+    /// Read the glyph order from the `public.glyphOrder` key of a UFO.
+    ///
+    /// `path` may be the `.ufo` directory, or the `lib.plist` file inside it.
+    /// Only the UFO's lib is loaded.
+    pub fn from_ufo(path: &Path) -> Result<GlyphMap, UfoGlyphOrderError> {
+        let path = match path.file_name() {
+            Some(name) if name == "lib.plist" => path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new(".")),
+            _ => path,
+        };
+        let request = norad::DataRequest::none().lib(true);
+        let font = norad::Font::load_requested_data(path, request)
+            .map_err(|e| UfoGlyphOrderError::Load(Arc::new(e)))?;
+        glyph_order_from_ufo(&font)
+    }
+}
+
+/// An error that occurs when extracting a glyph order from a UFO.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum UfoGlyphOrderError {
+    /// Missing 'public.glyphOrder' key
+    #[error("No public.glyphOrder key in lib.plist")]
+    KeyNotSet,
+    /// Glyph order is present, but malformed
+    #[error("public.glyphOrder exists, but is not an array of strings")]
+    Malformed,
+    /// The UFO could not be loaded
+    #[error("Failed to load UFO: '{0}'")]
+    Load(#[source] Arc<norad::error::FontLoadError>),
+}
+
+/// An error that occurs when extracting a glyph order from a font file.
+#[derive(Clone, Debug, thiserror::Error)]
+pub enum FontGlyphOrderError {
+    /// Failed to read font data
+    #[error("Failed to read font data: '{0}'")]
+    ReadError(
+        #[from]
+        #[source]
+        ReadError,
+    ),
+    /// Post table is missing glyph names
+    #[error("The post table exists, but did not include all glyph names")]
+    MissingNames,
+}
+
+static GLYPH_ORDER_KEY: &str = "public.glyphOrder";
+
+/// The glyph order in the `public.glyphOrder` key of a UFO's lib.
+pub(crate) fn glyph_order_from_ufo(font: &norad::Font) -> Result<GlyphMap, UfoGlyphOrderError> {
+    font.lib
+        .get(GLYPH_ORDER_KEY)
+        .ok_or(UfoGlyphOrderError::KeyNotSet)?
+        .as_array()
+        .and_then(|name_array| {
+            name_array
+                .iter()
+                .map(|val| val.as_string().map(GlyphName::new))
+                .collect()
+        })
+        .ok_or(UfoGlyphOrderError::Malformed)
+}
+
+/// The glyph names in a `post` table, if it has a name for every glyph.
+pub(crate) fn glyph_order_from_post(post: &Post) -> Option<GlyphMap> {
+    post.glyph_name_index.as_ref().and_then(|items| {
+        items
+            .iter()
+            .map(|name_idx| match *name_idx {
+                i @ 0..=257 => Some(GlyphName::new(DEFAULT_GLYPH_NAMES[i as usize])),
+                i => post
+                    .string_data
+                    .as_ref()
+                    .unwrap()
+                    .get((i - 258) as usize)
+                    .map(GlyphName::new),
+            })
+            .collect()
+    })
+}
+
+/// Generate names for a font that does not include them.
+fn synthesize_glyph_names(num_glyphs: u16, cmap: &BTreeMap<u32, GlyphId>) -> GlyphMap {
+    let mut names = HashMap::new();
+    // if multiple codepoints map to a glyph, the lowest is used
+    for (codepoint, gid) in cmap.iter().rev() {
+        let name = match codepoint {
+            0..=0xFFFF => format!("uni{codepoint:04X}"),
+            _ => format!("u{codepoint:05X}"),
+        };
+        names.insert(*gid, name);
+    }
+    (0..num_glyphs)
+        .map(|gid| match (gid, names.remove(&GlyphId::new(gid))) {
+            (0, _) => GlyphName::new(".notdef"),
+            (_, Some(name)) => GlyphName::new(name),
+            (_, None) => GlyphName::new(format!("glyph{gid:05}")),
+        })
+        .collect()
+}
+
+/// The Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev_row = (0..=b.len()).collect::<Vec<_>>();
+    let mut row = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = prev_row[j] + usize::from(a_char != *b_char);
+            row[j + 1] = substitution.min(prev_row[j + 1] + 1).min(row[j] + 1);
+        }
+        std::mem::swap(&mut row, &mut prev_row);
+    }
+    prev_row[b.len()]
 }
 
 impl FromIterator<u16> for GlyphMap {
@@ -180,3 +360,130 @@ mod sealed {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn closest_name() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        let map = ["a", "b", "onehalf", "onequarter", "A.sc"]
+            .into_iter()
+            .map(GlyphName::from)
+            .collect::<GlyphMap>();
+        assert_eq!(map.closest_name("onehallf").unwrap(), "onehalf");
+        assert_eq!(map.closest_name("a.sc").unwrap(), "A.sc");
+        // 'c' is equally close to 'a' and 'b'
+        assert_eq!(map.closest_name("c").unwrap(), "a");
+        assert_eq!(map.closest_name("threequarters"), None);
+    }
+
+    #[test]
+    fn glyph_order_from_ufo() {
+        let name = format!("fea-rs-glyph-order-{}.ufo", std::process::id());
+        let ufo = std::env::temp_dir().join(name);
+        std::fs::create_dir_all(&ufo).unwrap();
+        let plist = |body: &str| {
+            format!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                <plist version=\"1.0\"><dict>{body}</dict></plist>\n"
+            )
+        };
+        let metainfo = "<key>creator</key><string>fea-rs</string>\
+            <key>formatVersion</key><integer>3</integer>";
+        std::fs::write(ufo.join("metainfo.plist"), plist(metainfo)).unwrap();
+        let lib = "<key>public.glyphOrder</key>\
+            <array><string>.notdef</string><string>b</string><string>a</string></array>";
+        std::fs::write(ufo.join("lib.plist"), plist(lib)).unwrap();
+
+        let from_dir = GlyphMap::from_ufo(&ufo);
+        let from_lib = GlyphMap::from_ufo(&ufo.join("lib.plist"));
+        std::fs::write(ufo.join("lib.plist"), plist("")).unwrap();
+        let missing = GlyphMap::from_ufo(&ufo);
+        std::fs::remove_dir_all(&ufo).unwrap();
+
+        let map = from_dir.unwrap();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map.get("b"), Some(GlyphId::new(1)));
+        assert_eq!(map.get("a"), Some(GlyphId::new(2)));
+        assert_eq!(from_lib.unwrap().get("a"), Some(GlyphId::new(2)));
+        assert!(matches!(missing, Err(UfoGlyphOrderError::KeyNotSet)));
+    }
+
+    #[test]
+    fn glyph_order_from_font() {
+        use write_fonts::{
+            dump_table,
+            tables::maxp::Maxp,
+            types::{Tag, Version16Dot16},
+            FontBuilder,
+        };
+
+        let names = [".notdef", "space", "A", "alef-hb"];
+        let glyph_map = names.into_iter().map(GlyphName::new).collect::<GlyphMap>();
+        let maxp = dump_table(&Maxp::new(names.len() as u16)).unwrap();
+        let mut post = glyph_map.make_post_table();
+        let mut builder = FontBuilder::default();
+        builder.add_table(Tag::new(b"maxp"), maxp.clone());
+        builder.add_table(Tag::new(b"post"), dump_table(&post).unwrap());
+        let map = GlyphMap::from_font_bytes(&builder.build()).unwrap();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("space"), Some(GlyphId::new(1)));
+        assert_eq!(map.get("alef-hb"), Some(GlyphId::new(3)));
+
+        // a cmap with a format 4 subtable mapping 'A' to glyph 2
+        #[rustfmt::skip]
+        let subtable: &[u16] = &[
+            4, 32, 0, // format, length, language
+            4, 4, 1, 0, // segCountX2, searchRange, entrySelector, rangeShift
+            0x41, 0xFFFF, // endCode
+            0, // reservedPad
+            0x41, 0xFFFF, // startCode
+            (2u16).wrapping_sub(0x41), 1, // idDelta
+            0, 0, // idRangeOffset
+        ];
+        let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12];
+        cmap.extend(subtable.iter().flat_map(|x| x.to_be_bytes()));
+
+        // a version 3.0 post table has no glyph names
+        post.version = Version16Dot16::VERSION_3_0;
+        post.num_glyphs = None;
+        post.glyph_name_index = None;
+        post.string_data = None;
+        let mut builder = FontBuilder::default();
+        builder.add_table(Tag::new(b"maxp"), maxp.clone());
+        builder.add_table(Tag::new(b"post"), dump_table(&post).unwrap());
+        builder.add_table(Tag::new(b"cmap"), cmap);
+        let map = GlyphMap::from_font_bytes(&builder.build()).unwrap();
+        let names = map
+            .reverse_map()
+            .into_values()
+            .map(|ident| ident.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [".notdef", "glyph00001", "uni0041", "glyph00003"]);
+
+        // without a post or cmap table, every name is synthesized
+        let mut builder = FontBuilder::default();
+        builder.add_table(Tag::new(b"maxp"), maxp);
+        let map = GlyphMap::from_font_bytes(&builder.build()).unwrap();
+        assert_eq!(map.len(), 4);
+        assert_eq!(map.get("glyph00002"), Some(GlyphId::new(2)));
+    }
+
+    #[test]
+    fn synthesized_names() {
+        let cmap = [(0x41, 1), (0x391, 1), (0x1F600, 3)]
+            .into_iter()
+            .map(|(cp, gid)| (cp, GlyphId::new(gid)))
+            .collect();
+        let map = synthesize_glyph_names(4, &cmap);
+        let names = map
+            .reverse_map()
+            .into_values()
+            .map(|ident| ident.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [".notdef", "uni0041", "glyph00002", "u1F600"]);
+    }
+}
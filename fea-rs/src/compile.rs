@@ -1,22 +1,38 @@
 //! Compiling OpenType Layout tables
 
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 use write_fonts::types::GlyphId;
 
-use crate::{parse::ParseTree, Diagnostic, GlyphMap, GlyphName};
+use crate::{
+    parse::{FileId, FileSystemResolver, ParseContext, ParseTree},
+    Diagnostic, GlyphMap, GlyphName,
+};
 
 use self::{
     compile_ctx::CompilationCtx,
-    error::{FontGlyphOrderError, GlyphOrderError, UfoGlyphOrderError},
+    compiler::{build_compilation, print_warnings_return_errors},
+    error::{CompilerError, FontGlyphOrderError, GlyphOrderError, UfoGlyphOrderError},
+    validate::ValidationCtx,
 };
 
 pub use compiler::Compiler;
-pub use opts::Opts;
+pub use font_info::{FontInfo, VerticalMetrics};
+pub use lookups::LookupId;
+pub use opts::{Dialect, Opts, PostFormat};
 pub use output::Compilation;
+pub use tables::{AxisLocation, AxisRecord, AxisValue, NameSpec, StatBuilder, StatFallbackName};
+pub use variations::{AxisMap, VariationAxis};
 
 mod compile_ctx;
 mod compiler;
 pub mod error;
 mod features;
+mod font_info;
 mod glyph_range;
 mod language_system;
 mod lookups;
@@ -26,32 +42,167 @@ mod tables;
 mod tags;
 mod validate;
 mod valuerecordext;
+mod variation_store;
+mod variations;
 
 /// Run the validation pass, returning any diagnostics.
-pub(crate) fn validate(node: &ParseTree, glyph_map: &GlyphMap) -> Vec<Diagnostic> {
-    let mut ctx = validate::ValidationCtx::new(glyph_map, node.source_map());
+pub(crate) fn validate(node: &ParseTree, glyph_map: &GlyphMap, opts: &Opts) -> Vec<Diagnostic> {
+    let mut ctx = validate::ValidationCtx::new(glyph_map, node.source_map(), opts);
     ctx.validate_root(&node.typed_root());
     ctx.errors
 }
 
-static GLYPH_ORDER_KEY: &str = "public.glyphOrder";
+/// Parse and validate a number of feature files, returning all diagnostics.
+///
+/// This is intended for linting: no compilation is performed, and each file is
+/// checked independently (and in parallel) so that errors in one file do not
+/// prevent the others from being checked. The results include both errors and
+/// warnings, and are returned in the same order as `paths`.
+///
+/// If a file has parse errors it is not validated, and only the parse
+/// diagnostics are reported.
+pub fn lint_files(paths: &[PathBuf], glyph_map: &GlyphMap) -> Vec<(PathBuf, Vec<Diagnostic>)> {
+    let next = AtomicUsize::new(0);
+    let n_threads = std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+        .min(paths.len());
+    let mut results = vec![Vec::new(); paths.len()];
+    std::thread::scope(|scope| {
+        let workers = (0..n_threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let idx = next.fetch_add(1, Ordering::Relaxed);
+                        match paths.get(idx) {
+                            Some(path) => done.push((idx, lint_file(path, glyph_map))),
+                            None => return done,
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for worker in workers {
+            for (idx, diagnostics) in worker.join().unwrap() {
+                results[idx] = diagnostics;
+            }
+        }
+    });
+    paths.iter().cloned().zip(results).collect()
+}
+
+fn lint_file(path: &Path, glyph_map: &GlyphMap) -> Vec<Diagnostic> {
+    let project_root = path.parent().map(PathBuf::from).unwrap_or_default();
+    let resolver = Box::new(FileSystemResolver::new(project_root));
+    let context = match ParseContext::parse(path.into(), Some(glyph_map), resolver) {
+        Ok(context) => context,
+        // there is no source to point at, so we use an empty span
+        Err(e) => return vec![Diagnostic::error(FileId::CURRENT_FILE, 0..0, e.to_string())],
+    };
+    let (tree, mut diagnostics) = context.generate_parse_tree();
+    // validation expects a well-formed tree
+    if !diagnostics.iter().any(Diagnostic::is_error) {
+        diagnostics.extend(validate(&tree, glyph_map, &Opts::default()));
+    }
+    diagnostics
+}
+
+/// Compile a family of instances that share most of their features.
+///
+/// Each instance is compiled from the `shared` feature file followed by its
+/// own file in `instance_overrides`, against the same glyph map; the result
+/// contains one [`Compilation`] per override, in the same order. This is
+/// equivalent to writing a root file for each instance that includes the
+/// shared file and then the override.
+///
+/// Because the shared source always comes first, its lookups have the same
+/// indices in every instance, and overrides can refer to shared classes and
+/// named lookups; a feature that references a shared lookup reuses it rather
+/// than producing a copy. The shared file is parsed, validated and compiled
+/// once, and each override then continues from a copy of the result. Any
+/// warnings in the shared file are included in every instance.
+pub fn compile_family(
+    shared: impl AsRef<Path>,
+    instance_overrides: &[PathBuf],
+    glyph_map: &GlyphMap,
+) -> Result<Vec<Compilation>, CompilerError> {
+    let shared = shared.as_ref();
+    let project_root = shared.parent().map(PathBuf::from).unwrap_or_default();
+    let parse = |path: &Path| -> Result<_, CompilerError> {
+        let resolver = Box::new(FileSystemResolver::new(project_root.clone()));
+        let context = ParseContext::parse(path.into(), Some(glyph_map), resolver)?;
+        let (tree, diagnostics) = context.generate_parse_tree();
+        let warnings = print_warnings_return_errors(diagnostics, &tree, false)
+            .map_err(CompilerError::ParseFail)?;
+        Ok((tree, warnings))
+    };
+    let opts = Opts::default();
+
+    let (shared_tree, mut shared_warnings) = parse(shared)?;
+    let shared_root = shared_tree.typed_root();
+    let shared_statements = shared_root.statements().collect::<Vec<_>>();
+    let mut validation = ValidationCtx::new(glyph_map, shared_tree.source_map(), &opts);
+    validation.validate_statements(&shared_statements);
+    shared_warnings.extend(
+        print_warnings_return_errors(std::mem::take(&mut validation.errors), &shared_tree, false)
+            .map_err(CompilerError::ValidationFail)?,
+    );
+    let mut ctx = CompilationCtx::new(glyph_map, shared_tree.source_map(), opts);
+    ctx.compile_statements(&shared_statements);
+    shared_warnings.extend(
+        print_warnings_return_errors(std::mem::take(&mut ctx.errors), &shared_tree, false)
+            .map_err(CompilerError::CompilationFail)?,
+    );
+
+    instance_overrides
+        .iter()
+        .map(|instance| {
+            let (instance_tree, parse_warnings) = parse(instance)?;
+            // positions in the shared tree are the same in the combined tree,
+            // so the state from the shared statements is still valid
+            let tree = shared_tree.append(&instance_tree);
+            let root = tree.typed_root();
+            let statements = root
+                .statements()
+                .skip(shared_statements.len())
+                .collect::<Vec<_>>();
+            let mut warnings = shared_warnings.clone();
+            warnings.extend(parse_warnings);
+
+            let mut validation = validation.fork_for_tree(tree.source_map());
+            validation.validate_statements(&statements);
+            validation.finalize();
+            warnings.extend(
+                print_warnings_return_errors(validation.errors, &tree, false)
+                    .map_err(CompilerError::ValidationFail)?,
+            );
+            let mut ctx = ctx.fork_for_tree(tree.source_map());
+            ctx.compile_statements(&statements);
+            ctx.finalize();
+            build_compilation(ctx, &tree, warnings, false)
+        })
+        .collect()
+}
+
+/// Compile a FEA source held in memory; used in unit tests.
+#[cfg(test)]
+pub(crate) fn compile_str(
+    fea: &str,
+    glyph_map: &GlyphMap,
+    opts: Opts,
+) -> Result<Compilation, error::CompilerError> {
+    Compiler::from_source(fea, glyph_map)
+        .with_opts(opts)
+        .compile()
+}
 
 /// A helper function for extracting the glyph order from a UFO
 ///
 /// If the public.glyphOrder key is missing, or the glyphOrder is malformed,
 /// this will return `None`.
 pub fn get_ufo_glyph_order(font: &norad::Font) -> Result<GlyphMap, UfoGlyphOrderError> {
-    font.lib
-        .get(GLYPH_ORDER_KEY)
-        .ok_or(UfoGlyphOrderError::KeyNotSet)?
-        .as_array()
-        .and_then(|name_array| {
-            name_array
-                .iter()
-                .map(|val| val.as_string().map(GlyphName::new))
-                .collect()
-        })
-        .ok_or(UfoGlyphOrderError::Malformed)
+    crate::common::glyph_order_from_ufo(font)
 }
 
 /// A helper function for extracting glyph order from a font with a 'post' table
@@ -60,27 +211,10 @@ pub fn get_ufo_glyph_order(font: &norad::Font) -> Result<GlyphMap, UfoGlyphOrder
 pub fn get_post_glyph_order(font_data: &[u8]) -> Result<GlyphMap, FontGlyphOrderError> {
     use write_fonts::{
         from_obj::ToOwnedTable,
-        read::{tables::post::DEFAULT_GLYPH_NAMES, FontRef, TableProvider},
-        tables::post::Post,
+        read::{FontRef, TableProvider},
     };
-    let post: Post = FontRef::new(font_data)?.post()?.to_owned_table();
-    post.glyph_name_index
-        .as_ref()
-        .and_then(|items| {
-            items
-                .iter()
-                .map(|name_idx| match *name_idx {
-                    i @ 0..=257 => Some(GlyphName::new(DEFAULT_GLYPH_NAMES[i as usize])),
-                    i => post
-                        .string_data
-                        .as_ref()
-                        .unwrap()
-                        .get((i - 258) as usize)
-                        .map(GlyphName::new),
-                })
-                .collect()
-        })
-        .ok_or(FontGlyphOrderError::MissingNames)
+    let post = FontRef::new(font_data)?.post()?.to_owned_table();
+    crate::common::glyph_order_from_post(&post).ok_or(FontGlyphOrderError::MissingNames)
 }
 
 /// Extract a glyph order from an ordered list of glyph names.
@@ -120,4 +254,110 @@ mod tests {
         assert_eq!(glyph_map.get("e.fina"), Some(GlyphId::new(214)));
         assert!(!glyph_map.contains("e.nada"));
     }
+
+    #[test]
+    fn lint_multiple_files() {
+        let dir = std::env::temp_dir().join(format!("fea-rs-lint-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sources = [
+            ("bad_parse.fea", "feature liga { sub f i by f_i } liga;"),
+            ("bad_glyph.fea", "feature liga { sub f i by nope; } liga;"),
+            ("good.fea", "feature liga { sub f i by f_i; } liga;"),
+        ];
+        let mut paths = sources
+            .iter()
+            .map(|(name, fea)| {
+                let path = dir.join(name);
+                std::fs::write(&path, fea).unwrap();
+                path
+            })
+            .collect::<Vec<_>>();
+        paths.push(dir.join("missing.fea"));
+
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let results = lint_files(&paths, &glyph_map);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().zip(&paths).all(|((path, _), p)| path == p));
+        let has_errors = results
+            .iter()
+            .map(|(_, diagnostics)| diagnostics.iter().any(Diagnostic::is_error))
+            .collect::<Vec<_>>();
+        assert_eq!(has_errors, [true, true, false, true]);
+    }
+
+    #[test]
+    fn compile_family_shares_lookups() {
+        let dir = std::env::temp_dir().join(format!("fea-rs-family-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sources = [
+            (
+                "shared.fea",
+                "lookup fl { sub f l by f_l; } fl; feature liga { lookup fl; } liga;",
+            ),
+            ("regular.fea", "feature smcp { sub a by a.sc; } smcp;"),
+            ("bold.fea", "feature calt { lookup fl; } calt;"),
+        ];
+        for (name, fea) in sources {
+            std::fs::write(dir.join(name), fea).unwrap();
+        }
+        let overrides = [dir.join("regular.fea"), dir.join("bold.fea")];
+
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let result = compile_family(dir.join("shared.fea"), &overrides, &glyph_map);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let instances = result.unwrap();
+
+        assert_eq!(instances.len(), 2);
+        let shared_lookup = instances[0].named_lookup_indices()["fl"];
+        assert_eq!(instances[1].named_lookup_indices()["fl"], shared_lookup);
+        assert!(instances[0].describe().contains("smcp"));
+        assert!(!instances[1].describe().contains("smcp"));
+        // the shared lookup is reused by calt, not copied
+        let mut bold_lookups = instances[1].features.values().flatten();
+        assert!(bold_lookups.all(|id| *id == shared_lookup));
+    }
+
+    #[test]
+    fn compile_family_continues_from_shared() {
+        let dir = std::env::temp_dir().join(format!("fea-rs-family-cont-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sources = [
+            (
+                "shared.fea",
+                "languagesystem DFLT dflt; languagesystem DFLT dflt; anchorDef 300 0 TOP;",
+            ),
+            (
+                "good.fea",
+                "feature curs { pos cursive a <anchor TOP> <anchor NULL>; } curs;",
+            ),
+            ("bad.fea", "feature liga { sub f i by nope; } liga;"),
+        ];
+        for (name, fea) in sources {
+            std::fs::write(dir.join(name), fea).unwrap();
+        }
+        let overrides = [dir.join("good.fea"), dir.join("bad.fea")];
+
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let good = compile_family(dir.join("shared.fea"), &overrides[..1], &glyph_map);
+        let bad = compile_family(dir.join("shared.fea"), &overrides, &glyph_map);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the anchor from the shared file is defined before its use, and the
+        // warning from the shared file is reported once
+        let good = good.unwrap();
+        let duplicates = good[0]
+            .warnings
+            .iter()
+            .filter(|w| w.text().contains("languagesystem"))
+            .count();
+        assert_eq!(duplicates, 1, "{:?}", good[0].warnings);
+
+        // errors in an override point at the override
+        let Err(CompilerError::ValidationFail(errors)) = bad else {
+            panic!("expected validation failure");
+        };
+        assert!(errors.to_string().contains("bad.fea"), "{errors}");
+    }
 }
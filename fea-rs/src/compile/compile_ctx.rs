@@ -1,5 +1,5 @@
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet, HashMap},
     convert::TryInto,
     ops::Range,
 };
@@ -10,7 +10,7 @@ use write_fonts::{
         self,
         gdef::CaretValue,
         gpos::{AnchorTable, ValueRecord},
-        layout::LookupFlag,
+        layout::{Device, LookupFlag},
     },
     types::Tag,
 };
@@ -23,7 +23,7 @@ use crate::{
         Token,
     },
     typed::ContextualRuleNode,
-    Diagnostic, GlyphIdent, GlyphMap, Kind, NodeOrToken,
+    Diagnostic, DiagnosticKind, GlyphIdent, GlyphMap, Kind, NodeOrToken, Phase,
 };
 
 use super::{
@@ -32,35 +32,73 @@ use super::{
     language_system::{DefaultLanguageSystems, LanguageSystem},
     lookups::{
         AllLookups, FeatureKey, FilterSetId, LookupFlagInfo, LookupId, PreviouslyAssignedClass,
-        SomeLookup,
+        SomeLookup, SubstitutionLookup,
     },
+    opts::DEFAULT_MAX_LIGATURE_SEQUENCES,
     output::Compilation,
-    tables::{ClassId, CvParams, ScriptRecord, Tables},
+    tables::{ClassId, CvParams, MetaBuilder, ScriptRecord, Tables},
     tags,
+    validate::{missing_cid_message, missing_glyph_message, CID_REQUIRES_CID_KEYED_MAP},
     valuerecordext::ValueRecordExt,
+    variation_store::{VariableValue, VariationStore, VariationStoreBuilder},
+    variations::{AxisCondition, FeatureVariation},
+    FontInfo, Opts,
 };
 
+/// The estimated subtable size at which we insert an automatic subtable break.
+///
+/// Subtables use 16-bit offsets internally, so they cannot be much larger than
+/// 64KiB. We check before each rule is added, so this leaves some space for
+/// the rule that crosses the threshold.
+const AUTOMATIC_SUBTABLE_BREAK_SIZE: usize = 0xF000;
+
+/// The latest version of the OS/2 table.
+const MAX_OS2_VERSION: u16 = 5;
+
+/// The maximum number of distinct `MarkAttachmentType` classes.
+///
+/// This limit is from the FEA spec; the lookup flag itself has room for 255.
+const MAX_MARK_ATTACH_CLASSES: u16 = 15;
+
+#[derive(Clone)]
 pub struct CompilationCtx<'a> {
     glyph_map: &'a GlyphMap,
     reverse_glyph_map: BTreeMap<GlyphId, GlyphIdent>,
     source_map: &'a SourceMap,
+    opts: Opts,
     pub errors: Vec<Diagnostic>,
     tables: Tables,
     features: BTreeMap<FeatureKey, Vec<LookupId>>,
     default_lang_systems: DefaultLanguageSystems,
+    /// set once we see a feature, lookup, or table; languagesystems must come first
+    seen_block: bool,
     lookups: AllLookups,
     lookup_flags: LookupFlagInfo,
+    /// the first lookupflag that skips glyphs based on their GDEF class
+    first_ignore_flag: Option<Range<usize>>,
     active_feature: Option<ActiveFeature>,
     vertical_feature: SpecialVerticalFeatureState,
     script: Option<Tag>,
-    glyph_class_defs: HashMap<SmolStr, GlyphClass>,
-    mark_classes: HashMap<SmolStr, MarkClass>,
-    anchor_defs: HashMap<SmolStr, (AnchorTable, usize)>,
-    mark_attach_class_id: HashMap<GlyphClass, u16>,
-    mark_filter_sets: HashMap<GlyphClass, FilterSetId>,
+    glyph_class_defs: BTreeMap<SmolStr, GlyphClass>,
+    /// named classes that had glyphs removed because they are not in the font
+    classes_with_skipped_glyphs: BTreeSet<SmolStr>,
+    /// classes that have been defined but not referenced, and their locations
+    unused_glyph_classes: BTreeMap<SmolStr, Range<usize>>,
+    mark_classes: BTreeMap<SmolStr, MarkClass>,
+    /// the glyphs in each mark class, computed on first use
+    mark_class_glyphs: BTreeMap<SmolStr, GlyphClass>,
+    anchor_defs: BTreeMap<SmolStr, (AnchorTable, usize)>,
+    device_defs: BTreeMap<SmolStr, Option<Device>>,
+    mark_attach_class_id: BTreeMap<GlyphClass, u16>,
+    mark_filter_sets: BTreeMap<GlyphClass, FilterSetId>,
     size: Option<SizeFeature>,
     aalt: Option<AaltFeature>,
-    required_features: HashSet<FeatureKey>,
+    required_features: BTreeSet<FeatureKey>,
+    condition_sets: BTreeMap<SmolStr, Vec<AxisCondition>>,
+    /// the lookups in `variation` blocks, grouped by conditionset in order of first use
+    feature_variations: Vec<(SmolStr, FeatureVariation)>,
+    /// the deltas of every variable metric, if there is an axis map
+    variation_store: Option<VariationStore>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -69,20 +107,27 @@ struct MarkClass {
 }
 
 impl<'a> CompilationCtx<'a> {
-    pub(crate) fn new(glyph_map: &'a GlyphMap, source_map: &'a SourceMap) -> Self {
+    pub(crate) fn new(glyph_map: &'a GlyphMap, source_map: &'a SourceMap, opts: Opts) -> Self {
         CompilationCtx {
             glyph_map,
             reverse_glyph_map: glyph_map.reverse_map(),
             source_map,
+            opts,
             errors: Vec::new(),
             tables: Tables::default(),
             default_lang_systems: Default::default(),
+            seen_block: false,
             glyph_class_defs: Default::default(),
+            classes_with_skipped_glyphs: Default::default(),
+            unused_glyph_classes: Default::default(),
             lookups: Default::default(),
             features: Default::default(),
             mark_classes: Default::default(),
+            mark_class_glyphs: Default::default(),
             anchor_defs: Default::default(),
+            device_defs: Default::default(),
             lookup_flags: Default::default(),
+            first_ignore_flag: None,
             active_feature: None,
             vertical_feature: Default::default(),
             script: None,
@@ -91,11 +136,49 @@ impl<'a> CompilationCtx<'a> {
             size: None,
             required_features: Default::default(),
             aalt: Default::default(),
+            condition_sets: Default::default(),
+            feature_variations: Default::default(),
+            variation_store: None,
         }
     }
 
     pub(crate) fn compile(&mut self, node: &typed::Root) {
-        for item in node.statements() {
+        self.build_variation_store(node);
+        self.compile_statements(&node.statements().collect::<Vec<_>>());
+        self.finalize();
+    }
+
+    /// Add the deltas of every variable metric to the variation store.
+    ///
+    /// This is done before compiling, so that value records and anchors can
+    /// refer to their final location in the store as they are compiled.
+    fn build_variation_store(&mut self, node: &typed::Root) {
+        let Some(axis_map) = self.opts.axis_map.as_ref() else { return };
+        let mut builder = VariationStoreBuilder::new(axis_map);
+        // visit the metrics in source order
+        let mut stack = node.statements().collect::<Vec<_>>();
+        stack.reverse();
+        while let Some(item) = stack.pop() {
+            if let Some(metric) = typed::VariableMetric::cast(item) {
+                if let Some(value) = self.variable_value(&metric) {
+                    builder.add(&value);
+                }
+            } else if let Some(node) = item.as_node() {
+                let first_child = stack.len();
+                stack.extend(node.iter_children());
+                stack[first_child..].reverse();
+            }
+        }
+        self.variation_store = Some(builder.build());
+    }
+
+    /// Compile some top-level statements.
+    ///
+    /// This can be called more than once, for statements that follow each
+    /// other; [`finalize`](Self::finalize) must be called after the last.
+    pub(crate) fn compile_statements(&mut self, statements: &[&NodeOrToken]) {
+        let mut items = statements.iter().copied().peekable();
+        while let Some(item) = items.next() {
             if let Some(language_system) = typed::LanguageSystem::cast(item) {
                 self.add_language_system(language_system);
             } else if let Some(class_def) = typed::GlyphClassDef::cast(item) {
@@ -104,36 +187,86 @@ impl<'a> CompilationCtx<'a> {
                 self.define_mark_class(mark_def);
             } else if let Some(anchor_def) = typed::AnchorDef::cast(item) {
                 self.define_named_anchor(anchor_def);
+            } else if let Some(device_def) = typed::DeviceDef::cast(item) {
+                self.define_named_device(device_def);
             } else if let Some(feature) = typed::Feature::cast(item) {
-                self.add_feature(feature);
+                self.seen_block = true;
+                if self.opts.parallel && self.is_independent_feature(&feature) {
+                    // take every independent feature that immediately follows
+                    let mut features = vec![feature];
+                    while let Some(next) = items.next_if(|item| {
+                        item.kind().is_trivia()
+                            || typed::Feature::cast(item)
+                                .filter(|next| self.is_independent_feature(next))
+                                .is_some()
+                    }) {
+                        features.extend(typed::Feature::cast(next));
+                    }
+                    self.add_independent_features(features);
+                } else {
+                    self.add_feature(feature);
+                }
+            } else if let Some(condition_set) = typed::ConditionSet::cast(item) {
+                self.define_condition_set(condition_set);
+            } else if let Some(variation) = typed::FeatureVariation::cast(item) {
+                self.seen_block = true;
+                self.add_feature_variation(variation);
             } else if let Some(lookup) = typed::LookupBlock::cast(item) {
+                self.seen_block = true;
                 self.resolve_lookup_block(lookup);
             } else if item.kind() == Kind::AnonBlockNode {
                 // noop
             } else if let Some(table) = typed::Table::cast(item) {
+                self.seen_block = true;
                 self.resolve_table(table);
             } else if !item.kind().is_trivia() {
-                let span = match item {
-                    NodeOrToken::Token(t) => t.range(),
-                    NodeOrToken::Node(node) => {
-                        let range = node.range();
-                        let end = range.end.min(range.start + 16);
-                        range.start..end
-                    }
-                };
-                self.error(span, format!("unhandled top-level item: '{}'", item.kind()));
+                self.unknown_item(item, format!("unhandled top-level item: '{}'", item.kind()));
             }
         }
+    }
 
-        self.finalize_gdef_table();
-        self.finalize_aalt();
+    /// Finish compilation, after all statements have been compiled.
+    pub(crate) fn finalize(&mut self) {
+        self.check_font_info();
+        // lookups refer to mark attachment classes and filter sets by their
+        // index in GDEF, so we assign those even if GDEF won't be written
+        if self.opts.includes_table(tags::GDEF)
+            || !self.mark_attach_class_id.is_empty()
+            || !self.mark_filter_sets.is_empty()
+        {
+            self.finalize_gdef_table();
+        }
+        if self.opts.includes_table(tags::GSUB) {
+            self.finalize_aalt();
+        }
         self.sort_and_dedupe_lookups();
+        if self.opts.warn_unused_glyph_classes {
+            self.report_unused_glyph_classes();
+        }
+    }
+
+    fn report_unused_glyph_classes(&mut self) {
+        let mut unused = std::mem::take(&mut self.unused_glyph_classes)
+            .into_iter()
+            .collect::<Vec<_>>();
+        unused.sort_unstable_by_key(|(_, range)| range.start);
+        for (name, range) in unused {
+            self.warning(
+                DiagnosticKind::UnusedGlyphClass,
+                range,
+                format!("glyph class '{name}' is never used"),
+            );
+        }
     }
 
     fn sort_and_dedupe_lookups(&mut self) {
         // if any duplicate lookups have made their way into our features, remove them;
         // they will be ignored by the shaper anyway.
-        for lookup in self.features.values_mut() {
+        let variation_lookups = self
+            .feature_variations
+            .iter_mut()
+            .flat_map(|(_, variation)| variation.features.values_mut());
+        for lookup in self.features.values_mut().chain(variation_lookups) {
             // note that the order of lookups in a feature doesn't matter, they
             // are processed in the order that they appear in the lookup list.
             lookup.sort_unstable();
@@ -179,6 +312,11 @@ impl<'a> CompilationCtx<'a> {
         // list:
         self.features
             .values_mut()
+            .chain(
+                self.feature_variations
+                    .iter_mut()
+                    .flat_map(|(_, variation)| variation.features.values_mut()),
+            )
             .flat_map(|x| x.iter_mut())
             .for_each(|id| id.adjust_if_gsub(aalt_lookup_indices.len()));
 
@@ -192,6 +330,13 @@ impl<'a> CompilationCtx<'a> {
     }
 
     pub(crate) fn build(&mut self) -> Result<Compilation, Vec<Diagnostic>> {
+        self.check_lookup_references();
+        // sort by location, so that the output doesn't depend on the order in
+        // which things were checked. The sort is stable, so diagnostics at the
+        // same location stay in the order they were reported.
+        self.errors
+            .sort_by_key(|diag| (diag.message.file, diag.span().start, diag.level));
+        crate::diagnostic::remove_duplicates(&mut self.errors);
         if self.errors.iter().any(Diagnostic::is_error) {
             return Err(self.errors.clone());
         }
@@ -203,9 +348,46 @@ impl<'a> CompilationCtx<'a> {
             tables: self.tables.clone(),
             size: self.size.clone(),
             required_features: self.required_features.clone(),
+            feature_variations: self
+                .feature_variations
+                .iter()
+                .map(|(_, variation)| variation.clone())
+                .collect(),
+            glyph_classes: self
+                .glyph_class_defs
+                .iter()
+                .map(|(name, class)| (name.clone(), class.items().to_vec()))
+                .collect(),
+            compact_pair_pos: self.opts.compact_pair_pos,
         })
     }
 
+    /// Ensure that every lookup referenced by a feature exists.
+    ///
+    /// This should always be true; a failure here indicates a bug in the
+    /// compiler (for instance when inserting the aalt lookups) that would
+    /// otherwise produce a corrupt font.
+    fn check_lookup_references(&mut self) {
+        let variation_features = self
+            .feature_variations
+            .iter()
+            .flat_map(|(_, variation)| variation.features.iter());
+        let missing = self
+            .features
+            .iter()
+            .chain(variation_features)
+            .flat_map(|(key, ids)| ids.iter().map(move |id| (key.feature, *id)))
+            .filter(|(_, id)| !self.lookups.contains(*id))
+            .collect::<Vec<_>>();
+        for (feature, id) in missing {
+            self.error(
+                DiagnosticKind::Internal,
+                0..0,
+                format!("internal error: feature '{feature}' references missing lookup {id:?}"),
+            );
+        }
+    }
+
     /// Infer/update GDEF table as required.
     ///
     /// If a GDEF table is not explicitly defined, we are supposed to create one,
@@ -221,8 +403,9 @@ impl<'a> CompilationCtx<'a> {
         let mut gdef = self.tables.gdef.take().unwrap_or_default();
         // infer glyph classes, if they were not declared explicitly
         if gdef.glyph_classes.is_empty() {
+            let mut inferred = BTreeMap::new();
             self.lookups.infer_glyph_classes(|glyph, class_id| {
-                gdef.glyph_classes.insert(glyph, class_id);
+                inferred.insert(glyph, class_id);
             });
             for glyph in self
                 .mark_classes
@@ -230,7 +413,29 @@ impl<'a> CompilationCtx<'a> {
                 .flat_map(|class| class.members.iter().map(|(cls, _)| cls.iter()))
                 .flatten()
             {
-                gdef.glyph_classes.insert(glyph, ClassId::Mark);
+                inferred.insert(glyph, ClassId::Mark);
+            }
+            // classes from the font's existing GDEF take precedence
+            if let Some(info) = self.font_info() {
+                gdef.glyph_classes.extend(
+                    info.glyph_classes
+                        .iter()
+                        .filter_map(|(gid, raw)| Some((*gid, ClassId::from_raw(*raw)?))),
+                );
+            }
+            for (glyph, class_id) in inferred {
+                gdef.glyph_classes.entry(glyph).or_insert(class_id);
+            }
+        }
+
+        if self.opts.warn_ineffective_lookupflags && gdef.glyph_classes.is_empty() {
+            if let Some(range) = self.first_ignore_flag.clone() {
+                self.warning(
+                    DiagnosticKind::NoEffect,
+                    range,
+                    "lookupflag has no effect: GDEF has no glyph classes, \
+                    and none could be inferred from the rules",
+                );
             }
         }
 
@@ -242,6 +447,18 @@ impl<'a> CompilationCtx<'a> {
             );
         }
 
+        // the font's existing mark glyph sets keep their indices, since the
+        // font's own lookups may refer to them
+        if gdef.mark_glyph_sets.is_empty() {
+            if let Some(info) = self.font_info() {
+                gdef.mark_glyph_sets = info
+                    .mark_glyph_sets
+                    .iter()
+                    .map(|set| set.iter().copied().collect())
+                    .collect();
+            }
+        }
+
         if !self.mark_filter_sets.is_empty() {
             let mut sorted = self
                 .mark_filter_sets
@@ -249,25 +466,108 @@ impl<'a> CompilationCtx<'a> {
                 .map(|(cls, id)| (*id, cls.clone()))
                 .collect::<Vec<_>>();
             sorted.sort_unstable();
-            gdef.mark_glyph_sets = sorted.into_iter().map(|(_, cls)| cls).collect();
+            // existing sets keep their indices; the sets used by
+            // `UseMarkFilteringSet` are appended after them (or reuse an
+            // identical existing set) and the lookups are updated to match.
+            let explicit_count = gdef.mark_glyph_sets.len();
+            let mut remap = HashMap::new();
+            for (id, cls) in sorted {
+                let new_id = match gdef.mark_glyph_sets.iter().position(|x| *x == cls) {
+                    Some(idx) => idx,
+                    None => {
+                        gdef.mark_glyph_sets.push(cls);
+                        gdef.mark_glyph_sets.len() - 1
+                    }
+                };
+                remap.insert(id, new_id as FilterSetId);
+            }
+            if explicit_count > 0 {
+                self.lookups.remap_mark_filter_sets(|id| remap[&id]);
+            }
         }
 
+        gdef.var_store = self
+            .variation_store
+            .as_ref()
+            .and_then(VariationStore::table);
+
         if !gdef.is_empty() {
             self.tables.gdef = Some(gdef);
         }
     }
 
-    fn error(&mut self, range: Range<usize>, message: impl Into<String>) {
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if let Some(sink) = &self.opts.diagnostic_sink {
+            sink.send(&diagnostic);
+        }
+        self.errors.push(diagnostic);
+    }
+
+    fn error(&mut self, kind: DiagnosticKind, range: Range<usize>, message: impl Into<String>) {
+        let (file, range) = self.source_map.resolve_range(range);
+        let diagnostic = Diagnostic::error(file, range, message);
+        self.report(diagnostic.in_phase(Phase::Compilation).with_kind(kind));
+    }
+
+    fn warning(&mut self, kind: DiagnosticKind, range: Range<usize>, message: impl Into<String>) {
         let (file, range) = self.source_map.resolve_range(range);
-        self.errors.push(Diagnostic::error(file, range, message));
+        let diagnostic = Diagnostic::warning(file, range, message);
+        self.report(diagnostic.in_phase(Phase::Compilation).with_kind(kind));
     }
 
-    fn warning(&mut self, range: Range<usize>, message: impl Into<String>) {
+    fn info(&mut self, kind: DiagnosticKind, range: Range<usize>, message: impl Into<String>) {
         let (file, range) = self.source_map.resolve_range(range);
-        self.errors.push(Diagnostic::warning(file, range, message));
+        let diagnostic = Diagnostic::info(file, range, message);
+        self.report(diagnostic.in_phase(Phase::Compilation).with_kind(kind));
+    }
+
+    /// Report an item we don't understand; this is an error unless
+    /// [`Opts::ignore_unknown_items`] is set.
+    ///
+    /// Loose tokens are what the parser skipped over, and it has already
+    /// reported them, so we only warn about nodes.
+    fn unknown_item(&mut self, item: &NodeOrToken, message: String) {
+        let span = match item {
+            NodeOrToken::Token(t) => t.range(),
+            NodeOrToken::Node(node) => {
+                let range = node.range();
+                let end = range.end.min(range.start + 16);
+                range.start..end
+            }
+        };
+        if !self.opts.ignore_unknown_items {
+            self.error(DiagnosticKind::UnknownItem, span, message);
+        } else if !item.is_token() {
+            self.warning(DiagnosticKind::UnknownItem, span, message);
+        }
+    }
+
+    /// The font info provided in the options, if any.
+    fn font_info(&self) -> Option<&FontInfo> {
+        self.opts.font_info.as_ref()
+    }
+
+    /// Warn if the font info does not match the glyph map.
+    ///
+    /// This is a problem with the inputs, not with the FEA, so there is no
+    /// location to attach a diagnostic to; it is logged instead.
+    fn check_font_info(&self) {
+        let n_glyphs = self.glyph_map.len();
+        if let Some(font_glyphs) = self.font_info().and_then(|info| info.num_glyphs) {
+            if usize::from(font_glyphs) != n_glyphs {
+                log::warn!("glyph map contains {n_glyphs} glyphs, but font has {font_glyphs}");
+            }
+        }
     }
 
     fn add_language_system(&mut self, language_system: typed::LanguageSystem) {
+        if self.seen_block {
+            return self.error(
+                DiagnosticKind::InvalidStatement,
+                language_system.range(),
+                "languagesystem statements must precede all features, lookups, and tables",
+            );
+        }
         let script = language_system.script().to_raw();
         let language = language_system.language().to_raw();
         self.default_lang_systems
@@ -289,6 +589,11 @@ impl<'a> CompilationCtx<'a> {
     }
 
     fn end_feature(&mut self) {
+        let active = self.finish_active_feature();
+        active.add_to_features(&mut self.features);
+    }
+
+    fn finish_active_feature(&mut self) -> ActiveFeature {
         if let Some((id, _name)) = self.lookups.finish_current() {
             assert!(
                 _name.is_none(),
@@ -296,13 +601,12 @@ impl<'a> CompilationCtx<'a> {
             );
             self.add_lookup_to_current_feature_if_present(id);
         }
-        let active = self.active_feature.take().expect("always present");
-        active.add_to_features(&mut self.features);
         self.vertical_feature.end_feature();
         self.lookup_flags.clear();
+        self.active_feature.take().expect("always present")
     }
 
-    fn start_lookup_block(&mut self, name: &Token) {
+    fn start_lookup_block(&mut self, name: &Token, use_extension: bool) {
         if let Some((id, _name)) = self.lookups.finish_current() {
             assert!(_name.is_none(), "lookup blocks cannot be nested");
             self.add_lookup_to_current_feature_if_present(id);
@@ -313,7 +617,7 @@ impl<'a> CompilationCtx<'a> {
         }
 
         self.vertical_feature.begin_lookup_block();
-        self.lookups.start_named(name.text.clone());
+        self.lookups.start_named(name.text.clone(), use_extension);
     }
 
     fn end_lookup_block(&mut self) {
@@ -339,6 +643,7 @@ impl<'a> CompilationCtx<'a> {
             language,
             stmt.exclude_dflt().is_some(),
             stmt.required().is_some(),
+            stmt.range(),
         );
     }
 
@@ -351,7 +656,7 @@ impl<'a> CompilationCtx<'a> {
         self.script = Some(script);
         self.lookup_flags.clear();
 
-        self.set_script_language(script, tags::LANG_DFLT, false, false);
+        self.set_script_language(script, tags::LANG_DFLT, false, false, stmt.range());
     }
 
     fn set_script_language(
@@ -360,16 +665,29 @@ impl<'a> CompilationCtx<'a> {
         language: Tag,
         exclude_dflt: bool,
         required: bool,
+        range: Range<usize>,
     ) {
         let system = LanguageSystem { script, language };
         if let Some((id, _name)) = self.lookups.finish_current() {
             self.add_lookup_to_current_feature_if_present(id);
         }
-        let key = self
-            .active_feature
-            .as_mut()
-            .unwrap()
-            .set_system(system, exclude_dflt);
+        let feature = self.active_feature.as_mut().unwrap();
+        let n_excluded = if exclude_dflt && self.opts.report_excluded_lookups {
+            feature.n_excludable_lookups(system)
+        } else {
+            0
+        };
+        let key = feature.set_system(system, exclude_dflt);
+        if n_excluded > 0 {
+            self.info(
+                DiagnosticKind::ExcludedLookups,
+                range,
+                format!(
+                    "exclude_dflt: {n_excluded} default lookup(s) not applied to '{}' for {script}/{language}",
+                    key.feature
+                ),
+            );
+        }
 
         if required {
             self.required_features.insert(key);
@@ -377,10 +695,27 @@ impl<'a> CompilationCtx<'a> {
     }
 
     fn set_lookup_flag(&mut self, node: typed::LookupFlag) {
+        let lookup_flags = self.resolve_lookup_flag(&node);
+        if self.opts.warn_redundant_lookupflags && lookup_flags == self.lookup_flags {
+            self.warning(
+                DiagnosticKind::RedundantLookupFlag,
+                node.range(),
+                "redundant lookupflag; these flags are already set",
+            );
+        }
+        let flags = lookup_flags.flags;
+        if flags.ignore_base_glyphs() || flags.ignore_ligatures() || flags.ignore_marks() {
+            self.first_ignore_flag.get_or_insert_with(|| node.range());
+        }
+        self.lookup_flags = lookup_flags;
+    }
+
+    fn resolve_lookup_flag(&mut self, node: &typed::LookupFlag) -> LookupFlagInfo {
         if let Some(number) = node.number() {
-            self.lookup_flags.flags =
-                LookupFlag::from_bits_truncate(number.parse_unsigned().unwrap());
-            return;
+            return LookupFlagInfo {
+                flags: LookupFlag::from_bits_truncate(number.parse_unsigned().unwrap()),
+                ..self.lookup_flags
+            };
         }
 
         let mut flags = LookupFlag::empty();
@@ -394,9 +729,8 @@ impl<'a> CompilationCtx<'a> {
                 Kind::IgnoreLigaturesKw => flags.set_ignore_ligatures(true),
                 Kind::IgnoreMarksKw => flags.set_ignore_marks(true),
 
-                //FIXME: we are not enforcing some requirements here. in particular,
-                // The glyph sets of the referenced classes must not overlap, and the MarkAttachmentType statement can reference at most 15 different classes.
-                // ALSO: this should accept mark classes.
+                // this may be a glyph class or a mark class; both are resolved
+                // to their glyphs by `resolve_glyph_class`.
                 Kind::MarkAttachmentTypeKw => {
                     let node = iter
                         .next()
@@ -417,10 +751,11 @@ impl<'a> CompilationCtx<'a> {
                 other => unreachable!("mark statements have been validated: '{:?}'", other),
             }
         }
-        self.lookup_flags = LookupFlagInfo::new(flags, mark_filter_set);
+        LookupFlagInfo::new(flags, mark_filter_set)
     }
 
     fn resolve_mark_attach_class(&mut self, glyphs: &typed::GlyphClass) -> u16 {
+        let range = glyphs.range();
         let glyphs = self.resolve_glyph_class(glyphs);
         let mark_set = glyphs.sort_and_dedupe();
         if let Some(id) = self.mark_attach_class_id.get(&mark_set) {
@@ -428,7 +763,30 @@ impl<'a> CompilationCtx<'a> {
         }
 
         let id = self.mark_attach_class_id.len() as u16 + 1;
-        //FIXME: I don't understand what is not allowed here
+        if id > MAX_MARK_ATTACH_CLASSES {
+            self.error(
+                DiagnosticKind::LimitExceeded,
+                range.clone(),
+                format!(
+                    "too many mark attachment classes (the maximum is {MAX_MARK_ATTACH_CLASSES})"
+                ),
+            );
+        }
+        // each glyph has a single class in the GDEF MarkAttachClassDef
+        let shared_glyph = self
+            .mark_attach_class_id
+            .keys()
+            .flat_map(|class| class.iter())
+            .filter(|glyph| mark_set.items().binary_search(glyph).is_ok())
+            .min();
+        if let Some(glyph) = shared_glyph {
+            let name = self.reverse_glyph_map.get(&glyph).unwrap();
+            self.error(
+                DiagnosticKind::ClassConflict,
+                range,
+                format!("glyph '{name}' is already in a different mark attachment class"),
+            );
+        }
 
         self.mark_attach_class_id.insert(mark_set, id);
         id
@@ -444,9 +802,34 @@ impl<'a> CompilationCtx<'a> {
             .or_insert_with(|| id.try_into().unwrap())
     }
 
-    pub fn add_subtable_break(&mut self) {
+    pub fn add_subtable_break(&mut self, range: Range<usize>) {
         if !self.lookups.add_subtable_break() {
-            //TODO: report that we weren't in a lookup?
+            self.warning(
+                DiagnosticKind::NoEffect,
+                range,
+                "'subtable' has no effect outside of a lookup",
+            );
+        }
+    }
+
+    /// Start a new subtable if a rule of this kind would be added to a current
+    /// subtable that is close to overflowing.
+    ///
+    /// Each automatic break is reported as a warning on the rule that starts
+    /// the new subtable. Since breaks only happen between rules, this has the
+    /// same effect as an explicit `subtable;` statement.
+    fn break_subtable_if_full(&mut self, kind: Kind, range: Range<usize>) {
+        if self.lookups.needs_new_lookup(kind) {
+            return;
+        }
+        let Some(size) = self.lookups.current_subtable_size() else { return };
+        if size >= AUTOMATIC_SUBTABLE_BREAK_SIZE {
+            self.lookups.add_subtable_break();
+            self.warning(
+                DiagnosticKind::LimitExceeded,
+                range,
+                format!("subtable is too large ({size} bytes); starting a new subtable"),
+            );
         }
     }
 
@@ -492,7 +875,11 @@ impl<'a> CompilationCtx<'a> {
             typed::GsubStatement::Type6(rule) => self.add_contextual_sub(&rule),
             typed::GsubStatement::Ignore(rule) => self.add_contextual_sub_ignore(&rule),
             typed::GsubStatement::Type8(rule) => self.add_reverse_contextual_sub(&rule),
-            _ => self.warning(node.range(), "unimplemented rule type"),
+            _ => self.warning(
+                DiagnosticKind::Unsupported,
+                node.range(),
+                "unimplemented rule type",
+            ),
         }
     }
 
@@ -535,17 +922,43 @@ impl<'a> CompilationCtx<'a> {
         let replace_ids = replace
             .map(|r| self.resolve_glyph_or_class(r))
             .unwrap_or(GlyphOrClass::Null);
+        // glyphs are matched by position, so if some were removed from either
+        // class, the rest would be paired with the wrong replacements.
+        if let (GlyphOrClass::Class(_), GlyphOrClass::Class(_)) = (&target_ids, &replace_ids) {
+            let skipped = [Some(target), replace]
+                .into_iter()
+                .flatten()
+                .find(|item| self.has_skipped_glyphs(item));
+            if let Some(item) = skipped {
+                self.error(
+                    DiagnosticKind::InvalidStatement,
+                    item.range(),
+                    "glyphs not in the font cannot be removed from a class that is \
+                    matched glyph-by-glyph with another class",
+                );
+                return None;
+            }
+        }
         match (target_ids, replace_ids) {
             (GlyphOrClass::Null, _) => {
-                self.error(target.range(), "NULL is not a valid substitution target");
+                self.error(
+                    DiagnosticKind::InvalidStatement,
+                    target.range(),
+                    "NULL is not a valid substitution target",
+                );
                 None
             }
             (GlyphOrClass::Glyph(_), GlyphOrClass::Class(_)) => {
-                self.error(replace.unwrap().range(), "cannot sub glyph by glyph class");
+                self.error(
+                    DiagnosticKind::InvalidStatement,
+                    replace.unwrap().range(),
+                    "cannot sub glyph by glyph class",
+                );
                 None
             }
             (GlyphOrClass::Class(c1), GlyphOrClass::Class(c2)) if c1.len() != c2.len() => {
                 self.error(
+                    DiagnosticKind::InvalidStatement,
                     replace.unwrap().range(),
                     format!(
                         "class has different length ({}) than target ({})",
@@ -555,10 +968,41 @@ impl<'a> CompilationCtx<'a> {
                 );
                 None
             }
+            (GlyphOrClass::Class(c1), GlyphOrClass::Class(c2)) => {
+                self.warn_conflicting_single_subs(target.range(), &c1, &c2);
+                Some((GlyphOrClass::Class(c1), GlyphOrClass::Class(c2)))
+            }
             other => Some(other),
         }
     }
 
+    /// Warn if a glyph occurs more than once in a target class with different
+    /// replacements; only the last replacement is used.
+    fn warn_conflicting_single_subs(
+        &mut self,
+        range: Range<usize>,
+        target: &GlyphClass,
+        replacement: &GlyphClass,
+    ) {
+        let mut seen = HashMap::new();
+        let mut conflicts = Vec::new();
+        for (glyph, replacement) in target.iter().zip(replacement.iter()) {
+            match seen.insert(glyph, replacement) {
+                Some(prev) if prev != replacement && !conflicts.contains(&glyph) => {
+                    conflicts.push(glyph)
+                }
+                _ => (),
+            }
+        }
+        for glyph in conflicts {
+            let name = self.reverse_glyph_map.get(&glyph).unwrap();
+            let message = format!(
+                "glyph '{name}' occurs more than once in the target class with different replacements; the last one is used"
+            );
+            self.warning(DiagnosticKind::ClassConflict, range.clone(), message);
+        }
+    }
+
     fn add_multiple_sub(&mut self, node: &typed::Gsub2) {
         let target = node.target();
         let target_id = self.resolve_glyph(&target);
@@ -580,13 +1024,48 @@ impl<'a> CompilationCtx<'a> {
             .map(|g| self.resolve_glyph_or_class(&g))
             .collect::<Vec<_>>();
         let replacement = self.resolve_glyph(&node.replacement());
+        let Some(sequences) = self.enumerate_ligature_sequences(node.range(), &target) else { return };
         let lookup = self.ensure_current_lookup_type(Kind::GsubType4);
 
-        for target in sequence_enumerator(&target) {
+        for target in sequences {
             lookup.add_gsub_type_4(target, replacement);
         }
     }
 
+    /// Expand the classes in a ligature target into each glyph sequence it matches.
+    ///
+    /// If this would produce more sequences than the configured limit, an
+    /// error is reported and we return `None`.
+    fn enumerate_ligature_sequences(
+        &mut self,
+        range: Range<usize>,
+        target: &[GlyphOrClass],
+    ) -> Option<Vec<Vec<GlyphId>>> {
+        let limit = self
+            .opts
+            .max_ligature_sequences
+            .unwrap_or(DEFAULT_MAX_LIGATURE_SEQUENCES);
+        // computed up front, so we don't allocate anything if we're over the limit
+        let count = target
+            .iter()
+            .try_fold(1_usize, |count, item| count.checked_mul(item.len()));
+        match count {
+            Some(count) if count <= limit => Some(sequence_enumerator(target)),
+            _ => {
+                let count = match count {
+                    Some(count) => count.to_string(),
+                    None => "too many".to_string(),
+                };
+                self.error(
+                    DiagnosticKind::LimitExceeded,
+                    range,
+                    format!("ligature rule matches {count} glyph sequences (limit is {limit})"),
+                );
+                None
+            }
+        }
+    }
+
     fn add_contextual_sub(&mut self, node: &typed::Gsub6) {
         let backtrack = self.resolve_backtrack_sequence(node.backtrack().items());
         let lookahead = self.resolve_lookahead_sequence(node.lookahead().items());
@@ -600,18 +1079,13 @@ impl<'a> CompilationCtx<'a> {
                     .map(|inp| self.resolve_glyph_or_class(&inp.target()))
                     .collect::<Vec<_>>();
                 let replacement = self.resolve_glyph(&rule.replacement_glyphs().next().unwrap());
+                let sequences = self.enumerate_ligature_sequences(node.range(), &target)?;
                 let lookup = self.ensure_current_lookup_type(Kind::GsubType6);
-                //FIXME: we should check that the whole sequence is not present the
-                //lookup before adding..
-                let mut to_return = None;
-                for target in sequence_enumerator(&target) {
-                    to_return = Some(
-                        lookup
-                            .as_gsub_contextual()
-                            .add_anon_gsub_type_4(target, replacement),
-                    );
-                }
-                to_return
+                Some(
+                    lookup
+                        .as_gsub_contextual()
+                        .add_anon_gsub_type_4(sequences, replacement),
+                )
             } else {
                 let target = input.items().next().unwrap().target();
                 let replacement = rule.replacements().next().unwrap();
@@ -647,6 +1121,7 @@ impl<'a> CompilationCtx<'a> {
                     let id = self.lookups.get_named(&lookup.label().text).unwrap(); // validated already
                     if matches!(id, LookupId::Gpos(_)) {
                         self.error(
+                            DiagnosticKind::InvalidStatement,
                             lookup.label().range(),
                             "Invalid lookup: expected GSUB, found GPOS",
                         );
@@ -688,7 +1163,17 @@ impl<'a> CompilationCtx<'a> {
 
     fn add_single_pos(&mut self, node: &typed::Gpos1) {
         let ids = self.resolve_glyph_or_class(&node.target());
-        let record = self.resolve_value_record(&node.value());
+        let value = node.value();
+        // unlike in a pair pos rule, a NULL value record here does nothing;
+        // we still add the rule (as feaLib does) but let the author know.
+        if let Some(null) = value.null() {
+            self.warning(
+                DiagnosticKind::NoEffect,
+                null.range(),
+                "a single positioning rule with a NULL value record has no effect",
+            );
+        }
+        let record = self.resolve_value_record(&value);
         let lookup = self.ensure_current_lookup_type(Kind::GposType1);
         for id in ids.iter() {
             lookup.add_gpos_type_1(id, record.clone());
@@ -709,9 +1194,16 @@ impl<'a> CompilationCtx<'a> {
             .unwrap_or_default()
             .for_pair_pos(in_vert_feature);
 
+        let is_class_pair =
+            (first_ids.is_class() || second_ids.is_class()) && node.enum_().is_none();
+        // class pairs are split into subtables as they are added; breaking
+        // between them here would change which pairs are matched.
+        if !is_class_pair {
+            self.break_subtable_if_full(Kind::GposType2, node.range());
+        }
         let lookup = self.ensure_current_lookup_type(Kind::GposType2);
 
-        if (first_ids.is_class() || second_ids.is_class()) && node.enum_().is_none() {
+        if is_class_pair {
             lookup.add_gpos_type_2_class(
                 first_ids.to_class().unwrap(),
                 second_ids.to_class().unwrap(),
@@ -746,11 +1238,13 @@ impl<'a> CompilationCtx<'a> {
 
     fn add_mark_to_base(&mut self, node: &typed::Gpos4) {
         let base_ids = self.resolve_glyph_or_class(&node.base());
+        self.break_subtable_if_full(Kind::GposType4, node.range());
         let _ = self.ensure_current_lookup_type(Kind::GposType4);
         for mark in node.attachments() {
             let base_anchor = self.resolve_anchor(&mark.anchor());
 
             let mark_class_node = mark.mark_class_name().expect("checked in validation");
+            self.warn_if_mark_class_is_empty(&mark_class_node);
             let class_name = mark_class_node.text().to_owned();
             let mark_class = self.mark_classes.get(&class_name).unwrap();
 
@@ -794,6 +1288,7 @@ impl<'a> CompilationCtx<'a> {
         // to that lig glyph table.
         // for each anchor point in each component, we add an anchor record
         // to that component
+        self.break_subtable_if_full(Kind::GposType5, node.range());
 
         let mut components = Vec::new();
         for component in node.ligature_components() {
@@ -813,6 +1308,7 @@ impl<'a> CompilationCtx<'a> {
                     }
                 };
                 let component_anchor = component_anchor.unwrap();
+                self.warn_if_mark_class_is_empty(&mark_class_node);
                 let class_name = mark_class_node.text();
                 let mark_class = self.mark_classes.get(class_name).unwrap();
 
@@ -856,10 +1352,12 @@ impl<'a> CompilationCtx<'a> {
     //significantly.
     fn add_mark_to_mark(&mut self, node: &typed::Gpos6) {
         let base_ids = self.resolve_glyph_or_class(&node.base());
+        self.break_subtable_if_full(Kind::GposType6, node.range());
         let _ = self.ensure_current_lookup_type(Kind::GposType6);
         for mark in node.attachments() {
             let base_anchor = self.resolve_anchor(&mark.anchor());
             let mark_class_node = mark.mark_class_name().expect("checked in validation");
+            self.warn_if_mark_class_is_empty(&mark_class_node);
             let class_name = mark_class_node.text();
             let mark_class = self.mark_classes.get(mark_class_node.text()).unwrap();
 
@@ -893,6 +1391,25 @@ impl<'a> CompilationCtx<'a> {
         }
     }
 
+    /// Warn if a mark class used in an attachment rule has no glyphs.
+    ///
+    /// This can happen if all of the class's glyphs were missing from the font,
+    /// and means the attachment will never be applied.
+    fn warn_if_mark_class_is_empty(&mut self, name: &typed::GlyphClassName) {
+        let is_empty = self
+            .mark_classes
+            .get(name.text())
+            .map(|class| class.members.iter().all(|(glyphs, _)| glyphs.len() == 0))
+            .unwrap_or(false);
+        if is_empty {
+            let msg = format!(
+                "mark class {} has no glyphs; this attachment does nothing",
+                name.text()
+            );
+            self.warning(DiagnosticKind::NoEffect, name.range(), msg);
+        }
+    }
+
     fn maybe_report_mark_class_conflict(
         &mut self,
         range: Range<usize>,
@@ -900,6 +1417,7 @@ impl<'a> CompilationCtx<'a> {
     ) {
         if let Some(PreviouslyAssignedClass { class, .. }) = maybe_err {
             self.error(
+                DiagnosticKind::ClassConflict,
                 range,
                 format!("mark class includes glyph in class '{class}', already used in lookup.",),
             );
@@ -915,8 +1433,13 @@ impl<'a> CompilationCtx<'a> {
             .map(|item| {
                 let glyphs = self.resolve_glyph_or_class(&item.target());
                 let mut lookups = Vec::new();
-                if let Some(value) = item.valuerecord() {
-                    let value = self.resolve_value_record(&value);
+                // a value record with no values has no effect, so we don't
+                // need to generate a lookup for it
+                let value = item
+                    .valuerecord()
+                    .map(|value| self.resolve_value_record(&value))
+                    .filter(|value| !value.format().is_empty());
+                if let Some(value) = value {
                     let anon_id = self
                         .ensure_current_lookup_type(Kind::GposType8)
                         .as_gpos_contextual()
@@ -928,9 +1451,17 @@ impl<'a> CompilationCtx<'a> {
                     let id = self.lookups.get_named(&lookup.label().text).unwrap();
                     if matches!(id, LookupId::Gsub(_)) {
                         self.error(
+                            DiagnosticKind::InvalidStatement,
                             lookup.label().range(),
                             "Invalid lookup type: expected GPOS, found GSUB",
                         );
+                    } else if self.lookups.is_contextual(id) {
+                        // allowed by the spec, but not handled well by shapers
+                        self.warning(
+                            DiagnosticKind::InvalidStatement,
+                            lookup.label().range(),
+                            "contextual rule references another contextual lookup",
+                        );
                     }
                     lookups.push(id);
                 }
@@ -971,60 +1502,141 @@ impl<'a> CompilationCtx<'a> {
     /// Resolve a value record, leaving zeros in place
     ///
     /// This is exposed to handle PairPos, which has special semantics for how
-    /// to interpret and handle zeros.
+    /// to interpret and handle zeros. A `<NULL>` record resolves to an empty
+    /// record with no fields set.
     fn resolve_value_record_raw(&mut self, record: &typed::ValueRecord) -> ValueRecord {
         if record.null().is_some() {
             return ValueRecord::default();
         }
 
-        if let Some(adv) = record.advance().map(|x| x.parse_signed()) {
-            let (x_advance, y_advance) = if self.vertical_feature.in_eligible_vertical_feature() {
-                (None, Some(adv))
+        if let Some(adv) = record.advance() {
+            let (adv, device) = self.resolve_metric(&adv);
+            let mut result = ValueRecord::default();
+            if self.vertical_feature.in_eligible_vertical_feature() {
+                result.y_advance = Some(adv);
+                result.y_advance_device.set(device);
             } else {
-                (Some(adv), None)
-            };
-
-            return ValueRecord {
-                x_advance,
-                y_advance,
-                ..Default::default()
-            };
-        }
-        if let Some([x_place, y_place, x_adv, y_adv]) = record.placement() {
+                result.x_advance = Some(adv);
+                result.x_advance_device.set(device);
+            }
+            return self.snap_value_record(result);
+        }
+        if let Some(metrics) = record.placement() {
+            let [x_place, y_place, x_adv, y_adv] = metrics.map(|x| self.resolve_metric(&x));
+            // validation ensures that a variable metric has no device
+            let [x_place_dev, y_place_dev, x_adv_dev, y_adv_dev] = record
+                .device()
+                .map(|devices| devices.map(|device| self.resolve_device(&device)))
+                .unwrap_or_default();
             let mut result = ValueRecord {
-                x_advance: Some(x_adv.parse_signed()),
-                y_advance: Some(y_adv.parse_signed()),
-                x_placement: Some(x_place.parse_signed()),
-                y_placement: Some(y_place.parse_signed()),
+                x_advance: Some(x_adv.0),
+                y_advance: Some(y_adv.0),
+                x_placement: Some(x_place.0),
+                y_placement: Some(y_place.0),
                 ..Default::default()
             };
-            if let Some([x_place_dev, y_place_dev, x_adv_dev, y_adv_dev]) = record.device() {
-                result.x_placement_device.set(x_place_dev.compile());
-                result.y_placement_device.set(y_place_dev.compile());
-                result.x_advance_device.set(x_adv_dev.compile());
-                result.y_advance_device.set(y_adv_dev.compile());
-            }
-            return result;
+            result.x_placement_device.set(x_place.1.or(x_place_dev));
+            result.y_placement_device.set(y_place.1.or(y_place_dev));
+            result.x_advance_device.set(x_adv.1.or(x_adv_dev));
+            result.y_advance_device.set(y_adv.1.or(y_adv_dev));
+            return self.snap_value_record(result);
         }
         if let Some(name) = record.named() {
             //FIXME:
-            self.warning(name.range(), "named value records not implemented yet");
+            self.warning(
+                DiagnosticKind::Unsupported,
+                name.range(),
+                "named value records not implemented yet",
+            );
         }
 
         ValueRecord::default()
     }
 
+    /// Round the values in a value record, if [`Opts::snap_value_records`] is set.
+    fn snap_value_record(&self, mut record: ValueRecord) -> ValueRecord {
+        let Some(grid) = self.opts.snap_value_records.filter(|grid| *grid > 1) else {
+            return record;
+        };
+        let grid = i32::from(grid);
+        // a value near the ends of the range may round to a multiple that
+        // doesn't fit; use the closest one that does.
+        let max = i32::from(i16::MAX) / grid * grid;
+        let min = i32::from(i16::MIN) / grid * grid;
+        let snap = |value: i16| {
+            let value = i32::from(value);
+            let snapped = value.signum() * ((value.abs() + grid / 2) / grid) * grid;
+            snapped.clamp(min, max) as i16
+        };
+        for value in [
+            &mut record.x_placement,
+            &mut record.y_placement,
+            &mut record.x_advance,
+            &mut record.y_advance,
+        ] {
+            *value = value.map(snap);
+        }
+        record
+    }
+
+    /// Resolve a number in a value record or anchor.
+    ///
+    /// A variable metric resolves to its value at the default location and a
+    /// device table that refers to its deltas in the variation store; there
+    /// is no device if the value is the same at every location.
+    fn resolve_metric(&self, metric: &typed::Scalar) -> (i16, Option<Device>) {
+        match metric {
+            typed::Scalar::Number(number) => (number.parse_signed(), None),
+            typed::Scalar::Metric(metric) => (metric.parse(), None),
+            typed::Scalar::Variable(metric) => {
+                // validation ensures we have an axis map, and a default value
+                let value = self.variable_value(metric).expect("validated");
+                let device = self
+                    .variation_store
+                    .as_ref()
+                    .and_then(|store| store.variation_index(&value));
+                (value.default, device)
+            }
+        }
+    }
+
+    /// The value of a variable metric at each location, and its deltas.
+    fn variable_value(&self, metric: &typed::VariableMetric) -> Option<VariableValue> {
+        let axis_map = self.opts.axis_map.as_ref()?;
+        let masters = metric
+            .location_values()
+            .map(|value| {
+                let location = value
+                    .location()
+                    .items()
+                    .map(|item| (item.axis().to_raw(), item.value().parse()))
+                    .collect();
+                (location, value.value().parse_signed())
+            })
+            .collect::<Vec<_>>();
+        VariableValue::new(axis_map, &masters)
+    }
+
     fn define_glyph_class(&mut self, class_decl: typed::GlyphClassDef) {
         let name = class_decl.class_name();
-        let glyphs = if let Some(class) = class_decl.class_def() {
-            self.resolve_glyph_class_literal(&class)
+        let (glyphs, skipped) = if let Some(class) = class_decl.class_def() {
+            let skipped = self.literal_has_skipped_glyphs(&class);
+            (self.resolve_glyph_class_literal(&class), skipped)
         } else if let Some(alias) = class_decl.class_alias() {
-            self.resolve_named_glyph_class(&alias)
+            let skipped = self.classes_with_skipped_glyphs.contains(alias.text());
+            (self.resolve_named_glyph_class(&alias), skipped)
         } else {
             panic!("write more code I guess");
         };
 
+        if skipped {
+            self.classes_with_skipped_glyphs.insert(name.text().clone());
+        } else {
+            self.classes_with_skipped_glyphs.remove(name.text());
+        }
         self.glyph_class_defs.insert(name.text().clone(), glyphs);
+        self.unused_glyph_classes
+            .insert(name.text().clone(), name.range());
     }
 
     fn define_mark_class(&mut self, class_decl: typed::MarkClassDef) {
@@ -1033,6 +1645,7 @@ impl<'a> CompilationCtx<'a> {
 
         let anchor = self.resolve_anchor(&class_decl.anchor());
         let class_name = class_decl.mark_class_name();
+        self.mark_class_glyphs.remove(class_name.text());
         self.mark_classes
             .entry(class_name.text().clone())
             .or_default()
@@ -1043,6 +1656,16 @@ impl<'a> CompilationCtx<'a> {
     fn add_feature(&mut self, feature: typed::Feature) {
         let tag = feature.tag();
         let tag_raw = tag.to_raw();
+        if self.opts.warn_unregistered_features
+            && !tags::is_registered_feature(tag_raw)
+            && !self.opts.private_feature_tags.contains(&tag_raw)
+        {
+            self.warning(
+                DiagnosticKind::UnregisteredFeature,
+                tag.range(),
+                format!("'{tag_raw}' is not a registered feature tag"),
+            );
+        }
         self.start_feature(tag);
         if tag_raw == tags::AALT {
             self.resolve_aalt_feature(&feature);
@@ -1056,69 +1679,348 @@ impl<'a> CompilationCtx<'a> {
             for item in feature.statements() {
                 self.resolve_statement(item);
             }
+            if tag_raw == tags::RTLM || tag_raw == tags::RTLA {
+                self.lint_mirroring_feature(tag_raw, feature.statements());
+            }
         }
         self.end_feature();
     }
 
-    fn resolve_aalt_feature(&mut self, feature: &typed::Feature) {
-        let mut aalt = AaltFeature::default();
-        for item in feature.statements() {
-            if let Some(node) = typed::Gsub1::cast(item) {
-                let Some((target, replacement)) = self.resolve_single_sub_glyphs(&node) else { continue };
-                aalt.extend(target.iter().zip(replacement.into_iter_for_target()))
-            } else if let Some(node) = typed::Gsub3::cast(item) {
-                let target = self.resolve_glyph(&node.target());
-                let alts = self.resolve_glyph_class(&node.alternates());
-                aalt.extend(std::iter::repeat(target).zip(alts.iter()));
-            } else if let Some(feature) = typed::FeatureRef::cast(item) {
-                aalt.add_feature_reference(feature.feature().to_raw());
-            }
+    /// `true` if this feature can be compiled separately from the features
+    /// around it.
+    ///
+    /// This is the case if it does not read or modify any state that could be
+    /// changed by another feature. See [`Opts::parallel`].
+    fn is_independent_feature(&self, feature: &typed::Feature) -> bool {
+        let tag = feature.tag().to_raw();
+        if tag == tags::AALT
+            || tag == tags::SIZE
+            || tags::is_stylistic_set(tag)
+            || tags::is_character_variant(tag)
+        {
+            return false;
         }
-        self.aalt = Some(aalt);
+        // included nodes are shared, and their positions are only correct for
+        // one traversal at a time
+        if !self.source_map.is_contiguous(feature.range()) {
+            return false;
+        }
+        let defines_class = feature.statements().any(|item| {
+            typed::GlyphClassDef::cast(item).is_some() || typed::MarkClassDef::cast(item).is_some()
+        });
+        !defines_class
+            && !feature.node().iter_tokens().any(|token| {
+                // named lookups, and mark attachment classes, which are
+                // numbered in order of first use
+                matches!(token.kind, Kind::LookupKw | Kind::MarkAttachmentTypeKw)
+            })
     }
 
-    fn resolve_stylistic_set_feature(&mut self, tag: Tag, feature: &typed::Feature) {
-        let mut names = Vec::new();
-        if let Some(feature_name) = feature.stylistic_set_feature_names() {
-            for name_spec in feature_name.statements() {
-                let resolved = self.resolve_name_spec(&name_spec);
-                names.push(resolved);
-            }
+    /// Compile a sequence of independent features, on multiple threads if the
+    /// `parallel` feature is enabled.
+    ///
+    /// Each thread compiles some of the features with its own copy of this
+    /// context. The results are then merged back in source order, so that
+    /// lookups, mark filtering sets and diagnostics are numbered and ordered
+    /// exactly as if the features were compiled one by one.
+    fn add_independent_features(&mut self, features: Vec<typed::Feature>) {
+        // a file that is included twice is only parsed once, and the
+        // positions in its shared nodes cannot be correct for both copies at
+        // the same time.
+        let mut locations = std::collections::HashSet::new();
+        if !features
+            .iter()
+            .all(|feature| locations.insert(self.source_map.resolve_range(feature.range())))
+        {
+            features
+                .into_iter()
+                .for_each(|feature| self.add_feature(feature));
+            return;
         }
-        if !names.is_empty() {
-            self.tables.stylistic_sets.insert(tag, names);
+        for fork in self.compile_in_forks(&features) {
+            self.merge_fork(fork);
         }
-        for item in feature
-            .statements()
-            .filter(|node| node.kind() != Kind::FeatureNamesKw)
-        {
-            self.resolve_statement(item);
+    }
+
+    #[cfg(feature = "parallel")]
+    fn compile_in_forks(&self, features: &[typed::Feature]) -> Vec<CompilationCtx<'a>> {
+        use rayon::prelude::*;
+
+        let chunk_size = features.len().div_ceil(rayon::current_num_threads());
+        features
+            .par_chunks(chunk_size)
+            .map(|chunk| self.compile_in_fork(chunk))
+            .collect()
+    }
+
+    // without rayon, we use a single fork; the result is the same.
+    #[cfg(not(feature = "parallel"))]
+    fn compile_in_forks(&self, features: &[typed::Feature]) -> Vec<CompilationCtx<'a>> {
+        vec![self.compile_in_fork(features)]
+    }
+
+    fn compile_in_fork(&self, features: &[typed::Feature]) -> CompilationCtx<'a> {
+        let mut fork = self.fork();
+        for feature in features {
+            fork.add_feature(feature.clone());
         }
+        fork
     }
 
-    fn resolve_character_variant_feature(&mut self, tag: Tag, feature: &typed::Feature) {
-        if let Some(cv_params) = feature.character_variant_params() {
-            let mut params = CvParams::default();
-            if let Some(node) = cv_params.feat_ui_label_name() {
-                params.feat_ui_label_name = node
-                    .statements()
-                    .map(|x| self.resolve_name_spec(&x))
-                    .collect();
-            }
-            if let Some(node) = cv_params.feat_tooltip_text_name() {
-                params.feat_ui_tooltip_text_name = node
-                    .statements()
-                    .map(|x| self.resolve_name_spec(&x))
-                    .collect();
-            }
-            if let Some(node) = cv_params.sample_text_name() {
-                params.samle_text_name = node
-                    .statements()
-                    .map(|x| self.resolve_name_spec(&x))
-                    .collect();
+    /// A copy of this context, for compiling statements that follow the ones
+    /// we have already compiled.
+    ///
+    /// `source_map` is for a tree made by appending to the one we have been
+    /// compiling (see `ParseTree::append`), so that the positions we have
+    /// already seen are still valid. Unlike [`fork`](Self::fork) the copy has
+    /// all of our state, including lookups and features, but none of our
+    /// diagnostics.
+    pub(crate) fn fork_for_tree<'b>(&self, source_map: &'b SourceMap) -> CompilationCtx<'b>
+    where
+        'a: 'b,
+    {
+        CompilationCtx {
+            source_map,
+            errors: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    /// A copy of this context, for compiling independent features.
+    ///
+    /// The copy has all of our definitions (language systems, glyph and mark
+    /// classes, named anchors, and so on), but no lookups, features or
+    /// diagnostics; its results are added back with [`merge_fork`].
+    ///
+    /// [`merge_fork`]: Self::merge_fork
+    fn fork(&self) -> Self {
+        CompilationCtx {
+            glyph_map: self.glyph_map,
+            reverse_glyph_map: self.reverse_glyph_map.clone(),
+            source_map: self.source_map,
+            // diagnostics are sent when they are merged, to keep their order
+            opts: Opts {
+                diagnostic_sink: None,
+                ..self.opts.clone()
+            },
+            errors: Vec::new(),
+            tables: Tables::default(),
+            features: Default::default(),
+            default_lang_systems: self.default_lang_systems.clone(),
+            seen_block: self.seen_block,
+            lookups: Default::default(),
+            lookup_flags: Default::default(),
+            first_ignore_flag: None,
+            active_feature: None,
+            vertical_feature: Default::default(),
+            script: None,
+            glyph_class_defs: self.glyph_class_defs.clone(),
+            classes_with_skipped_glyphs: self.classes_with_skipped_glyphs.clone(),
+            unused_glyph_classes: self.unused_glyph_classes.clone(),
+            mark_classes: self.mark_classes.clone(),
+            mark_class_glyphs: self.mark_class_glyphs.clone(),
+            anchor_defs: self.anchor_defs.clone(),
+            device_defs: self.device_defs.clone(),
+            mark_attach_class_id: self.mark_attach_class_id.clone(),
+            mark_filter_sets: self.mark_filter_sets.clone(),
+            size: None,
+            aalt: None,
+            required_features: Default::default(),
+            condition_sets: self.condition_sets.clone(),
+            feature_variations: Default::default(),
+            variation_store: self.variation_store.clone(),
+        }
+    }
+
+    /// Add the results of a [`fork`](Self::fork) to this context.
+    ///
+    /// The fork's lookups are appended to ours, and the ids in its features
+    /// are updated to match.
+    fn merge_fork(&mut self, fork: CompilationCtx) {
+        for diagnostic in fork.errors {
+            self.report(diagnostic);
+        }
+
+        // the fork started with our mark filtering sets; any new sets are
+        // numbered after ours, in the order the fork first used them.
+        let mut fork_sets = fork
+            .mark_filter_sets
+            .into_iter()
+            .map(|(set, id)| (id, set))
+            .collect::<Vec<_>>();
+        fork_sets.sort_unstable();
+        let mut set_ids = HashMap::new();
+        for (fork_id, set) in fork_sets {
+            let next_id = self.mark_filter_sets.len().try_into().unwrap();
+            let id = *self.mark_filter_sets.entry(set).or_insert(next_id);
+            set_ids.insert(fork_id, id);
+        }
+        let mut lookups = fork.lookups;
+        lookups.remap_mark_filter_sets(|id| set_ids[&id]);
+
+        let remap = self.lookups.merge(lookups);
+        for (key, ids) in fork.features {
+            self.features
+                .entry(key)
+                .or_default()
+                .extend(ids.into_iter().map(&remap));
+        }
+        self.required_features.extend(fork.required_features);
+        if self.first_ignore_flag.is_none() {
+            self.first_ignore_flag = fork.first_ignore_flag;
+        }
+        self.unused_glyph_classes
+            .retain(|name, _| fork.unused_glyph_classes.contains_key(name));
+    }
+
+    /// Warn about rules in `rtlm` or `rtla` that are not simple substitutions.
+    ///
+    /// These features replace glyphs with their mirrored or right-to-left
+    /// forms, which only needs single substitution (and, in `rtla`, alternate
+    /// substitution). Anything else is allowed, but is likely a mistake.
+    ///
+    /// Only the rule types are checked, not the lookupflags. The one flag
+    /// that relates to writing direction, `RightToLeft`, only affects cursive
+    /// attachment in GPOS, so it has no meaning for these lookups and there
+    /// is nothing useful to check.
+    fn lint_mirroring_feature<'b>(
+        &mut self,
+        tag: Tag,
+        statements: impl Iterator<Item = &'b NodeOrToken>,
+    ) {
+        let allow_alternates = tag == tags::RTLA;
+        let message = if allow_alternates {
+            "rtla should only contain single or alternate substitutions"
+        } else {
+            "rtlm should only contain single substitutions"
+        };
+        for item in statements {
+            if let Some(lookup) = typed::LookupBlock::cast(item) {
+                self.lint_mirroring_feature(tag, lookup.statements());
+            } else if let Some(lookup) = typed::LookupRef::cast(item) {
+                let label = lookup.label();
+                let Some(id) = self.lookups.get_named(&label.text) else { continue };
+                let is_allowed = match self.lookups.get_gsub_lookup(&id) {
+                    Some(SubstitutionLookup::Single(_)) => true,
+                    Some(SubstitutionLookup::Alternate(_)) => allow_alternates,
+                    _ => false,
+                };
+                if !is_allowed {
+                    self.warning(DiagnosticKind::InvalidStatement, label.range(), message);
+                }
+            } else if item.kind().is_rule() {
+                let is_allowed = match item.kind() {
+                    Kind::GsubType1 => true,
+                    Kind::GsubType3 => allow_alternates,
+                    _ => false,
+                };
+                if !is_allowed {
+                    self.warning(DiagnosticKind::InvalidStatement, item.range(), message);
+                }
+            }
+        }
+    }
+
+    fn define_condition_set(&mut self, node: typed::ConditionSet) {
+        // without an axis map there is nothing we can do; this is reported
+        // during validation.
+        let Some(axis_map) = self.opts.axis_map.as_ref() else { return };
+        let conditions = node
+            .conditions()
+            .filter_map(|condition| {
+                let tag = condition.tag().to_raw();
+                Some(AxisCondition {
+                    axis_index: axis_map.axis_index(tag)?,
+                    min: axis_map.normalize(tag, condition.min().parse())?,
+                    max: axis_map.normalize(tag, condition.max().parse())?,
+                })
+            })
+            .collect();
+        self.condition_sets
+            .insert(node.label().text.clone(), conditions);
+    }
+
+    /// A `variation` block is compiled like a feature block, but its lookups
+    /// are only applied when the conditions in the conditionset are met.
+    ///
+    /// If the feature has no unconditional lookups for a given language system,
+    /// an empty feature is used as the default.
+    fn add_feature_variation(&mut self, node: typed::FeatureVariation) {
+        let name = node.condition_set().text.clone();
+        let Some(conditions) = self.condition_sets.get(&name).cloned() else { return };
+        self.start_feature(node.tag());
+        for item in node.statements() {
+            self.resolve_statement(item);
+        }
+        let active = self.finish_active_feature();
+
+        let idx = match self.feature_variations.iter().position(|(x, _)| *x == name) {
+            Some(idx) => idx,
+            None => {
+                let variation = FeatureVariation {
+                    conditions,
+                    features: Default::default(),
+                };
+                self.feature_variations.push((name, variation));
+                self.feature_variations.len() - 1
+            }
+        };
+        active.add_to_features(&mut self.feature_variations[idx].1.features);
+    }
+
+    fn resolve_aalt_feature(&mut self, feature: &typed::Feature) {
+        let mut aalt = AaltFeature::default();
+        for item in feature.statements() {
+            if let Some(node) = typed::Gsub1::cast(item) {
+                let Some((target, replacement)) = self.resolve_single_sub_glyphs(&node) else { continue };
+                aalt.extend(target.iter().zip(replacement.into_iter_for_target()))
+            } else if let Some(node) = typed::Gsub3::cast(item) {
+                let target = self.resolve_glyph(&node.target());
+                let alts = self.resolve_glyph_class(&node.alternates());
+                aalt.extend(std::iter::repeat(target).zip(alts.iter()));
+            } else if let Some(feature) = typed::FeatureRef::cast(item) {
+                aalt.add_feature_reference(feature.feature().to_raw());
+            }
+        }
+        self.aalt = Some(aalt);
+    }
+
+    fn resolve_stylistic_set_feature(&mut self, tag: Tag, feature: &typed::Feature) {
+        let mut names = Vec::new();
+        if let Some(feature_name) = feature.stylistic_set_feature_names() {
+            for name_spec in feature_name.statements() {
+                let resolved = self.resolve_name_spec(&name_spec);
+                names.push(resolved);
+            }
+        }
+        if !names.is_empty() {
+            self.tables.stylistic_sets.insert(tag, names);
+        }
+        for item in feature
+            .statements()
+            .filter(|node| node.kind() != Kind::FeatureNamesKw)
+        {
+            self.resolve_statement(item);
+        }
+    }
+
+    fn resolve_character_variant_feature(&mut self, tag: Tag, feature: &typed::Feature) {
+        if let Some(cv_params) = feature.character_variant_params() {
+            let mut params = CvParams::default();
+            if let Some(node) = cv_params.feat_ui_label_name() {
+                params.feat_ui_label_name = node
+                    .statements()
+                    .map(|x| self.resolve_name_spec(&x))
+                    .collect();
+            }
+            if let Some(node) = cv_params.feat_tooltip_text_name() {
+                params.feat_ui_tooltip_text_name = node
+                    .statements()
+                    .map(|x| self.resolve_name_spec(&x))
+                    .collect();
             }
             if let Some(node) = cv_params.sample_text_name() {
-                params.samle_text_name = node
+                params.sample_text_name = node
                     .statements()
                     .map(|x| self.resolve_name_spec(&x))
                     .collect();
@@ -1190,6 +2092,7 @@ impl<'a> CompilationCtx<'a> {
             typed::Table::Head(table) => self.resolve_head(&table),
             typed::Table::Os2(table) => self.resolve_os2(&table),
             typed::Table::Stat(table) => self.resolve_stat(&table),
+            typed::Table::Meta(table) => self.resolve_meta(&table),
             _ => (),
         }
     }
@@ -1239,15 +2142,23 @@ impl<'a> CompilationCtx<'a> {
 
     fn resolve_os2(&mut self, table: &typed::Os2Table) {
         let mut os2 = super::tables::Os2Builder::default();
+        // fields that require a table version > 0, and where they were set
+        let mut versioned_fields = Vec::new();
         for item in table.statements() {
-            match item {
+            match &item {
                 typed::Os2TableItem::Number(val) => {
                     let value = val.number().parse_unsigned().unwrap();
                     match val.keyword().text.as_str() {
                         "WeightClass" => os2.us_weight_class = value,
                         "WidthClass" => os2.us_width_class = value,
-                        "LowerOpSize" => os2.us_lower_optical_point_size = Some(value),
-                        "UpperOpSize" => os2.us_upper_optical_point_size = Some(value),
+                        "LowerOpSize" => {
+                            os2.us_lower_optical_point_size = Some(value);
+                            versioned_fields.push((5, item.range()));
+                        }
+                        "UpperOpSize" => {
+                            os2.us_upper_optical_point_size = Some(value);
+                            versioned_fields.push((5, item.range()));
+                        }
                         "FSType" => os2.fs_type = value,
                         _ => unreachable!("checked at parse time"),
                     }
@@ -1258,8 +2169,14 @@ impl<'a> CompilationCtx<'a> {
                         Kind::TypoAscenderKw => os2.s_typo_ascender = value,
                         Kind::TypoDescenderKw => os2.s_typo_descender = value,
                         Kind::TypoLineGapKw => os2.s_typo_line_gap = value,
-                        Kind::XHeightKw => os2.sx_height = value,
-                        Kind::CapHeightKw => os2.s_cap_height = value,
+                        Kind::XHeightKw => {
+                            os2.sx_height = value;
+                            versioned_fields.push((2, item.range()));
+                        }
+                        Kind::CapHeightKw => {
+                            os2.s_cap_height = value;
+                            versioned_fields.push((2, item.range()));
+                        }
                         Kind::WinAscentKw => os2.us_win_ascent = value as u16,
                         Kind::WinDescentKw => os2.us_win_descent = value as u16,
                         _ => unreachable!("checked at parse time"),
@@ -1281,6 +2198,7 @@ impl<'a> CompilationCtx<'a> {
                             os2.code_page_range
                                 .add_code_page(val.parse_unsigned().unwrap());
                         }
+                        versioned_fields.push((1, item.range()));
                     }
                     _ => unreachable!("checked at parse time"),
                 },
@@ -1292,9 +2210,51 @@ impl<'a> CompilationCtx<'a> {
                 }
             }
         }
+
+        os2.version = match self.opts.os2_version {
+            Some(version) => {
+                if version > MAX_OS2_VERSION {
+                    self.error(
+                        DiagnosticKind::InvalidValue,
+                        table.tag().range(),
+                        format!("OS/2 version {version} was requested, but the latest version is {MAX_OS2_VERSION}"),
+                    );
+                }
+                for (required, range) in versioned_fields {
+                    if required > version {
+                        self.error(
+                            DiagnosticKind::InvalidValue,
+                            range,
+                            format!("field requires OS/2 version {required}, but version {version} was requested"),
+                        );
+                    }
+                }
+                version
+            }
+            None => versioned_fields
+                .iter()
+                .map(|(version, _)| *version)
+                .max()
+                .unwrap_or(0),
+        };
         self.tables.os2 = Some(os2);
     }
 
+    fn resolve_meta(&mut self, table: &typed::MetaTable) {
+        let mut meta = MetaBuilder::default();
+        for record in table.statements() {
+            let value = record.value().as_str().trim_matches('"');
+            // validated already
+            let languages = MetaBuilder::parse_language_list(value).unwrap_or_default();
+            if record.is_design_languages() {
+                meta.design_languages = languages;
+            } else {
+                meta.supported_languages = languages;
+            }
+        }
+        self.tables.meta = Some(meta);
+    }
+
     fn resolve_stat(&mut self, table: &typed::StatTable) {
         let mut stat = super::tables::StatBuilder {
             name: super::tables::StatFallbackName::Id(u16::MAX),
@@ -1423,18 +2383,83 @@ impl<'a> CompilationCtx<'a> {
 
     fn resolve_vmtx(&mut self, table: &typed::VmtxTable) {
         let mut vmtx = super::tables::VmtxBuilder::default();
+        let mut entries = Vec::new();
         for item in table.statements() {
             let glyph = self.resolve_glyph(&item.glyph());
             let value = item.value().parse_signed();
-            match item.keyword().kind {
+            let kind = item.keyword().kind;
+            match kind {
                 Kind::VertAdvanceYKw => vmtx.advances_y.push((glyph, value)),
                 Kind::VertOriginYKw => vmtx.origins_y.push((glyph, value)),
                 _ => unreachable!(),
             }
+            entries.push((item.range(), kind, glyph, value));
+        }
+        if self.opts.includes_table(Tag::new(b"vmtx")) {
+            self.check_vmtx_metrics(&entries);
         }
         self.tables.vmtx = Some(vmtx);
     }
 
+    /// Check that the values in a `vmtx` block can be applied to the font's metrics.
+    ///
+    /// A vmtx table must have metrics for every glyph, so it can only be
+    /// generated if the font's existing metrics are available.
+    fn check_vmtx_metrics(&mut self, entries: &[(Range<usize>, Kind, GlyphId, i16)]) {
+        let bake_origins = self.opts.bake_vertical_origins;
+        let Some((first, ..)) = entries
+            .iter()
+            .find(|(_, kind, ..)| *kind == Kind::VertAdvanceYKw || bake_origins)
+        else {
+            return;
+        };
+        let Some(info) = self
+            .font_info()
+            .filter(|info| info.vertical_metrics.is_some())
+        else {
+            self.warning(
+                DiagnosticKind::NoEffect,
+                first.clone(),
+                "vmtx is not generated without the font's vertical metrics (see Opts::font_info)",
+            );
+            return;
+        };
+        let metrics = info.vertical_metrics.as_ref().unwrap();
+        let mut problems = Vec::new();
+        for (range, kind, glyph, value) in entries {
+            let problem = match kind {
+                _ if metrics.advance(*glyph).is_none() => {
+                    Some("glyph is not in the font's vmtx".to_string())
+                }
+                Kind::VertAdvanceYKw
+                    if !metrics.has_long_metric(*glyph)
+                        && metrics.advance(*glyph) != Some(*value as u16) =>
+                {
+                    Some(format!(
+                        "glyph shares the font's last vertical advance ({}), which cannot be changed",
+                        metrics.advance(*glyph).unwrap()
+                    ))
+                }
+                Kind::VertOriginYKw if bake_origins => match info.glyph_y_max.get(glyph) {
+                    None => Some(
+                        "cannot compute the top side bearing without the glyph's bounds".into(),
+                    ),
+                    Some(y_max) if value.checked_sub(*y_max).is_none() => {
+                        Some("top side bearing is out of range".into())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(problem) = problem {
+                problems.push((range.clone(), problem));
+            }
+        }
+        for (range, message) in problems {
+            self.error(DiagnosticKind::InvalidValue, range, message);
+        }
+    }
+
     fn resolve_gdef(&mut self, table: &typed::GdefTable) {
         let mut gdef = super::tables::GdefBuilder::default();
         for statement in table.statements() {
@@ -1490,7 +2515,7 @@ impl<'a> CompilationCtx<'a> {
                             gdef.add_glyph_class(self.resolve_glyph_class(&class), id)
                         {
                             let bad_glyph_name = self.reverse_glyph_map.get(&bad_glyph).unwrap();
-                            self.error(class.range(), format!("class includes glyph '{bad_glyph_name}', already in class {old_class}"));
+                            self.error(DiagnosticKind::ClassConflict, class.range(), format!("class includes glyph '{bad_glyph_name}', already in class {old_class}"));
                         }
                     }
                 }
@@ -1501,6 +2526,8 @@ impl<'a> CompilationCtx<'a> {
 
     fn resolve_head(&mut self, table: &typed::HeadTable) {
         let mut head = super::tables::HeadBuilder::default();
+        // if there are multiple statements the last one wins; the others are
+        // reported during validation
         let font_rev = table.statements().last().unwrap().value();
         head.font_revision = font_rev.parse_fixed();
         self.tables.head = Some(head);
@@ -1532,6 +2559,11 @@ impl<'a> CompilationCtx<'a> {
     }
 
     fn resolve_lookup_ref(&mut self, lookup: typed::LookupRef) {
+        // a lookup reference ends any current anonymous lookup, so rules
+        // that follow it go into a new lookup (this matches feaLib)
+        if let Some((id, _name)) = self.lookups.finish_current() {
+            self.add_lookup_to_current_feature_if_present(id);
+        }
         let id = self
             .lookups
             .get_named(&lookup.label().text)
@@ -1540,9 +2572,8 @@ impl<'a> CompilationCtx<'a> {
     }
 
     fn resolve_lookup_block(&mut self, lookup: typed::LookupBlock) {
-        self.start_lookup_block(lookup.tag());
+        self.start_lookup_block(lookup.tag(), lookup.use_extension().is_some());
 
-        //let use_extension = lookup.use_extension().is_some();
         for item in lookup.statements() {
             self.resolve_statement(item);
         }
@@ -1561,7 +2592,7 @@ impl<'a> CompilationCtx<'a> {
         } else if let Some(glyph_def) = typed::MarkClassDef::cast(item) {
             self.define_mark_class(glyph_def);
         } else if item.kind() == Kind::SubtableNode {
-            self.add_subtable_break();
+            self.add_subtable_break(item.range());
         } else if let Some(lookup) = typed::LookupRef::cast(item) {
             self.resolve_lookup_ref(lookup);
         } else if let Some(lookup) = typed::LookupBlock::cast(item) {
@@ -1571,61 +2602,89 @@ impl<'a> CompilationCtx<'a> {
         } else if let Some(rule) = typed::GposStatement::cast(item) {
             self.add_gpos_statement(rule)
         } else {
-            let span = match item {
-                NodeOrToken::Token(t) => t.range(),
-                NodeOrToken::Node(node) => {
-                    let range = node.range();
-                    let end = range.end.min(range.start + 16);
-                    range.start..end
-                }
-            };
-            self.error(span, format!("unhandled statement: '{}'", item.kind()));
+            self.unknown_item(item, format!("unhandled statement: '{}'", item.kind()));
+        }
+    }
+
+    fn define_named_device(&mut self, device_def: typed::DeviceDef) {
+        let device = self.resolve_device(&device_def.device());
+        self.device_defs
+            .insert(device_def.name().text.clone(), device);
+    }
+
+    /// Compile a device, or look up a named device.
+    ///
+    /// Uses of the same named device produce identical tables, which are
+    /// shared when the font is serialized.
+    fn resolve_device(&mut self, device: &typed::Device) -> Option<Device> {
+        let Some(name) = device.name() else { return device.compile() };
+        match self.device_defs.get(&name.text) {
+            Some(device) => device.clone(),
+            None => {
+                // this should have been caught during validation
+                self.error(
+                    DiagnosticKind::UndefinedName,
+                    name.range(),
+                    format!("device '{}' is not defined", name.text),
+                );
+                None
+            }
         }
     }
 
     fn define_named_anchor(&mut self, anchor_def: typed::AnchorDef) {
         let anchor_block = anchor_def.anchor();
         let name = anchor_def.name();
-        let anchor = match self.resolve_anchor(&anchor_block) {
-            Some(a @ AnchorTable::Format1(_) | a @ AnchorTable::Format2(_)) => a,
-            Some(_) => {
-                return self.error(
-                    anchor_block.range(),
-                    "named anchor definition can only be in format A or B",
-                )
-            }
-            None => return,
-        };
+        let Some(anchor) = self.resolve_anchor(&anchor_block) else { return };
         if let Some(_prev) = self
             .anchor_defs
             .insert(name.text.clone(), (anchor, anchor_def.range().start))
         {
-            self.error(name.range(), "duplicate anchor definition");
+            self.error(
+                DiagnosticKind::DuplicateDefinition,
+                name.range(),
+                "duplicate anchor definition",
+            );
         }
     }
 
     fn resolve_anchor(&mut self, item: &typed::Anchor) -> Option<AnchorTable> {
-        if let Some((x, y)) = item.coords().map(|(x, y)| (x.parse(), y.parse())) {
+        if let Some((x, y)) = item.coords() {
+            let ((x, x_var_device), (y, y_var_device)) =
+                (self.resolve_metric(&x), self.resolve_metric(&y));
+            // validation ensures that a variable coordinate has no device
+            // and no contourpoint
+            let (x_device, y_device) = match item.devices() {
+                Some((x_coord, y_coord)) => {
+                    (self.resolve_device(&x_coord), self.resolve_device(&y_coord))
+                }
+                None => (None, None),
+            };
+            let (x_device, y_device) = (x_var_device.or(x_device), y_var_device.or(y_device));
             if let Some(point) = item.contourpoint() {
                 match point.parse_unsigned() {
                     Some(point) => return Some(AnchorTable::format_2(x, y, point)),
                     None => panic!("negative contourpoint, go fix your parser"),
                 }
-            } else if let Some((x_coord, y_coord)) = item.devices() {
-                return Some(AnchorTable::format_3(
-                    x,
-                    y,
-                    x_coord.compile(),
-                    y_coord.compile(),
-                ));
+            } else if item.devices().is_some() || x_device.is_some() || y_device.is_some() {
+                return Some(AnchorTable::format_3(x, y, x_device, y_device));
             } else {
                 return Some(AnchorTable::format_1(x, y));
             }
         } else if let Some(name) = item.name() {
+            // this is a cheap copy, since a named anchor can only have devices
+            // for variable coordinates; identical anchors are written once
+            // when the table is serialized.
             match self.anchor_defs.get(&name.text) {
                 Some((anchor, pos)) if *pos < item.range().start => return Some(anchor.clone()),
-                _ => {
-                    self.error(name.range(), "anchor is not defined");
+                Some(_) => {
+                    let msg = format!("anchor '{}' is defined after its use here", name.text);
+                    self.error(DiagnosticKind::InvalidStatement, name.range(), msg);
+                    return None;
+                }
+                None => {
+                    let msg = format!("anchor '{}' is not defined", name.text);
+                    self.error(DiagnosticKind::UndefinedName, name.range(), msg);
                     return None;
                 }
             }
@@ -1665,14 +2724,30 @@ impl<'a> CompilationCtx<'a> {
     }
 
     fn resolve_glyph_class_literal(&mut self, class: &typed::GlyphClassLiteral) -> GlyphClass {
+        // a literal that only wraps a named class (e.g. '[@CLASS]') can share
+        // the named class's glyphs instead of copying them.
+        let mut items = class.items();
+        if let (Some(item), None) = (items.next(), items.next()) {
+            if let Some(alias) = typed::GlyphClassName::cast(item) {
+                return self.resolve_named_glyph_class(&alias);
+            }
+        }
+
         let mut glyphs = Vec::new();
         for item in class.items() {
-            if let Some(id) =
-                typed::GlyphName::cast(item).map(|name| self.resolve_glyph_name(&name))
-            {
-                glyphs.push(id);
-            } else if let Some(id) = typed::Cid::cast(item).map(|cid| self.resolve_cid(&cid)) {
-                glyphs.push(id);
+            if let Some(name) = typed::GlyphName::cast(item) {
+                // if we're skipping missing glyphs, validation has warned
+                if self.opts.skip_missing_class_glyphs && self.glyph_map.get(name.text()).is_none()
+                {
+                    continue;
+                }
+                glyphs.push(self.resolve_glyph_name(&name));
+            } else if let Some(cid) = typed::Cid::cast(item) {
+                if self.opts.skip_missing_class_glyphs && self.glyph_map.get(&cid.parse()).is_none()
+                {
+                    continue;
+                }
+                glyphs.push(self.resolve_cid(&cid));
             } else if let Some(range) = typed::GlyphRange::cast(item) {
                 self.add_glyphs_from_range(&range, &mut glyphs);
             } else if let Some(alias) = typed::GlyphClassName::cast(item) {
@@ -1684,23 +2759,69 @@ impl<'a> CompilationCtx<'a> {
         glyphs.into()
     }
 
+    /// `true` if glyphs not in the font were removed from this class.
+    ///
+    /// This only happens if [`Opts::skip_missing_class_glyphs`] is set.
+    fn has_skipped_glyphs(&self, item: &typed::GlyphOrClass) -> bool {
+        match item {
+            typed::GlyphOrClass::Class(class) => self.literal_has_skipped_glyphs(class),
+            typed::GlyphOrClass::NamedClass(name) => {
+                self.classes_with_skipped_glyphs.contains(name.text())
+            }
+            _ => false,
+        }
+    }
+
+    fn literal_has_skipped_glyphs(&self, class: &typed::GlyphClassLiteral) -> bool {
+        class.items().any(|item| {
+            if let Some(name) = typed::GlyphName::cast(item) {
+                self.glyph_map.get(name.text()).is_none()
+            } else if let Some(cid) = typed::Cid::cast(item) {
+                self.glyph_map.get(&cid.parse()).is_none()
+            } else if let Some(alias) = typed::GlyphClassName::cast(item) {
+                self.classes_with_skipped_glyphs.contains(alias.text())
+            } else {
+                false
+            }
+        })
+    }
+
     fn resolve_named_glyph_class(&mut self, name: &typed::GlyphClassName) -> GlyphClass {
-        self.glyph_class_defs
+        self.unused_glyph_classes.remove(name.text());
+        if let Some(class) = self
+            .glyph_class_defs
             .get(name.text())
-            .cloned()
-            .or_else(|| {
-                self.mark_classes.get(name.text()).map(|cls| {
-                    cls.members
-                        .iter()
-                        .flat_map(|(glyphs, _)| glyphs.iter())
-                        .collect()
-                })
-            })
-            .unwrap()
+            .or_else(|| self.mark_class_glyphs.get(name.text()))
+        {
+            return class.clone();
+        }
+
+        // this should have been caught during validation
+        let Some(mark_class) = self.mark_classes.get(name.text()) else {
+            let msg = format!("undefined glyph class {}", name.text());
+            self.error(DiagnosticKind::UndefinedGlyphClass, name.range(), msg);
+            return GlyphClass::empty();
+        };
+        let class: GlyphClass = mark_class
+            .members
+            .iter()
+            .flat_map(|(glyphs, _)| glyphs.iter())
+            .collect();
+        self.mark_class_glyphs
+            .insert(name.text().clone(), class.clone());
+        class
     }
 
     fn resolve_glyph_name(&mut self, name: &typed::GlyphName) -> GlyphId {
-        self.glyph_map.get(name.text()).unwrap()
+        match self.glyph_map.get(name.text()) {
+            Some(id) => id,
+            // this should have been caught during validation
+            None => {
+                let msg = missing_glyph_message(self.glyph_map, name.text(), false);
+                self.error(DiagnosticKind::UndefinedGlyph, name.range(), msg);
+                GlyphId::NOTDEF
+            }
+        }
     }
 
     fn resolve_lookahead_sequence(
@@ -1720,7 +2841,23 @@ impl<'a> CompilationCtx<'a> {
     }
 
     fn resolve_cid(&mut self, cid: &typed::Cid) -> GlyphId {
-        self.glyph_map.get(&cid.parse()).unwrap()
+        match self.glyph_map.get(&cid.parse()) {
+            Some(id) => id,
+            // this should have been caught during validation
+            None if !self.glyph_map.is_cid_keyed() => {
+                self.error(
+                    DiagnosticKind::InvalidStatement,
+                    cid.range(),
+                    CID_REQUIRES_CID_KEYED_MAP,
+                );
+                GlyphId::NOTDEF
+            }
+            None => {
+                let msg = missing_cid_message(self.glyph_map, cid.parse(), false);
+                self.error(DiagnosticKind::UndefinedGlyph, cid.range(), msg);
+                GlyphId::NOTDEF
+            }
+        }
     }
 
     fn add_glyphs_from_range(&mut self, range: &typed::GlyphRange, out: &mut Vec<GlyphId>) {
@@ -1735,13 +2872,14 @@ impl<'a> CompilationCtx<'a> {
                         None => {
                             // this is techincally allowed, but we error for now
                             self.error(
+                                DiagnosticKind::UndefinedGlyph,
                                 range.range(),
                                 format!("Range member '{}' does not exist in font", cid),
                             );
                         }
                     }
                 }) {
-                    self.error(range.range(), err);
+                    self.error(DiagnosticKind::InvalidValue, range.range(), err);
                 }
             }
             (Kind::GlyphName, Kind::GlyphName) => {
@@ -1751,16 +2889,21 @@ impl<'a> CompilationCtx<'a> {
                         None => {
                             // this is techincally allowed, but we error for now
                             self.error(
+                                DiagnosticKind::UndefinedGlyph,
                                 range.range(),
                                 format!("Range member '{}' does not exist in font", name),
                             );
                         }
                     }
                 }) {
-                    self.error(range.range(), err);
+                    self.error(DiagnosticKind::InvalidValue, range.range(), err);
                 }
             }
-            (_, _) => self.error(range.range(), "Invalid types in glyph range"),
+            (_, _) => self.error(
+                DiagnosticKind::InvalidValue,
+                range.range(),
+                "Invalid types in glyph range",
+            ),
         }
     }
 }
@@ -1799,7 +2942,36 @@ fn sequence_enumerator_impl(
 
 #[cfg(test)]
 mod tests {
+    use std::{ffi::OsStr, sync::Arc};
+
     use super::*;
+    use crate::parse::SourceLoadError;
+
+    /// Parse and compile, then pass the finished context to the provided closure.
+    fn with_compiled_ctx<R>(fea: &str, opts: Opts, f: impl FnOnce(CompilationCtx) -> R) -> R {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea: Arc<str> = fea.into();
+        let resolver = move |_: &OsStr| -> Result<Arc<str>, SourceLoadError> { Ok(fea.clone()) };
+        let (tree, errs) =
+            crate::parse::parse_root("test.fea".into(), Some(&glyph_map), resolver).unwrap();
+        assert!(errs.is_empty(), "{errs:?}");
+        let mut ctx = CompilationCtx::new(&glyph_map, tree.source_map(), opts);
+        ctx.compile(&tree.typed_root());
+        f(ctx)
+    }
+
+    /// Parse and compile, returning all diagnostics generated during compilation.
+    fn compile_diagnostics(fea: &str, opts: Opts) -> Vec<Diagnostic> {
+        with_compiled_ctx(fea, opts, |ctx| ctx.errors)
+    }
+
+    fn feature_key(feature: &[u8; 4], script: &[u8; 4], language: &[u8; 4]) -> FeatureKey {
+        FeatureKey {
+            feature: Tag::new(feature),
+            script: Tag::new(script),
+            language: Tag::new(language),
+        }
+    }
 
     fn glyph_id_vec<const N: usize>(ids: [u16; N]) -> Vec<GlyphId> {
         ids.iter().copied().map(GlyphId::new).collect()
@@ -1825,4 +2997,660 @@ mod tests {
             ]
         );
     }
+
+    static KEYWORD_GLYPHS_FEA: &str = r#"
+languagesystem DFLT dflt;
+
+feature test {
+    sub \sub by \table;
+    sub \feature \lookup by \by;
+    sub A.sc by a.alt1;
+    sub [one.oldstyle two.oldstyle] by [f_f_i o_f_f_i];
+} test;
+"#;
+
+    #[test]
+    fn escaped_and_dotted_glyph_names_are_preserved() {
+        let (root, errs) = crate::parse::parse_string(KEYWORD_GLYPHS_FEA);
+        assert!(errs.is_empty(), "{errs:?}");
+        let names = root
+            .iter_tokens()
+            .filter(|t| t.kind == Kind::GlyphName)
+            .map(|t| t.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "sub",
+                "table",
+                "feature",
+                "lookup",
+                "by",
+                "A.sc",
+                "a.alt1",
+                "one.oldstyle",
+                "two.oldstyle",
+                "f_f_i",
+                "o_f_f_i"
+            ]
+        );
+    }
+
+    #[test]
+    fn keyword_glyph_names_resolve() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let compilation =
+            crate::compile::compile_str(KEYWORD_GLYPHS_FEA, &glyph_map, Default::default());
+        assert!(compilation.is_ok(), "{:?}", compilation.err());
+    }
+
+    #[test]
+    fn mixed_inline_context() {
+        // marked and unmarked glyphs are written inline, without any
+        // separate backtrack/lookahead syntax
+        let fea = "\
+        lookup ALT { sub b by b.alt; } ALT;
+        feature test {
+            sub a b' c' d by c_t;
+            sub a b' lookup ALT c' d;
+            pos a b' 10 c' 20 d;
+        } test;";
+
+        fn names(items: impl Iterator<Item = typed::GlyphOrClass>) -> Vec<String> {
+            items
+                .map(|item| match item {
+                    typed::GlyphOrClass::Glyph(name) => name.text().to_string(),
+                    other => panic!("unexpected item {other:?}"),
+                })
+                .collect()
+        }
+
+        fn split(rule: &impl typed::ContextualRuleNode) -> [Vec<String>; 3] {
+            [
+                names(rule.backtrack().items()),
+                names(rule.input().items().map(|item| item.target())),
+                names(rule.lookahead().items()),
+            ]
+        }
+
+        let (root, errs) = crate::parse::parse_string(fea);
+        assert!(errs.is_empty(), "{errs:?}");
+        let feature = root.iter_children().find_map(typed::Feature::cast).unwrap();
+        let rules = feature
+            .statements()
+            .filter(|item| !item.kind().is_trivia())
+            .collect::<Vec<_>>();
+        assert_eq!(rules.len(), 3);
+
+        let expected = [vec!["a"], vec!["b", "c"], vec!["d"]];
+        let sub_ligature = typed::Gsub6::cast(rules[0]).unwrap();
+        assert_eq!(split(&sub_ligature), expected);
+        let sub_lookup = typed::Gsub6::cast(rules[1]).unwrap();
+        assert_eq!(split(&sub_lookup), expected);
+        let pos = typed::Gpos8::cast(rules[2]).unwrap();
+        assert_eq!(split(&pos), expected);
+
+        // and we can compile it
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let compilation = crate::compile::compile_str(fea, &glyph_map, Default::default());
+        assert!(compilation.is_ok(), "{:?}", compilation.err());
+    }
+
+    #[test]
+    fn report_excluded_lookups() {
+        let fea = "\
+        languagesystem DFLT dflt;
+        languagesystem latn dflt;
+        languagesystem latn TRK;
+        feature liga {
+            sub f i by f_i;
+            script latn;
+            sub f l by f_l;
+            language TRK exclude_dflt;
+            sub f f by f_f;
+        } liga;";
+
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+        let diagnostics = compile_diagnostics(fea, Opts::new().report_excluded_lookups(true));
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].level, crate::Level::Info);
+        assert!(
+            diagnostics[0].text().contains("2 default lookup(s)"),
+            "{}",
+            diagnostics[0].text()
+        );
+    }
+
+    #[test]
+    fn existing_mark_glyph_sets_come_first() {
+        let fea = "\
+        @MARKS_ONE = [acute grave];
+        @MARKS_TWO = [cedilla ogonek];
+        feature test {
+            lookupflag UseMarkFilteringSet @MARKS_ONE;
+            pos a b 10;
+            lookupflag UseMarkFilteringSet @MARKS_TWO;
+            pos a c 10;
+        } test;";
+
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let glyphs = |names: &[&str]| -> BTreeSet<GlyphId> {
+            names
+                .iter()
+                .map(|name| glyph_map.get(*name).unwrap())
+                .collect()
+        };
+        // the mark glyph sets in the font's GDEF
+        let font_info = FontInfo {
+            mark_glyph_sets: vec![
+                glyphs(&["dieresis", "macron"]),
+                glyphs(&["cedilla", "ogonek"]),
+            ],
+            ..Default::default()
+        };
+        let opts = Opts::new().font_info(font_info);
+        with_compiled_ctx(fea, opts, |ctx| {
+            assert!(ctx.errors.is_empty(), "{:?}", ctx.errors);
+            let gdef = ctx.tables.gdef.as_ref().unwrap();
+            let sets = gdef
+                .mark_glyph_sets
+                .iter()
+                .map(|set| set.iter().collect::<BTreeSet<_>>())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                sets,
+                [
+                    glyphs(&["dieresis", "macron"]),
+                    glyphs(&["cedilla", "ogonek"]),
+                    glyphs(&["acute", "grave"]),
+                ]
+            );
+            // @MARKS_TWO is identical to an existing set, so reuses it
+            assert_eq!(ctx.lookups.gpos_mark_filter_sets(), vec![Some(2), Some(1)]);
+        });
+    }
+
+    #[test]
+    fn subtable_outside_lookup() {
+        let fea = "\
+        feature kern {
+            subtable;
+            pos a b 5;
+            subtable;
+            pos c d 5;
+        } kern;";
+        let warnings = compile_diagnostics(fea, Opts::new());
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].text(),
+            "'subtable' has no effect outside of a lookup"
+        );
+        assert!(fea[warnings[0].span()].starts_with("subtable"));
+    }
+
+    #[test]
+    fn unregistered_features() {
+        let fea = "\
+        feature liga { sub f i by f_i; } liga;
+        feature ligs { sub f i by f_i; } ligs;
+        feature ss01 { sub a by b; } ss01;
+        feature priv { sub a by b; } priv;";
+
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+        let opts = Opts::new().warn_unregistered_features(true);
+        let warnings = compile_diagnostics(fea, opts.clone());
+        let messages = warnings.iter().map(|w| w.text()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "'ligs' is not a registered feature tag",
+                "'priv' is not a registered feature tag"
+            ]
+        );
+        let opts = opts.private_feature_tags(&[Tag::new(b"priv")]);
+        let warnings = compile_diagnostics(fea, opts);
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn unused_glyph_classes() {
+        let fea = "\
+        @USED = [a b];
+        @ALIASED = [c d];
+        @ALIAS = @ALIASED;
+        @UNUSED = [e f];
+        feature test {
+            @ALSO_UNUSED = [g h];
+            sub @USED by x;
+            sub @ALIAS by y;
+        } test;";
+
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+        let warnings = compile_diagnostics(fea, Opts::new().warn_unused_glyph_classes(true));
+        let messages = warnings.iter().map(|w| w.text()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "glyph class '@UNUSED' is never used",
+                "glyph class '@ALSO_UNUSED' is never used"
+            ]
+        );
+    }
+
+    #[test]
+    fn interleaved_language_and_lookup_refs() {
+        let fea = "\
+        languagesystem DFLT dflt;
+        languagesystem latn dflt;
+        languagesystem latn TRK;
+        lookup A { sub a by b; } A;
+        lookup B { sub c by d; } B;
+        feature test {
+            script latn;
+            lookup A;
+            language TRK;
+            lookup B;
+        } test;";
+
+        let features = with_compiled_ctx(fea, Opts::new(), |ctx| {
+            assert!(ctx.errors.is_empty(), "{:?}", ctx.errors);
+            ctx.features
+        });
+        let (a, b) = (LookupId::Gsub(0), LookupId::Gsub(1));
+        // lookups referenced before 'language' belong to the script default,
+        // and are inherited by the language
+        assert_eq!(features[&feature_key(b"test", b"latn", b"dflt")], [a]);
+        assert_eq!(features[&feature_key(b"test", b"latn", b"TRK ")], [a, b]);
+    }
+
+    #[test]
+    fn lookup_ref_ends_anonymous_lookup() {
+        let fea = "\
+        lookup A { sub a by b; } A;
+        feature test {
+            sub c by d;
+            lookup A;
+            sub e by f;
+        } test;";
+
+        let features = with_compiled_ctx(fea, Opts::new(), |ctx| ctx.features);
+        assert_eq!(
+            features[&feature_key(b"test", b"DFLT", b"dflt")],
+            [LookupId::Gsub(0), LookupId::Gsub(1), LookupId::Gsub(2)]
+        );
+    }
+
+    #[test]
+    fn null_contextual_value_record() {
+        let fea = "\
+        feature test {
+            pos a b' <0 0 0 0> c;
+            pos a d' <10 0 0 0> c;
+        } test;";
+
+        with_compiled_ctx(fea, Opts::new(), |ctx| {
+            assert!(ctx.errors.is_empty(), "{:?}", ctx.errors);
+            // one contextual lookup, and one anonymous lookup for 'd'
+            assert_eq!(ctx.lookups.n_gpos_lookups(), 2);
+        });
+    }
+
+    #[test]
+    fn misplaced_languagesystem() {
+        let fea = "\
+        languagesystem DFLT dflt;
+        feature test {
+            sub a by b;
+        } test;
+        languagesystem latn dflt;";
+
+        let errs = compile_diagnostics(fea, Opts::new());
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert!(errs[0].is_error());
+        assert!(errs[0].text().contains("must precede"));
+    }
+
+    #[test]
+    fn font_info_glyph_count() {
+        let fea = "feature test { sub a by b; } test;";
+        let font_info = FontInfo {
+            num_glyphs: Some(5),
+            ..Default::default()
+        };
+        // the mismatch is logged, since it has no location in the FEA
+        let errs = compile_diagnostics(fea, Opts::new().font_info(font_info));
+        assert!(errs.is_empty(), "{errs:?}");
+    }
+
+    #[test]
+    fn glyph_classes_from_font_info() {
+        let fea = "\
+        markClass acute <anchor 100 500> @TOP;
+        feature mark {
+            pos base [a c] <anchor 250 450> mark @TOP;
+        } mark;";
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        let font_info = FontInfo {
+            glyph_classes: [(gid("a"), 4), (gid("b"), 1)].into_iter().collect(),
+            ..Default::default()
+        };
+        let opts = Opts::new().font_info(font_info);
+        let classes = with_compiled_ctx(fea, opts, |ctx| ctx.tables.gdef.unwrap().glyph_classes);
+        assert_eq!(classes.len(), 4);
+        // existing classes are kept, and inference fills in the rest
+        assert_eq!(classes[&gid("a")], ClassId::Component);
+        assert_eq!(classes[&gid("b")], ClassId::Base);
+        assert_eq!(classes[&gid("c")], ClassId::Base);
+        assert_eq!(classes[&gid("acute")], ClassId::Mark);
+    }
+
+    #[test]
+    fn conflicting_single_subs() {
+        let fea = "feature test { sub [a b a] by [x y z]; } test;";
+        let warnings = compile_diagnostics(fea, Opts::new());
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(!warnings[0].is_error());
+        let message = warnings[0].text();
+        assert!(
+            message.starts_with("glyph 'a' occurs more than once"),
+            "{message}"
+        );
+        assert_eq!(&fea[warnings[0].span()], "[a b a]");
+
+        // a repeated glyph with the same replacement is harmless
+        let fea = "feature test { sub [a b a] by [x y x]; } test;";
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+    }
+
+    #[test]
+    fn mark_attach_class_limits() {
+        let fea = "\
+        feature test {
+            lookupflag MarkAttachmentType [acute grave];
+            pos a 10;
+            lookupflag MarkAttachmentType [grave dieresis];
+            pos b 10;
+            lookupflag MarkAttachmentType [grave acute];
+            pos c 10;
+        } test;";
+        let errs = compile_diagnostics(fea, Opts::new());
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert_eq!(
+            errs[0].text(),
+            "glyph 'grave' is already in a different mark attachment class"
+        );
+        assert_eq!(&fea[errs[0].span()], "[grave dieresis]");
+
+        let mut fea = String::new();
+        for (i, glyph) in ('A'..='P').enumerate() {
+            fea.push_str(&format!(
+                "lookup L{i} {{ lookupflag MarkAttachmentType [{glyph}]; pos a {i}; }} L{i};\n"
+            ));
+        }
+        let errs = compile_diagnostics(&fea, Opts::new());
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        let message = errs[0].text();
+        assert!(message.starts_with("too many"), "{message}");
+        assert_eq!(&fea[errs[0].span()], "[P]");
+    }
+
+    #[test]
+    fn mark_class_as_mark_attachment_type() {
+        let fea = "\
+        markClass [acute grave] <anchor 0 500> @TOP;
+        markClass cedilla <anchor 0 0> @BOTTOM;
+        feature test {
+            lookupflag MarkAttachmentType @TOP;
+            pos a 10;
+        } test;";
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        let mark_attach_class = with_compiled_ctx(fea, Opts::new(), |ctx| {
+            assert!(ctx.errors.is_empty(), "{:?}", ctx.errors);
+            ctx.tables.gdef.unwrap().mark_attach_class
+        });
+        let expected = [(gid("acute"), 1), (gid("grave"), 1)].into_iter().collect();
+        assert_eq!(mark_attach_class, expected);
+    }
+
+    #[test]
+    fn missing_lookup_is_internal_error() {
+        let fea = "feature test { sub a by b; } test;";
+        let errs = with_compiled_ctx(fea, Opts::new(), |mut ctx| {
+            assert!(ctx.build().is_ok());
+            let key = feature_key(b"test", b"DFLT", b"dflt");
+            ctx.features.get_mut(&key).unwrap().push(LookupId::Gsub(99));
+            ctx.build().err().unwrap()
+        });
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert!(errs[0].text().starts_with("internal error"));
+    }
+
+    #[test]
+    fn null_single_pos() {
+        let errs = compile_diagnostics("feature test { pos a <NULL>; } test;", Opts::new());
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        assert!(!errs[0].is_error());
+        assert!(errs[0].text().contains("NULL"));
+
+        let errs = compile_diagnostics("feature test { pos a <0 0 0 0>; } test;", Opts::new());
+        assert!(errs.is_empty(), "{errs:?}");
+
+        // NULL is meaningful in pair pos, so no warning
+        let errs = compile_diagnostics("feature test { pos a <NULL> b 5; } test;", Opts::new());
+        assert!(errs.is_empty(), "{errs:?}");
+    }
+
+    #[test]
+    fn lookups_numbered_in_source_order() {
+        let fea = "\
+        lookup A { sub a by b; } A;
+        feature test {
+            sub c by d;
+            sub e' f by g;
+            lookup B { sub h by i; } B;
+        } test;";
+
+        with_compiled_ctx(fea, Opts::new(), |ctx| {
+            assert!(ctx.errors.is_empty(), "{:?}", ctx.errors);
+            assert_eq!(ctx.lookups.get_named("A"), Some(LookupId::Gsub(0)));
+            assert_eq!(ctx.lookups.get_named("B"), Some(LookupId::Gsub(4)));
+            // the contextual lookup (2) is followed by its anonymous lookup (3)
+            assert_eq!(
+                ctx.features[&feature_key(b"test", b"DFLT", b"dflt")],
+                [LookupId::Gsub(1), LookupId::Gsub(2), LookupId::Gsub(4)]
+            );
+        });
+    }
+
+    #[test]
+    fn missing_glyph_is_error() {
+        let fea = "feature frac { sub onehallf by one slash two; } frac;";
+        let diagnostics = compile_diagnostics(fea, Opts::new());
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+        assert!(diagnostics[0].is_error());
+        assert_eq!(
+            diagnostics[0].text(),
+            "glyph not in font; did you mean 'onehalf'?"
+        );
+    }
+
+    #[test]
+    fn ignore_flags_without_glyph_classes() {
+        let fea = "\
+        feature kern {
+            lookupflag IgnoreMarks;
+            pos a b -10;
+        } kern;";
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+        let opts = Opts::new().warn_ineffective_lookupflags(true);
+        let warnings = compile_diagnostics(fea, opts.clone());
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(warnings[0].text().starts_with("lookupflag has no effect"));
+        assert!(fea[warnings[0].span()].starts_with("lookupflag IgnoreMarks"));
+
+        // glyph classes can be inferred from mark classes
+        let fea = format!("markClass acute <anchor 0 0> @MARK;\n{fea}");
+        assert!(compile_diagnostics(&fea, opts).is_empty());
+    }
+
+    #[test]
+    fn redundant_lookupflags() {
+        let fea = "\
+        feature test {
+            lookupflag IgnoreMarks;
+            sub a by b;
+            lookupflag IgnoreMarks;
+            sub c by d;
+            lookupflag RightToLeft;
+            sub e by f;
+            lookupflag 1;
+        } test;";
+
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+        let warnings = compile_diagnostics(fea, Opts::new().warn_redundant_lookupflags(true));
+        let lines = warnings
+            .iter()
+            .map(|w| {
+                assert!(!w.is_error());
+                let line = &fea[..w.span().start];
+                line.lines().count()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [4, 8]);
+    }
+
+    #[test]
+    fn missing_cid_is_error() {
+        let glyph_map = (0..=10_u16).collect::<GlyphMap>();
+        let fea: Arc<str> = "feature test { sub \\1 by \\20; sub \\2 by \\3; } test;".into();
+        let resolver = move |_: &OsStr| -> Result<Arc<str>, SourceLoadError> { Ok(fea.clone()) };
+        let (tree, errs) =
+            crate::parse::parse_root("test.fea".into(), Some(&glyph_map), resolver).unwrap();
+        assert!(errs.is_empty(), "{errs:?}");
+        let mut ctx = CompilationCtx::new(&glyph_map, tree.source_map(), Opts::new());
+        ctx.compile(&tree.typed_root());
+
+        let messages = ctx.errors.iter().map(|e| e.text()).collect::<Vec<_>>();
+        assert_eq!(messages, ["CID 20 not in font (valid CIDs are 0..=10)"]);
+        // the second rule is still compiled, into the same lookup
+        let feature = &ctx.features[&feature_key(b"test", b"DFLT", b"dflt")];
+        assert_eq!(feature.len(), 1);
+    }
+
+    #[test]
+    fn parallel_ranges_pair_in_order() {
+        let fea = "feature smcp { sub [A-Z] by [A.sc-Z.sc]; sub [a-c z] by [C.sc B.sc A.sc Z.sc]; } smcp;";
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        let explicit = [("a", "C.sc"), ("b", "B.sc"), ("c", "A.sc"), ("z", "Z.sc")];
+        let mut expected = ('A'..='Z')
+            .map(|c| (gid(&c.to_string()), gid(&format!("{c}.sc"))))
+            .chain(explicit.map(|(a, b)| (gid(a), gid(b))))
+            .collect::<Vec<_>>();
+        expected.sort_unstable();
+
+        let mut pairs = with_compiled_ctx(fea, Opts::new(), |ctx| {
+            assert!(ctx.errors.is_empty(), "{:?}", ctx.errors);
+            let lookups = ctx.lookups.aalt_lookups(LookupId::Gsub(0));
+            let [SubstitutionLookup::Single(lookup)] = lookups.as_slice() else { panic!() };
+            lookup
+                .iter_subtables()
+                .flat_map(|sub| sub.iter_pairs())
+                .collect::<Vec<_>>()
+        });
+        pairs.sort_unstable();
+        assert_eq!(pairs, expected);
+    }
+
+    #[test]
+    fn undefined_glyph_class_is_error() {
+        let fea = "feature test { sub @nope by b; sub c by d; } test;";
+        let (messages, feature) = with_compiled_ctx(fea, Opts::new(), |ctx| {
+            let messages = ctx.errors.iter().map(|e| e.text().to_owned());
+            let messages = messages.collect::<Vec<_>>();
+            let feature = ctx.features[&feature_key(b"test", b"DFLT", b"dflt")].clone();
+            (messages, feature)
+        });
+        assert_eq!(messages, ["undefined glyph class @nope"]);
+        assert_eq!(feature.len(), 1);
+    }
+
+    #[test]
+    fn ligature_sequence_limit() {
+        let fea = "feature liga {
+            sub [a b c] [d e] by f_l;
+            sub x [a b c]' [d e]' by f_l;
+            sub [a b] [d e] by f_l;
+        } liga;";
+        assert!(compile_diagnostics(fea, Opts::new()).is_empty());
+        let errors = compile_diagnostics(fea, Opts::new().max_ligature_sequences(4));
+        let messages = errors.iter().map(|e| e.text()).collect::<Vec<_>>();
+        let expected = "ligature rule matches 6 glyph sequences (limit is 4)";
+        assert_eq!(messages, [expected, expected]);
+    }
+
+    #[test]
+    fn nested_contextual_pos_lookup_warns() {
+        let fea = "\
+        lookup SIMPLE { pos a 10; } SIMPLE;
+        lookup CTX { pos a' lookup SIMPLE b; } CTX;
+        feature kern {
+            pos c' lookup SIMPLE e;
+            pos c' lookup CTX d;
+        } kern;";
+        let warnings = compile_diagnostics(fea, Opts::new());
+        assert_eq!(warnings.len(), 1, "{warnings:?}");
+        assert!(!warnings[0].is_error());
+        assert_eq!(
+            warnings[0].text(),
+            "contextual rule references another contextual lookup"
+        );
+        assert_eq!(&fea[warnings[0].span()], "CTX");
+        assert!(fea[..warnings[0].span().start].ends_with("pos c' lookup "));
+    }
+
+    #[test]
+    fn empty_mark_class_warns() {
+        let fea = "\
+        @NOTHING = [];
+        markClass @NOTHING <anchor 0 500> @TOP;
+        markClass [acute] <anchor 0 500> @ACUTE;
+        feature mark {
+            pos base a <anchor 250 450> mark @TOP;
+            pos base b <anchor 250 450> mark @ACUTE;
+        } mark;";
+        let warnings = compile_diagnostics(fea, Opts::new());
+        let messages = warnings.iter().map(|w| w.text()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            ["mark class @TOP has no glyphs; this attachment does nothing"]
+        );
+        assert!(!warnings[0].is_error());
+    }
+
+    #[test]
+    fn mirroring_feature_lint() {
+        let fea = "\
+        lookup LIGA { sub f l by f_l; } LIGA;
+        lookup ALT { sub a from [A A.sc]; } ALT;
+        feature rtlm {
+            sub b by B;
+            sub f l by f_l;
+            lookup LIGA;
+            lookup ALT;
+        } rtlm;
+        feature rtla {
+            sub c by C;
+            lookup ALT;
+            pos d 10;
+        } rtla;";
+        let warnings = compile_diagnostics(fea, Opts::new());
+        assert!(warnings.iter().all(|w| !w.is_error()));
+        let messages = warnings.iter().map(|w| w.text()).collect::<Vec<_>>();
+        let rtlm = "rtlm should only contain single substitutions";
+        let rtla = "rtla should only contain single or alternate substitutions";
+        assert_eq!(messages, [rtlm, rtlm, rtlm, rtla]);
+    }
 }
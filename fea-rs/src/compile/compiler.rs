@@ -1,20 +1,31 @@
 //! The main public API for compilation
 
 use std::{
-    ffi::OsString,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    ops::Range,
     path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use write_fonts::{
+    read::{FontRef, ReadError, TableProvider},
+    types::Tag,
 };
 
 use crate::{
-    parse::{FileSystemResolver, SourceResolver},
-    Diagnostic, GlyphMap, ParseTree,
+    parse::{FileSystemResolver, SourceLoadError, SourceResolver},
+    Diagnostic, DiagnosticKind, GlyphMap, Level, ParseTree,
 };
 
 use super::{
-    error::{CompilerError, DiagnosticSet},
-    Compilation, Opts,
+    error::{CompilerError, DiagnosticSet, OutputValidationError},
+    tags, AxisMap, Compilation, CompilationCtx, Opts,
 };
 
+/// The file name used in diagnostics for a source passed to [`Compiler::from_source`].
+const IN_MEMORY_SOURCE_NAME: &str = "<source>";
+
 /// A builder-style entry point for the compiler.
 ///
 /// This is intended as the principal public API for this crate.
@@ -31,9 +42,11 @@ use super::{
 pub struct Compiler<'a> {
     root_path: OsString,
     project_root: Option<PathBuf>,
+    include_dirs: Vec<PathBuf>,
     glyph_map: &'a GlyphMap,
     verbose: bool,
     opts: Opts,
+    axis_map: Option<AxisMap>,
     resolver: Option<Box<dyn SourceResolver>>,
 }
 
@@ -51,12 +64,34 @@ impl<'a> Compiler<'a> {
             root_path: root_path.into(),
             glyph_map,
             opts: Default::default(),
+            axis_map: None,
             verbose: false,
             resolver: Default::default(),
             project_root: Default::default(),
+            include_dirs: Default::default(),
         }
     }
 
+    /// Configure a new compilation run for a FEA source held in memory.
+    ///
+    /// This never touches the filesystem: `include` statements are not
+    /// resolved, and are reported as errors. In diagnostics the source has
+    /// the file name `<source>`.
+    pub fn from_source(source: &str, glyph_map: &'a GlyphMap) -> Self {
+        let source: Arc<str> = source.into();
+        let resolver = move |path: &OsStr| -> Result<Arc<str>, SourceLoadError> {
+            if path == IN_MEMORY_SOURCE_NAME {
+                Ok(source.clone())
+            } else {
+                Err(SourceLoadError::new(
+                    path.into(),
+                    "include is not supported when compiling from memory",
+                ))
+            }
+        };
+        Compiler::new(IN_MEMORY_SOURCE_NAME, glyph_map).with_resolver(resolver)
+    }
+
     /// Provide a custom `SourceResolver`, for mapping paths to their contents.
     pub fn with_resolver(mut self, resolver: impl SourceResolver + 'static) -> Self {
         self.resolver = Some(Box::new(resolver));
@@ -80,18 +115,44 @@ impl<'a> Compiler<'a> {
         self
     }
 
+    /// Specify additional directories to search for included files.
+    ///
+    /// These are tried in order when an `include` path is not found relative
+    /// to the project root or the including file. They are not used if a
+    /// custom resolver is provided with [`with_resolver`].
+    ///
+    /// [`with_resolver`]: Self::with_resolver
+    pub fn with_include_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.include_dirs = dirs.into_iter().collect();
+        self
+    }
+
     /// Specify additional compiler options.
     pub fn with_opts(mut self, opts: Opts) -> Self {
         self.opts = opts;
         self
     }
 
+    /// Provide the axes of the variable font being compiled.
+    ///
+    /// This is required in order to compile the variable metrics extension,
+    /// e.g. `pos a (wght=200:-20 wght=900:-60);`. It is not needed when
+    /// compiling static fonts. The deltas for these metrics are written to an
+    /// item variation store in the GDEF table.
+    pub fn with_axis_map(mut self, axis_map: AxisMap) -> Self {
+        self.axis_map = Some(axis_map);
+        self
+    }
+
     /// Parse, validate and compile this source.
     ///
     /// This returns a `Compilation` object that contains all of the features
     /// and lookups generated during compilation. If you would like to go directly
     /// to a binary font, you can use [`compile_binary`] instead.
     ///
+    /// Any warnings generated while parsing, validating, or compiling are
+    /// stored in [`Compilation::warnings`].
+    ///
     /// [`compile_binary`]: Self::compile_binary
     pub fn compile(self) -> Result<Compilation, CompilerError> {
         let resolver = self.resolver.unwrap_or_else(|| {
@@ -101,53 +162,194 @@ impl<'a> Compiler<'a> {
                     .map(PathBuf::from)
                     .unwrap_or_default()
             });
-            Box::new(FileSystemResolver::new(project_root))
+            Box::new(FileSystemResolver::new(project_root).with_include_dirs(self.include_dirs))
         });
 
-        let (tree, diagnostics) =
-            crate::parse::ParseContext::parse(self.root_path, Some(self.glyph_map), resolver)?
-                .generate_parse_tree();
-        print_warnings_return_errors(diagnostics, &tree, self.verbose)
+        let mut context =
+            crate::parse::ParseContext::parse(self.root_path, Some(self.glyph_map), resolver)?;
+        if let Some(depth) = self.opts.max_include_depth {
+            context.set_max_include_depth(depth);
+        }
+        let (tree, mut diagnostics) = context.generate_parse_tree();
+        if self.opts.ignore_unknown_items {
+            // the parser skips over anything it doesn't understand, so we can
+            // keep going; validation and compilation won't report these again.
+            diagnostics
+                .iter_mut()
+                .filter(|diag| diag.kind() == DiagnosticKind::UnknownItem)
+                .for_each(|diag| diag.level = Level::Warning);
+        }
+        if let Some(sink) = &self.opts.diagnostic_sink {
+            for diagnostic in &diagnostics {
+                sink.send(diagnostic);
+            }
+        }
+        let mut warnings = print_warnings_return_errors(diagnostics, &tree, self.verbose)
             .map_err(CompilerError::ParseFail)?;
-        let diagnostics = super::validate(&tree, self.glyph_map);
-        print_warnings_return_errors(diagnostics, &tree, self.verbose)
-            .map_err(CompilerError::ValidationFail)?;
-        let mut ctx = super::CompilationCtx::new(self.glyph_map, tree.source_map());
+        let opts = Opts {
+            axis_map: self.axis_map,
+            ..self.opts
+        };
+        let diagnostics = super::validate(&tree, self.glyph_map, &opts);
+        warnings.extend(
+            print_warnings_return_errors(diagnostics, &tree, self.verbose)
+                .map_err(CompilerError::ValidationFail)?,
+        );
+        let mut ctx = CompilationCtx::new(self.glyph_map, tree.source_map(), opts);
         ctx.compile(&tree.typed_root());
-
-        // we 'take' the errors here because it's easier for us to handle the
-        // warnings using our helper method.
-        print_warnings_return_errors(std::mem::take(&mut ctx.errors), &tree, self.verbose)
-            .map_err(CompilerError::CompilationFail)?;
-        Ok(ctx.build().unwrap()) // we've taken the errors, so this can't fail
+        build_compilation(ctx, &tree, warnings, self.verbose)
     }
 
     /// Compile to a binary font.
+    ///
+    /// If [`Opts::validate_output`] is set, the generated binary is parsed
+    /// before it is returned.
     pub fn compile_binary(self) -> Result<Vec<u8>, CompilerError> {
+        self.compile_binary_with_warnings().map(|(bytes, _)| bytes)
+    }
+
+    /// Compile to a binary font, also returning any warnings.
+    ///
+    /// This includes the warnings from parsing, validation, and compilation.
+    /// They are returned whether or not [`verbose`] is set, which only
+    /// controls whether they are also printed.
+    ///
+    /// [`verbose`]: Self::verbose
+    pub fn compile_binary_with_warnings(self) -> Result<(Vec<u8>, Vec<Diagnostic>), CompilerError> {
+        self.compile_binary_impl()
+            .map(|(bytes, _, warnings)| (bytes, warnings))
+    }
+
+    /// Compile to a binary font, also returning the location of each table.
+    ///
+    /// The returned map contains the range in the binary occupied by each
+    /// table, which is useful for tools that want to inspect or patch
+    /// specific tables.
+    pub fn compile_binary_with_layout(
+        self,
+    ) -> Result<(Vec<u8>, HashMap<Tag, Range<usize>>), CompilerError> {
+        self.compile_binary_impl()
+            .map(|(bytes, layout, _)| (bytes, layout))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn compile_binary_impl(
+        self,
+    ) -> Result<(Vec<u8>, HashMap<Tag, Range<usize>>, Vec<Diagnostic>), CompilerError> {
         let opts = self.opts.clone();
         let glyph_map = self.glyph_map;
-        Ok(self.compile()?.assemble(glyph_map, opts)?.build())
+        let validate_output = opts.validate_output;
+        let compilation = self.compile()?;
+        let (bytes, layout) = compilation.build_binary_with_layout(glyph_map, opts)?;
+        if validate_output {
+            validate_output_bytes(&bytes)?;
+        }
+        Ok((bytes, layout, compilation.warnings))
+    }
+}
+
+/// Ensure that the layout tables in the generated binary can be parsed.
+fn validate_output_bytes(bytes: &[u8]) -> Result<(), OutputValidationError> {
+    type Check = fn(&FontRef) -> Result<(), ReadError>;
+    let font = FontRef::new(bytes).map_err(|error| OutputValidationError { table: None, error })?;
+    let checks: [(Tag, Check); 3] = [
+        (tags::GSUB, check_gsub),
+        (tags::GPOS, check_gpos),
+        (Tag::new(b"GDEF"), check_gdef),
+    ];
+    for (tag, check) in checks {
+        if font.data_for_tag(tag).is_some() {
+            check(&font).map_err(|error| OutputValidationError {
+                table: Some(tag),
+                error,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+fn check_gsub(font: &FontRef) -> Result<(), ReadError> {
+    let gsub = font.gsub()?;
+    gsub.script_list()?;
+    gsub.feature_list()?;
+    for lookup in gsub.lookup_list()?.lookups().iter() {
+        lookup?;
     }
+    Ok(())
 }
 
-fn print_warnings_return_errors(
+fn check_gpos(font: &FontRef) -> Result<(), ReadError> {
+    let gpos = font.gpos()?;
+    gpos.script_list()?;
+    gpos.feature_list()?;
+    for lookup in gpos.lookup_list()?.lookups().iter() {
+        lookup?;
+    }
+    Ok(())
+}
+
+fn check_gdef(font: &FontRef) -> Result<(), ReadError> {
+    let gdef = font.gdef()?;
+    gdef.glyph_class_def().transpose()?;
+    gdef.attach_list().transpose()?;
+    gdef.lig_caret_list().transpose()?;
+    gdef.mark_attach_class_def().transpose()?;
+    if let Some(sets) = gdef.mark_glyph_sets_def() {
+        sets?;
+    }
+    Ok(())
+}
+
+/// Finish a compilation, once all of the statements in `tree` have been
+/// compiled and finalized.
+///
+/// The diagnostics from compilation are added after the `warnings` from
+/// earlier passes.
+pub(crate) fn build_compilation(
+    mut ctx: CompilationCtx,
+    tree: &ParseTree,
+    mut warnings: Vec<Diagnostic>,
+    verbose: bool,
+) -> Result<Compilation, CompilerError> {
+    // we 'take' the errors here because it's easier for us to handle the
+    // warnings using our helper method.
+    warnings.extend(
+        print_warnings_return_errors(std::mem::take(&mut ctx.errors), tree, verbose)
+            .map_err(CompilerError::CompilationFail)?,
+    );
+    // any remaining errors are internal errors found while building
+    let mut compilation = ctx.build().map_err(|messages| {
+        CompilerError::CompilationFail(DiagnosticSet {
+            messages,
+            sources: tree.sources.clone(),
+        })
+    })?;
+    warnings.append(&mut compilation.warnings);
+    compilation.warnings = warnings;
+    Ok(compilation)
+}
+
+pub(crate) fn print_warnings_return_errors(
     mut diagnostics: Vec<Diagnostic>,
     tree: &ParseTree,
     verbose: bool,
-) -> Result<(), DiagnosticSet> {
-    diagnostics.sort_unstable_by_key(|diag| diag.level);
+) -> Result<Vec<Diagnostic>, DiagnosticSet> {
+    // errors first, then each level by location; the sort is stable, so that
+    // diagnostics at the same location stay in the order they were reported.
+    diagnostics.sort_by_key(|diag| (diag.level, diag.message.file, diag.span().start));
+    crate::diagnostic::remove_duplicates(&mut diagnostics);
     let split_at = diagnostics
         .iter()
         .position(|x| !x.is_error())
         .unwrap_or(diagnostics.len());
     let warnings = diagnostics.split_off(split_at);
     if verbose {
-        for w in warnings {
-            eprintln!("{}", tree.format_diagnostic(&w));
+        for w in &warnings {
+            eprintln!("{}", tree.format_diagnostic(w));
         }
     }
     if diagnostics.is_empty() {
-        Ok(())
+        Ok(warnings)
     } else {
         Err(DiagnosticSet {
             messages: diagnostics,
@@ -155,3 +357,305 @@ fn print_warnings_return_errors(
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    use crate::Phase;
+
+    #[test]
+    fn validate_output() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let resolver = |_: &OsStr| -> Result<Arc<str>, SourceLoadError> {
+            Ok("feature liga { sub f i by f_i; } liga; feature kern { pos a b 5; } kern;".into())
+        };
+        let bytes = Compiler::new("test.fea", &glyph_map)
+            .with_resolver(resolver)
+            .with_opts(Opts::new().validate_output(true))
+            .compile_binary()
+            .unwrap();
+        assert!(validate_output_bytes(&bytes).is_ok());
+    }
+
+    #[test]
+    fn table_layout() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let resolver = |_: &OsStr| -> Result<Arc<str>, SourceLoadError> {
+            Ok("feature liga { sub f i by f_i; } liga; feature kern { pos a b 5; } kern;".into())
+        };
+        let (bytes, layout) = Compiler::new("test.fea", &glyph_map)
+            .with_resolver(resolver)
+            .compile_binary_with_layout()
+            .unwrap();
+        let font = FontRef::new(&bytes).unwrap();
+        let records = font.table_directory.table_records();
+        assert_eq!(layout.len(), records.len());
+        for record in records {
+            let data = font.data_for_tag(record.tag()).unwrap();
+            assert_eq!(&bytes[layout[&record.tag()].clone()], data.as_ref());
+        }
+        assert!(layout.contains_key(&tags::GSUB) && layout.contains_key(&tags::GPOS));
+    }
+
+    #[test]
+    fn from_source() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "feature liga { sub f i by f_i; } liga;";
+        assert!(Compiler::from_source(fea, &glyph_map).compile().is_ok());
+
+        let fea = "feature liga { sub f i by nope; } liga;";
+        let Err(CompilerError::ValidationFail(errors)) =
+            Compiler::from_source(fea, &glyph_map).compile()
+        else {
+            panic!("expected validation failure");
+        };
+        assert!(errors.to_string().contains(IN_MEMORY_SOURCE_NAME));
+
+        let fea = "include(other.fea);";
+        assert!(Compiler::from_source(fea, &glyph_map).compile().is_err());
+    }
+
+    #[test]
+    fn warnings_on_success() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "languagesystem DFLT dflt; languagesystem DFLT dflt;
+            feature rtlm { sub f i by f_i; } rtlm;";
+        let (bytes, warnings) = Compiler::from_source(fea, &glyph_map)
+            .compile_binary_with_warnings()
+            .unwrap();
+        assert!(FontRef::new(&bytes).is_ok());
+        let phases = warnings.iter().map(|w| w.phase()).collect::<Vec<_>>();
+        assert_eq!(phases, [Phase::Validation, Phase::Compilation]);
+        assert!(warnings.iter().all(|w| !w.is_error()));
+    }
+
+    #[test]
+    fn ignore_unknown_items() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "futureKeyword 42;
+            feature liga { futureStatement f i; sub f i by f_i; } liga;";
+        let result = Compiler::from_source(fea, &glyph_map).compile();
+        let Err(CompilerError::ParseFail(errors)) = result else {
+            panic!("expected parse failure");
+        };
+        assert_eq!(errors.messages.len(), 2);
+
+        let compilation = Compiler::from_source(fea, &glyph_map)
+            .with_opts(Opts::new().ignore_unknown_items(true))
+            .compile()
+            .unwrap();
+        // each unknown item is reported once, by the parser
+        let kinds = compilation
+            .warnings
+            .iter()
+            .map(|w| (w.phase(), w.kind()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [(Phase::Parse, DiagnosticKind::UnknownItem); 2],
+            "{:?}",
+            compilation.warnings
+        );
+        let description = compilation.describe();
+        assert!(description.starts_with("liga"), "{description}");
+    }
+
+    #[test]
+    fn include_dirs() {
+        let dir = std::env::temp_dir().join(format!("fea-rs-include-dirs-{}", std::process::id()));
+        let sources = [
+            ("font/features.fea", "include(shared.fea);"),
+            ("font/missing.fea", "include(nope.fea);"),
+            ("lib1/shared.fea", "feature kern { pos a b 10; } kern;"),
+            ("lib2/shared.fea", "feature liga { sub f i by f_i; } liga;"),
+        ];
+        for (name, fea) in sources {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, fea).unwrap();
+        }
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let include_dirs = [dir.join("lib1"), dir.join("lib2")];
+        let compile = |root: &str| {
+            Compiler::new(dir.join(root), &glyph_map)
+                .with_include_dirs(include_dirs.clone())
+                .compile()
+        };
+        let found = compile("font/features.fea");
+        let missing = compile("font/missing.fea");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // the first directory that contains the file is used
+        let description = found.unwrap().describe();
+        assert!(description.starts_with("kern"), "{description}");
+
+        let Err(CompilerError::ParseFail(errors)) = missing else {
+            panic!("expected include to fail");
+        };
+        let searched = ["font", "lib1", "lib2"]
+            .map(|sub| format!("'{}'", dir.join(sub).join("nope.fea").display()))
+            .join(", ");
+        let message = errors.messages[0].text();
+        assert!(
+            message.ends_with(&format!("(searched {searched})'")),
+            "{message}"
+        );
+    }
+
+    #[test]
+    fn duplicate_diagnostics() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        // the conflicts in the included file are found each time it is included
+        let resolver = |path: &OsStr| -> Result<Arc<str>, SourceLoadError> {
+            match path.to_str().unwrap() {
+                "conflict.fea" => Ok("sub [a b a c c] by [x y z w v];".into()),
+                _ => Ok("feature test {
+                        include(conflict.fea);
+                        include(conflict.fea);
+                    } test;"
+                    .into()),
+            }
+        };
+        let compilation = Compiler::new("test.fea", &glyph_map)
+            .with_resolver(resolver)
+            .compile()
+            .unwrap();
+        let messages = compilation
+            .warnings
+            .iter()
+            .map(Diagnostic::text)
+            .collect::<Vec<_>>();
+        // each problem is reported once, but distinct problems at the same
+        // location are kept
+        assert_eq!(
+            messages,
+            [
+                "glyph 'a' occurs more than once in the target class with different replacements; the last one is used",
+                "glyph 'c' occurs more than once in the target class with different replacements; the last one is used",
+            ]
+        );
+        assert_eq!(compilation.warnings[0].span(), 4..15);
+        assert_eq!(compilation.warnings[1].span(), 4..15);
+    }
+
+    #[test]
+    fn diagnostics_sorted_by_location() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "@UNUSED = [a b];
+            feature liga { subtable; sub f i by f_i; } liga;";
+        let compilation = Compiler::from_source(fea, &glyph_map)
+            .with_opts(Opts::new().warn_unused_glyph_classes(true))
+            .compile()
+            .unwrap();
+        // unused classes are only reported at the end of compilation
+        let messages = compilation
+            .warnings
+            .iter()
+            .map(Diagnostic::text)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "glyph class '@UNUSED' is never used",
+                "'subtable' has no effect outside of a lookup"
+            ]
+        );
+    }
+
+    #[test]
+    fn deterministic_output() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "
+            languagesystem DFLT dflt; languagesystem latn dflt; languagesystem latn DEU;
+            @UC = [A B C D]; @LC = [a b c d];
+            markClass [acute grave] <anchor 0 500> @TOP;
+            markClass [cedilla] <anchor 0 0> @BOTTOM;
+            feature aalt { feature smcp; feature salt; } aalt;
+            feature smcp { sub @LC by @UC; } smcp;
+            feature salt { sub a from [A B]; sub b from [C]; } salt;
+            feature liga { sub f i by f_i; script latn; language DEU; sub c c by d; } liga;
+            feature kern {
+                lookupflag MarkAttachmentType @TOP;
+                pos @UC @LC -20;
+                pos [e f] [g h] -10;
+                lookupflag UseMarkFilteringSet [dieresis cedilla];
+                pos x y 5;
+            } kern;
+            feature mark {
+                pos base [a b c] <anchor 250 450> mark @TOP <anchor 250 0> mark @BOTTOM;
+            } mark;
+        ";
+        let compile = || {
+            Compiler::from_source(fea, &glyph_map)
+                .compile_binary()
+                .unwrap()
+        };
+        let first = compile();
+        for _ in 0..5 {
+            assert_eq!(first, compile());
+        }
+    }
+
+    #[test]
+    fn parallel_matches_serial() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "
+            languagesystem DFLT dflt; languagesystem latn dflt;
+            @UC = [A B C D]; @LC = [a b c d];
+            markClass [acute grave] <anchor 0 500> @TOP;
+            feature aalt { feature smcp; } aalt;
+            feature smcp { sub @LC by @UC; } smcp;
+            feature cpsp { pos [A B] 5; } cpsp;
+            feature calt {
+                lookupflag UseMarkFilteringSet [grave];
+                sub a' b by c;
+                sub c' [d e] by a;
+            } calt;
+            feature kern {
+                lookupflag UseMarkFilteringSet [acute grave];
+                pos @UC @LC -20;
+                pos a b' 10 c;
+                lookupflag UseMarkFilteringSet [grave];
+                pos x y 5;
+            } kern;
+            lookup SHARED { sub f i by f_i; } SHARED;
+            feature liga { lookup SHARED; } liga;
+            feature mark {
+                pos base [a b c] <anchor 250 450> mark @TOP;
+            } mark;
+            feature dlig { sub c c by d; } dlig;
+        ";
+        let compile = |parallel| {
+            Compiler::from_source(fea, &glyph_map)
+                .with_opts(Opts::new().parallel(parallel))
+                .compile_binary_with_warnings()
+                .unwrap()
+        };
+        assert_eq!(compile(false), compile(true));
+    }
+
+    #[test]
+    fn diagnostic_sink() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "languagesystem DFLT dflt; languagesystem DFLT dflt;
+            feature rtlm { sub f i by f_i; } rtlm;";
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let seen = seen.clone();
+            move |diagnostic: &Diagnostic| seen.lock().unwrap().push(diagnostic.clone())
+        };
+        let (_, warnings) = Compiler::from_source(fea, &glyph_map)
+            .with_opts(Opts::new().diagnostic_sink(sink))
+            .compile_binary_with_warnings()
+            .unwrap();
+        assert_eq!(*seen.lock().unwrap(), warnings);
+    }
+
+    #[test]
+    fn invalid_output() {
+        let err = validate_output_bytes(b"definitely not a font").unwrap_err();
+        assert!(err.table.is_none());
+    }
+}
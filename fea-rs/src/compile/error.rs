@@ -1,39 +1,15 @@
 //! Error types related to compilation
 
-use std::sync::Arc;
+use std::{ffi::OsStr, fmt::Write, path::Path, sync::Arc};
 
-use write_fonts::{read::ReadError, validate::ValidationReport};
+use write_fonts::{read::ReadError, types::Tag, validate::ValidationReport};
 
 use crate::{
     parse::{SourceList, SourceLoadError},
-    Diagnostic,
+    Diagnostic, Level, Phase,
 };
 
-/// An error that occurs when extracting a glyph order from a UFO.
-#[derive(Clone, Debug, thiserror::Error)]
-pub enum UfoGlyphOrderError {
-    /// Missing 'public.glyphOrder' key
-    #[error("No public.glyphOrder key in lib.plist")]
-    KeyNotSet,
-    /// Glyph order is present, but malformed
-    #[error("public.glyphOrder exists, but is not an array of strings")]
-    Malformed,
-}
-
-/// An error that occurs when extracting a glyph order from a font file.
-#[derive(Clone, Debug, thiserror::Error)]
-pub enum FontGlyphOrderError {
-    /// Failed to read font data
-    #[error("Failed to read font data: '{0}'")]
-    ReadError(
-        #[from]
-        #[source]
-        ReadError,
-    ),
-    /// Post table is missing glyph names
-    #[error("The post table exists, but did not include all glyph names")]
-    MissingNames,
-}
+pub use crate::common::{FontGlyphOrderError, UfoGlyphOrderError};
 
 /// An error that occurs when loading a raw glyph order.
 #[derive(Clone, Debug, thiserror::Error)]
@@ -65,20 +41,178 @@ pub enum CompilerError {
     CompilationFail(DiagnosticSet),
     #[error("Binary generation failed: '{0}'")]
     WriteFail(#[from] BinaryCompilationError),
+    #[error("{0}")]
+    OutputValidationFail(#[from] OutputValidationError),
 }
 
 /// An error that occured when generating the binary font
 #[derive(Debug, thiserror::Error)]
-#[error("Binary generation failed: '{0}'")]
-pub struct BinaryCompilationError(ValidationReport);
+pub enum BinaryCompilationError {
+    /// A generated table failed validation
+    #[error("Binary generation failed: '{0}'")]
+    Validation(ValidationReport),
+    /// A generated table is too large for the offsets in its format
+    #[error("Binary generation failed: the '{0}' table is too large")]
+    TableTooLarge(Tag),
+}
+
+/// An error that occurs when the generated binary cannot be read back.
+///
+/// This is only reported if [`Opts::validate_output`] is set, and indicates
+/// a bug in the compiler.
+///
+/// [`Opts::validate_output`]: super::Opts::validate_output
+#[derive(Clone, Debug, thiserror::Error)]
+pub struct OutputValidationError {
+    /// The table that failed to parse, if the error was not in the font header
+    pub table: Option<Tag>,
+    /// The underlying error
+    #[source]
+    pub error: ReadError,
+}
 
 /// A set of diagnostics with the associated source info
+///
+/// When displayed with the alternate format (`{:#}`), each diagnostic
+/// includes its [`DiagnosticKind`], as in `error[undefined-glyph]`.
+///
+/// [`DiagnosticKind`]: crate::DiagnosticKind
 #[derive(Clone)]
 pub struct DiagnosticSet {
     pub(crate) messages: Vec<Diagnostic>,
     pub(crate) sources: Arc<SourceList>,
 }
 
+impl CompilerError {
+    /// The diagnostics that caused this error, if it was caused by the source.
+    pub fn diagnostics(&self) -> Option<&DiagnosticSet> {
+        match self {
+            CompilerError::ParseFail(diagnostics)
+            | CompilerError::ValidationFail(diagnostics)
+            | CompilerError::CompilationFail(diagnostics) => Some(diagnostics),
+            _ => None,
+        }
+    }
+}
+
+impl DiagnosticSet {
+    /// The diagnostics in this set.
+    pub fn messages(&self) -> &[Diagnostic] {
+        &self.messages
+    }
+
+    /// The number of errors that were generated in the given phase.
+    pub fn error_count(&self, phase: Phase) -> usize {
+        self.messages
+            .iter()
+            .filter(|diagnostic| diagnostic.is_error() && diagnostic.phase() == phase)
+            .count()
+    }
+
+    /// Serialize these diagnostics as a JSON array, for use by editors.
+    ///
+    /// Each diagnostic is an object in the shape of a Language Server Protocol
+    /// `Diagnostic`, with an additional `uri` field for the file it is in:
+    ///
+    /// ```json
+    /// {
+    ///   "uri": "file:///path/to/features.fea",
+    ///   "range": {
+    ///     "start": { "line": 2, "character": 30 },
+    ///     "end": { "line": 2, "character": 34 }
+    ///   },
+    ///   "severity": 1,
+    ///   "code": "undefined-glyph",
+    ///   "source": "fea-rs",
+    ///   "message": "glyph not in font"
+    /// }
+    /// ```
+    ///
+    /// Lines and characters are zero-based, and characters are counted in
+    /// UTF-16 code units, as in the LSP. Diagnostics in an included file refer
+    /// to that file. Relative paths are resolved against the current directory.
+    pub fn to_lsp_json(&self) -> String {
+        let mut out = String::from("[");
+        for (i, diagnostic) in self.messages.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let source = self.sources.get(&diagnostic.message.file).unwrap();
+            let span = diagnostic.span();
+            let start = json_position(source.utf16_position(span.start));
+            let end = json_position(source.utf16_position(span.end));
+            let uri = json_string(&file_uri(source.path()));
+            let severity = match diagnostic.level {
+                Level::Error => 1,
+                Level::Warning => 2,
+                Level::Info => 3,
+            };
+            let code = json_string(diagnostic.kind().code());
+            let message = json_string(diagnostic.text());
+            write!(
+                &mut out,
+                r#"{{"uri":{uri},"range":{{"start":{start},"end":{end}}},"severity":{severity},"#
+            )
+            .unwrap();
+            write!(
+                &mut out,
+                r#""code":{code},"source":"fea-rs","message":{message}}}"#
+            )
+            .unwrap();
+        }
+        out.push(']');
+        out
+    }
+}
+
+/// Convert a path to a `file://` URI.
+fn file_uri(path: &OsStr) -> String {
+    let path = Path::new(path);
+    let path = match std::env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path.to_owned(),
+    };
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut uri = String::from("file://");
+    // windows paths start with the drive letter
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => write!(&mut uri, "%{byte:02X}").unwrap(),
+        }
+    }
+    uri
+}
+
+/// Format a zero-based (line, character) pair as an LSP `Position`.
+fn json_position((line, character): (usize, usize)) -> String {
+    format!(r#"{{"line":{line},"character":{character}}}"#)
+}
+
+/// Quote and escape a string for JSON.
+fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => write!(&mut out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 impl std::fmt::Display for DiagnosticSet {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let mut first = true;
@@ -86,7 +220,7 @@ impl std::fmt::Display for DiagnosticSet {
             if !first {
                 writeln!(f)?;
             }
-            write!(f, "{}", self.sources.format_diagnostic(err))?;
+            write!(f, "{}", self.sources.format_diagnostic(err, f.alternate()))?;
             first = false;
         }
         Ok(())
@@ -102,19 +236,109 @@ impl std::fmt::Debug for DiagnosticSet {
     }
 }
 
+impl std::fmt::Display for OutputValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.table {
+            Some(table) => write!(f, "Generated '{table}' table is invalid: '{}'", self.error),
+            None => write!(f, "Generated font is invalid: '{}'", self.error),
+        }
+    }
+}
+
 impl From<ValidationReport> for BinaryCompilationError {
     fn from(src: ValidationReport) -> BinaryCompilationError {
-        BinaryCompilationError(src)
+        BinaryCompilationError::Validation(src)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        compile::{compile_str, Compiler, Opts},
+        DiagnosticKind,
+    };
 
     #[test]
     fn assert_compiler_error_is_send() {
         fn send_me_baby<T: Send>() {}
         send_me_baby::<CompilerError>();
     }
+
+    #[test]
+    fn error_phases() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let opts = Opts::new().max_ligature_sequences(1);
+        let error_counts = |fea: &str| {
+            let err = compile_str(fea, &glyph_map, opts.clone()).unwrap_err();
+            let diagnostics = err.diagnostics().unwrap();
+            [Phase::Parse, Phase::Validation, Phase::Compilation]
+                .map(|phase| diagnostics.error_count(phase))
+        };
+        let parse = "feature liga { sub f i by f_i } liga;";
+        let validate = "feature liga { sub f i by nope; } liga;";
+        let compile = "feature liga { sub [f F] i by f_i; } liga;";
+        assert_eq!(error_counts(parse), [1, 0, 0]);
+        assert_eq!(error_counts(validate), [0, 1, 0]);
+        assert_eq!(error_counts(compile), [0, 0, 1]);
+    }
+
+    #[test]
+    fn diagnostic_kinds() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea = "feature liga { sub f i by nope; sub @NOPE by a; } liga;";
+        let err = compile_str(fea, &glyph_map, Opts::new()).unwrap_err();
+        let diagnostics = err.diagnostics().unwrap();
+        let kinds = diagnostics
+            .messages()
+            .iter()
+            .map(|diag| diag.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                DiagnosticKind::UndefinedGlyph,
+                DiagnosticKind::UndefinedGlyphClass
+            ]
+        );
+        assert!(!diagnostics.to_string().contains("[undefined-glyph]"));
+        assert!(format!("{diagnostics:#}").contains("[undefined-glyph]"));
+    }
+
+    #[test]
+    fn lsp_json() {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let resolver = |path: &OsStr| -> Result<Arc<str>, SourceLoadError> {
+            match path.to_str().unwrap() {
+                "/fonts/main.fea" => Ok("include(/fonts/my classes.fea);\n\
+                    feature liga { sub f i by nope; } liga;"
+                    .into()),
+                _ => Ok("# classes\ntable name { nameid 9 \"😀\"; } name; @LC = [bogus];".into()),
+            }
+        };
+        let err = Compiler::new("/fonts/main.fea", &glyph_map)
+            .with_resolver(resolver)
+            .compile()
+            .unwrap_err();
+        let json = err.diagnostics().unwrap().to_lsp_json();
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let diagnostics = json.as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2, "{json:#}");
+
+        let included = &diagnostics[0];
+        assert_eq!(included["uri"], "file:///fonts/my%20classes.fea");
+        assert_eq!(included["severity"], 1);
+        assert_eq!(included["code"], "undefined-glyph");
+        // the emoji is four bytes, but two UTF-16 code units
+        let start = &included["range"]["start"];
+        assert_eq!(start["line"], 1);
+        assert_eq!(start["character"], 43);
+
+        let main = &diagnostics[1];
+        assert_eq!(main["uri"], "file:///fonts/main.fea");
+        assert_eq!(main["message"], "glyph not in font");
+        let range = &main["range"];
+        assert_eq!(range["start"]["line"], 1);
+        assert_eq!(range["end"]["line"], 1);
+    }
 }
@@ -1,6 +1,6 @@
 //! Logic for tracking features during compilation
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 
 use write_fonts::{
     tables::layout::SizeParams,
@@ -15,12 +15,13 @@ use super::{
 };
 
 /// Tracking lookups in a feature block
+#[derive(Clone)]
 pub(crate) struct ActiveFeature {
     tag: Tag,
     default_systems: DefaultLanguageSystems,
     current_lang_sys: Option<LanguageSystem>,
-    lookups: HashMap<LanguageSystem, Vec<LookupId>>,
-    script_default_lookups: HashMap<Tag, Vec<LookupId>>,
+    lookups: BTreeMap<LanguageSystem, Vec<LookupId>>,
+    script_default_lookups: BTreeMap<Tag, Vec<LookupId>>,
 }
 
 /// State required to generate the aalt feature.
@@ -31,7 +32,7 @@ pub(crate) struct ActiveFeature {
 #[derive(Clone, Debug, Default)]
 pub(crate) struct AaltFeature {
     aalt_features: Vec<Tag>,
-    pub(crate) all_alts: HashMap<GlyphId, Vec<GlyphId>>,
+    pub(crate) all_alts: BTreeMap<GlyphId, Vec<GlyphId>>,
     // to avoid duplicates
     all_pairs: HashSet<(GlyphId, GlyphId)>,
 }
@@ -87,41 +88,62 @@ impl ActiveFeature {
 
         // but if this is a fully-resolved language system, we add the default
         // lookups now, when we have access to the 'exclude_dflt' flag.
-        if system.language != tags::LANG_DFLT {
-            let mut lookups = Vec::new();
-            if !exclude_dflt {
-                let script_dflt = LanguageSystem {
-                    script: system.script,
-                    language: tags::LANG_DFLT,
-                };
-                // if *either* this is an explicit default, or this is part of
-                // a script where script/dflt is an explicit default, and we have
-                // seen a script keyword, add the default lookups
-                if self.default_systems.contains(&system)
-                    || (self.default_systems.contains(&script_dflt)
-                        && self.script_default_lookups.contains_key(&system.script))
-                {
-                    lookups.extend(
-                        self.lookups
-                            .get(&LanguageSystem::default())
-                            .into_iter()
-                            .flat_map(|v| v.iter().copied()),
-                    );
-                }
-                lookups.extend(
-                    self.script_default_lookups
-                        .get(&system.script)
-                        .into_iter()
-                        .flat_map(|v| v.iter().copied()),
-                );
-            }
-            self.lookups.entry(system).or_insert_with(|| lookups);
+        if system.language != tags::LANG_DFLT && !self.lookups.contains_key(&system) {
+            let lookups = if exclude_dflt {
+                Vec::new()
+            } else {
+                self.default_lookups_for(system)
+            };
+            self.lookups.insert(system, lookups);
         }
 
         self.current_lang_sys = Some(system);
         system.to_feature_key(self.tag)
     }
 
+    /// The number of default lookups that `exclude_dflt` would prevent from
+    /// being added to this system, if it were set now.
+    ///
+    /// This is zero if the system has already been set in this feature, since
+    /// its lookups were determined at that point.
+    pub(crate) fn n_excludable_lookups(&self, system: LanguageSystem) -> usize {
+        if system.language == tags::LANG_DFLT || self.lookups.contains_key(&system) {
+            return 0;
+        }
+        self.default_lookups_for(system).len()
+    }
+
+    /// The lookups that a (non-default) language system inherits from the
+    /// defaults, unless `exclude_dflt` is set.
+    fn default_lookups_for(&self, system: LanguageSystem) -> Vec<LookupId> {
+        let mut lookups = Vec::new();
+        let script_dflt = LanguageSystem {
+            script: system.script,
+            language: tags::LANG_DFLT,
+        };
+        // if *either* this is an explicit default, or this is part of
+        // a script where script/dflt is an explicit default, and we have
+        // seen a script keyword, add the default lookups
+        if self.default_systems.contains(&system)
+            || (self.default_systems.contains(&script_dflt)
+                && self.script_default_lookups.contains_key(&system.script))
+        {
+            lookups.extend(
+                self.lookups
+                    .get(&LanguageSystem::default())
+                    .into_iter()
+                    .flat_map(|v| v.iter().copied()),
+            );
+        }
+        lookups.extend(
+            self.script_default_lookups
+                .get(&system.script)
+                .into_iter()
+                .flat_map(|v| v.iter().copied()),
+        );
+        lookups
+    }
+
     pub(crate) fn add_lookup(&mut self, lookup: LookupId) {
         // there is a distinction between "implicit DFLT/dflt" and having
         // an explicit 'DFLT' script in the lookup block.
@@ -393,4 +415,27 @@ mod tests {
         let key = LATN_DEF.to_feature_key(TAG_TEST);
         assert_eq!(built.get(&key), Some(&vec![id8]));
     }
+
+    #[test]
+    fn count_excluded_lookups() {
+        let defaults = default_systems([DFLT_DFLT, LATN_DFLT, LATN_DEU]);
+        let [id1, id2, id3] = make_ids();
+
+        let mut feature = ActiveFeature::new(TAG_TEST, defaults);
+        feature.add_lookup(id1);
+        feature.set_system(LATN_DFLT, false);
+        feature.add_lookup(id2);
+        assert_eq!(feature.n_excludable_lookups(LATN_DEU), 2);
+        // not a registered default, but latn/dflt is
+        assert_eq!(feature.n_excludable_lookups(LATN_TRK), 2);
+        feature.set_system(LATN_DEU, true);
+        feature.add_lookup(id3);
+        // already set; exclude_dflt no longer has any effect
+        assert_eq!(feature.n_excludable_lookups(LATN_DEU), 0);
+        assert_eq!(feature.n_excludable_lookups(LATN_DFLT), 0);
+
+        let built = feature.build_features();
+        let key = LATN_DEU.to_feature_key(TAG_TEST);
+        assert_eq!(built.get(&key), Some(&vec![id3]));
+    }
 }
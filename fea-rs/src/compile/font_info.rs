@@ -0,0 +1,264 @@
+//! Information about the font being compiled
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use write_fonts::read::{
+    tables::{gdef::Gdef, glyf::Glyph, layout::ClassDef},
+    FontRef, ReadError, TableProvider,
+};
+
+use crate::common::{read_cmap, GlyphId};
+
+/// Information about the font that the FEA is being compiled for.
+///
+/// Some parts of compilation (such as resolving unicode values, or metrics
+/// expressed relative to the em) require information that is not available in
+/// the FEA itself. This can be provided manually, or read from an existing
+/// font with [`FontInfo::from_font_data`].
+#[derive(Clone, Debug, Default)]
+pub struct FontInfo {
+    /// The font's units per em, if known.
+    pub units_per_em: Option<u16>,
+    /// A map from unicode codepoints to glyph ids.
+    pub cmap: BTreeMap<u32, GlyphId>,
+    /// The total number of glyphs in the font, if known.
+    pub num_glyphs: Option<u16>,
+    /// The glyph classes from the font's existing GDEF table.
+    ///
+    /// The values are those used in the GDEF `GlyphClassDef`: 1 for base
+    /// glyphs, 2 for ligatures, 3 for marks and 4 for components. If the FEA
+    /// does not declare glyph classes, these are used in the generated GDEF,
+    /// and only glyphs that are missing here are assigned inferred classes.
+    pub glyph_classes: BTreeMap<GlyphId, u16>,
+    /// The mark glyph sets from the font's existing GDEF table.
+    ///
+    /// These keep their indices in the generated GDEF, since lookups in the
+    /// font may refer to them. The sets used by `UseMarkFilteringSet` in the
+    /// FEA are added after them, reusing an identical set if there is one.
+    pub mark_glyph_sets: Vec<BTreeSet<GlyphId>>,
+    /// The font's existing vertical metrics, from its `vmtx` table.
+    ///
+    /// A `vmtx` table block in the FEA only sets the metrics of some glyphs,
+    /// so a `vmtx` table is only generated if these are provided.
+    pub vertical_metrics: Option<VerticalMetrics>,
+    /// The top of each glyph's bounding box (its `yMax`).
+    ///
+    /// This is needed to convert `VertOriginY` values to top side bearings
+    /// when [`Opts::bake_vertical_origins`] is set. Glyphs without outlines
+    /// have no entry.
+    ///
+    /// [`Opts::bake_vertical_origins`]: super::Opts::bake_vertical_origins
+    pub glyph_y_max: BTreeMap<GlyphId, i16>,
+}
+
+/// The metrics in a font's `vmtx` table.
+///
+/// This matches the layout of the table: the first glyphs each have a full
+/// metric, and the remaining glyphs only have a top side bearing, and use the
+/// last advance.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VerticalMetrics {
+    /// The advance height and top side bearing of each glyph with a full metric.
+    pub long_metrics: Vec<(u16, i16)>,
+    /// The top side bearing of each of the remaining glyphs.
+    pub top_side_bearings: Vec<i16>,
+}
+
+impl FontInfo {
+    /// Read the font info from a font binary.
+    ///
+    /// The `cmap` is built from the unicode subtables in formats 4 and 12;
+    /// other subtables are ignored. The glyph classes and mark glyph sets are
+    /// read from the `GDEF` table, the vertical metrics from the `vmtx` table,
+    /// and the glyph bounds from the `glyf` table, if these are present.
+    pub fn from_font_data(data: &[u8]) -> Result<Self, ReadError> {
+        let font = FontRef::new(data)?;
+        let units_per_em = Some(font.head()?.units_per_em());
+        let num_glyphs = Some(font.maxp()?.num_glyphs());
+        let cmap = match font.cmap() {
+            Ok(cmap) => read_cmap(&cmap)?,
+            Err(ReadError::TableIsMissing(_)) => Default::default(),
+            Err(e) => return Err(e),
+        };
+        let (glyph_classes, mark_glyph_sets) = match font.gdef() {
+            Ok(gdef) => (read_glyph_classes(&gdef)?, read_mark_glyph_sets(&gdef)?),
+            Err(ReadError::TableIsMissing(_)) => Default::default(),
+            Err(e) => return Err(e),
+        };
+        let vertical_metrics = match font.vmtx() {
+            Ok(vmtx) => Some(VerticalMetrics {
+                long_metrics: vmtx
+                    .v_metrics()
+                    .iter()
+                    .map(|metric| (metric.advance(), metric.side_bearing()))
+                    .collect(),
+                top_side_bearings: vmtx.top_side_bearings().iter().map(|x| x.get()).collect(),
+            }),
+            Err(ReadError::TableIsMissing(_)) => None,
+            Err(e) => return Err(e),
+        };
+        let glyph_y_max = read_glyph_y_max(&font, num_glyphs.unwrap_or_default())?;
+        Ok(FontInfo {
+            units_per_em,
+            cmap,
+            num_glyphs,
+            glyph_classes,
+            mark_glyph_sets,
+            vertical_metrics,
+            glyph_y_max,
+        })
+    }
+
+    /// The glyph for the provided codepoint, if it is in the cmap.
+    pub fn glyph_for_codepoint(&self, codepoint: u32) -> Option<GlyphId> {
+        self.cmap.get(&codepoint).copied()
+    }
+}
+
+impl VerticalMetrics {
+    /// The number of glyphs covered by these metrics.
+    pub fn len(&self) -> usize {
+        self.long_metrics.len() + self.top_side_bearings.len()
+    }
+
+    /// Returns `true` if these metrics cover no glyphs.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The advance height of a glyph, if it is covered by these metrics.
+    pub fn advance(&self, glyph: GlyphId) -> Option<u16> {
+        let idx = glyph.to_u16() as usize;
+        if idx >= self.len() {
+            return None;
+        }
+        self.long_metrics
+            .get(idx)
+            .or_else(|| self.long_metrics.last())
+            .map(|(advance, _)| *advance)
+    }
+
+    /// `true` if this glyph has a full metric, so its advance can be changed.
+    pub(crate) fn has_long_metric(&self, glyph: GlyphId) -> bool {
+        (glyph.to_u16() as usize) < self.long_metrics.len()
+    }
+}
+
+/// Read the top of the bounding box of each glyph from the `glyf` table.
+///
+/// Fonts without a `glyf` table (such as CFF fonts) have no bounds.
+fn read_glyph_y_max(font: &FontRef, num_glyphs: u16) -> Result<BTreeMap<GlyphId, i16>, ReadError> {
+    let (loca, glyf) = match (font.loca(None), font.glyf()) {
+        (Ok(loca), Ok(glyf)) => (loca, glyf),
+        (Err(ReadError::TableIsMissing(_)), _) | (_, Err(ReadError::TableIsMissing(_))) => {
+            return Ok(Default::default())
+        }
+        (Err(e), _) | (_, Err(e)) => return Err(e),
+    };
+    let mut result = BTreeMap::new();
+    for gid in (0..num_glyphs).map(GlyphId::new) {
+        let y_max = match loca.get_glyf(gid, &glyf)? {
+            Some(Glyph::Simple(glyph)) => glyph.y_max(),
+            Some(Glyph::Composite(glyph)) => glyph.y_max(),
+            None => continue,
+        };
+        result.insert(gid, y_max);
+    }
+    Ok(result)
+}
+
+/// Read the glyph class definition table from a GDEF table.
+fn read_glyph_classes(gdef: &Gdef) -> Result<BTreeMap<GlyphId, u16>, ReadError> {
+    let mut result = BTreeMap::new();
+    match gdef.glyph_class_def().transpose()? {
+        Some(ClassDef::Format1(class_def)) => {
+            let start = class_def.start_glyph_id().to_u16();
+            for (i, class) in class_def.class_value_array().iter().enumerate() {
+                let class = class.get();
+                if class != 0 {
+                    let gid = u16::try_from(i)
+                        .ok()
+                        .and_then(|i| start.checked_add(i))
+                        .ok_or(ReadError::OutOfBounds)?;
+                    result.insert(GlyphId::new(gid), class);
+                }
+            }
+        }
+        Some(ClassDef::Format2(class_def)) => {
+            for record in class_def.class_range_records() {
+                let class = record.class();
+                if class != 0 {
+                    let start = record.start_glyph_id().to_u16();
+                    let end = record.end_glyph_id().to_u16();
+                    result.extend((start..=end).map(|gid| (GlyphId::new(gid), class)));
+                }
+            }
+        }
+        None => (),
+    }
+    Ok(result)
+}
+
+/// Read the mark glyph sets from a GDEF table, in order.
+fn read_mark_glyph_sets(gdef: &Gdef) -> Result<Vec<BTreeSet<GlyphId>>, ReadError> {
+    match gdef.mark_glyph_sets_def().transpose()? {
+        Some(sets) => sets
+            .coverages()
+            .iter()
+            .map(|coverage| coverage.map(|coverage| coverage.iter().collect()))
+            .collect(),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::read::{FontData, FontRead};
+
+    use super::*;
+
+    #[test]
+    fn gdef_glyph_classes() {
+        // version 1.0, with a GlyphClassDef at offset 12 and no other subtables
+        let mut data = vec![0, 1, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0];
+        // format 2, two ranges
+        let class_def: &[u16] = &[2, 2, 3, 5, 1, 8, 8, 3];
+        data.extend(class_def.iter().flat_map(|x| x.to_be_bytes()));
+
+        let gdef = Gdef::read(FontData::new(&data)).unwrap();
+        let classes = read_glyph_classes(&gdef).unwrap();
+        assert_eq!(classes.len(), 4);
+        assert_eq!(classes.get(&GlyphId::new(4)), Some(&1));
+        assert_eq!(classes.get(&GlyphId::new(8)), Some(&3));
+        assert!(read_mark_glyph_sets(&gdef).unwrap().is_empty());
+
+        // format 1, starting at glyph 2
+        let mut data = vec![0, 1, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0];
+        let class_def: &[u16] = &[1, 2, 3, 1, 0, 3];
+        data.extend(class_def.iter().flat_map(|x| x.to_be_bytes()));
+        let gdef = Gdef::read(FontData::new(&data)).unwrap();
+        let classes = read_glyph_classes(&gdef).unwrap();
+        assert_eq!(classes.len(), 2);
+        assert_eq!(classes.get(&GlyphId::new(4)), Some(&3));
+    }
+
+    #[test]
+    fn gdef_mark_glyph_sets() {
+        // version 1.2, with only a MarkGlyphSetsDef, at offset 14
+        let mut data = vec![0, 1, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 14];
+        // format 1, one set, whose coverage is at offset 8
+        data.extend([0, 1, 0, 1, 0, 0, 0, 8]);
+        // coverage format 1, with glyphs 3 and 5
+        let coverage: &[u16] = &[1, 2, 3, 5];
+        data.extend(coverage.iter().flat_map(|x| x.to_be_bytes()));
+
+        let gdef = Gdef::read(FontData::new(&data)).unwrap();
+        assert!(read_glyph_classes(&gdef).unwrap().is_empty());
+        let sets = read_mark_glyph_sets(&gdef).unwrap();
+        assert_eq!(sets.len(), 1);
+        assert_eq!(
+            sets[0].iter().copied().collect::<Vec<_>>(),
+            [GlyphId::new(3), GlyphId::new(5)]
+        );
+    }
+}
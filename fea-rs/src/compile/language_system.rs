@@ -1,13 +1,13 @@
 //! helpers for managing tracking language systems
 
-use std::{collections::HashSet, rc::Rc};
+use std::{collections::BTreeSet, sync::Arc};
 
 use write_fonts::types::Tag;
 
 use super::{lookups::FeatureKey, tags};
 
 /// A script/language pair
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct LanguageSystem {
     pub script: Tag,
     pub language: Tag,
@@ -20,16 +20,16 @@ pub struct LanguageSystem {
 pub(crate) struct DefaultLanguageSystems {
     has_explicit_entry: bool,
     // this is me being fancy, because we clone this everytime we start a lookup.
-    items: Rc<HashSet<LanguageSystem>>,
+    items: Arc<BTreeSet<LanguageSystem>>,
 }
 
 impl DefaultLanguageSystems {
     pub(crate) fn insert(&mut self, system: LanguageSystem) {
         if !self.has_explicit_entry {
-            Rc::get_mut(&mut self.items).unwrap().clear();
+            Arc::get_mut(&mut self.items).unwrap().clear();
             self.has_explicit_entry = true;
         }
-        Rc::get_mut(&mut self.items).unwrap().insert(system);
+        Arc::get_mut(&mut self.items).unwrap().insert(system);
     }
 
     pub(crate) fn contains(&self, key: &LanguageSystem) -> bool {
@@ -65,7 +65,7 @@ impl Default for DefaultLanguageSystems {
     fn default() -> Self {
         Self {
             has_explicit_entry: false,
-            items: Rc::new(HashSet::from_iter([LanguageSystem::default()])),
+            items: Arc::new(BTreeSet::from_iter([LanguageSystem::default()])),
         }
     }
 }
@@ -6,7 +6,7 @@ mod gsub;
 mod helpers;
 
 use std::{
-    collections::{BTreeMap, HashMap, HashSet},
+    collections::{BTreeMap, BTreeSet},
     convert::TryInto,
 };
 
@@ -17,11 +17,13 @@ use write_fonts::{
         gpos::{self as write_gpos, AnchorTable, ValueRecord},
         gsub as write_gsub,
         layout::{
-            Feature, FeatureList, FeatureRecord, LangSys, LangSysRecord, Lookup as RawLookup,
-            LookupFlag, LookupList, Script, ScriptList, ScriptRecord,
+            ConditionFormat1, ConditionSet, Feature, FeatureList, FeatureRecord,
+            FeatureTableSubstitution, FeatureTableSubstitutionRecord, FeatureVariationRecord,
+            FeatureVariations, LangSys, LangSysRecord, Lookup as RawLookup, LookupFlag, LookupList,
+            Script, ScriptList, ScriptRecord,
         },
     },
-    types::Tag,
+    types::{F2Dot14, Tag},
 };
 
 use crate::{
@@ -30,7 +32,11 @@ use crate::{
     Kind,
 };
 
-use super::{tables::ClassId, tags};
+use super::{
+    tables::ClassId,
+    tags,
+    variations::{AxisCondition, FeatureVariation},
+};
 
 use contextual::{
     ContextualLookupBuilder, PosChainContextBuilder, PosContextBuilder, ReverseChainBuilder,
@@ -42,6 +48,7 @@ use gpos::{
     SinglePosBuilder,
 };
 use gsub::{AlternateSubBuilder, LigatureSubBuilder, MultipleSubBuilder, SingleSubBuilder};
+use helpers::coverage_size;
 pub(crate) use helpers::ClassDefBuilder2;
 
 pub trait Builder {
@@ -51,13 +58,27 @@ pub trait Builder {
 
 pub(crate) type FilterSetId = u16;
 
+/// Lookups whose subtables are estimated to be larger than this in total are
+/// written as extension lookups, even without `useExtension`.
+///
+/// Subtables are referenced from their lookup by 16-bit offsets, which a
+/// lookup this large is likely to overflow. Smaller lookups are not promoted,
+/// since the extension subtables add some overhead.
+const AUTO_EXTENSION_SIZE: usize = u16::MAX as usize;
+
+/// All of the lookups in the FEA.
+///
+/// Lookups are assigned ids in source order as they are finished; see the docs
+/// on [`Compilation`](super::Compilation) for the precise guarantee.
 #[derive(Clone, Debug, Default)]
 pub(crate) struct AllLookups {
     current: Option<SomeLookup>,
     current_name: Option<SmolStr>,
+    // set by the `useExtension` keyword on the current named lookup
+    current_use_extension: bool,
     gpos: Vec<PositionLookup>,
     gsub: Vec<SubstitutionLookup>,
-    named: HashMap<SmolStr, LookupId>,
+    named: BTreeMap<SmolStr, LookupId>,
 }
 
 #[derive(Clone, Debug)]
@@ -65,6 +86,8 @@ pub(crate) struct LookupBuilder<T> {
     flags: LookupFlag,
     mark_set: Option<FilterSetId>,
     subtables: Vec<T>,
+    // if true, each subtable is wrapped in an extension subtable
+    use_extension: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -100,9 +123,14 @@ pub(crate) enum SomeLookup {
     GsubContextual(ContextualLookupBuilder<SubstitutionLookup>),
 }
 
+/// The identifier of a lookup in the generated `GPOS` or `GSUB` table.
+///
+/// The contained value is the index of the lookup in that table's lookup list.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
-pub(crate) enum LookupId {
+pub enum LookupId {
+    /// A lookup in the `GPOS` table
     Gpos(usize),
+    /// A lookup in the `GSUB` table
     Gsub(usize),
     /// Used when a named lookup block has no rules.
     ///
@@ -111,7 +139,7 @@ pub(crate) enum LookupId {
 }
 
 /// Tracks the current lookupflags state
-#[derive(Clone, Copy, Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub(crate) struct LookupFlagInfo {
     pub(crate) flags: LookupFlag,
     pub(crate) mark_filter_set: Option<FilterSetId>,
@@ -129,6 +157,8 @@ pub(crate) struct PosSubBuilder<T> {
     lookups: Vec<T>,
     scripts: BTreeMap<Tag, BTreeMap<Tag, LangSys>>,
     features: BTreeMap<(Tag, Vec<u16>), u16>,
+    // conditions, and a map of feature index to alternate lookups
+    variations: Vec<(Vec<AxisCondition>, BTreeMap<u16, Vec<u16>>)>,
 }
 
 impl<T: Default> LookupBuilder<T> {
@@ -137,6 +167,7 @@ impl<T: Default> LookupBuilder<T> {
             flags,
             mark_set,
             subtables: vec![Default::default()],
+            use_extension: false,
         }
     }
 
@@ -149,6 +180,7 @@ impl<T: Default> LookupBuilder<T> {
             flags,
             mark_set,
             subtables,
+            use_extension: false,
         }
     }
 
@@ -173,13 +205,30 @@ impl<U> LookupBuilder<U> {
             flags,
             mark_set,
             subtables,
+            use_extension,
         } = self;
         LookupBuilder {
             flags,
             mark_set,
             subtables: subtables.into_iter().map(Into::into).collect(),
+            use_extension,
         }
     }
+
+    /// Build the lookup, wrapping each subtable in an extension subtable.
+    fn build_extension<T, E>(self, wrap: impl Fn(T) -> E) -> RawLookup<E>
+    where
+        U: Builder<Output = Vec<T>>,
+        E: Default,
+    {
+        let subtables = self
+            .subtables
+            .into_iter()
+            .flat_map(|b| b.build().into_iter())
+            .map(wrap)
+            .collect();
+        RawLookup::new(self.flags, subtables, self.mark_set.unwrap_or_default())
+    }
 }
 
 impl PositionLookup {
@@ -195,6 +244,164 @@ impl PositionLookup {
             PositionLookup::ChainedContextual(lookup) => lookup.force_subtable_break(),
         }
     }
+
+    fn mark_set_mut(&mut self) -> &mut Option<FilterSetId> {
+        match self {
+            PositionLookup::Single(lookup) => &mut lookup.mark_set,
+            PositionLookup::Pair(lookup) => &mut lookup.mark_set,
+            PositionLookup::Cursive(lookup) => &mut lookup.mark_set,
+            PositionLookup::MarkToBase(lookup) => &mut lookup.mark_set,
+            PositionLookup::MarkToLig(lookup) => &mut lookup.mark_set,
+            PositionLookup::MarkToMark(lookup) => &mut lookup.mark_set,
+            PositionLookup::Contextual(lookup) => &mut lookup.mark_set,
+            PositionLookup::ChainedContextual(lookup) => &mut lookup.mark_set,
+        }
+    }
+
+    fn set_use_extension(&mut self) {
+        match self {
+            PositionLookup::Single(lookup) => lookup.use_extension = true,
+            PositionLookup::Pair(lookup) => lookup.use_extension = true,
+            PositionLookup::Cursive(lookup) => lookup.use_extension = true,
+            PositionLookup::MarkToBase(lookup) => lookup.use_extension = true,
+            PositionLookup::MarkToLig(lookup) => lookup.use_extension = true,
+            PositionLookup::MarkToMark(lookup) => lookup.use_extension = true,
+            PositionLookup::Contextual(lookup) => lookup.use_extension = true,
+            PositionLookup::ChainedContextual(lookup) => lookup.use_extension = true,
+        }
+    }
+
+    fn use_extension(&self) -> bool {
+        match self {
+            PositionLookup::Single(lookup) => lookup.use_extension,
+            PositionLookup::Pair(lookup) => lookup.use_extension,
+            PositionLookup::Cursive(lookup) => lookup.use_extension,
+            PositionLookup::MarkToBase(lookup) => lookup.use_extension,
+            PositionLookup::MarkToLig(lookup) => lookup.use_extension,
+            PositionLookup::MarkToMark(lookup) => lookup.use_extension,
+            PositionLookup::Contextual(lookup) => lookup.use_extension,
+            PositionLookup::ChainedContextual(lookup) => lookup.use_extension,
+        }
+    }
+
+    /// Offset the lookups referenced by a contextual lookup.
+    fn bump_all_lookup_ids(&mut self, by: usize) {
+        match self {
+            PositionLookup::Contextual(lookup) => lookup
+                .subtables
+                .iter_mut()
+                .for_each(|sub| sub.bump_all_lookup_ids(by)),
+            PositionLookup::ChainedContextual(lookup) => lookup
+                .subtables
+                .iter_mut()
+                .for_each(|sub| sub.bump_all_lookup_ids(by)),
+            _ => (),
+        }
+    }
+
+    /// Build this lookup as a GPOS type 9 (extension) lookup.
+    fn build_extension(self) -> RawLookup<write_gpos::Extension> {
+        use write_gpos::{Extension, ExtensionPosFormat1 as Ext};
+        match self {
+            PositionLookup::Single(lookup) => {
+                lookup.build_extension(|sub| Extension::Single(Ext::new(sub)))
+            }
+            PositionLookup::Pair(lookup) => {
+                lookup.build_extension(|sub| Extension::Pair(Ext::new(sub)))
+            }
+            PositionLookup::Cursive(lookup) => {
+                lookup.build_extension(|sub| Extension::Cursive(Ext::new(sub)))
+            }
+            PositionLookup::MarkToBase(lookup) => {
+                lookup.build_extension(|sub| Extension::MarkToBase(Ext::new(sub)))
+            }
+            PositionLookup::MarkToLig(lookup) => {
+                lookup.build_extension(|sub| Extension::MarkToLig(Ext::new(sub)))
+            }
+            PositionLookup::MarkToMark(lookup) => {
+                lookup.build_extension(|sub| Extension::MarkToMark(Ext::new(sub)))
+            }
+            PositionLookup::Contextual(lookup) => {
+                lookup.build_extension(|sub| Extension::Contextual(Ext::new(sub.into())))
+            }
+            PositionLookup::ChainedContextual(lookup) => {
+                lookup.build_extension(|sub| Extension::ChainContextual(Ext::new(sub.into())))
+            }
+        }
+    }
+
+    /// An estimate of the size of the current subtable, for the lookup types
+    /// that are likely to overflow.
+    ///
+    /// For pair positioning this only counts the glyph pairs: class pairs
+    /// are already split into subtables as needed when they are added.
+    fn current_subtable_size(&self) -> Option<usize> {
+        match self {
+            PositionLookup::Pair(lookup) => {
+                lookup.subtables.last().map(|sub| sub.glyph_pairs_size())
+            }
+            PositionLookup::MarkToBase(lookup) => {
+                lookup.subtables.last().map(|sub| sub.size_estimate())
+            }
+            PositionLookup::MarkToLig(lookup) => {
+                lookup.subtables.last().map(|sub| sub.size_estimate())
+            }
+            PositionLookup::MarkToMark(lookup) => {
+                lookup.subtables.last().map(|sub| sub.size_estimate())
+            }
+            _ => None,
+        }
+    }
+
+    /// An estimate of the total size of this lookup's subtables, for the
+    /// lookup types that are likely to overflow.
+    fn size_estimate(&self) -> Option<usize> {
+        match self {
+            PositionLookup::Pair(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            PositionLookup::MarkToBase(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            PositionLookup::MarkToLig(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            PositionLookup::MarkToMark(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            _ => None,
+        }
+    }
+
+    fn use_smallest_pair_format(&mut self) {
+        if let PositionLookup::Pair(lookup) = self {
+            lookup
+                .subtables
+                .iter_mut()
+                .for_each(PairPosBuilder::use_smallest_format);
+        }
+    }
+
+    fn glyph_pairs_first(&mut self) {
+        if let PositionLookup::Pair(lookup) = self {
+            let subtables = std::mem::take(&mut lookup.subtables);
+            lookup.subtables = PairPosBuilder::glyph_pairs_first(subtables);
+        }
+    }
+
+    /// A human-readable name for the type of this lookup
+    fn type_name(&self) -> &'static str {
+        match self {
+            PositionLookup::Single(_) => "single adjustment",
+            PositionLookup::Pair(_) => "pair adjustment",
+            PositionLookup::Cursive(_) => "cursive attachment",
+            PositionLookup::MarkToBase(_) => "mark-to-base attachment",
+            PositionLookup::MarkToLig(_) => "mark-to-ligature attachment",
+            PositionLookup::MarkToMark(_) => "mark-to-mark attachment",
+            PositionLookup::Contextual(_) => "contextual positioning",
+            PositionLookup::ChainedContextual(_) => "chained contextual positioning",
+        }
+    }
 }
 
 impl SubstitutionLookup {
@@ -209,6 +416,118 @@ impl SubstitutionLookup {
             SubstitutionLookup::ChainedContextual(lookup) => lookup.force_subtable_break(),
         }
     }
+
+    fn mark_set_mut(&mut self) -> &mut Option<FilterSetId> {
+        match self {
+            SubstitutionLookup::Single(lookup) => &mut lookup.mark_set,
+            SubstitutionLookup::Multiple(lookup) => &mut lookup.mark_set,
+            SubstitutionLookup::Alternate(lookup) => &mut lookup.mark_set,
+            SubstitutionLookup::Ligature(lookup) => &mut lookup.mark_set,
+            SubstitutionLookup::Contextual(lookup) => &mut lookup.mark_set,
+            SubstitutionLookup::Reverse(lookup) => &mut lookup.mark_set,
+            SubstitutionLookup::ChainedContextual(lookup) => &mut lookup.mark_set,
+        }
+    }
+
+    fn set_use_extension(&mut self) {
+        match self {
+            SubstitutionLookup::Single(lookup) => lookup.use_extension = true,
+            SubstitutionLookup::Multiple(lookup) => lookup.use_extension = true,
+            SubstitutionLookup::Alternate(lookup) => lookup.use_extension = true,
+            SubstitutionLookup::Ligature(lookup) => lookup.use_extension = true,
+            SubstitutionLookup::Contextual(lookup) => lookup.use_extension = true,
+            SubstitutionLookup::ChainedContextual(lookup) => lookup.use_extension = true,
+            SubstitutionLookup::Reverse(lookup) => lookup.use_extension = true,
+        }
+    }
+
+    fn use_extension(&self) -> bool {
+        match self {
+            SubstitutionLookup::Single(lookup) => lookup.use_extension,
+            SubstitutionLookup::Multiple(lookup) => lookup.use_extension,
+            SubstitutionLookup::Alternate(lookup) => lookup.use_extension,
+            SubstitutionLookup::Ligature(lookup) => lookup.use_extension,
+            SubstitutionLookup::Contextual(lookup) => lookup.use_extension,
+            SubstitutionLookup::ChainedContextual(lookup) => lookup.use_extension,
+            SubstitutionLookup::Reverse(lookup) => lookup.use_extension,
+        }
+    }
+
+    /// Offset the lookups referenced by a contextual lookup.
+    fn bump_all_lookup_ids(&mut self, by: usize) {
+        match self {
+            SubstitutionLookup::Contextual(lookup) => lookup
+                .subtables
+                .iter_mut()
+                .for_each(|sub| sub.bump_all_lookup_ids(by)),
+            SubstitutionLookup::ChainedContextual(lookup) => lookup
+                .subtables
+                .iter_mut()
+                .for_each(|sub| sub.bump_all_lookup_ids(by)),
+            _ => (),
+        }
+    }
+
+    /// Build this lookup as a GSUB type 7 (extension) lookup.
+    fn build_extension(self) -> RawLookup<write_gsub::ExtensionSubtable> {
+        use write_gsub::{ExtensionSubstFormat1 as Ext, ExtensionSubtable as Extension};
+        match self {
+            SubstitutionLookup::Single(lookup) => {
+                lookup.build_extension(|sub| Extension::Single(Ext::new(sub)))
+            }
+            SubstitutionLookup::Multiple(lookup) => {
+                lookup.build_extension(|sub| Extension::Multiple(Ext::new(sub)))
+            }
+            SubstitutionLookup::Alternate(lookup) => {
+                lookup.build_extension(|sub| Extension::Alternate(Ext::new(sub)))
+            }
+            SubstitutionLookup::Ligature(lookup) => {
+                lookup.build_extension(|sub| Extension::Ligature(Ext::new(sub)))
+            }
+            SubstitutionLookup::Contextual(lookup) => {
+                lookup.build_extension(|sub| Extension::Contextual(Ext::new(sub.into())))
+            }
+            SubstitutionLookup::ChainedContextual(lookup) => {
+                lookup.build_extension(|sub| Extension::ChainContextual(Ext::new(sub.into())))
+            }
+            SubstitutionLookup::Reverse(lookup) => {
+                lookup.build_extension(|sub| Extension::Reverse(Ext::new(sub)))
+            }
+        }
+    }
+
+    /// An estimate of the total size of this lookup's subtables, for the
+    /// lookup types that are likely to overflow.
+    fn size_estimate(&self) -> Option<usize> {
+        match self {
+            SubstitutionLookup::Single(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            SubstitutionLookup::Multiple(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            SubstitutionLookup::Alternate(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            SubstitutionLookup::Ligature(lookup) => {
+                Some(lookup.subtables.iter().map(|sub| sub.size_estimate()).sum())
+            }
+            _ => None,
+        }
+    }
+
+    /// A human-readable name for the type of this lookup
+    fn type_name(&self) -> &'static str {
+        match self {
+            SubstitutionLookup::Single(_) => "single substitution",
+            SubstitutionLookup::Multiple(_) => "multiple substitution",
+            SubstitutionLookup::Alternate(_) => "alternate substitution",
+            SubstitutionLookup::Ligature(_) => "ligature substitution",
+            SubstitutionLookup::Contextual(_) => "contextual substitution",
+            SubstitutionLookup::ChainedContextual(_) => "chained contextual substitution",
+            SubstitutionLookup::Reverse(_) => "reverse chaining substitution",
+        }
+    }
 }
 
 impl<U, T> Builder for LookupBuilder<T>
@@ -232,6 +551,13 @@ impl Builder for PositionLookup {
     type Output = write_gpos::PositionLookup;
 
     fn build(self) -> Self::Output {
+        let is_large = self
+            .size_estimate()
+            .filter(|size| *size > AUTO_EXTENSION_SIZE)
+            .is_some();
+        if self.use_extension() || is_large {
+            return write_gpos::PositionLookup::Extension(self.build_extension());
+        }
         match self {
             PositionLookup::Single(lookup) => write_gpos::PositionLookup::Single(lookup.build()),
             PositionLookup::Pair(lookup) => write_gpos::PositionLookup::Pair(lookup.build()),
@@ -259,6 +585,13 @@ impl Builder for SubstitutionLookup {
     type Output = write_gsub::SubstitutionLookup;
 
     fn build(self) -> Self::Output {
+        let is_large = self
+            .size_estimate()
+            .filter(|size| *size > AUTO_EXTENSION_SIZE)
+            .is_some();
+        if self.use_extension() || is_large {
+            return write_gsub::SubstitutionLookup::Extension(self.build_extension());
+        }
         match self {
             SubstitutionLookup::Single(lookup) => {
                 write_gsub::SubstitutionLookup::Single(lookup.build())
@@ -335,6 +668,20 @@ impl AllLookups {
         self.named.get(name).copied()
     }
 
+    /// Iterate over all named lookups, and their ids.
+    pub(crate) fn iter_named(&self) -> impl Iterator<Item = (&SmolStr, LookupId)> + '_ {
+        self.named.iter().map(|(name, id)| (name, *id))
+    }
+
+    /// `true` if this id refers to an existing lookup.
+    pub(crate) fn contains(&self, id: LookupId) -> bool {
+        match id {
+            LookupId::Gpos(idx) => idx < self.gpos.len(),
+            LookupId::Gsub(idx) => idx < self.gsub.len(),
+            LookupId::Empty => true,
+        }
+    }
+
     pub(crate) fn current_mut(&mut self) -> Option<&mut SomeLookup> {
         self.current.as_mut()
     }
@@ -363,9 +710,22 @@ impl AllLookups {
         }
     }
 
+    /// An estimate of the size in bytes of the current lookup's last subtable.
+    ///
+    /// This is only available for the lookup types that are likely to
+    /// overflow the 16-bit offsets in a subtable: pair and mark attachment
+    /// positioning.
+    pub(crate) fn current_subtable_size(&self) -> Option<usize> {
+        match self.current.as_ref()? {
+            SomeLookup::GposLookup(lookup) => lookup.current_subtable_size(),
+            _ => None,
+        }
+    }
+
     // doesn't start it, just stashes the name
-    pub(crate) fn start_named(&mut self, name: SmolStr) {
+    pub(crate) fn start_named(&mut self, name: SmolStr, use_extension: bool) {
         self.current_name = Some(name);
+        self.current_use_extension = use_extension;
     }
 
     pub(crate) fn start_lookup(&mut self, kind: Kind, flags: LookupFlagInfo) -> Option<LookupId> {
@@ -389,8 +749,16 @@ impl AllLookups {
     }
 
     pub(crate) fn finish_current(&mut self) -> Option<(LookupId, Option<SmolStr>)> {
+        let use_extension = std::mem::take(&mut self.current_use_extension);
         if let Some(lookup) = self.current.take() {
             let id = self.push(lookup);
+            if use_extension {
+                match id {
+                    LookupId::Gpos(idx) => self.gpos[idx].set_use_extension(),
+                    LookupId::Gsub(idx) => self.gsub[idx].set_use_extension(),
+                    LookupId::Empty => (),
+                }
+            }
             if let Some(name) = self.current_name.take() {
                 self.named.insert(name.clone(), id);
                 Some((id, Some(name)))
@@ -406,6 +774,67 @@ impl AllLookups {
         }
     }
 
+    /// Append the lookups from `other` after our own.
+    ///
+    /// The appended lookups (and any references between them) are renumbered
+    /// to follow ours; the returned function does the same for any other ids
+    /// that refer to them, such as the lookups of a feature. Neither set of
+    /// lookups can have an unfinished lookup.
+    pub(crate) fn merge(&mut self, other: AllLookups) -> impl Fn(LookupId) -> LookupId {
+        assert!(
+            self.current.is_none() && other.current.is_none(),
+            "lookups are finished before merging"
+        );
+        let n_gsub = self.gsub.len();
+        let n_gpos = self.gpos.len();
+        let remap = move |mut id: LookupId| {
+            id.adjust_if_gsub(n_gsub);
+            id.adjust_if_gpos(n_gpos);
+            id
+        };
+        self.gsub.extend(other.gsub.into_iter().map(|mut lookup| {
+            lookup.bump_all_lookup_ids(n_gsub);
+            lookup
+        }));
+        self.gpos.extend(other.gpos.into_iter().map(|mut lookup| {
+            lookup.bump_all_lookup_ids(n_gpos);
+            lookup
+        }));
+        self.named
+            .extend(other.named.into_iter().map(|(name, id)| (name, remap(id))));
+        remap
+    }
+
+    /// Update the mark filtering set of every lookup.
+    ///
+    /// This is used when the final order of the mark glyph sets in GDEF is
+    /// different from the order in which they were assigned.
+    pub(crate) fn remap_mark_filter_sets(&mut self, f: impl Fn(FilterSetId) -> FilterSetId) {
+        let gpos = self.gpos.iter_mut().map(PositionLookup::mark_set_mut);
+        let gsub = self.gsub.iter_mut().map(SubstitutionLookup::mark_set_mut);
+        for mark_set in gpos.chain(gsub) {
+            if let Some(id) = mark_set.as_mut() {
+                *id = f(*id);
+            }
+        }
+    }
+
+    /// The number of GPOS lookups, including anonymous contextual lookups.
+    #[cfg(test)]
+    pub(crate) fn n_gpos_lookups(&self) -> usize {
+        self.gpos.len()
+    }
+
+    /// The mark filtering set of each GPOS lookup, in order.
+    #[cfg(test)]
+    pub(crate) fn gpos_mark_filter_sets(&self) -> Vec<Option<FilterSetId>> {
+        self.gpos
+            .clone()
+            .iter_mut()
+            .map(|lookup| *lookup.mark_set_mut())
+            .collect()
+    }
+
     pub(crate) fn infer_glyph_classes(&self, mut f: impl FnMut(GlyphId, ClassId)) {
         for lookup in &self.gpos {
             match lookup {
@@ -468,7 +897,31 @@ impl AllLookups {
         }
     }
 
-    fn get_gsub_lookup(&self, id: &LookupId) -> Option<&SubstitutionLookup> {
+    /// `true` if the lookup with this id is a (chained) contextual lookup.
+    pub(crate) fn is_contextual(&self, id: LookupId) -> bool {
+        match id {
+            LookupId::Gpos(idx) => matches!(
+                self.gpos.get(idx),
+                Some(PositionLookup::Contextual(_) | PositionLookup::ChainedContextual(_))
+            ),
+            LookupId::Gsub(idx) => matches!(
+                self.gsub.get(idx),
+                Some(SubstitutionLookup::Contextual(_) | SubstitutionLookup::ChainedContextual(_))
+            ),
+            LookupId::Empty => false,
+        }
+    }
+
+    /// A human-readable name for the type of the lookup with this id.
+    pub(crate) fn type_name(&self, id: LookupId) -> Option<&'static str> {
+        match id {
+            LookupId::Gpos(idx) => self.gpos.get(idx).map(PositionLookup::type_name),
+            LookupId::Gsub(idx) => self.gsub.get(idx).map(SubstitutionLookup::type_name),
+            LookupId::Empty => None,
+        }
+    }
+
+    pub(crate) fn get_gsub_lookup(&self, id: &LookupId) -> Option<&SubstitutionLookup> {
         match id {
             LookupId::Gsub(idx) => self.gsub.get(*idx),
             _ => None,
@@ -477,7 +930,7 @@ impl AllLookups {
 
     pub(crate) fn insert_aalt_lookups(
         &mut self,
-        all_alts: HashMap<GlyphId, Vec<GlyphId>>,
+        all_alts: BTreeMap<GlyphId, Vec<GlyphId>>,
     ) -> Vec<LookupId> {
         let mut single = SingleSubBuilder::default();
         let mut alt = AlternateSubBuilder::default();
@@ -510,20 +963,17 @@ impl AllLookups {
         // now we need to insert these lookups at the front of our gsub lookups,
         // and bump all of their ids:
 
-        self.gsub.iter_mut().for_each(|lookup| match lookup {
-            SubstitutionLookup::Contextual(lookup) => lookup
-                .subtables
-                .iter_mut()
-                .for_each(|sub| sub.bump_all_lookup_ids(lookups.len())),
-            SubstitutionLookup::ChainedContextual(lookup) => lookup
-                .subtables
-                .iter_mut()
-                .for_each(|sub| sub.bump_all_lookup_ids(lookups.len())),
-            _ => (),
-        });
+        self.gsub
+            .iter_mut()
+            .for_each(|lookup| lookup.bump_all_lookup_ids(lookups.len()));
 
+        let n_inserted = lookups.len();
         let prev_lookups = std::mem::replace(&mut self.gsub, lookups);
         self.gsub.extend(prev_lookups);
+        // named lookups are reported in the output, so they need updating too
+        self.named
+            .values_mut()
+            .for_each(|id| id.adjust_if_gsub(n_inserted));
 
         lookup_ids
     }
@@ -531,10 +981,29 @@ impl AllLookups {
     pub(crate) fn build(
         &self,
         features: &BTreeMap<FeatureKey, Vec<LookupId>>,
-        required_features: &HashSet<FeatureKey>,
+        required_features: &BTreeSet<FeatureKey>,
+        feature_variations: &[FeatureVariation],
+        compact_pair_pos: bool,
+        include_table: impl Fn(Tag) -> bool,
     ) -> (Option<write_gsub::Gsub>, Option<write_gpos::Gpos>) {
-        let mut gpos_builder = PosSubBuilder::new(self.gpos.clone());
-        let mut gsub_builder = PosSubBuilder::new(self.gsub.clone());
+        // the lookups of an excluded table are never cloned or built
+        let mut gpos = if include_table(tags::GPOS) {
+            self.gpos.clone()
+        } else {
+            Vec::new()
+        };
+        if compact_pair_pos {
+            gpos.iter_mut()
+                .for_each(PositionLookup::use_smallest_pair_format);
+        }
+        gpos.iter_mut().for_each(PositionLookup::glyph_pairs_first);
+        let gsub = if include_table(tags::GSUB) {
+            self.gsub.clone()
+        } else {
+            Vec::new()
+        };
+        let mut gpos_builder = PosSubBuilder::new(gpos);
+        let mut gsub_builder = PosSubBuilder::new(gsub);
 
         for (key, feature_indices) in features {
             let required = required_features.contains(key);
@@ -554,7 +1023,25 @@ impl AllLookups {
             }
         }
 
-        (gsub_builder.build(), gpos_builder.build())
+        for variation in feature_variations {
+            let mut gpos_features = BTreeMap::new();
+            let mut gsub_features = BTreeMap::new();
+            for (key, feature_indices) in &variation.features {
+                let (gpos_idxes, gsub_idxes) = split_lookups(feature_indices);
+                if !gpos_idxes.is_empty() {
+                    gpos_features.insert(*key, gpos_idxes);
+                }
+                if !gsub_idxes.is_empty() {
+                    gsub_features.insert(*key, gsub_idxes);
+                }
+            }
+            gpos_builder.add_variation(&variation.conditions, gpos_features);
+            gsub_builder.add_variation(&variation.conditions, gsub_features);
+        }
+
+        let gsub = include_table(tags::GSUB).then(|| gsub_builder.build());
+        let gpos = include_table(tags::GPOS).then(|| gpos_builder.build());
+        (gsub.flatten(), gpos.flatten())
     }
 }
 
@@ -619,6 +1106,18 @@ impl LookupId {
         }
     }
 
+    pub(crate) fn adjust_if_gpos(&mut self, value: usize) {
+        if let LookupId::Gpos(idx) = self {
+            *idx += value;
+        }
+    }
+
+    /// Offset this id, whichever table it refers to.
+    fn offset_by(&mut self, value: usize) {
+        self.adjust_if_gsub(value);
+        self.adjust_if_gpos(value);
+    }
+
     pub(crate) fn to_gpos_id_or_die(self) -> u16 {
         let LookupId::Gpos(x) = self else { panic!("this *really* shouldn't happen") };
         x.try_into().unwrap()
@@ -880,10 +1379,12 @@ impl<T> PosSubBuilder<T> {
             lookups,
             scripts: Default::default(),
             features: Default::default(),
+            variations: Default::default(),
         }
     }
 
-    fn add(&mut self, key: FeatureKey, lookups: Vec<u16>, required: bool) {
+    /// Add a feature for a language system, returning its feature index.
+    fn add(&mut self, key: FeatureKey, lookups: Vec<u16>, required: bool) -> u16 {
         let feat_key = (key.feature, lookups);
         let next_feature = self.features.len();
         let idx = *self
@@ -903,6 +1404,46 @@ impl<T> PosSubBuilder<T> {
         } else {
             lang_sys.feature_indices.push(idx);
         }
+        idx
+    }
+
+    /// The index of the feature currently used for this key, if any.
+    fn feature_index(&self, key: FeatureKey) -> Option<u16> {
+        let lang_sys = self.scripts.get(&key.script)?.get(&key.language)?;
+        self.features.iter().find_map(|((tag, _), idx)| {
+            let in_lang_sys =
+                lang_sys.required_feature_index == *idx || lang_sys.feature_indices.contains(idx);
+            (*tag == key.feature && in_lang_sys).then_some(*idx)
+        })
+    }
+
+    /// Add the lookups that replace some features when `conditions` are met.
+    ///
+    /// A feature that has no lookups outside of a variation block still needs
+    /// a (empty) feature record to be substituted, so we add one here.
+    fn add_variation(
+        &mut self,
+        conditions: &[AxisCondition],
+        features: BTreeMap<FeatureKey, Vec<u16>>,
+    ) {
+        if features.is_empty() {
+            return;
+        }
+        let mut substitutions = BTreeMap::<_, Vec<_>>::new();
+        for (key, lookups) in features {
+            let idx = match self.feature_index(key) {
+                Some(idx) => idx,
+                None => self.add(key, Vec::new(), false),
+            };
+            // language systems that share a default feature also share
+            // its alternate.
+            substitutions.entry(idx).or_default().extend(lookups);
+        }
+        for lookups in substitutions.values_mut() {
+            lookups.sort_unstable();
+            lookups.dedup();
+        }
+        self.variations.push((conditions.to_vec(), substitutions));
     }
 }
 
@@ -911,7 +1452,14 @@ where
     T: Builder,
     T::Output: Default,
 {
-    fn build_raw(self) -> Option<(LookupList<T::Output>, ScriptList, FeatureList)> {
+    fn build_raw(
+        self,
+    ) -> Option<(
+        LookupList<T::Output>,
+        ScriptList,
+        FeatureList,
+        Option<FeatureVariations>,
+    )> {
         if self.lookups.is_empty() && self.features.is_empty() {
             return None;
         }
@@ -940,11 +1488,42 @@ where
             })
             .collect::<Vec<_>>();
 
+        let feature_variations = (!self.variations.is_empty()).then(|| {
+            let records = self
+                .variations
+                .into_iter()
+                .map(|(conditions, substitutions)| {
+                    let conditions = conditions
+                        .into_iter()
+                        .map(|cond| {
+                            ConditionFormat1::new(
+                                cond.axis_index,
+                                F2Dot14::from_f32(cond.min),
+                                F2Dot14::from_f32(cond.max),
+                            )
+                        })
+                        .collect();
+                    let substitutions = substitutions
+                        .into_iter()
+                        .map(|(idx, lookups)| {
+                            FeatureTableSubstitutionRecord::new(idx, Feature::new(None, lookups))
+                        })
+                        .collect();
+                    FeatureVariationRecord::new(
+                        Some(ConditionSet::new(conditions)),
+                        Some(FeatureTableSubstitution::new(substitutions)),
+                    )
+                })
+                .collect();
+            FeatureVariations::new(records)
+        });
+
         let lookups = self.lookups.into_iter().map(|x| x.build()).collect();
         Some((
             LookupList::new(lookups),
             ScriptList::new(scripts),
             FeatureList::new(features),
+            feature_variations,
         ))
     }
 }
@@ -953,8 +1532,12 @@ impl Builder for PosSubBuilder<PositionLookup> {
     type Output = Option<write_gpos::Gpos>;
 
     fn build(self) -> Self::Output {
-        self.build_raw()
-            .map(|(lookups, scripts, features)| write_gpos::Gpos::new(scripts, features, lookups))
+        self.build_raw().map(
+            |(lookups, scripts, features, variations)| write_gpos::Gpos {
+                feature_variations: variations.into(),
+                ..write_gpos::Gpos::new(scripts, features, lookups)
+            },
+        )
     }
 }
 
@@ -962,8 +1545,12 @@ impl Builder for PosSubBuilder<SubstitutionLookup> {
     type Output = Option<write_gsub::Gsub>;
 
     fn build(self) -> Self::Output {
-        self.build_raw()
-            .map(|(lookups, scripts, features)| write_gsub::Gsub::new(scripts, features, lookups))
+        self.build_raw().map(
+            |(lookups, scripts, features, variations)| write_gsub::Gsub {
+                feature_variations: variations.into(),
+                ..write_gsub::Gsub::new(scripts, features, lookups)
+            },
+        )
     }
 }
 
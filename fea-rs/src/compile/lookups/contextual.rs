@@ -171,18 +171,26 @@ impl ContextualLookupBuilder<SubstitutionLookup> {
         self.current_anon_lookup_id()
     }
 
+    /// Add a ligature for each of `targets` to an anonymous lookup.
+    ///
+    /// All of the ligatures are added to the same lookup, since a contextual
+    /// rule can only reference one lookup at each position. If an existing
+    /// anonymous lookup already contains all of them, that lookup is reused.
     pub(crate) fn add_anon_gsub_type_4(
         &mut self,
-        target: Vec<GlyphId>,
+        targets: Vec<Vec<GlyphId>>,
         replacement: GlyphId,
     ) -> LookupId {
+        if let Some(existing) = self.find_anon_ligatures(&targets, replacement) {
+            return existing;
+        }
         // do we need a new lookup or can we use the existing one?
         self.add_new_lookup_if_necessary(
             |existing| match existing {
                 SubstitutionLookup::Ligature(builder) => builder
                     .subtables
                     .iter()
-                    .any(|sub| sub.contains_target(target.first().copied().unwrap())),
+                    .any(|sub| targets.iter().any(|target| sub.contains_target(target[0]))),
                 _ => true,
             },
             |flags, mark_set| SubstitutionLookup::Ligature(LookupBuilder::new(flags, mark_set)),
@@ -194,9 +202,35 @@ impl ContextualLookupBuilder<SubstitutionLookup> {
         };
 
         let sub = subtables.last_mut().unwrap();
-        sub.insert(target, replacement);
+        for target in targets {
+            sub.insert(target, replacement);
+        }
         self.current_anon_lookup_id()
     }
+
+    /// The id of an anonymous lookup that already contains all of these ligatures.
+    ///
+    /// Ligatures in a lookup only share a first glyph if they were added by the
+    /// same rule, in which case they have the same length; reusing the lookup
+    /// cannot match a longer sequence than the rule intends.
+    fn find_anon_ligatures(
+        &self,
+        targets: &[Vec<GlyphId>],
+        replacement: GlyphId,
+    ) -> Option<LookupId> {
+        self.anon_lookups
+            .iter()
+            .position(|lookup| match lookup {
+                SubstitutionLookup::Ligature(builder) => targets.iter().all(|target| {
+                    builder
+                        .subtables
+                        .iter()
+                        .any(|sub| sub.contains(target, replacement))
+                }),
+                _ => false,
+            })
+            .map(|idx| LookupId::Gsub(self.root_id.to_raw() + idx + 1))
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -253,7 +287,7 @@ impl ContextBuilder {
         })
     }
 
-    // for adjusting ids if we insert aalt at the front
+    // for adjusting ids if we insert aalt at the front, or merge lookups
     pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
         self.rules
             .iter_mut()
@@ -342,9 +376,7 @@ impl SubContextBuilder {
 impl ContextRule {
     pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
         for (_, lookups) in &mut self.context {
-            lookups
-                .iter_mut()
-                .for_each(|x| *x = LookupId::Gsub(x.to_raw() + by))
+            lookups.iter_mut().for_each(|x| x.offset_by(by))
         }
     }
     fn is_chain_rule(&self) -> bool {
@@ -549,11 +581,24 @@ impl ChainContextBuilder {
     }
 }
 
+impl PosContextBuilder {
+    pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
+        self.0.bump_all_lookup_ids(by)
+    }
+}
+
 impl SubContextBuilder {
     pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
         self.0.bump_all_lookup_ids(by)
     }
 }
+
+impl PosChainContextBuilder {
+    pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
+        self.0 .0.bump_all_lookup_ids(by)
+    }
+}
+
 impl SubChainContextBuilder {
     pub(crate) fn bump_all_lookup_ids(&mut self, by: usize) {
         self.0 .0.bump_all_lookup_ids(by)
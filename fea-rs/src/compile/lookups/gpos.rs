@@ -1,6 +1,6 @@
 //! GPOS subtable builders
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use smol_str::SmolStr;
 use write_fonts::{
@@ -13,7 +13,7 @@ use write_fonts::{
 
 use crate::common::GlyphClass;
 
-use super::{Builder, ClassDefBuilder2};
+use super::{coverage_size, Builder, ClassDefBuilder2};
 
 #[derive(Clone, Debug, Default)]
 pub struct SinglePosBuilder {
@@ -106,10 +106,20 @@ fn cmp_coverage_key(coverage: &CoverageTable) -> impl Ord {
 pub struct PairPosBuilder {
     pairs: GlyphPairPosBuilder,
     classes: ClassPairPosBuilder,
+    // glyph pairs from this lookup that were moved to earlier subtables; see
+    // `PairPosBuilder::glyph_pairs_first`
+    earlier_pairs: GlyphPairPosBuilder,
+    // if true, class pairs may be written as format 1 subtables, when smaller
+    use_smallest_format: bool,
 }
 
 #[derive(Clone, Debug, Default)]
-struct GlyphPairPosBuilder(BTreeMap<GlyphId, BTreeMap<GlyphId, (ValueRecord, ValueRecord)>>);
+struct GlyphPairPosBuilder {
+    pairs: BTreeMap<GlyphId, BTreeMap<GlyphId, (ValueRecord, ValueRecord)>>,
+    // the size of the pair sets, kept up to date as pairs are inserted so that
+    // it can be checked after every rule; see `format_1_size`
+    pair_sets_size: usize,
+}
 
 #[derive(Clone, Debug)]
 struct ClassPairPosSubtable {
@@ -155,6 +165,53 @@ impl ClassPairPosBuilder {
     }
 }
 
+impl GlyphPairPosBuilder {
+    fn insert(&mut self, glyph1: GlyphId, glyph2: GlyphId, records: (ValueRecord, ValueRecord)) {
+        let record_size =
+            |(v1, v2): &(ValueRecord, ValueRecord)| 2 + v1.encoded_size() + v2.encoded_size();
+        self.pair_sets_size += record_size(&records);
+        let pair_set = self.pairs.entry(glyph1).or_insert_with(|| {
+            // the offset to the pair set, and its count
+            self.pair_sets_size += 2 + 2;
+            Default::default()
+        });
+        if let Some(prev) = pair_set.insert(glyph2, records) {
+            self.pair_sets_size -= record_size(&prev);
+        }
+    }
+
+    fn contains(&self, glyph1: GlyphId, glyph2: GlyphId) -> bool {
+        self.pairs
+            .get(&glyph1)
+            .map(|pairs| pairs.contains_key(&glyph2))
+            .unwrap_or(false)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Add the pairs from `other` that are not already present.
+    ///
+    /// If a pair is in both, the first rule takes precedence at shaping time.
+    fn extend(&mut self, other: &GlyphPairPosBuilder) {
+        for (glyph1, pairs) in &other.pairs {
+            for (glyph2, records) in pairs {
+                if !self.contains(*glyph1, *glyph2) {
+                    self.insert(*glyph1, *glyph2, records.clone());
+                }
+            }
+        }
+    }
+
+    /// An estimate of the size in bytes of these pairs as format 1 subtables.
+    ///
+    /// This is cheap, and ignores the split into one subtable per value format.
+    fn format_1_size(&self) -> usize {
+        10 + coverage_size(self.pairs.len()) + self.pair_sets_size
+    }
+}
+
 impl ClassPairPosSubtable {
     fn can_add(&self, class1: &GlyphClass, class2: &GlyphClass) -> bool {
         self.classdef_1.can_add(class1) && self.classdef_2.can_add(class2)
@@ -174,6 +231,82 @@ impl ClassPairPosSubtable {
             .or_default()
             .insert(class2, (record1, record2));
     }
+
+    fn coverage(&self) -> impl Iterator<Item = GlyphId> + '_ {
+        self.items.keys().flat_map(GlyphClass::iter)
+    }
+
+    /// An estimate of the size in bytes of this subtable in format 2.
+    fn format_2_size(&self) -> usize {
+        let class2s = self
+            .items
+            .values()
+            .flat_map(|classes| classes.keys())
+            .collect::<HashSet<_>>();
+        let n_glyphs_1 = self.coverage().count();
+        let n_glyphs_2 = class2s.iter().map(|class| class.len()).sum::<usize>();
+        let record_size = self
+            .items
+            .values()
+            .flat_map(|classes| classes.values())
+            .next()
+            .map(|(v1, v2)| v1.encoded_size() + v2.encoded_size())
+            .unwrap_or_default();
+        // class 0 of the second classdef holds the glyphs not in any class
+        let n_records = self.items.len() * (class2s.len() + 1);
+        16 + coverage_size(n_glyphs_1)
+            + class_def_size(n_glyphs_1)
+            + class_def_size(n_glyphs_2)
+            + n_records * record_size
+    }
+
+    /// Expand the class pairs in this subtable into individual glyph pairs.
+    ///
+    /// Pairs that are already in `existing` are skipped: those will be in
+    /// an earlier subtable, and so this subtable would never be reached.
+    fn expand(&self, existing: &[&GlyphPairPosBuilder]) -> GlyphPairPosBuilder {
+        let mut result = GlyphPairPosBuilder::default();
+        for (class1, classes) in &self.items {
+            for glyph1 in class1.iter() {
+                for (class2, records) in classes {
+                    for glyph2 in class2.iter() {
+                        if !existing.iter().any(|pairs| pairs.contains(glyph1, glyph2)) {
+                            result.insert(glyph1, glyph2, records.clone());
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+// like `coverage_size`, this assumes a format 1 classdef table.
+fn class_def_size(n_glyphs: usize) -> usize {
+    6 + 2 * n_glyphs
+}
+
+fn anchor_size(anchor: &AnchorTable) -> usize {
+    match anchor {
+        AnchorTable::Format1(_) => 6,
+        AnchorTable::Format2(_) => 8,
+        // ignoring the size of any device tables
+        AnchorTable::Format3(_) => 10,
+    }
+}
+
+/// The size of the anchors of the components of a ligature
+fn anchors_size(components: &[BTreeMap<SmolStr, AnchorTable>]) -> usize {
+    components
+        .iter()
+        .flat_map(|anchors| anchors.values())
+        .map(anchor_size)
+        .sum()
+}
+
+/// The size of the coverage and array of base (or mark2) glyphs
+fn base_array_size(n_bases: usize, n_classes: usize, anchors_size: usize) -> usize {
+    coverage_size(n_bases) + 2 + n_bases * n_classes * 2 + anchors_size
 }
 
 impl PairPosBuilder {
@@ -184,11 +317,7 @@ impl PairPosBuilder {
         glyph2: GlyphId,
         record2: ValueRecord,
     ) {
-        self.pairs
-            .0
-            .entry(glyph1)
-            .or_default()
-            .insert(glyph2, (record1, record2));
+        self.pairs.insert(glyph1, glyph2, (record1, record2));
     }
 
     pub(crate) fn insert_classes(
@@ -200,14 +329,116 @@ impl PairPosBuilder {
     ) {
         self.classes.insert(class1, record1, class2, record2)
     }
+
+    /// An estimate of the size in bytes of the glyph pairs in this subtable.
+    ///
+    /// Unlike `size_estimate`, this is cheap enough to check after every rule.
+    pub(crate) fn glyph_pairs_size(&self) -> usize {
+        self.pairs.format_1_size()
+    }
+
+    /// An estimate of the size in bytes of the largest subtable we will write.
+    pub(crate) fn size_estimate(&self) -> usize {
+        self.classes
+            .0
+            .values()
+            .flatten()
+            .map(ClassPairPosSubtable::format_2_size)
+            .fold(self.pairs.format_1_size(), usize::max)
+    }
+
+    /// Reorder the subtables of a lookup so that all glyph pairs come first.
+    ///
+    /// A specific pair is often an exception to the kerning between the
+    /// classes of its glyphs, and the first subtable that matches a pair is
+    /// the one that is used. Within a single builder the glyph pairs are
+    /// always written first, but after a subtable break (explicit or
+    /// automatic) the class pairs from an earlier subtable would otherwise
+    /// take precedence. As in feaLib, the glyph pairs of every subtable are
+    /// written before any of the class pairs.
+    pub(crate) fn glyph_pairs_first(subtables: Vec<Self>) -> Vec<Self> {
+        if subtables.len() < 2 {
+            return subtables;
+        }
+        let mut all_pairs = GlyphPairPosBuilder::default();
+        if subtables.iter().any(|sub| sub.use_smallest_format) {
+            for sub in &subtables {
+                all_pairs.extend(&sub.pairs);
+            }
+        }
+        let (pairs, classes): (Vec<_>, Vec<_>) = subtables
+            .into_iter()
+            .map(|sub| {
+                let pairs = PairPosBuilder {
+                    pairs: sub.pairs,
+                    use_smallest_format: sub.use_smallest_format,
+                    ..Default::default()
+                };
+                let classes = PairPosBuilder {
+                    classes: sub.classes,
+                    earlier_pairs: all_pairs.clone(),
+                    use_smallest_format: sub.use_smallest_format,
+                    ..Default::default()
+                };
+                (pairs, classes)
+            })
+            .unzip();
+        pairs
+            .into_iter()
+            .chain(classes)
+            .filter(|sub| !sub.pairs.is_empty() || !sub.classes.0.is_empty())
+            .collect()
+    }
+
+    /// Write class pairs as format 1 subtables, if that would be smaller.
+    pub(crate) fn use_smallest_format(&mut self) {
+        self.use_smallest_format = true;
+    }
+
+    /// Choose the format for each class subtable.
+    ///
+    /// A format 2 subtable matches every second glyph, even those in no class,
+    /// and so it prevents any later subtable from being applied to the glyphs
+    /// in its coverage. The expanded format 1 version does not, so we only
+    /// use it if no later subtable covers any of the same glyphs.
+    fn build_class_subtables(&self) -> Vec<write_gpos::PairPos> {
+        let subtables = self.classes.0.values().flatten().collect::<Vec<_>>();
+        let mut later_coverage = HashSet::new();
+        let mut result = Vec::new();
+        for subtable in subtables.into_iter().rev() {
+            let is_shadowing = subtable.coverage().any(|gid| later_coverage.contains(&gid));
+            later_coverage.extend(subtable.coverage());
+            let expanded = subtable.expand(&[&self.pairs, &self.earlier_pairs]);
+            let format_1_size = expanded.format_1_size();
+            let format_2_size = subtable.format_2_size();
+            if !is_shadowing && format_1_size < format_2_size {
+                log::debug!(
+                    "class pair subtable: format 1 ({format_1_size} bytes, vs {format_2_size})"
+                );
+                result.extend(expanded.build().into_iter().rev());
+            } else {
+                log::debug!(
+                    "class pair subtable: format 2 ({format_2_size} bytes, vs {format_1_size})"
+                );
+                result.push(subtable.clone().build());
+            }
+        }
+        result.reverse();
+        result
+    }
 }
 
 impl Builder for PairPosBuilder {
     type Output = Vec<write_gpos::PairPos>;
 
     fn build(self) -> Self::Output {
+        let classes = if self.use_smallest_format {
+            self.build_class_subtables()
+        } else {
+            self.classes.build()
+        };
         let mut out = self.pairs.build();
-        out.extend(self.classes.build());
+        out.extend(classes);
         out
     }
 }
@@ -217,7 +448,7 @@ impl Builder for GlyphPairPosBuilder {
 
     fn build(self) -> Self::Output {
         let mut split_by_format = BTreeMap::<_, BTreeMap<_, Vec<_>>>::default();
-        for (g1, map) in self.0 {
+        for (g1, map) in self.pairs {
             for (g2, (v1, v2)) in map {
                 split_by_format
                     .entry((v1.format(), v2.format()))
@@ -360,6 +591,8 @@ struct MarkList {
     glyphs: BTreeMap<GlyphId, MarkRecord>,
     // map class names to their idx for this table
     classes: HashMap<SmolStr, u16>,
+    // the total size of the mark anchors, kept up to date as marks are added
+    anchors_size: usize,
 }
 
 impl MarkList {
@@ -374,11 +607,12 @@ impl MarkList {
     ) -> Result<u16, PreviouslyAssignedClass> {
         let next_id = self.classes.len().try_into().unwrap();
         let id = *self.classes.entry(class).or_insert(next_id);
-        if let Some(prev) = self
-            .glyphs
-            .insert(glyph, MarkRecord::new(id, anchor))
-            .filter(|prev| prev.mark_class != id)
-        {
+        self.anchors_size += anchor_size(&anchor);
+        let prev = self.glyphs.insert(glyph, MarkRecord::new(id, anchor));
+        if let Some(prev) = prev.as_ref() {
+            self.anchors_size -= anchor_size(&prev.mark_anchor);
+        }
+        if let Some(prev) = prev.filter(|prev| prev.mark_class != id) {
             let class = self
                 .classes
                 .iter()
@@ -397,6 +631,11 @@ impl MarkList {
         self.glyphs.keys().copied()
     }
 
+    /// An estimate of the size in bytes of the mark coverage and mark array.
+    fn size_estimate(&self) -> usize {
+        coverage_size(self.glyphs.len()) + 2 + 4 * self.glyphs.len() + self.anchors_size
+    }
+
     fn get_class(&self, class_name: &SmolStr) -> u16 {
         *self
             .classes
@@ -419,6 +658,8 @@ impl Builder for MarkList {
 pub struct MarkToBaseBuilder {
     marks: MarkList,
     bases: BTreeMap<GlyphId, Vec<(u16, AnchorTable)>>,
+    // the total size of the base anchors, for `size_estimate`
+    base_anchors_size: usize,
 }
 
 /// An error indicating a given glyph is has be
@@ -443,6 +684,7 @@ impl MarkToBaseBuilder {
 
     pub fn insert_base(&mut self, glyph: GlyphId, class: &SmolStr, anchor: AnchorTable) {
         let class = self.marks.get_class(class);
+        self.base_anchors_size += anchor_size(&anchor);
         self.bases.entry(glyph).or_default().push((class, anchor))
     }
 
@@ -450,6 +692,13 @@ impl MarkToBaseBuilder {
         self.bases.keys().copied()
     }
 
+    /// An estimate of the size in bytes of this subtable.
+    pub(crate) fn size_estimate(&self) -> usize {
+        let n_classes = self.marks.classes.len();
+        let bases = base_array_size(self.bases.len(), n_classes, self.base_anchors_size);
+        12 + self.marks.size_estimate() + bases
+    }
+
     pub fn mark_glyphs(&self) -> impl Iterator<Item = GlyphId> + Clone + '_ {
         self.marks.glyphs()
     }
@@ -459,7 +708,7 @@ impl Builder for MarkToBaseBuilder {
     type Output = Vec<write_gpos::MarkBasePosFormat1>;
 
     fn build(self) -> Self::Output {
-        let MarkToBaseBuilder { marks, bases } = self;
+        let MarkToBaseBuilder { marks, bases, .. } = self;
         let n_classes = marks.classes.len();
 
         let (mark_coverage, mark_array) = marks.build();
@@ -488,6 +737,10 @@ impl Builder for MarkToBaseBuilder {
 pub struct MarkToLigBuilder {
     marks: MarkList,
     ligatures: BTreeMap<GlyphId, Vec<BTreeMap<SmolStr, AnchorTable>>>,
+    // the number of components, and the size of their anchors, across all
+    // ligatures; for `size_estimate`
+    n_components: usize,
+    component_anchors_size: usize,
 }
 
 impl MarkToLigBuilder {
@@ -501,7 +754,12 @@ impl MarkToLigBuilder {
     }
 
     pub fn add_lig(&mut self, glyph: GlyphId, components: Vec<BTreeMap<SmolStr, AnchorTable>>) {
-        self.ligatures.insert(glyph, components);
+        self.n_components += components.len();
+        self.component_anchors_size += anchors_size(&components);
+        if let Some(prev) = self.ligatures.insert(glyph, components) {
+            self.n_components -= prev.len();
+            self.component_anchors_size -= anchors_size(&prev);
+        }
     }
 
     pub fn mark_glyphs(&self) -> impl Iterator<Item = GlyphId> + Clone + '_ {
@@ -511,13 +769,27 @@ impl MarkToLigBuilder {
     pub fn lig_glyphs(&self) -> impl Iterator<Item = GlyphId> + Clone + '_ {
         self.ligatures.keys().copied()
     }
+
+    /// An estimate of the size in bytes of this subtable.
+    pub(crate) fn size_estimate(&self) -> usize {
+        let n_classes = self.marks.classes.len();
+        let n_ligatures = self.ligatures.len();
+        // for each ligature, an offset to its LigatureAttach and a count,
+        // and then the component records
+        let ligatures =
+            n_ligatures * (2 + 2) + self.n_components * n_classes * 2 + self.component_anchors_size;
+        let lig_array = coverage_size(n_ligatures) + 2 + ligatures;
+        12 + self.marks.size_estimate() + lig_array
+    }
 }
 
 impl Builder for MarkToLigBuilder {
     type Output = Vec<write_gpos::MarkLigPosFormat1>;
 
     fn build(self) -> Self::Output {
-        let MarkToLigBuilder { marks, ligatures } = self;
+        let MarkToLigBuilder {
+            marks, ligatures, ..
+        } = self;
         let n_classes = marks.classes.len();
 
         // LigArray:
@@ -557,6 +829,8 @@ impl Builder for MarkToLigBuilder {
 pub struct MarkToMarkBuilder {
     attaching_marks: MarkList,
     base_marks: BTreeMap<GlyphId, Vec<(u16, AnchorTable)>>,
+    // the total size of the base mark anchors, for `size_estimate`
+    base_anchors_size: usize,
 }
 
 impl MarkToMarkBuilder {
@@ -571,6 +845,7 @@ impl MarkToMarkBuilder {
 
     pub fn insert_base(&mut self, glyph: GlyphId, class: &SmolStr, anchor: AnchorTable) {
         let id = self.attaching_marks.get_class(class);
+        self.base_anchors_size += anchor_size(&anchor);
         self.base_marks.entry(glyph).or_default().push((id, anchor))
     }
 
@@ -581,6 +856,14 @@ impl MarkToMarkBuilder {
     pub fn mark2_glyphs(&self) -> impl Iterator<Item = GlyphId> + Clone + '_ {
         self.base_marks.keys().copied()
     }
+
+    /// An estimate of the size in bytes of this subtable.
+    pub(crate) fn size_estimate(&self) -> usize {
+        let n_classes = self.attaching_marks.classes.len();
+        let n_bases = self.base_marks.len();
+        let bases = base_array_size(n_bases, n_classes, self.base_anchors_size);
+        12 + self.attaching_marks.size_estimate() + bases
+    }
 }
 
 impl Builder for MarkToMarkBuilder {
@@ -590,6 +873,7 @@ impl Builder for MarkToMarkBuilder {
         let MarkToMarkBuilder {
             attaching_marks,
             base_marks,
+            ..
         } = self;
         let n_classes = attaching_marks.classes.len();
 
@@ -7,7 +7,7 @@ use write_fonts::{
     types::{FixedSize, GlyphId},
 };
 
-use super::Builder;
+use super::{coverage_size, Builder};
 
 #[derive(Clone, Debug, Default)]
 pub struct SingleSubBuilder {
@@ -44,6 +44,11 @@ impl SingleSubBuilder {
     pub(crate) fn iter_pairs(&self) -> impl Iterator<Item = (GlyphId, GlyphId)> + '_ {
         self.items.iter().map(|(target, (alt, _))| (*target, *alt))
     }
+
+    /// An estimate of the size in bytes of this subtable, as format 2.
+    pub(crate) fn size_estimate(&self) -> usize {
+        6 + coverage_size(self.items.len()) + 2 * self.items.len()
+    }
 }
 
 impl Builder for SingleSubBuilder {
@@ -164,6 +169,11 @@ impl MultipleSubBuilder {
     pub fn insert(&mut self, target: GlyphId, replacement: Vec<GlyphId>) {
         self.items.insert(target, replacement);
     }
+
+    /// An estimate of the size in bytes of this subtable.
+    pub(crate) fn size_estimate(&self) -> usize {
+        6 + coverage_size(self.items.len()) + sequences_size(self.items.values())
+    }
 }
 
 #[derive(Clone, Debug, Default)]
@@ -186,6 +196,11 @@ impl AlternateSubBuilder {
             .iter()
             .flat_map(|(target, alt)| alt.iter().map(|alt| (*target, *alt)))
     }
+
+    /// An estimate of the size in bytes of this subtable.
+    pub(crate) fn size_estimate(&self) -> usize {
+        6 + coverage_size(self.items.len()) + sequences_size(self.items.values())
+    }
 }
 
 impl Builder for AlternateSubBuilder {
@@ -205,6 +220,12 @@ impl Builder for AlternateSubBuilder {
     }
 }
 
+/// The size of the sequences or alternate sets of a multiple or alternate
+/// substitution, including their offsets.
+fn sequences_size<'a>(sequences: impl Iterator<Item = &'a Vec<GlyphId>>) -> usize {
+    sequences.map(|glyphs| 4 + 2 * glyphs.len()).sum()
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct LigatureSubBuilder {
     items: BTreeMap<GlyphId, Vec<(Vec<GlyphId>, GlyphId)>>,
@@ -226,6 +247,34 @@ impl LigatureSubBuilder {
         //lookup anytime the target exists? idk
         self.items.contains_key(&target)
     }
+
+    /// `true` if this exact ligature has already been added.
+    pub fn contains(&self, target: &[GlyphId], replacement: GlyphId) -> bool {
+        let Some((first, rest)) = target.split_first() else { return false };
+        self.items
+            .get(first)
+            .into_iter()
+            .flatten()
+            .any(|(components, lig)| components == rest && *lig == replacement)
+    }
+
+    /// An estimate of the size in bytes of this subtable.
+    pub(crate) fn size_estimate(&self) -> usize {
+        let lig_sets = self
+            .items
+            .values()
+            .map(|ligs| {
+                // each ligature has an offset, a glyph, a count, and all but
+                // the first of its components
+                let ligatures = ligs
+                    .iter()
+                    .map(|(rest, _)| 6 + 2 * rest.len())
+                    .sum::<usize>();
+                4 + ligatures
+            })
+            .sum::<usize>();
+        6 + coverage_size(self.items.len()) + lig_sets
+    }
 }
 
 impl Builder for LigatureSubBuilder {
@@ -81,6 +81,12 @@ impl ClassDefBuilder2 {
     }
 }
 
+// this estimate assumes a format 1 coverage table, which is not always what
+// we write, but is good enough for deciding how to split or promote lookups.
+pub(crate) fn coverage_size(n_glyphs: usize) -> usize {
+    4 + 2 * n_glyphs
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,9 +1,95 @@
 //! Options used during compilation
 
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+
+use write_fonts::types::Tag;
+
+use super::{AxisMap, FontInfo};
+use crate::Diagnostic;
+
+/// The default value of [`Opts::max_ligature_sequences`].
+pub(crate) const DEFAULT_MAX_LIGATURE_SEQUENCES: usize = 100_000;
+
+/// The version of the `post` table to generate, if any.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PostFormat {
+    /// Do not generate a post table.
+    #[default]
+    None,
+    /// Generate a version 2.0 post table, which includes glyph names.
+    Format2,
+    /// Generate a version 3.0 post table, which has no glyph names.
+    ///
+    /// This is smaller, and appropriate for fonts where file size matters.
+    Format3,
+}
+
+/// The dialect of the FEA syntax that a source is expected to use.
+///
+/// See [`Opts::dialect`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// The syntax described in the AFDKO feature file specification, without
+    /// the extensions for variable fonts.
+    Classic,
+    /// The classic syntax, plus variable metrics, `conditionset` blocks, and
+    /// `variation` blocks.
+    #[default]
+    Variable,
+}
+
+/// A callback that is passed each diagnostic as it is reported.
+///
+/// See [`Opts::diagnostic_sink`].
+#[derive(Clone)]
+pub(crate) struct DiagnosticSink(Arc<Mutex<dyn FnMut(&Diagnostic) + Send>>);
+
+impl DiagnosticSink {
+    pub(crate) fn send(&self, diagnostic: &Diagnostic) {
+        // if a previous call panicked, we still want to report diagnostics
+        let mut sink = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        (*sink)(diagnostic)
+    }
+}
+
+impl Debug for DiagnosticSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("DiagnosticSink")
+    }
+}
+
 /// Options for configuring compilation behaviour.
 #[derive(Clone, Debug, Default)]
 pub struct Opts {
-    pub(crate) make_post_table: bool,
+    pub(crate) make_post_table: PostFormat,
+    pub(crate) bake_vertical_origins: bool,
+    pub(crate) emit_empty_layout_tables: bool,
+    pub(crate) compact_pair_pos: bool,
+    pub(crate) validate_output: bool,
+    pub(crate) skip_checksum_adjustment: bool,
+    pub(crate) report_excluded_lookups: bool,
+    pub(crate) warn_unused_glyph_classes: bool,
+    pub(crate) warn_redundant_lookupflags: bool,
+    pub(crate) warn_ineffective_lookupflags: bool,
+    pub(crate) warn_unregistered_features: bool,
+    pub(crate) private_feature_tags: BTreeSet<Tag>,
+    pub(crate) ignore_unknown_items: bool,
+    pub(crate) skip_missing_class_glyphs: bool,
+    pub(crate) dialect: Dialect,
+    pub(crate) max_ligature_sequences: Option<usize>,
+    pub(crate) max_include_depth: Option<usize>,
+    pub(crate) snap_value_records: Option<u16>,
+    pub(crate) os2_version: Option<u16>,
+    pub(crate) font_info: Option<FontInfo>,
+    pub(crate) tables: Option<BTreeSet<Tag>>,
+    pub(crate) parallel: bool,
+    pub(crate) diagnostic_sink: Option<DiagnosticSink>,
+    // set via `Compiler::with_axis_map`
+    pub(crate) axis_map: Option<AxisMap>,
 }
 
 impl Opts {
@@ -12,9 +98,283 @@ impl Opts {
         Self::default()
     }
 
-    /// If `true`, we will generate a post table from the glyph map.
-    pub fn make_post_table(mut self, flag: bool) -> Self {
-        self.make_post_table = flag;
+    /// Choose whether to generate a post table from the glyph map.
+    ///
+    /// See [`PostFormat`] for the options. By default no post table is
+    /// generated.
+    pub fn make_post_table(mut self, format: PostFormat) -> Self {
+        self.make_post_table = format;
+        self
+    }
+
+    /// Choose where `VertOriginY` values from the `vmtx` table are written.
+    ///
+    /// By default they produce a VORG table. If `true`, they are instead
+    /// written as top side bearings in the generated vmtx table, and no VORG
+    /// table is produced. The top side bearing is the origin minus the top of
+    /// the glyph's bounding box, so this requires the glyph bounds and vertical
+    /// metrics from [`Opts::font_info`].
+    pub fn bake_vertical_origins(mut self, flag: bool) -> Self {
+        self.bake_vertical_origins = flag;
+        self
+    }
+
+    /// If `true`, we will always generate GSUB and GPOS tables.
+    ///
+    /// If the source contains no rules for one of these tables, it will be
+    /// written with empty script, feature, and lookup lists. This is off by
+    /// default, in which case the table is omitted.
+    pub fn emit_empty_layout_tables(mut self, flag: bool) -> Self {
+        self.emit_empty_layout_tables = flag;
+        self
+    }
+
+    /// If `true`, class-based pair positioning may be written in format 1.
+    ///
+    /// By default, kerning between glyph classes always produces format 2
+    /// (class) subtables, matching feaLib. If this is set, each of these
+    /// subtables is instead expanded into individual glyph pairs (format 1)
+    /// when that would be smaller, which is often the case for sparse
+    /// kerning between small classes. Shaping behaviour is unchanged.
+    pub fn compact_pair_pos(mut self, flag: bool) -> Self {
+        self.compact_pair_pos = flag;
+        self
+    }
+
+    /// If `true`, the generated binary will be parsed after it is written.
+    ///
+    /// This checks that the GSUB, GPOS, and GDEF tables can be read back, and
+    /// is intended to catch bugs in the compiler. It only applies when using
+    /// [`Compiler::compile_binary`].
+    ///
+    /// [`Compiler::compile_binary`]: super::Compiler::compile_binary
+    pub fn validate_output(mut self, flag: bool) -> Self {
+        self.validate_output = flag;
+        self
+    }
+
+    /// If `true`, the `checksumAdjustment` field of the head table is not set.
+    ///
+    /// By default this field is computed from the complete binary, as required
+    /// for a standalone font. When the generated tables are going to be merged
+    /// into another font, which will recompute the checksum, this step can be
+    /// skipped and the field is left as zero. It only applies when using
+    /// [`Compiler::compile_binary`] or [`Compilation::build_binary`].
+    ///
+    /// [`Compiler::compile_binary`]: super::Compiler::compile_binary
+    /// [`Compilation::build_binary`]: super::Compilation::build_binary
+    pub fn skip_checksum_adjustment(mut self, flag: bool) -> Self {
+        self.skip_checksum_adjustment = flag;
+        self
+    }
+
+    /// If `true`, report when `exclude_dflt` prevents default lookups from
+    /// being applied to a language system.
+    ///
+    /// This generates an informational diagnostic for each `language`
+    /// statement where default lookups were excluded, listing how many.
+    pub fn report_excluded_lookups(mut self, flag: bool) -> Self {
+        self.report_excluded_lookups = flag;
+        self
+    }
+
+    /// If `true`, warn about glyph classes that are defined but never used.
+    pub fn warn_unused_glyph_classes(mut self, flag: bool) -> Self {
+        self.warn_unused_glyph_classes = flag;
+        self
+    }
+
+    /// If `true`, warn about `lookupflag` statements that set the flags that
+    /// are already active.
+    pub fn warn_redundant_lookupflags(mut self, flag: bool) -> Self {
+        self.warn_redundant_lookupflags = flag;
+        self
+    }
+
+    /// If `true`, warn about `lookupflag` statements that ignore base glyphs,
+    /// ligatures or marks when the font has no GDEF glyph classes.
+    ///
+    /// These flags skip glyphs based on their GDEF class, so without any
+    /// classes (declared, inferred from the rules, or from
+    /// [`Opts::font_info`]) they have no effect.
+    pub fn warn_ineffective_lookupflags(mut self, flag: bool) -> Self {
+        self.warn_ineffective_lookupflags = flag;
         self
     }
+
+    /// If `true`, warn about features whose tag is not registered in the
+    /// OpenType spec.
+    ///
+    /// This catches typos such as `ligs` instead of `liga`. The `ssXX` and
+    /// `cvXX` tags are always accepted; other tags that are used on purpose
+    /// can be allowed with [`Opts::private_feature_tags`].
+    pub fn warn_unregistered_features(mut self, flag: bool) -> Self {
+        self.warn_unregistered_features = flag;
+        self
+    }
+
+    /// Feature tags that are not reported by
+    /// [`Opts::warn_unregistered_features`].
+    ///
+    /// This is intended for private or experimental features.
+    pub fn private_feature_tags(mut self, tags: &[Tag]) -> Self {
+        self.private_feature_tags = tags.iter().copied().collect();
+        self
+    }
+
+    /// If `true`, unknown top-level items and statements are reported as
+    /// warnings instead of errors.
+    ///
+    /// This allows files that use extensions to the FEA syntax to be compiled,
+    /// ignoring the parts that are not understood. This is off by default.
+    pub fn ignore_unknown_items(mut self, flag: bool) -> Self {
+        self.ignore_unknown_items = flag;
+        self
+    }
+
+    /// If `true`, glyphs in a glyph class that are not in the font are removed
+    /// from the class with a warning, instead of being an error.
+    ///
+    /// This applies to glyph names and CIDs in both named and inline classes,
+    /// so that rules using these classes can still be compiled; members of
+    /// glyph ranges that are not in the font are always handled this way. A
+    /// missing glyph outside of a class is still an error, as is one in a
+    /// class that is matched glyph-by-glyph with another, such as the classes
+    /// in `sub [a b] by [c d];`. This is off by default.
+    pub fn skip_missing_class_glyphs(mut self, flag: bool) -> Self {
+        self.skip_missing_class_glyphs = flag;
+        self
+    }
+
+    /// Set the dialect of the FEA syntax that sources are expected to use.
+    ///
+    /// When targeting [`Dialect::Classic`], using the variable font extensions
+    /// to the syntax is an error. The default is [`Dialect::Variable`], which
+    /// accepts everything.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// The maximum number of glyph sequences a single ligature rule may match.
+    ///
+    /// A ligature rule that contains glyph classes, such as
+    /// `sub [f f.alt] [i l] by f_i;`, is expanded into a rule for every
+    /// combination of its glyphs. A rule that would exceed this limit is
+    /// reported as an error instead of being expanded. This applies to both
+    /// plain ligature rules and ligatures in contextual rules. The default is
+    /// 100,000.
+    pub fn max_ligature_sequences(mut self, limit: usize) -> Self {
+        self.max_ligature_sequences = Some(limit);
+        self
+    }
+
+    /// The maximum depth of nested `include` statements.
+    ///
+    /// The root file is at depth 0, a file it includes is at depth 1, and so
+    /// on. An `include` that would exceed this depth is reported as an error,
+    /// and the file is not included. This guards against runaway chains of
+    /// includes; large projects with deeply nested files may need to raise it.
+    /// The default is 50.
+    pub fn max_include_depth(mut self, depth: usize) -> Self {
+        self.max_include_depth = Some(depth);
+        self
+    }
+
+    /// Round the values in value records to the nearest multiple of `grid`.
+    ///
+    /// This is intended for normalizing machine-generated kerning, which may
+    /// contain values like `-37` where the designer intended `-35`. It applies
+    /// to the placement and advance values of every value record; anchors and
+    /// device tables are not changed. Values exactly halfway between two
+    /// multiples are rounded away from zero, and values that would round to a
+    /// multiple outside the range of an `i16` use the closest multiple inside
+    /// it. A `grid` of 0 or 1 has no effect.
+    pub fn snap_value_records(mut self, grid: u16) -> Self {
+        self.snap_value_records = Some(grid);
+        self
+    }
+
+    /// Specify the version of the OS/2 table to generate.
+    ///
+    /// By default we use the lowest version that includes all of the fields
+    /// set in the FEA. If a version is specified here, it is an error for the
+    /// FEA to set fields that require a higher version. Versions 2 through 4
+    /// share the same layout, and versions above 5 are an error.
+    pub fn os2_version(mut self, version: u16) -> Self {
+        self.os2_version = Some(version);
+        self
+    }
+
+    /// Provide information about the font being compiled.
+    ///
+    /// This is required for features that depend on things like the font's
+    /// cmap or units per em. If the font's glyph count is provided, it is
+    /// also checked against the glyph map. If it includes glyph classes,
+    /// these are used in the generated GDEF table in preference to the
+    /// classes inferred from the rules.
+    pub fn font_info(mut self, font_info: FontInfo) -> Self {
+        self.font_info = Some(font_info);
+        self
+    }
+
+    /// Only generate the tables with the provided tags.
+    ///
+    /// This is useful when only some tables are wanted, for instance when
+    /// merging GPOS into an existing font. Other tables are not built at all.
+    /// By default every table is generated.
+    ///
+    /// This includes tables that are added automatically, such as `maxp`
+    /// and `post`. The `aalt` feature is part of GSUB, and is only generated
+    /// if GSUB is requested. If GDEF is not requested, glyph classes are not
+    /// inferred from the rules, unless lookups use mark attachment classes or
+    /// mark filtering sets; the lookups refer to these by their index in GDEF.
+    /// Names referenced by other tables (such as STAT) are only written if
+    /// `name` is requested.
+    pub fn tables(mut self, tables: &[Tag]) -> Self {
+        self.tables = Some(tables.iter().copied().collect());
+        self
+    }
+
+    /// If `true`, independent feature blocks are compiled on multiple threads.
+    ///
+    /// This is intended for very large generated sources, such as the kerning
+    /// for a big font. Consecutive top-level feature blocks are split between
+    /// threads, and the resulting lookups are merged in source order, so the
+    /// output is the same as when compiling on a single thread.
+    ///
+    /// Features that share state with other parts of the source are still
+    /// compiled in order: this includes any feature that defines or references
+    /// a named lookup, defines a glyph or mark class, uses
+    /// `MarkAttachmentType`, or contains an `include`, as well as `aalt`,
+    /// `size`, and the stylistic set and character variant features.
+    ///
+    /// This requires the `parallel` cargo feature; without it, this option has
+    /// no effect. It is off by default.
+    pub fn parallel(mut self, flag: bool) -> Self {
+        self.parallel = flag;
+        self
+    }
+
+    /// Provide a function that is called with each diagnostic as it is reported.
+    ///
+    /// This is intended for displaying errors and warnings while a long
+    /// compilation is still running. It receives the diagnostics from parsing,
+    /// validation, and compilation, including those that are not errors.
+    /// The diagnostics are still collected and returned as usual, and are
+    /// printed if [`Compiler::verbose`] is set.
+    ///
+    /// [`Compiler::verbose`]: super::Compiler::verbose
+    pub fn diagnostic_sink(mut self, sink: impl FnMut(&Diagnostic) + Send + 'static) -> Self {
+        self.diagnostic_sink = Some(DiagnosticSink(Arc::new(Mutex::new(sink))));
+        self
+    }
+
+    /// `true` if the table with this tag should be generated.
+    pub(crate) fn includes_table(&self, tag: Tag) -> bool {
+        self.tables
+            .as_ref()
+            .map(|tables| tables.contains(&tag))
+            .unwrap_or(true)
+    }
 }
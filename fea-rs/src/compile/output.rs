@@ -1,15 +1,23 @@
 //! The result of a compilation
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Write,
+    ops::Range,
+};
 
+use smol_str::SmolStr;
 use write_fonts::{
     dump_table,
     read::{FontRef, TableProvider, TopLevelTable},
     tables::{
-        layout::{FeatureParams, StylisticSetParams},
+        gpos::Gpos,
+        gsub::Gsub,
+        layout::{FeatureList, FeatureParams, LookupList, ScriptList, StylisticSetParams},
         maxp::Maxp,
     },
-    types::Tag,
+    types::{GlyphId, Tag, Version16Dot16},
     FontBuilder,
 };
 
@@ -17,8 +25,10 @@ use super::{
     error::BinaryCompilationError,
     features::SizeFeature,
     lookups::{AllLookups, FeatureKey, LookupId},
-    tables::Tables,
-    tags, Opts,
+    tables::{StatBuilder, Tables},
+    tags,
+    variations::FeatureVariation,
+    Opts, PostFormat,
 };
 
 use crate::{Diagnostic, GlyphMap};
@@ -27,6 +37,15 @@ use crate::{Diagnostic, GlyphMap};
 ///
 /// This losslessly contains all of the information parsed from the FEA necessary
 /// for generating the various OpenType tables.
+///
+/// # Lookup order
+///
+/// Lookups are numbered in the order in which they first appear in the
+/// source, whether they are named lookup blocks or anonymous lookups created
+/// for rules in a feature. The lookups generated for inline rules in a
+/// contextual rule immediately follow that contextual lookup, and any lookups
+/// generated for the `aalt` feature come first. This means that two similar
+/// FEA files will produce lookups in a comparable order.
 //TODO: ability to generate new errors during this final compilation step
 pub struct Compilation {
     /// Any warnings that were generated during compilation
@@ -34,79 +53,352 @@ pub struct Compilation {
     pub(crate) tables: Tables,
     pub(crate) lookups: AllLookups,
     pub(crate) features: BTreeMap<FeatureKey, Vec<LookupId>>,
-    pub(crate) required_features: HashSet<FeatureKey>,
+    pub(crate) required_features: BTreeSet<FeatureKey>,
     pub(crate) size: Option<SizeFeature>,
+    pub(crate) feature_variations: Vec<FeatureVariation>,
+    pub(crate) glyph_classes: BTreeMap<SmolStr, Vec<GlyphId>>,
+    pub(crate) compact_pair_pos: bool,
 }
 
 impl Compilation {
+    /// The contents of the `STAT` table, if one was declared.
+    ///
+    /// This is useful for tooling that wants to inspect axes and axis values
+    /// (for instance, to generate instance names) without parsing the
+    /// compiled binary.
+    pub fn stat(&self) -> Option<&StatBuilder> {
+        self.tables.stat.as_ref()
+    }
+
+    /// The final lookup index of each named lookup block.
+    ///
+    /// This can be used to map the lookup indices reported by a shaper (for
+    /// instance in `hb-shape --trace`) back to names in the FEA source. Named
+    /// lookups that contained no rules are not included.
+    pub fn named_lookup_indices(&self) -> HashMap<SmolStr, LookupId> {
+        self.lookups
+            .iter_named()
+            .filter(|(_, id)| *id != LookupId::Empty)
+            .map(|(name, id)| (name.clone(), id))
+            .collect()
+    }
+
+    /// The glyphs in the named glyph class, as resolved from the FEA.
+    ///
+    /// The name includes the leading `@`, as in `@LOWERCASE`. Glyphs are in
+    /// the order of the class definition. This is intended for testing and
+    /// debugging the construction of classes; mark classes are not included.
+    pub fn glyph_class(&self, name: &str) -> Option<&[GlyphId]> {
+        self.glyph_classes.get(name).map(Vec::as_slice)
+    }
+
+    /// `true` if `glyph` is a member of the named glyph class.
+    ///
+    /// See [`Compilation::glyph_class`].
+    pub fn glyph_class_contains(&self, name: &str, glyph: GlyphId) -> bool {
+        self.glyph_class(name)
+            .filter(|glyphs| glyphs.contains(&glyph))
+            .is_some()
+    }
+
+    /// A human-readable summary of the features in this compilation.
+    ///
+    /// For each feature tag this lists the script/language pairs it is
+    /// registered under (marking those where it is the required feature) and
+    /// the number of lookups of each type it uses. Lookups shared between
+    /// language systems are only counted once. This is intended for
+    /// documentation and debugging; the format is not stable.
+    pub fn describe(&self) -> String {
+        self.describe_impl(None)
+    }
+
+    /// Like [`describe`](Self::describe), but also showing the name of each
+    /// feature.
+    ///
+    /// Registered features use the name from the OpenType spec, such as
+    /// "Small Capitals" for `smcp`. Names in `custom_names` take precedence
+    /// over these, and can be used to name private features. Features with
+    /// no known name are shown with only their tag.
+    pub fn describe_with_names(&self, custom_names: &HashMap<Tag, String>) -> String {
+        self.describe_impl(Some(custom_names))
+    }
+
+    fn describe_impl(&self, custom_names: Option<&HashMap<Tag, String>>) -> String {
+        let mut features: BTreeMap<Tag, (Vec<String>, BTreeSet<LookupId>)> = BTreeMap::new();
+        for (key, lookups) in &self.features {
+            let (languages, feature_lookups) = features.entry(key.feature).or_default();
+            let required = if self.required_features.contains(key) {
+                " (required)"
+            } else {
+                ""
+            };
+            let language = key.language.to_string();
+            let language = language.trim_end();
+            languages.push(format!("{}/{language}{required}", key.script));
+            feature_lookups.extend(lookups.iter().copied());
+        }
+
+        let mut out = String::new();
+        for (tag, (mut languages, lookups)) in features {
+            languages.sort_unstable();
+            let mut types = BTreeMap::<_, usize>::new();
+            for name in lookups.iter().filter_map(|id| self.lookups.type_name(*id)) {
+                *types.entry(name).or_default() += 1;
+            }
+            let lookups = if types.is_empty() {
+                "none".to_string()
+            } else {
+                let types = types.iter().map(|(name, n)| format!("{n} {name}"));
+                types.collect::<Vec<_>>().join(", ")
+            };
+            let name = custom_names
+                .and_then(|names| names.get(&tag).cloned().or_else(|| tags::feature_name(tag)));
+            match name {
+                Some(name) => writeln!(&mut out, "{tag} ({name})").unwrap(),
+                None => writeln!(&mut out, "{tag}").unwrap(),
+            }
+            writeln!(&mut out, "  languages: {}", languages.join(", ")).unwrap();
+            writeln!(&mut out, "  lookups: {lookups}").unwrap();
+        }
+        out
+    }
+
+    /// The size in bytes of each table generated from the FEA.
+    ///
+    /// This does not include tables that are only added during
+    /// [`assemble`](Self::assemble), such as `maxp` or `post`.
+    pub fn table_sizes(&self) -> Result<BTreeMap<Tag, usize>, BinaryCompilationError> {
+        let tables = self.apply(None, &Opts::default())?;
+        Ok(tables
+            .iter()
+            .map(|(tag, data)| (*tag, data.len()))
+            .collect())
+    }
+
+    /// Check that generated tables do not exceed a maximum size.
+    ///
+    /// Each budget is a table tag and the maximum allowed size of that table,
+    /// in bytes; tables that are not generated are always within budget. On
+    /// failure, returns a `(tag, actual size, allowed size)` tuple for each
+    /// table that was too large.
+    ///
+    /// This is intended to catch regressions in table size in tests or CI.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the tables cannot be compiled; see
+    /// [`table_sizes`](Self::table_sizes) to handle this case.
+    pub fn assert_under_budget(
+        &self,
+        budgets: &[(Tag, usize)],
+    ) -> Result<(), Vec<(Tag, usize, usize)>> {
+        let sizes = self.table_sizes().expect("failed to compile tables");
+        let over_budget = budgets
+            .iter()
+            .filter_map(|(tag, allowed)| {
+                let actual = sizes.get(tag).copied().unwrap_or_default();
+                (actual > *allowed).then_some((*tag, actual, *allowed))
+            })
+            .collect::<Vec<_>>();
+        if over_budget.is_empty() {
+            Ok(())
+        } else {
+            Err(over_budget)
+        }
+    }
+
     /// Generate all the final tables and add them to a builder.
     ///
     /// This builder can be used to get generate the final binary.
+    /// Building it directly does not set the head table's `checksumAdjustment`;
+    /// use [`build_binary`](Self::build_binary) to generate a standalone font.
     pub fn assemble(
         &self,
         glyph_map: &GlyphMap,
         opts: Opts,
     ) -> Result<FontBuilder<'static>, BinaryCompilationError> {
-        let mut builder = self.apply(None)?;
+        self.assemble_tables(glyph_map, opts)
+            .map(FontTables::into_builder)
+    }
+
+    fn assemble_tables(
+        &self,
+        glyph_map: &GlyphMap,
+        opts: Opts,
+    ) -> Result<FontTables<'static>, BinaryCompilationError> {
+        let mut builder = self.apply(None, &opts)?;
         // because we often inspect our output with ttx, and ttx fails if maxp is
         // missing, we create a maxp table.
-        let maxp = Maxp::new(glyph_map.len().try_into().unwrap());
-        builder.add_table(Tag::new(b"maxp"), dump_table(&maxp).unwrap());
-        if opts.make_post_table {
-            let post = glyph_map.make_post_table();
+        if opts.includes_table(Tag::new(b"maxp")) {
+            let maxp = Maxp::new(glyph_map.len().try_into().unwrap());
+            builder.add_table(Tag::new(b"maxp"), dump_table(&maxp).unwrap());
+        }
+        let make_post_table = if opts.includes_table(Tag::new(b"post")) {
+            opts.make_post_table
+        } else {
+            PostFormat::None
+        };
+        let post = match make_post_table {
+            PostFormat::None => None,
+            PostFormat::Format2 => Some(glyph_map.make_post_table()),
+            PostFormat::Format3 => {
+                // the same header as version 2.0, without the glyph names
+                let mut post = glyph_map.make_post_table();
+                post.version = Version16Dot16::VERSION_3_0;
+                post.num_glyphs = None;
+                post.glyph_name_index = None;
+                post.string_data = None;
+                Some(post)
+            }
+        };
+        if let Some(post) = post {
             builder.add_table(Tag::new(b"post"), dump_table(&post).unwrap());
         }
+        if let Some(vmtx) = self.tables.vmtx.as_ref() {
+            // the FEA only has some of the metrics; we need the rest from the font
+            let vmtx_data = opts
+                .font_info
+                .as_ref()
+                .filter(|_| opts.includes_table(Tag::new(b"vmtx")))
+                .and_then(|info| {
+                    let metrics = info.vertical_metrics.as_ref()?;
+                    vmtx.build_vmtx(metrics, &info.glyph_y_max, opts.bake_vertical_origins)
+                });
+            if let Some(data) = vmtx_data {
+                builder.add_table(Tag::new(b"vmtx"), data);
+            }
+            if !opts.bake_vertical_origins && opts.includes_table(Tag::new(b"VORG")) {
+                if let Some(data) = vmtx.build_vorg() {
+                    builder.add_table(Tag::new(b"VORG"), data);
+                }
+            }
+        }
+        if opts.emit_empty_layout_tables {
+            if !builder.contains(tags::GSUB) && opts.includes_table(tags::GSUB) {
+                let gsub = Gsub::new(
+                    ScriptList::new(Vec::new()),
+                    FeatureList::new(Vec::new()),
+                    LookupList::new(Vec::new()),
+                );
+                builder.add_table(tags::GSUB, dump_table(&gsub)?);
+            }
+            if !builder.contains(tags::GPOS) && opts.includes_table(tags::GPOS) {
+                let gpos = Gpos::new(
+                    ScriptList::new(Vec::new()),
+                    FeatureList::new(Vec::new()),
+                    LookupList::new(Vec::new()),
+                );
+                builder.add_table(tags::GPOS, dump_table(&gpos)?);
+            }
+        }
         Ok(builder)
     }
 
+    /// Generate the final binary font.
+    ///
+    /// This is [`assemble`](Self::assemble), followed by setting the
+    /// `checksumAdjustment` field of the `head` table (if there is one) so that
+    /// the output is a valid standalone font. This last step can be skipped
+    /// with [`Opts::skip_checksum_adjustment`].
+    pub fn build_binary(
+        &self,
+        glyph_map: &GlyphMap,
+        opts: Opts,
+    ) -> Result<Vec<u8>, BinaryCompilationError> {
+        self.build_binary_with_layout(glyph_map, opts)
+            .map(|(bytes, _)| bytes)
+    }
+
+    /// Generate the final binary font, also returning the location of each table.
+    ///
+    /// This is the same as [`build_binary`](Self::build_binary); the returned
+    /// map contains the range in the binary occupied by each table.
+    pub fn build_binary_with_layout(
+        &self,
+        glyph_map: &GlyphMap,
+        opts: Opts,
+    ) -> Result<(Vec<u8>, HashMap<Tag, Range<usize>>), BinaryCompilationError> {
+        let skip_checksum_adjustment = opts.skip_checksum_adjustment;
+        let tables = self.assemble_tables(glyph_map, opts)?;
+        let layout = tables.layout();
+        let mut bytes = tables.into_builder().build();
+        if let Some(head) = layout.get(&Tag::new(b"head")) {
+            if !skip_checksum_adjustment {
+                set_checksum_adjustment(&mut bytes, head.start);
+            }
+        }
+        Ok((bytes, layout))
+    }
+
     //FIXME: this is left over from a previous API. `font` is always none.
     //This should be removed and merged with `build_raw`, above.
     fn apply<'a>(
         &self,
         font: impl Into<Option<FontRef<'a>>>,
-    ) -> Result<FontBuilder<'a>, BinaryCompilationError> {
+        opts: &Opts,
+    ) -> Result<FontTables<'a>, BinaryCompilationError> {
         let font = font.into();
-        let mut builder = FontBuilder::default();
-        if let Some(head_raw) = &self.tables.head {
+        let include = |tag: &[u8; 4]| opts.includes_table(Tag::new(tag));
+        let mut builder = FontTables::default();
+        if let Some(head_raw) = self.tables.head.as_ref().filter(|_| include(b"head")) {
             let head = head_raw.build(font.as_ref());
             builder.add_table(Tag::new(b"head"), dump_table(&head).unwrap());
         }
 
         //TODO: can this contain some subset of keys? should we preserve
         //existing values in this case?
-        if let Some(hhea_raw) = self.tables.hhea.as_ref() {
+        if let Some(hhea_raw) = self.tables.hhea.as_ref().filter(|_| include(b"hhea")) {
             let data = dump_table(hhea_raw)?;
             builder.add_table(Tag::new(b"hhea"), data);
         }
 
-        if let Some(vhea_raw) = self.tables.vhea.as_ref() {
-            let data = dump_table(vhea_raw)?;
+        if let Some(vhea_raw) = self.tables.vhea.as_ref().filter(|_| include(b"vhea")) {
+            let mut vhea = vhea_raw.clone();
+            // this describes the font's vmtx, which the FEA cannot change
+            if let Some(metrics) = opts
+                .font_info
+                .as_ref()
+                .and_then(|info| info.vertical_metrics.as_ref())
+            {
+                vhea.number_of_long_ver_metrics = metrics.long_metrics.len() as u16;
+            }
+            let data = dump_table(&vhea)?;
             builder.add_table(Tag::new(b"vhea"), data);
         }
 
-        if let Some(os2) = self.tables.os2.as_ref() {
+        if let Some(os2) = self.tables.os2.as_ref().filter(|_| include(b"OS/2")) {
             let table = os2.build();
             let data = dump_table(&table)?;
             builder.add_table(write_fonts::tables::os2::Os2::TAG, data);
         }
 
-        if let Some(gdef) = &self.tables.gdef {
+        if let Some(gdef) = self.tables.gdef.as_ref().filter(|_| include(b"GDEF")) {
             builder.add_table(Tag::new(b"GDEF"), gdef.build()?);
         }
 
-        if let Some(base) = &self.tables.base {
+        if let Some(base) = self.tables.base.as_ref().filter(|_| include(b"BASE")) {
             let data = dump_table(&base.build())?;
             builder.add_table(Tag::new(b"BASE"), data);
         }
 
+        if let Some(meta) = self.tables.meta.as_ref().filter(|_| include(b"meta")) {
+            builder.add_table(Tag::new(b"meta"), meta.build());
+        }
+
         //TODO: reuse any existing names if name table present
         let mut name_builder = self.tables.name.clone();
-        if let Some(stat_raw) = self.tables.stat.as_ref() {
+        if let Some(stat_raw) = self.tables.stat.as_ref().filter(|_| include(b"STAT")) {
             let stat = stat_raw.build(&mut name_builder);
             builder.add_table(Tag::new(b"STAT"), dump_table(&stat)?);
         }
 
-        let (mut gsub, mut gpos) = self.lookups.build(&self.features, &self.required_features);
+        let (mut gsub, mut gpos) = self.lookups.build(
+            &self.features,
+            &self.required_features,
+            &self.feature_variations,
+            self.compact_pair_pos,
+            |tag| opts.includes_table(tag),
+        );
 
         let mut feature_params = HashMap::new();
         if let Some(size) = self.size.as_ref() {
@@ -153,8 +445,9 @@ impl Compilation {
             builder.add_table(Tag::new(b"GPOS"), dump_table(&gpos)?);
         }
 
-        if let Some(name) = name_builder.build() {
-            builder.add_table(Tag::new(b"name"), dump_table(&name)?);
+        let name = include(b"name").then(|| name_builder.build()).transpose()?;
+        if let Some(name) = name.flatten() {
+            builder.add_table(Tag::new(b"name"), name);
         }
 
         if let Some(font) = font {
@@ -169,3 +462,1663 @@ impl Compilation {
         Ok(builder)
     }
 }
+
+/// The tables of a font, before they are written to a binary.
+///
+/// This has the same API as [`FontBuilder`] for adding tables, but also lets
+/// us know where each table will be in the final binary.
+#[derive(Clone, Debug, Default)]
+struct FontTables<'a> {
+    tables: BTreeMap<Tag, Cow<'a, [u8]>>,
+}
+
+impl<'a> FontTables<'a> {
+    fn add_table(&mut self, tag: Tag, data: impl Into<Cow<'a, [u8]>>) -> &mut Self {
+        self.tables.insert(tag, data.into());
+        self
+    }
+
+    fn contains(&self, tag: Tag) -> bool {
+        self.tables.contains_key(&tag)
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&Tag, &Cow<'a, [u8]>)> + '_ {
+        self.tables.iter()
+    }
+
+    /// The range of each table in the binary written by [`into_builder`].
+    ///
+    /// `FontBuilder` writes the table directory, followed by the tables in tag
+    /// order, each padded to a multiple of four bytes.
+    ///
+    /// [`into_builder`]: Self::into_builder
+    fn layout(&self) -> HashMap<Tag, Range<usize>> {
+        // sfnt version, four u16 fields, and a 16-byte record for each table
+        let mut position = 12 + 16 * self.tables.len();
+        self.tables
+            .iter()
+            .map(|(tag, data)| {
+                let range = position..position + data.len();
+                position += (data.len() + 3) & !3;
+                (*tag, range)
+            })
+            .collect()
+    }
+
+    fn into_builder(self) -> FontBuilder<'a> {
+        let mut builder = FontBuilder::default();
+        for (tag, data) in self.tables {
+            builder.add_table(tag, data);
+        }
+        builder
+    }
+}
+
+/// The value that the checksum of a complete font must have.
+const FONT_CHECKSUM_MAGIC: u32 = 0xB1B0AFBA;
+
+/// The offset of the `checksumAdjustment` field in the head table.
+const CHECKSUM_ADJUSTMENT_OFFSET: usize = 8;
+
+/// Set `checksumAdjustment` in the head table, so the font's checksum is correct.
+fn set_checksum_adjustment(bytes: &mut [u8], head_offset: usize) {
+    let start = head_offset + CHECKSUM_ADJUSTMENT_OFFSET;
+    let field = start..start + 4;
+    // the adjustment is computed with the field itself set to zero
+    bytes[field.clone()].fill(0);
+    let adjustment = FONT_CHECKSUM_MAGIC.wrapping_sub(checksum(bytes));
+    bytes[field].copy_from_slice(&adjustment.to_be_bytes());
+}
+
+/// The OpenType checksum of some data: the sum of its big-endian u32s.
+///
+/// If the length is not a multiple of four, the data is padded with zeros.
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4)
+        .map(|chunk| {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_be_bytes(word)
+        })
+        .fold(0u32, u32::wrapping_add)
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        read::tables::{
+            gdef::CaretValue,
+            gpos::{self, AnchorTable, PairPos, PositionLookup, SinglePos},
+            gsub::{self, SubstitutionLookup},
+            layout::{self, CoverageTable, DeviceOrVariationIndex},
+            stat::AxisValue,
+        },
+        tables::gpos::ValueFormat,
+        types::{F2Dot14, Fixed},
+    };
+
+    use std::{ffi::OsStr, sync::Arc};
+
+    use super::*;
+    use crate::{
+        compile::{
+            compile_str, error::CompilerError, AxisLocation, AxisMap, Compiler, FontInfo,
+            StatFallbackName, VariationAxis, VerticalMetrics,
+        },
+        parse::SourceLoadError,
+        util::ttx::make_glyph_map,
+        DiagnosticKind, Level,
+    };
+
+    static VERTICAL_FEA: &str = "\
+table vmtx {
+    VertOriginY a 880;
+    VertOriginY b 880;
+    VertOriginY c 870;
+    VertAdvanceY a 1000;
+} vmtx;
+";
+
+    fn read_u16(data: &[u8], offset: usize) -> u16 {
+        u16::from_be_bytes([data[offset], data[offset + 1]])
+    }
+
+    fn read_i16(data: &[u8], offset: usize) -> i16 {
+        read_u16(data, offset) as i16
+    }
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        (read_u16(data, offset) as u32) << 16 | read_u16(data, offset + 2) as u32
+    }
+
+    /// Compile `fea` against the test glyph map, and call `f` with the
+    /// assembled font.
+    fn with_font<R>(fea: &str, opts: Opts, f: impl FnOnce(&FontRef) -> R) -> R {
+        let glyph_map = make_glyph_map();
+        let bytes = compile_str(fea, &glyph_map, opts.clone())
+            .unwrap()
+            .assemble(&glyph_map, opts)
+            .unwrap()
+            .build();
+        f(&FontRef::new(&bytes).unwrap())
+    }
+
+    /// The lookup at `index` in the font's GPOS table.
+    fn gpos_lookup<'a>(font: &FontRef<'a>, index: usize) -> PositionLookup<'a> {
+        let lookup_list = font.gpos().unwrap().lookup_list().unwrap();
+        lookup_list.lookups().get(index).unwrap()
+    }
+
+    /// The lookup at `index` in the font's GSUB table.
+    fn gsub_lookup<'a>(font: &FontRef<'a>, index: usize) -> SubstitutionLookup<'a> {
+        let lookup_list = font.gsub().unwrap().lookup_list().unwrap();
+        lookup_list.lookups().get(index).unwrap()
+    }
+
+    /// The coordinates of a format 1 anchor.
+    fn anchor_coords(anchor: AnchorTable) -> (i16, i16) {
+        match anchor {
+            AnchorTable::Format1(anchor) => (anchor.x_coordinate(), anchor.y_coordinate()),
+            _ => panic!("expected anchor format 1"),
+        }
+    }
+
+    /// Font info with vertical metrics where 'a' has a full metric, and 'b'
+    /// and 'c' share the last advance.
+    fn vertical_font_info(glyph_map: &GlyphMap) -> FontInfo {
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        let n_long = gid("a").to_u16() as usize + 1;
+        FontInfo {
+            vertical_metrics: Some(VerticalMetrics {
+                long_metrics: vec![(900, 20); n_long],
+                top_side_bearings: vec![20; glyph_map.len() - n_long],
+            }),
+            glyph_y_max: [(gid("a"), 700), (gid("b"), 700), (gid("c"), 650)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn vertical_origins_as_vorg() {
+        let glyph_map = make_glyph_map();
+        let opts = Opts::new().font_info(vertical_font_info(&glyph_map));
+        let bytes = compile_str(VERTICAL_FEA, &glyph_map, opts.clone())
+            .unwrap()
+            .assemble(&glyph_map, opts)
+            .unwrap()
+            .build();
+        let font = FontRef::new(&bytes).unwrap();
+
+        let vorg = font.data_for_tag(Tag::new(b"VORG")).unwrap();
+        let vorg: &[u8] = vorg.as_ref();
+        assert_eq!(read_i16(vorg, 4), 880, "default origin");
+        assert_eq!(read_u16(vorg, 6), 1, "one explicit record");
+        assert_eq!(read_u16(vorg, 8), glyph_map.get("c").unwrap().to_u16());
+        assert_eq!(read_i16(vorg, 10), 870);
+
+        // the font's metrics are kept, except for the new advance
+        let a = glyph_map.get("a").unwrap().to_u16() as usize;
+        let vmtx = font.vmtx().unwrap();
+        assert_eq!(vmtx.v_metrics().len(), a + 1);
+        assert_eq!(vmtx.v_metrics()[a].advance(), 1000);
+        assert_eq!(vmtx.v_metrics()[a].side_bearing(), 20);
+        assert_eq!(vmtx.v_metrics()[a - 1].advance(), 900);
+        assert!(vmtx.top_side_bearings().iter().all(|tsb| tsb.get() == 20));
+    }
+
+    #[test]
+    fn vertical_origins_in_vmtx() {
+        let glyph_map = make_glyph_map();
+        let opts = Opts::new()
+            .bake_vertical_origins(true)
+            .font_info(vertical_font_info(&glyph_map));
+        let bytes = compile_str(VERTICAL_FEA, &glyph_map, opts.clone())
+            .unwrap()
+            .assemble(&glyph_map, opts)
+            .unwrap()
+            .build();
+        let font = FontRef::new(&bytes).unwrap();
+        assert!(font.data_for_tag(Tag::new(b"VORG")).is_none());
+
+        // the top side bearing is the origin minus the glyph's yMax
+        let vmtx = font.vmtx().unwrap();
+        let a = glyph_map.get("a").unwrap().to_u16() as usize;
+        assert_eq!(vmtx.v_metrics()[a].advance(), 1000);
+        assert_eq!(vmtx.v_metrics()[a].side_bearing(), 180);
+        let short_tsb = |name: &str| {
+            let gid = glyph_map.get(name).unwrap().to_u16() as usize;
+            vmtx.top_side_bearings()[gid - a - 1].get()
+        };
+        assert_eq!(short_tsb("b"), 180);
+        assert_eq!(short_tsb("c"), 220);
+        assert_eq!(short_tsb("d"), 20);
+    }
+
+    #[test]
+    fn vmtx_requires_font_metrics() {
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(VERTICAL_FEA, &glyph_map, Opts::new()).unwrap();
+        assert_eq!(compilation.warnings.len(), 1, "{:?}", compilation.warnings);
+        assert!(compilation.warnings[0]
+            .text()
+            .contains("font's vertical metrics"));
+        let bytes = compilation
+            .assemble(&glyph_map, Opts::new())
+            .unwrap()
+            .build();
+        let font = FontRef::new(&bytes).unwrap();
+        assert!(font.data_for_tag(Tag::new(b"vmtx")).is_none());
+        assert!(font.data_for_tag(Tag::new(b"VORG")).is_some());
+
+        // 'b' does not have its own advance in the font's vmtx
+        let fea = "table vmtx { VertAdvanceY b 1000; } vmtx;";
+        let opts = Opts::new().font_info(vertical_font_info(&glyph_map));
+        let result = compile_str(fea, &glyph_map, opts);
+        let Err(CompilerError::CompilationFail(errors)) = result else {
+            panic!("expected compilation to fail");
+        };
+        assert!(errors.messages[0]
+            .text()
+            .contains("last vertical advance (900)"));
+    }
+
+    #[test]
+    fn inspect_stat() {
+        let fea = r#"
+table STAT {
+    ElidedFallbackName { name "Regular"; };
+    DesignAxis wght 0 { name "Weight"; };
+    AxisValue {
+        location wght 400;
+        name "Regular";
+        flag ElidableAxisValueName;
+    };
+    AxisValue {
+        location wght 700 600 800;
+        name "Bold";
+    };
+} STAT;
+"#;
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        let stat = compilation.stat().unwrap();
+        match &stat.name {
+            StatFallbackName::Record(names) => assert_eq!(names[0].decoded_string(), "Regular"),
+            other => panic!("unexpected fallback name {other:?}"),
+        }
+        assert_eq!(stat.records.len(), 1);
+        assert_eq!(stat.records[0].tag, Tag::new(b"wght"));
+        assert_eq!(stat.records[0].name[0].decoded_string(), "Weight");
+
+        assert_eq!(stat.values.len(), 2);
+        assert_eq!(stat.values[0].flags, 2);
+        assert!(matches!(
+            stat.values[0].location,
+            AxisLocation::One { value, .. } if value == Fixed::from_f64(400.0)
+        ));
+        assert_eq!(stat.values[1].name[0].decoded_string(), "Bold");
+        assert!(matches!(stat.values[1].location, AxisLocation::Two { .. }));
+    }
+
+    #[test]
+    fn stat_format_2_axis_value() {
+        let fea = r#"
+table STAT {
+    ElidedFallbackName { name "Regular"; };
+    DesignAxis wght 0 { name "Weight"; };
+    AxisValue {
+        location wght 700 600 800;
+        name "Bold";
+    };
+} STAT;
+"#;
+        with_font(fea, Opts::new(), |font| {
+            let stat = font.stat().unwrap();
+            assert_eq!(stat.axis_value_count(), 1);
+            let axis_values = stat.offset_to_axis_values().unwrap().unwrap();
+            let AxisValue::Format2(value) = axis_values.axis_values().get(0).unwrap() else {
+                panic!("expected axis value format 2");
+            };
+            assert_eq!(value.axis_index(), 0);
+            let nominal_min_max = [
+                value.nominal_value(),
+                value.range_min_value(),
+                value.range_max_value(),
+            ];
+            assert_eq!(nominal_min_max.map(Fixed::to_f64), [700.0, 600.0, 800.0]);
+        });
+    }
+
+    #[test]
+    fn contextual_ligatures_are_shared() {
+        let fea = "\
+        feature test {
+            sub x [a b]' [c d]' by f_i;
+            sub y [a b]' [c d]' by f_i;
+        } test;";
+        with_font(fea, Opts::new(), |font| {
+            let lookup_list = font.gsub().unwrap().lookup_list().unwrap();
+            assert_eq!(lookup_list.lookup_count(), 2);
+            let SubstitutionLookup::Ligature(lookup) = gsub_lookup(font, 1) else {
+                panic!("expected ligature lookup");
+            };
+            assert_eq!(lookup.sub_table_count(), 1);
+            let subtable = lookup.subtables().get(0).unwrap();
+            assert_eq!(subtable.ligature_set_count(), 2, "sets for a and b");
+            let lig_set = subtable.ligature_sets().get(0).unwrap();
+            assert_eq!(lig_set.ligature_count(), 2, "ligatures for a c and a d");
+        });
+    }
+
+    #[test]
+    fn large_lookups_use_extension() {
+        let marks = ('A'..='Z')
+            .map(|c| format!("{c}.sc"))
+            .chain(('A'..='N').map(|c| format!("{c}.swash")))
+            .collect::<Vec<_>>();
+        let mut fea = String::new();
+        for (i, mark) in marks.iter().enumerate() {
+            fea.push_str(&format!("markClass {mark} <anchor 0 0> @M{i};\n"));
+        }
+        // each base is about 400 bytes, for about 80k in total
+        fea.push_str("lookup BIG {\n");
+        for cid in 800..1000 {
+            fea.push_str(&format!("pos base \\{cid}"));
+            for i in 0..marks.len() {
+                fea.push_str(&format!(" <anchor {i} 500 contourpoint 1> mark @M{i}"));
+            }
+            fea.push_str(";\n");
+        }
+        fea.push_str("} BIG;\n");
+        fea.push_str("lookup SMALL { pos a b 5; } SMALL;\n");
+        fea.push_str("feature mark { lookup BIG; } mark;\n");
+        fea.push_str("feature kern { lookup SMALL; } kern;\n");
+
+        with_font(&fea, Opts::new(), |font| {
+            let lookup_list = font.gpos().unwrap().lookup_list().unwrap();
+            assert_eq!(lookup_list.lookup_count(), 2);
+            let PositionLookup::Extension(big) = gpos_lookup(font, 0) else {
+                panic!("expected extension lookup");
+            };
+            assert!(matches!(
+                big.subtables().get(0).unwrap(),
+                gpos::ExtensionSubtable::MarkToBase(_)
+            ));
+            assert!(matches!(gpos_lookup(font, 1), PositionLookup::Pair(_)));
+        });
+    }
+
+    #[test]
+    fn large_lookup_with_small_subtables_uses_extension() {
+        let marks = ('A'..='Z')
+            .map(|c| format!("{c}.sc"))
+            .chain(('A'..='N').map(|c| format!("{c}.swash")))
+            .collect::<Vec<_>>();
+        let mut fea = String::new();
+        for (i, mark) in marks.iter().enumerate() {
+            fea.push_str(&format!("markClass {mark} <anchor 0 0> @M{i};\n"));
+        }
+        // one subtable of about 80k, followed by a few tiny ones that would be
+        // out of reach of a 16-bit offset
+        fea.push_str("lookup BIG {\n");
+        for cid in 800..1000 {
+            fea.push_str(&format!("pos base \\{cid}"));
+            for i in 0..marks.len() {
+                fea.push_str(&format!(" <anchor {i} 500 contourpoint 1> mark @M{i}"));
+            }
+            fea.push_str(";\n");
+        }
+        for base in ["a", "b", "c"] {
+            fea.push_str(&format!(
+                "subtable;\npos base {base} <anchor 0 0> mark @M0;\n"
+            ));
+        }
+        fea.push_str("} BIG;\n");
+        fea.push_str("feature mark { lookup BIG; } mark;\n");
+
+        with_font(&fea, Opts::new(), |font| {
+            let PositionLookup::Extension(big) = gpos_lookup(font, 0) else {
+                panic!("expected extension lookup");
+            };
+            assert_eq!(big.sub_table_count(), 4);
+            assert!(big
+                .subtables()
+                .iter()
+                .all(|sub| matches!(sub, Ok(gpos::ExtensionSubtable::MarkToBase(_)))));
+        });
+    }
+
+    #[test]
+    fn large_gsub_lookups_use_extension() {
+        // each sequence is about 400 bytes, for about 80k in total; they are
+        // all different, so that they can't be shared.
+        let mut fea = String::from("lookup BIG {\n");
+        for cid in 800..1000 {
+            fea.push_str(&format!("sub \\{cid} by"));
+            for i in 0..200 {
+                fea.push_str(&format!(" \\{}", 800 + (cid - 800 + i) % 200));
+            }
+            fea.push_str(";\n");
+        }
+        fea.push_str("subtable;\nsub a by b c;\nsubtable;\nsub d by e f;\n");
+        fea.push_str("} BIG;\n");
+        fea.push_str("lookup SMALL { sub f by g h; } SMALL;\n");
+        fea.push_str("feature ccmp { lookup BIG; lookup SMALL; } ccmp;\n");
+
+        with_font(&fea, Opts::new(), |font| {
+            let SubstitutionLookup::Extension(big) = gsub_lookup(font, 0) else {
+                panic!("expected extension lookup");
+            };
+            assert_eq!(big.sub_table_count(), 3);
+            assert!(big
+                .subtables()
+                .iter()
+                .all(|sub| matches!(sub, Ok(gsub::ExtensionSubtable::Multiple(_)))));
+            assert!(matches!(
+                gsub_lookup(font, 1),
+                SubstitutionLookup::Multiple(_)
+            ));
+        });
+    }
+
+    #[test]
+    fn mark_to_base_subtable_break() {
+        let fea = "\
+markClass [acute grave] <anchor 0 500> @TOP;
+feature mark {
+    pos base a <anchor 250 450> mark @TOP;
+    subtable;
+    pos base b <anchor 300 450> mark @TOP;
+} mark;";
+        let glyph_map = make_glyph_map();
+        with_font(fea, Opts::new(), |font| {
+            let PositionLookup::MarkToBase(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected mark-to-base lookup");
+            };
+            assert_eq!(lookup.sub_table_count(), 2, "two subtables");
+
+            let mut mark_arrays = Vec::new();
+            for (subtable, base) in lookup.subtables().iter().zip(["a", "b"]) {
+                let subtable = subtable.unwrap();
+                // each subtable needs every mark that its bases attach to
+                let marks = subtable.mark_coverage().unwrap();
+                assert_eq!(marks.iter().count(), 2, "two marks");
+                let bases = subtable.base_coverage().unwrap().iter().collect::<Vec<_>>();
+                assert_eq!(bases, [glyph_map.get(base).unwrap()]);
+                let mark_array = subtable.mark_array().unwrap();
+                assert_eq!(mark_array.mark_count(), 2, "two mark records");
+                mark_arrays.push(mark_array.offset_data().as_ref().as_ptr());
+            }
+            // identical mark arrays are shared when the table is written
+            assert_eq!(mark_arrays[0], mark_arrays[1]);
+        });
+    }
+
+    #[test]
+    fn head_checksum_adjustment() {
+        let glyph_map = make_glyph_map();
+        let fea = "table head { FontRevision 1.5; } head;";
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        let bytes = compilation.build_binary(&glyph_map, Opts::new()).unwrap();
+        let head = FontRef::new(&bytes).unwrap().head().unwrap();
+        assert_ne!(head.checksum_adjustment(), 0);
+        assert_eq!(checksum(&bytes), FONT_CHECKSUM_MAGIC);
+
+        let opts = Opts::new().skip_checksum_adjustment(true);
+        let bytes = compilation.build_binary(&glyph_map, opts).unwrap();
+        let head = FontRef::new(&bytes).unwrap().head().unwrap();
+        assert_eq!(head.checksum_adjustment(), 0);
+    }
+
+    #[test]
+    fn empty_layout_tables() {
+        let fea = "feature kern { pos a b 20; } kern;";
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+
+        let bytes = compilation
+            .assemble(&glyph_map, Opts::new())
+            .unwrap()
+            .build();
+        let font = FontRef::new(&bytes).unwrap();
+        assert!(font.gpos().is_ok());
+        assert!(font.gsub().is_err());
+
+        let opts = Opts::new().emit_empty_layout_tables(true);
+        let bytes = compilation.assemble(&glyph_map, opts).unwrap().build();
+        let font = FontRef::new(&bytes).unwrap();
+        let gpos = font.gpos().unwrap();
+        assert_eq!(gpos.lookup_list().unwrap().lookup_count(), 1);
+        let gsub = font.gsub().unwrap();
+        assert_eq!(gsub.lookup_list().unwrap().lookup_count(), 0);
+        assert_eq!(gsub.feature_list().unwrap().feature_count(), 0);
+        assert_eq!(gsub.script_list().unwrap().script_count(), 0);
+    }
+
+    #[test]
+    fn table_size_budget() {
+        let fea = "feature kern { pos a b 20; } kern;";
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+
+        let sizes = compilation.table_sizes().unwrap();
+        let gpos_size = sizes[&tags::GPOS];
+        assert!(gpos_size > 0);
+        assert!(!sizes.contains_key(&tags::GSUB));
+
+        let budget = [(tags::GPOS, gpos_size), (tags::GSUB, 0)];
+        assert!(compilation.assert_under_budget(&budget).is_ok());
+        assert_eq!(
+            compilation.assert_under_budget(&[(tags::GPOS, 10)]),
+            Err(vec![(tags::GPOS, gpos_size, 10)])
+        );
+    }
+
+    fn compile_os2(fea: &str, opts: Opts) -> Vec<u8> {
+        let glyph_map = make_glyph_map();
+        compile_str(fea, &glyph_map, opts)
+            .unwrap()
+            .assemble(&glyph_map, Opts::new())
+            .unwrap()
+            .build()
+    }
+
+    #[test]
+    fn os2_minimum_version() {
+        let fea = "table OS/2 { WeightClass 400; CodePageRange 1252; } OS/2;";
+        let bytes = compile_os2(fea, Opts::new());
+        let os2 = FontRef::new(&bytes).unwrap().os2().unwrap();
+        assert_eq!(os2.version(), 1);
+        assert!(os2.sx_height().is_none());
+
+        let fea = "table OS/2 { WeightClass 400; LowerOpSize 80; UpperOpSize 120; } OS/2;";
+        let bytes = compile_os2(fea, Opts::new());
+        let os2 = FontRef::new(&bytes).unwrap().os2().unwrap();
+        assert_eq!(os2.version(), 5);
+        assert_eq!(os2.us_lower_optical_point_size(), Some(80));
+        assert_eq!(os2.us_upper_optical_point_size(), Some(120));
+    }
+
+    #[test]
+    fn os2_requested_version() {
+        let fea = "table OS/2 { WeightClass 400; } OS/2;";
+        let bytes = compile_os2(fea, Opts::new().os2_version(5));
+        let os2 = FontRef::new(&bytes).unwrap().os2().unwrap();
+        assert_eq!(os2.version(), 5);
+
+        let fea = "table OS/2 { LowerOpSize 80; UpperOpSize 120; } OS/2;";
+        let glyph_map = make_glyph_map();
+        assert!(compile_str(fea, &glyph_map, Opts::new().os2_version(1)).is_err());
+
+        // there is no version 6
+        let fea = "table OS/2 { WeightClass 400; } OS/2;";
+        assert!(compile_str(fea, &glyph_map, Opts::new().os2_version(6)).is_err());
+    }
+
+    #[test]
+    fn post_table_formats() {
+        let glyph_map = make_glyph_map();
+        let compilation =
+            compile_str("languagesystem DFLT dflt;", &glyph_map, Opts::new()).unwrap();
+        let post_for_format = |format| {
+            let opts = Opts::new().make_post_table(format);
+            let bytes = compilation.assemble(&glyph_map, opts).unwrap().build();
+            let font = FontRef::new(&bytes).unwrap();
+            font.post()
+                .ok()
+                .map(|post| (post.version(), post.num_glyphs()))
+        };
+
+        assert!(post_for_format(PostFormat::None).is_none());
+        assert_eq!(
+            post_for_format(PostFormat::Format2),
+            Some((Version16Dot16::VERSION_2_0, Some(glyph_map.len() as u16)))
+        );
+        // no glyph names
+        assert_eq!(
+            post_for_format(PostFormat::Format3),
+            Some((Version16Dot16::VERSION_3_0, None))
+        );
+    }
+
+    #[test]
+    fn restrict_tables() {
+        let fea = "\
+        markClass acute <anchor 0 500> @TOP;
+        feature liga { sub f l by f_l; } liga;
+        feature mark { pos base a <anchor 250 450> mark @TOP; } mark;
+        table hhea { Ascender 800; } hhea;";
+        let glyph_map = make_glyph_map();
+        let opts = Opts::new()
+            .tables(&[tags::GPOS])
+            .make_post_table(PostFormat::Format2);
+        let compilation = compile_str(fea, &glyph_map, opts.clone()).unwrap();
+        let bytes = compilation.assemble(&glyph_map, opts).unwrap().build();
+        let font = FontRef::new(&bytes).unwrap();
+        let tables = font
+            .table_directory
+            .table_records()
+            .iter()
+            .map(|record| record.tag())
+            .collect::<Vec<_>>();
+        assert_eq!(tables, [tags::GPOS]);
+    }
+
+    #[test]
+    fn meta_table() {
+        let fea = "\
+        table meta {
+            DesignLanguages \"en-Latn\";
+            SupportedLanguages \"en, ja\";
+        } meta;";
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        let bytes = compilation
+            .assemble(&glyph_map, Opts::new())
+            .unwrap()
+            .build();
+        let font = FontRef::new(&bytes).unwrap();
+        let meta = font.data_for_tag(Tag::new(b"meta")).unwrap();
+        let meta = meta.as_ref();
+
+        assert_eq!(read_u32(meta, 0), 1, "version");
+        assert_eq!(read_u32(meta, 12), 2, "dataMapsCount");
+        assert_eq!(&meta[16..20], b"dlng");
+        assert_eq!(read_u32(meta, 20), 40);
+        assert_eq!(read_u32(meta, 24), 7);
+        assert_eq!(&meta[28..32], b"slng");
+        assert_eq!(&meta[40..], b"en-Latnen, ja");
+
+        let fea = "table meta { DesignLanguages \"en_US\"; } meta;";
+        assert!(compile_str(fea, &glyph_map, Opts::new()).is_err());
+    }
+
+    #[test]
+    fn compact_class_pair_value_format() {
+        let fea = "\
+        @A = [a b];
+        @B = [c d];
+        @C = [e f];
+        feature kern {
+            pos @A @B -20;
+            pos @A @C 30;
+            pos @B @C -5;
+        } kern;";
+        with_font(fea, Opts::new(), |font| {
+            let lookup_list = font.gpos().unwrap().lookup_list().unwrap();
+            assert_eq!(lookup_list.lookup_count(), 1);
+            let PositionLookup::Pair(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected pair pos lookup");
+            };
+            assert_eq!(lookup.sub_table_count(), 1);
+            let PairPos::Format2(subtable) = lookup.subtables().get(0).unwrap() else {
+                panic!("expected class-based pair pos");
+            };
+            // only the x advance is set, so no other fields should be present
+            assert_eq!(subtable.value_format1(), ValueFormat::X_ADVANCE);
+            assert_eq!(subtable.value_format2(), ValueFormat::empty());
+        });
+    }
+
+    #[test]
+    fn identical_anchors_are_shared() {
+        let fea = "\
+        anchorDef 250 450 TOP;
+        markClass acute <anchor 0 500> @MARK_TOP;
+        feature mark {
+            pos base a <anchor TOP> mark @MARK_TOP;
+            pos base b <anchor TOP> mark @MARK_TOP;
+            pos base c <anchor 250 450> mark @MARK_TOP;
+        } mark;";
+        with_font(fea, Opts::new(), |font| {
+            let PositionLookup::MarkToBase(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected mark-to-base lookup");
+            };
+            let subtable = lookup.subtables().get(0).unwrap();
+            let base_array = subtable.base_array().unwrap();
+            assert_eq!(base_array.base_count(), 3, "three bases");
+            // one mark class, so each base record is a single anchor offset
+            let anchors = base_array
+                .base_records()
+                .iter()
+                .map(|record| record.unwrap().base_anchor_offsets()[0].get())
+                .collect::<Vec<_>>();
+            assert!(anchors.iter().all(|offset| *offset == anchors[0]));
+        });
+    }
+
+    #[test]
+    fn compact_pair_pos_chooses_smallest_format() {
+        // the format of each of the first lookup's subtables, with the number
+        // of pairs in each pair set of the glyph pair subtables
+        fn compile_pair_pos(fea: &str, compact: bool) -> Vec<(u16, Vec<u16>)> {
+            let opts = Opts::new().compact_pair_pos(compact);
+            with_font(fea, opts, |font| {
+                let PositionLookup::Pair(lookup) = gpos_lookup(font, 0) else {
+                    panic!("expected pair pos lookup");
+                };
+                lookup
+                    .subtables()
+                    .iter()
+                    .map(|subtable| match subtable.unwrap() {
+                        PairPos::Format1(table) => {
+                            let pair_sets = table.pair_sets().iter();
+                            let counts = pair_sets.map(|x| x.unwrap().pair_value_count());
+                            (1, counts.collect())
+                        }
+                        PairPos::Format2(_) => (2, Vec::new()),
+                    })
+                    .collect()
+            })
+        }
+
+        let sparse = "feature kern { pos a c 5; pos [a b] [c d] -20; } kern;";
+        let subtables = compile_pair_pos(sparse, false);
+        let formats = subtables.iter().map(|(format, _)| *format);
+        assert_eq!(formats.collect::<Vec<_>>(), [1, 2]);
+
+        // the explicit pair a c takes precedence, so is not repeated
+        let subtables = compile_pair_pos(sparse, true);
+        assert_eq!(subtables, [(1, vec![1]), (1, vec![1, 2])]);
+
+        let dense = "feature kern { pos [A-Z] [a-z] -10; } kern;";
+        assert_eq!(compile_pair_pos(dense, true), [(2, vec![])]);
+    }
+
+    #[test]
+    fn pair_exceptions_precede_classes() {
+        // the pair `a c` is an exception to the kerning between the classes,
+        // but it is in a later subtable
+        let fea = "\
+        @LEFT = [a b];
+        @RIGHT = [c d];
+        feature kern {
+            pos @LEFT @RIGHT -20;
+            subtable;
+            pos b e 10;
+            enum pos a @RIGHT 5;
+        } kern;";
+        let glyph_map = make_glyph_map();
+        with_font(fea, Opts::new(), |font| {
+            let lookup_list = font.gpos().unwrap().lookup_list().unwrap();
+            assert_eq!(lookup_list.lookup_count(), 1);
+            let PositionLookup::Pair(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected pair pos lookup");
+            };
+            let formats = lookup.subtables().iter().map(|subtable| match subtable {
+                Ok(PairPos::Format1(_)) => 1,
+                Ok(PairPos::Format2(_)) => 2,
+                Err(e) => panic!("{e}"),
+            });
+            assert_eq!(formats.collect::<Vec<_>>(), [1, 2], "glyph pairs first");
+
+            // the glyph pairs are in a single subtable, with both pair sets
+            let PairPos::Format1(subtable) = lookup.subtables().get(0).unwrap() else {
+                panic!("expected glyph pairs");
+            };
+            assert_eq!(subtable.pair_set_count(), 2, "two pair sets");
+            let pair_set_a = subtable.pair_sets().get(0).unwrap();
+            assert_eq!(pair_set_a.pair_value_count(), 2, "a c, a d");
+            let pair = pair_set_a.pair_value_records().get(0).unwrap();
+            assert_eq!(pair.second_glyph(), glyph_map.get("c").unwrap());
+            // the value of the exception, not of the classes
+            assert_eq!(pair.value_record1().x_advance(), Some(5));
+        });
+    }
+
+    #[test]
+    fn automatic_subtable_breaks() {
+        // each rule adds a pair set of 100 pairs, about 1KiB
+        let glyph_map = (0..400_u16).collect::<GlyphMap>();
+        let mut fea = String::from("feature kern {\n");
+        for i in 1..=70 {
+            fea.push_str(&format!("    enum pos \\{i} [\\200-\\299] <1 2 3 4>;\n"));
+        }
+        fea.push_str("} kern;");
+
+        let compilation = compile_str(&fea, &glyph_map, Opts::new()).unwrap();
+        assert_eq!(compilation.warnings.len(), 1);
+        let warning = compilation.warnings[0].text();
+        assert!(warning.starts_with("subtable is too large"), "{warning}");
+        let bytes = compilation
+            .assemble(&glyph_map, Opts::new())
+            .unwrap()
+            .build();
+        let font = FontRef::new(&bytes).unwrap();
+        let lookup_list = font.gpos().unwrap().lookup_list().unwrap();
+        assert_eq!(lookup_list.lookup_count(), 1, "one lookup");
+        let PositionLookup::Pair(lookup) = gpos_lookup(&font, 0) else {
+            panic!("expected a pair lookup");
+        };
+        assert_eq!(lookup.sub_table_count(), 2, "two subtables");
+    }
+
+    #[test]
+    fn no_automatic_subtable_breaks_between_class_pairs() {
+        // about 80KiB of class pair records; breaking these between rules
+        // would change which pairs are matched.
+        let glyph_map = (0..400_u16).collect::<GlyphMap>();
+        let mut fea = String::from("feature kern {\n");
+        for i in 1..100 {
+            let (first, second) = ((i, 100 + i), (200 + i, 300 + i));
+            fea.push_str(&format!(
+                "    pos [\\{} \\{}] [\\{} \\{}] <1 2 3 4>;\n",
+                first.0, first.1, second.0, second.1
+            ));
+        }
+        fea.push_str("} kern;");
+
+        let compilation = compile_str(&fea, &glyph_map, Opts::new()).unwrap();
+        let warnings = &compilation.warnings;
+        assert!(warnings.is_empty(), "{warnings:?}");
+    }
+
+    #[test]
+    fn use_extension() {
+        let fea = "\
+        lookup SUB useExtension { sub a by b; } SUB;
+        lookup POS useExtension { pos a 10; } POS;
+        lookup PLAIN { pos b 10; } PLAIN;
+        feature test {
+            lookup SUB;
+            lookup POS;
+            lookup PLAIN;
+        } test;";
+        with_font(fea, Opts::new(), |font| {
+            let gsub_lookups = font.gsub().unwrap().lookup_list().unwrap();
+            assert_eq!(gsub_lookups.lookup_count(), 1);
+            let SubstitutionLookup::Extension(lookup) = gsub_lookup(font, 0) else {
+                panic!("expected extension lookup");
+            };
+            assert!(matches!(
+                lookup.subtables().get(0).unwrap(),
+                gsub::ExtensionSubtable::Single(_)
+            ));
+
+            let gpos_lookups = font.gpos().unwrap().lookup_list().unwrap();
+            assert_eq!(gpos_lookups.lookup_count(), 2);
+            let PositionLookup::Extension(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected extension lookup");
+            };
+            assert!(matches!(
+                lookup.subtables().get(0).unwrap(),
+                gpos::ExtensionSubtable::Single(_)
+            ));
+            assert!(matches!(gpos_lookup(font, 1), PositionLookup::Single(_)));
+        });
+    }
+
+    #[test]
+    fn ligature_sets_grouped_longest_first() {
+        let fea = "\
+        feature liga {
+            sub f i by f_i;
+            sub f l by f_l;
+            sub f f i by f_f_i;
+            sub f f by f_f;
+        } liga;";
+        let ligatures = with_font(fea, Opts::new(), |font| {
+            let lookup_list = font.gsub().unwrap().lookup_list().unwrap();
+            assert_eq!(lookup_list.lookup_count(), 1);
+            let SubstitutionLookup::Ligature(lookup) = gsub_lookup(font, 0) else {
+                panic!("expected ligature lookup");
+            };
+            assert_eq!(lookup.sub_table_count(), 1);
+            let subtable = lookup.subtables().get(0).unwrap();
+            // all ligatures start with 'f', so there is a single ligature set
+            assert_eq!(subtable.ligature_set_count(), 1);
+            let lig_set = subtable.ligature_sets().get(0).unwrap();
+            lig_set
+                .ligatures()
+                .iter()
+                .map(|lig| {
+                    let lig = lig.unwrap();
+                    let components = lig.component_glyph_ids().iter().map(|gid| gid.get());
+                    (lig.ligature_glyph(), components.collect::<Vec<_>>())
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let glyph_map = make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        // longest first, then ordered by components
+        assert_eq!(
+            ligatures,
+            [
+                (gid("f_f_i"), vec![gid("f"), gid("i")]),
+                (gid("f_f"), vec![gid("f")]),
+                (gid("f_i"), vec![gid("i")]),
+                (gid("f_l"), vec![gid("l")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn size_feature_params() {
+        let fea = "\
+        languagesystem DFLT dflt;
+        languagesystem latn dflt;
+        languagesystem latn TRK;
+        feature size {
+            parameters 10.0 3 80 139;
+            sizemenuname \"Small\";
+        } size;";
+        with_font(fea, Opts::new(), |font| {
+            let gpos = font.gpos().unwrap();
+            let feature_list = gpos.feature_list().unwrap();
+            let size_features = feature_list
+                .feature_records()
+                .iter()
+                .enumerate()
+                .filter(|(_, record)| record.feature_tag() == tags::SIZE)
+                .map(|(i, record)| (i as u16, record))
+                .collect::<Vec<_>>();
+            assert!(!size_features.is_empty());
+
+            // every language system should reference a size feature
+            let script_list = gpos.script_list().unwrap();
+            let mut lang_systems = Vec::new();
+            for script_record in script_list.script_records() {
+                let script = script_record.script(script_list.offset_data()).unwrap();
+                lang_systems.extend(script.default_lang_sys().map(Result::unwrap));
+                for record in script.lang_sys_records() {
+                    lang_systems.push(record.lang_sys(script.offset_data()).unwrap());
+                }
+            }
+            assert_eq!(lang_systems.len(), 3);
+            for lang_sys in lang_systems {
+                assert!(lang_sys
+                    .feature_indices()
+                    .iter()
+                    .any(|idx| size_features.iter().any(|(i, _)| *i == idx.get())));
+            }
+
+            for (_, record) in size_features {
+                let feature = record.feature(feature_list.offset_data()).unwrap();
+                assert_eq!(feature.lookup_index_count(), 0, "no lookups");
+                let Some(Ok(layout::FeatureParams::Size(params))) = feature.feature_params() else {
+                    panic!("size feature must have params");
+                };
+                assert_eq!(params.design_size(), 100, "design size in decipoints");
+                assert_eq!(params.identifier(), 3, "subfamily identifier");
+                assert!(params.name_entry() >= 256, "subfamily name id");
+                assert_eq!(params.range_start(), 80);
+                assert_eq!(params.range_end(), 139);
+            }
+        });
+    }
+
+    #[test]
+    fn character_variant_params() {
+        let fea = "\
+        feature cv01 {
+            cvParameters {
+                FeatUILabelNameID { name \"Label\"; };
+                FeatUITooltipTextNameID { name \"Tooltip\"; };
+                SampleTextNameID { name \"Sample\"; };
+                ParamUILabelNameID { name \"First\"; };
+                ParamUILabelNameID { name \"Second\"; };
+                Character 0x61;
+                Character 0x1F600;
+            };
+            sub a by b;
+        } cv01;";
+        with_font(fea, Opts::new(), |font| {
+            let feature_list = font.gsub().unwrap().feature_list().unwrap();
+            assert_eq!(feature_list.feature_count(), 1);
+            let record = &feature_list.feature_records()[0];
+            assert_eq!(record.feature_tag(), Tag::new(b"cv01"));
+            let feature = record.feature(feature_list.offset_data()).unwrap();
+            let params = feature.feature_params();
+            let Some(Ok(layout::FeatureParams::CharacterVariant(params))) = params else {
+                panic!("expected character variant params");
+            };
+            assert_eq!(params.format(), 0);
+            assert_eq!(params.num_named_parameters(), 2);
+            let characters = params.character().iter().map(|c| c.get().to_u32());
+            assert_eq!(characters.collect::<Vec<_>>(), [0x61, 0x1F600]);
+
+            // each name is written, in order, with the ids of the params following the first
+            let first_param = params.first_param_ui_label_name_id();
+            let name_ids = [
+                params.feat_ui_label_name_id(),
+                params.feat_ui_tooltip_text_name_id(),
+                params.sample_text_name_id(),
+                first_param,
+                first_param + 1,
+            ];
+            assert_eq!(name_ids, [256, 257, 258, 259, 260]);
+            let name = font.name().unwrap();
+            let strings = name_ids.map(|id| {
+                let mut records = name.name_record().iter();
+                let record = records.find(|record| record.name_id() == id).unwrap();
+                record.string(name.string_data()).unwrap().to_string()
+            });
+            assert_eq!(strings, ["Label", "Tooltip", "Sample", "First", "Second"]);
+        });
+    }
+
+    #[test]
+    fn mark_class_anchors_per_statement() {
+        let fea = "\
+        markClass [acute grave] <anchor 100 500> @TOP;
+        markClass dieresis <anchor 200 600> @TOP;
+        feature mark {
+            pos base a <anchor 250 450> mark @TOP;
+        } mark;";
+        let anchors = with_font(fea, Opts::new(), |font| {
+            let PositionLookup::MarkToBase(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected mark-to-base lookup");
+            };
+            let subtable = lookup.subtables().get(0).unwrap();
+            assert_eq!(subtable.mark_class_count(), 1);
+
+            let coverage = subtable.mark_coverage().unwrap();
+            assert!(matches!(coverage, CoverageTable::Format1(_)));
+            let mark_array = subtable.mark_array().unwrap();
+            assert_eq!(coverage.iter().count(), mark_array.mark_count() as usize);
+            coverage
+                .iter()
+                .zip(mark_array.mark_records())
+                .map(|(glyph, record)| {
+                    assert_eq!(record.mark_class(), 0, "mark class");
+                    let anchor = record.mark_anchor(mark_array.offset_data()).unwrap();
+                    (glyph, anchor_coords(anchor))
+                })
+                .collect::<BTreeMap<_, _>>()
+        });
+
+        let glyph_map = make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        assert_eq!(
+            anchors,
+            BTreeMap::from([
+                (gid("acute"), (100, 500)),
+                (gid("grave"), (100, 500)),
+                (gid("dieresis"), (200, 600)),
+            ])
+        );
+    }
+
+    #[test]
+    fn vertical_pair_pos_uses_y_advance() {
+        let fea = "\
+        feature vkrn {
+            pos a <0 0 0 -50> b <0 0 0 0>;
+        } vkrn;";
+        let glyph_map = make_glyph_map();
+        with_font(fea, Opts::new(), |font| {
+            let PositionLookup::Pair(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected pair pos lookup");
+            };
+            let PairPos::Format1(subtable) = lookup.subtables().get(0).unwrap() else {
+                panic!("expected glyph pairs");
+            };
+            // both records use y-advance, including the all-zero second record
+            assert_eq!(subtable.value_format1(), ValueFormat::Y_ADVANCE);
+            assert_eq!(subtable.value_format2(), ValueFormat::Y_ADVANCE);
+
+            assert_eq!(subtable.pair_set_count(), 1, "one pair set");
+            let pair_set = subtable.pair_sets().get(0).unwrap();
+            assert_eq!(pair_set.pair_value_count(), 1, "one pair");
+            let pair = pair_set.pair_value_records().get(0).unwrap();
+            assert_eq!(pair.second_glyph(), glyph_map.get("b").unwrap());
+            assert_eq!(pair.value_record1().y_advance(), Some(-50));
+            assert_eq!(pair.value_record2().y_advance(), Some(0));
+        });
+    }
+
+    /// Compile with a single `wght` axis, with a default value of 400.
+    fn compile_with_wght_axis(fea: &'static str) -> Result<Vec<u8>, CompilerError> {
+        let resolver = move |_: &OsStr| -> Result<Arc<str>, SourceLoadError> { Ok(fea.into()) };
+        let glyph_map = make_glyph_map();
+        let axes = AxisMap::new([VariationAxis::new(Tag::new(b"wght"), 100., 400., 900.)]);
+        Compiler::new("test.fea", &glyph_map)
+            .with_resolver(resolver)
+            .with_axis_map(axes)
+            .compile_binary()
+    }
+
+    #[test]
+    fn variable_metrics() {
+        let fea = "feature kern { pos a (wght=100:-10 wght=400:-30 wght=900:-60); } kern;";
+        // without an axis map, this fails validation
+        let glyph_map = make_glyph_map();
+        assert!(compile_str(fea, &glyph_map, Opts::new()).is_err());
+
+        let bytes = compile_with_wght_axis(fea).unwrap();
+        let font = FontRef::new(&bytes).unwrap();
+        let gdef = font.gdef().unwrap();
+        assert_eq!(gdef.version().minor, 3);
+        let store = gdef.item_var_store().unwrap().unwrap();
+        let regions = store.variation_region_list().unwrap().variation_regions();
+        let regions = regions
+            .iter()
+            .map(|region| {
+                let axis = &region.unwrap().region_axes()[0];
+                [axis.start_coord(), axis.peak_coord(), axis.end_coord()].map(F2Dot14::to_f32)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(regions, [[-1.0, -1.0, 0.0], [0.0, 1.0, 1.0]]);
+        let data = store
+            .item_variation_datas()
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(data.item_count(), 1);
+        // the deltas from the default value of -30, as bytes
+        assert_eq!(data.delta_sets(), [20, (-30i8) as u8]);
+
+        let PositionLookup::Single(lookup) = gpos_lookup(&font, 0) else {
+            panic!("expected single pos lookup");
+        };
+        let SinglePos::Format1(subtable) = lookup.subtables().get(0).unwrap() else {
+            panic!("expected single pos format 1");
+        };
+        let record = subtable.value_record();
+        assert_eq!(record.x_advance(), Some(-30));
+        let device = record.x_advance_device(subtable.offset_data());
+        let Some(Ok(DeviceOrVariationIndex::VariationIndex(index))) = device else {
+            panic!("expected a variation index");
+        };
+        assert_eq!(
+            (index.delta_set_outer_index(), index.delta_set_inner_index()),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    fn variable_metric_without_variation() {
+        let fea = "feature kern { pos a <(wght=100:5 wght=900:5 wght=400:5) 0 0 0>; } kern;";
+        let bytes = compile_with_wght_axis(fea).unwrap();
+        let font = FontRef::new(&bytes).unwrap();
+        // there is nothing to store, so there is no GDEF
+        assert!(font.data_for_tag(tags::GDEF).is_none());
+        let PositionLookup::Single(lookup) = gpos_lookup(&font, 0) else {
+            panic!("expected single pos lookup");
+        };
+        let SinglePos::Format1(subtable) = lookup.subtables().get(0).unwrap() else {
+            panic!("expected single pos format 1");
+        };
+        assert_eq!(subtable.value_format(), ValueFormat::X_PLACEMENT);
+        assert_eq!(subtable.value_record().x_placement(), Some(5));
+    }
+
+    #[test]
+    fn variable_metric_with_device() {
+        let fea = "\
+        feature kern {
+            pos a <0 0 (wght=400:0 wght=900:10) 0
+                <device NULL> <device NULL> <device 11 -1> <device NULL>>;
+        } kern;";
+        let Err(CompilerError::ValidationFail(errors)) = compile_with_wght_axis(fea) else {
+            panic!("expected validation to fail");
+        };
+        assert_eq!(errors.messages.len(), 1);
+        assert_eq!(errors.messages[0].kind(), DiagnosticKind::InvalidStatement);
+    }
+
+    #[test]
+    fn variable_anchors() {
+        let fea = "\
+        anchorDef (wght=100:110 wght=400:120 wght=900:130) 500 TOP;
+        markClass acute <anchor TOP> @TOP;
+        feature mark {
+            pos base a <anchor (wght=100:200 wght=400:250 wght=900:300) 450> mark @TOP;
+        } mark;";
+        /// The coordinates of a format 3 anchor, and the index of its x deltas.
+        fn variable_anchor(anchor: AnchorTable) -> (i16, i16, (u16, u16)) {
+            let AnchorTable::Format3(anchor) = anchor else {
+                panic!("expected anchor format 3");
+            };
+            assert!(anchor.y_device().is_none());
+            let Some(Ok(DeviceOrVariationIndex::VariationIndex(index))) = anchor.x_device() else {
+                panic!("expected a variation index");
+            };
+            let index = (index.delta_set_outer_index(), index.delta_set_inner_index());
+            (anchor.x_coordinate(), anchor.y_coordinate(), index)
+        }
+
+        let bytes = compile_with_wght_axis(fea).unwrap();
+        let font = FontRef::new(&bytes).unwrap();
+        let store = font.gdef().unwrap().item_var_store().unwrap().unwrap();
+        let data = store
+            .item_variation_datas()
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        // the deltas of the base anchor sort first
+        assert_eq!(data.delta_sets(), [-50i8, 50, -10, 10].map(|x| x as u8));
+
+        let PositionLookup::MarkToBase(lookup) = gpos_lookup(&font, 0) else {
+            panic!("expected mark-to-base lookup");
+        };
+        let subtable = lookup.subtables().get(0).unwrap();
+        let mark_array = subtable.mark_array().unwrap();
+        let mark_anchor = mark_array.mark_records()[0]
+            .mark_anchor(mark_array.offset_data())
+            .unwrap();
+        assert_eq!(variable_anchor(mark_anchor), (120, 500, (0, 1)));
+
+        let base_array = subtable.base_array().unwrap();
+        let base_record = base_array.base_records().get(0).unwrap();
+        let base_anchor = base_record
+            .base_anchors(base_array.offset_data())
+            .next()
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(variable_anchor(base_anchor), (250, 450, (0, 0)));
+    }
+
+    #[test]
+    fn variable_anchor_errors() {
+        let fea = "\
+        markClass acute <anchor 0 500> @TOP;
+        feature mark {
+            pos base a <anchor (wght=400:250 wght=900:300) 450 contourpoint 2> mark @TOP;
+            pos base b <anchor (wght=400:250 wght=900:300) 450
+                <device 11 -1> <device NULL>> mark @TOP;
+        } mark;";
+        let Err(CompilerError::ValidationFail(errors)) = compile_with_wght_axis(fea) else {
+            panic!("expected validation to fail");
+        };
+        let kinds = errors.messages.iter().map(|x| x.kind()).collect::<Vec<_>>();
+        assert_eq!(kinds, [DiagnosticKind::InvalidStatement; 2]);
+    }
+
+    #[test]
+    fn gdef_only() {
+        let fea = "\
+        @BASE = [a b];
+        @LIGA = [f_i];
+        @MARK = [acute];
+        markClass grave <anchor 0 0> @TOP;
+        table GDEF {
+            GlyphClassDef @BASE, @LIGA, @MARK, ;
+            LigatureCaretByPos f_i 300;
+        } GDEF;";
+        let glyph_map = make_glyph_map();
+        with_font(fea, Opts::new(), |font| {
+            assert!(font.data_for_tag(tags::GSUB).is_none());
+            assert!(font.data_for_tag(tags::GPOS).is_none());
+            let gdef = font.gdef().unwrap();
+
+            let class_def = gdef.glyph_class_def().unwrap().unwrap();
+            let class = |name| class_def.get(glyph_map.get(name).unwrap());
+            assert_eq!(class("a"), 1);
+            assert_eq!(class("b"), 1);
+            assert_eq!(class("f_i"), 2);
+            assert_eq!(class("acute"), 3);
+            // the explicit classes are not combined with inferred mark classes
+            assert_eq!(class("grave"), 0);
+            assert_eq!(class("c"), 0);
+
+            let lig_caret_list = gdef.lig_caret_list().unwrap().unwrap();
+            assert_eq!(lig_caret_list.lig_glyph_count(), 1, "one ligature");
+            let lig_glyph = lig_caret_list.lig_glyphs().get(0).unwrap();
+            assert_eq!(lig_glyph.caret_count(), 1, "one caret");
+            let CaretValue::Format1(caret) = lig_glyph.caret_values().get(0).unwrap() else {
+                panic!("expected caret format 1");
+            };
+            assert_eq!(caret.coordinate(), 300);
+        });
+    }
+
+    #[test]
+    fn named_lookup_indices() {
+        let fea = "\
+        feature aalt {
+            feature salt;
+        } aalt;
+        lookup A { sub a by b; } A;
+        feature salt { sub c by d; } salt;
+        lookup K { pos a b 5; } K;
+        feature kern { lookup K; } kern;";
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        // the aalt lookup is inserted first, so 'A' is moved back
+        assert_eq!(
+            compilation.named_lookup_indices(),
+            HashMap::from([
+                ("A".into(), LookupId::Gsub(1)),
+                ("K".into(), LookupId::Gpos(0)),
+            ])
+        );
+    }
+
+    #[test]
+    fn glyph_class_membership() {
+        let fea = "\
+        @LC = [a - c];
+        @ALL = [@LC A.sc];
+        feature liga {
+            @LOCAL = [f i];
+            sub @LOCAL by f_i;
+        } liga;";
+        let glyph_map = make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        assert_eq!(
+            compilation.glyph_class("@ALL"),
+            Some(["a", "b", "c", "A.sc"].map(gid).as_slice())
+        );
+        assert!(compilation.glyph_class_contains("@LC", gid("b")));
+        assert!(!compilation.glyph_class_contains("@LC", gid("A.sc")));
+        assert!(compilation.glyph_class_contains("@LOCAL", gid("i")));
+        assert!(!compilation.glyph_class_contains("@NOPE", gid("a")));
+        assert!(compilation.glyph_class("LC").is_none());
+    }
+
+    #[test]
+    fn skip_missing_class_glyphs() {
+        let fea = "\
+        @LETTERS = [a nope b];
+        feature test {
+            sub @LETTERS by c;
+            sub [d missing] by e;
+        } test;";
+        let glyph_map = make_glyph_map();
+        let gid = |name: &str| glyph_map.get(name).unwrap();
+
+        let Err(CompilerError::ValidationFail(errors)) = compile_str(fea, &glyph_map, Opts::new())
+        else {
+            panic!("missing glyphs should be errors by default");
+        };
+        assert_eq!(errors.messages.len(), 2);
+
+        let opts = Opts::new().skip_missing_class_glyphs(true);
+        let compilation = compile_str(fea, &glyph_map, opts).unwrap();
+        assert_eq!(compilation.warnings.len(), 2);
+        for warning in &compilation.warnings {
+            assert_eq!(warning.level, Level::Warning);
+            assert_eq!(warning.kind(), DiagnosticKind::UndefinedGlyph);
+            assert!(warning
+                .text()
+                .starts_with("glyph not in font, and will be removed from the class"));
+        }
+        assert_eq!(
+            compilation.glyph_class("@LETTERS"),
+            Some([gid("a"), gid("b")].as_slice())
+        );
+
+        // glyphs outside of a class are still errors
+        let fea = "feature test { sub nope by a; } test;";
+        let opts = Opts::new().skip_missing_class_glyphs(true);
+        assert!(compile_str(fea, &glyph_map, opts).is_err());
+
+        // as are glyphs in a class that is matched glyph-by-glyph, since
+        // removing them would pair 'b' with 'd'
+        let fea = "\
+        @FROM = [a b nope];
+        feature test {
+            sub @FROM by [c nope d];
+            sub [a b nope] by [c nope d];
+            sub [a b] by [c d];
+        } test;";
+        let opts = Opts::new().skip_missing_class_glyphs(true);
+        let errors = match compile_str(fea, &glyph_map, opts) {
+            Err(CompilerError::CompilationFail(errors)) => errors,
+            _ => panic!("removing glyphs from these classes should be an error"),
+        };
+        let errors = errors
+            .messages
+            .iter()
+            .filter(|x| x.is_error())
+            .map(|x| &fea[x.span()])
+            .collect::<Vec<_>>();
+        assert_eq!(errors, ["@FROM", "[a b nope]"]);
+    }
+
+    #[test]
+    fn describe() {
+        let fea = "\
+        languagesystem DFLT dflt;
+        languagesystem latn dflt;
+        feature kern {
+            pos a b 5;
+            pos a 10;
+        } kern;
+        feature liga {
+            sub f l by f_l;
+            script latn;
+            language TRK required;
+            sub a by b;
+        } liga;";
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        assert_eq!(
+            compilation.describe(),
+            "\
+kern
+  languages: DFLT/dflt, latn/dflt
+  lookups: 1 pair adjustment, 1 single adjustment
+liga
+  languages: DFLT/dflt, latn/TRK (required), latn/dflt
+  lookups: 1 ligature substitution, 1 single substitution
+"
+        );
+    }
+
+    #[test]
+    fn describe_with_names() {
+        let fea = "\
+        feature smcp {
+            sub a by A.sc;
+        } smcp;
+        feature ss02 {
+            sub a by b;
+        } ss02;
+        feature liga {
+            sub f l by f_l;
+        } liga;
+        feature ZZZZ {
+            sub b by c;
+        } ZZZZ;
+        feature YYYY {
+            sub c by d;
+        } YYYY;";
+        let glyph_map = make_glyph_map();
+        let compilation = compile_str(fea, &glyph_map, Opts::new()).unwrap();
+        let custom = HashMap::from([
+            (Tag::new(b"ZZZZ"), "Private Thing".to_string()),
+            (Tag::new(b"liga"), "Ligatures".to_string()),
+        ]);
+        let description = compilation.describe_with_names(&custom);
+        let headers = description
+            .lines()
+            .filter(|line| !line.starts_with(' '))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            headers,
+            [
+                "YYYY",
+                "ZZZZ (Private Thing)",
+                "liga (Ligatures)",
+                "smcp (Small Capitals)",
+                "ss02 (Stylistic Set 2)",
+            ]
+        );
+        // the plain description is unchanged
+        assert!(compilation.describe().starts_with("YYYY\n"));
+        assert!(compilation.describe().contains("\nsmcp\n"));
+    }
+
+    #[test]
+    fn feature_variations() {
+        let fea = "\
+        conditionset heavy {
+            wght 700 900;
+        } heavy;
+        feature rlig {
+            sub a by b;
+        } rlig;
+        variation rlig heavy {
+            sub a by c;
+        } rlig;
+        variation rvrn heavy {
+            sub b by c;
+        } rvrn;";
+        let bytes = compile_with_wght_axis(fea).unwrap();
+        let font = FontRef::new(&bytes).unwrap();
+        let gsub = font.gsub().unwrap();
+        assert_eq!(gsub.version().minor, 1);
+        let lookups = |feature: layout::Feature| {
+            let indices = feature.lookup_list_indices().iter();
+            indices.map(|idx| idx.get()).collect::<Vec<_>>()
+        };
+
+        // 'rlig' has a default feature, and 'rvrn' gets an empty one
+        let feature_list = gsub.feature_list().unwrap();
+        let features = feature_list
+            .feature_records()
+            .iter()
+            .map(|record| {
+                let feature = record.feature(feature_list.offset_data()).unwrap();
+                (record.feature_tag(), lookups(feature))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            features,
+            [(Tag::new(b"rlig"), vec![0]), (Tag::new(b"rvrn"), vec![])]
+        );
+
+        let variations = gsub.feature_variations().unwrap().unwrap();
+        assert_eq!(variations.feature_variation_record_count(), 1);
+        let record = &variations.feature_variation_records()[0];
+        let condition_set = record
+            .condition_set(variations.offset_data())
+            .unwrap()
+            .unwrap();
+        assert_eq!(condition_set.condition_count(), 1);
+        let condition = condition_set.conditions().get(0).unwrap();
+        // the normalized range of 700..=900
+        assert_eq!(condition.format(), 1);
+        assert_eq!(condition.axis_index(), 0);
+        assert_eq!(condition.filter_range_min_value(), F2Dot14::from_f32(0.6));
+        assert_eq!(condition.filter_range_max_value(), F2Dot14::from_f32(1.0));
+
+        let substitution = record
+            .feature_table_substitution(variations.offset_data())
+            .unwrap()
+            .unwrap();
+        let alternates = substitution
+            .substitutions()
+            .iter()
+            .map(|record| {
+                let feature = record.alternate_feature(substitution.offset_data());
+                (record.feature_index(), lookups(feature.unwrap()))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(alternates, [(0, vec![1]), (1, vec![2])]);
+    }
+
+    #[test]
+    fn named_devices_are_shared() {
+        let fea = "\
+        deviceDef <device 11 -1, 12 -1> KERN_FIX;
+        feature kern {
+            pos a <0 0 -10 0 <device NULL> <device NULL> <device KERN_FIX> <device NULL>>;
+            pos b <0 0 -20 0 <device NULL> <device NULL> <device KERN_FIX> <device NULL>>;
+        } kern;";
+        with_font(fea, Opts::new(), |font| {
+            let PositionLookup::Single(lookup) = gpos_lookup(font, 0) else {
+                panic!("expected single pos lookup");
+            };
+            assert_eq!(lookup.sub_table_count(), 1);
+            let SinglePos::Format2(subtable) = lookup.subtables().get(0).unwrap() else {
+                panic!("expected single pos format 2");
+            };
+            assert!(subtable
+                .value_format()
+                .contains(ValueFormat::X_ADVANCE_DEVICE));
+
+            let records = subtable.value_records();
+            let (first, second) = (records.get(0).unwrap(), records.get(1).unwrap());
+            assert_eq!(first.x_advance_device.get(), second.x_advance_device.get());
+            let device = first.x_advance_device(subtable.offset_data());
+            let Some(Ok(DeviceOrVariationIndex::Device(device))) = device else {
+                panic!("expected a device table");
+            };
+            assert_eq!(device.start_size(), 11);
+            assert_eq!(device.end_size(), 12);
+        });
+    }
+
+    #[test]
+    fn snap_value_records() {
+        let fea = "\
+        feature kern {
+            pos a <-37 3 -12 8 <device NULL> <device NULL> <device 11 -1> <device NULL>>;
+        } kern;";
+        let compile_single_pos = |opts: Opts| {
+            with_font(fea, opts, |font| {
+                let PositionLookup::Single(lookup) = gpos_lookup(font, 0) else {
+                    panic!("expected single pos lookup");
+                };
+                let SinglePos::Format1(subtable) = lookup.subtables().get(0).unwrap() else {
+                    panic!("expected single pos format 1");
+                };
+                let value_format = ValueFormat::X_PLACEMENT
+                    | ValueFormat::Y_PLACEMENT
+                    | ValueFormat::X_ADVANCE
+                    | ValueFormat::Y_ADVANCE
+                    | ValueFormat::X_ADVANCE_DEVICE;
+                assert_eq!(subtable.value_format(), value_format);
+                let record = subtable.value_record();
+                let values = [
+                    record.x_placement(),
+                    record.y_placement(),
+                    record.x_advance(),
+                    record.y_advance(),
+                ];
+                let device = record.x_advance_device(subtable.offset_data());
+                let Some(Ok(DeviceOrVariationIndex::Device(device))) = device else {
+                    panic!("expected a device table");
+                };
+                let device = (device.start_size(), device.end_size());
+                (values.map(Option::unwrap), device)
+            })
+        };
+
+        let (values, device) = compile_single_pos(Opts::new());
+        assert_eq!(values, [-37, 3, -12, 8]);
+        assert_eq!(device, (11, 11));
+
+        let (values, device) = compile_single_pos(Opts::new().snap_value_records(5));
+        assert_eq!(values, [-35, 5, -10, 10]);
+        assert_eq!(device, (11, 11), "device tables are unchanged");
+    }
+
+    #[test]
+    fn snap_extreme_value_records() {
+        let fea = "feature kern { pos a <-32768 -32767 32767 -3>; } kern;";
+        let compile_single_pos = |grid| {
+            with_font(fea, Opts::new().snap_value_records(grid), |font| {
+                let PositionLookup::Single(lookup) = gpos_lookup(font, 0) else {
+                    panic!("expected single pos lookup");
+                };
+                let SinglePos::Format1(subtable) = lookup.subtables().get(0).unwrap() else {
+                    panic!("expected single pos format 1");
+                };
+                let record = subtable.value_record();
+                [
+                    record.x_placement(),
+                    record.y_placement(),
+                    record.x_advance(),
+                    record.y_advance(),
+                ]
+                .map(Option::unwrap_or_default)
+            })
+        };
+
+        assert_eq!(compile_single_pos(5), [-32765, -32765, 32765, -5]);
+        // the nearest multiples, -33000 and 33000, are out of range
+        assert_eq!(compile_single_pos(1000), [-32000, -32000, 32000, 0]);
+        assert_eq!(compile_single_pos(u16::MAX), [0, 0, 0, 0]);
+    }
+}
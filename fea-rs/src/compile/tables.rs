@@ -15,6 +15,7 @@ use write_fonts::{
             MarkGlyphSets,
         },
         layout::{ClassDef, ClassDefBuilder, CoverageTableBuilder},
+        variations::ItemVariationStore,
     },
     types::{Fixed, LongDateTime, Tag, Uint24},
     validate::ValidationReport,
@@ -22,7 +23,11 @@ use write_fonts::{
 
 use crate::{
     common::{GlyphClass, GlyphId},
-    compile::tags::{MAC_PLATFORM_ID, WIN_PLATFORM_ID},
+    compile::{
+        error::BinaryCompilationError,
+        tags::{MAC_PLATFORM_ID, WIN_PLATFORM_ID},
+        VerticalMetrics,
+    },
 };
 
 /// The explicit tables allowed in a fea file
@@ -39,6 +44,7 @@ pub(crate) struct Tables {
     pub base: Option<Base>,
     pub os2: Option<Os2Builder>,
     pub stat: Option<StatBuilder>,
+    pub meta: Option<MetaBuilder>,
 }
 #[derive(Clone, Debug, Default)]
 #[allow(non_camel_case_types)]
@@ -52,20 +58,38 @@ pub struct VmtxBuilder {
     pub advances_y: Vec<(GlyphId, i16)>,
 }
 
+/// The language tags for the `meta` table.
+///
+/// This is set from the `table meta` block, which is an fea-rs extension.
+#[derive(Clone, Debug, Default)]
+pub struct MetaBuilder {
+    /// The languages the font is designed for (the `dlng` record)
+    pub design_languages: Vec<String>,
+    /// The languages the font supports (the `slng` record)
+    pub supported_languages: Vec<String>,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct CvParams {
     pub feat_ui_label_name: Vec<NameSpec>,
     pub feat_ui_tooltip_text_name: Vec<NameSpec>,
-    pub samle_text_name: Vec<NameSpec>,
+    pub sample_text_name: Vec<NameSpec>,
     pub param_ui_label_names: Vec<Vec<NameSpec>>,
     pub characters: Vec<char>,
 }
 
+/// A name record, as written in the FEA source.
 #[derive(Clone, Debug, Default)]
 pub struct NameSpec {
+    /// The platform ID
     pub platform_id: u16,
+    /// The platform-specific encoding ID
     pub encoding_id: u16,
+    /// The language ID
     pub language_id: u16,
+    /// The raw string, including quotes and any escape sequences.
+    ///
+    /// Use [`NameSpec::decoded_string`] to get the actual text.
     pub string: SmolStr,
 }
 
@@ -78,6 +102,19 @@ pub enum ClassId {
     Component = 4,
 }
 
+impl ClassId {
+    /// The class with this value in a GDEF `GlyphClassDef`, if it is valid.
+    pub(crate) fn from_raw(raw: u16) -> Option<Self> {
+        match raw {
+            1 => Some(ClassId::Base),
+            2 => Some(ClassId::Ligature),
+            3 => Some(ClassId::Mark),
+            4 => Some(ClassId::Component),
+            _ => None,
+        }
+    }
+}
+
 impl From<ClassId> for GlyphClassDef {
     fn from(src: ClassId) -> GlyphClassDef {
         match src {
@@ -96,6 +133,7 @@ pub struct GdefBuilder {
     pub ligature_pos: BTreeMap<GlyphId, Vec<CaretValue>>,
     pub mark_attach_class: BTreeMap<GlyphId, u16>,
     pub mark_glyph_sets: Vec<GlyphClass>,
+    pub var_store: Option<ItemVariationStore>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -132,6 +170,8 @@ pub struct Os2Builder {
     pub s_typo_line_gap: i16,
     pub us_lower_optical_point_size: Option<u16>,
     pub us_upper_optical_point_size: Option<u16>,
+    /// The table version; this determines which optional fields are written.
+    pub version: u16,
 }
 
 #[derive(Clone, Debug)]
@@ -141,51 +181,84 @@ pub struct ScriptRecord {
     pub values: Vec<i16>,
 }
 
+/// The contents of a `STAT` table, as declared in the FEA.
+///
+/// Names have not yet been assigned IDs; that happens when the table is built.
 #[derive(Clone, Debug)]
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub struct StatBuilder {
+    /// The elided fallback name
     pub name: StatFallbackName,
+    /// The design axes, in the order they were declared
     pub records: Vec<AxisRecord>,
+    /// The axis values, in the order they were declared
     pub values: Vec<AxisValue>,
 }
 
+/// A `DesignAxis` statement
 #[derive(Clone, Debug)]
 pub struct AxisRecord {
+    /// The axis tag
     pub tag: Tag,
+    /// The axis name, in one or more languages
     pub name: Vec<NameSpec>,
+    /// The axis ordering
     pub ordering: u16,
 }
 
+/// An `AxisValue` statement
 #[derive(Clone, Debug)]
 pub struct AxisValue {
+    /// The raw `AxisValueTableFlags` bits
     pub flags: u16,
+    /// The name of this value, in one or more languages
     pub name: Vec<NameSpec>,
+    /// The location (and implicitly, the format) of this value
     pub location: AxisLocation,
 }
 
+/// The location of an axis value.
+///
+/// Each variant corresponds to an axis value table format.
 #[derive(Clone, Debug)]
 pub enum AxisLocation {
+    /// A single value on a single axis (format 1)
     One {
+        /// The axis tag
         tag: Tag,
+        /// The value
         value: Fixed,
     },
+    /// A range on a single axis (format 2)
     Two {
+        /// The axis tag
         tag: Tag,
+        /// The nominal value
         nominal: Fixed,
+        /// The start of the range
         min: Fixed,
+        /// The end of the range
         max: Fixed,
     },
+    /// A value with a linked value, for style linking (format 3)
     Three {
+        /// The axis tag
         tag: Tag,
+        /// The value
         value: Fixed,
+        /// The linked value
         linked: Fixed,
     },
+    /// A value on each of multiple axes (format 4)
     Four(Vec<(Tag, Fixed)>),
 }
 
+/// The `ElidedFallbackName` or `ElidedFallbackNameID` of a STAT table.
 #[derive(Clone, Debug)]
 pub enum StatFallbackName {
+    /// The ID of an existing name record
     Id(u16),
+    /// A name, in one or more languages
     Record(Vec<NameSpec>),
 }
 
@@ -362,29 +435,78 @@ impl NameBuilder {
         self.last_anon_id + 1
     }
 
-    pub(crate) fn build(&self) -> Option<write_fonts::tables::name::Name> {
-        (!self.records.is_empty()).then(|| {
-            write_fonts::tables::name::Name::new(
-                self.records
-                    .iter()
-                    .filter_map(|(id, spec)| {
-                        spec.is_implemented_in_fontations()
-                            .then(|| spec.to_otf(*id))
-                    })
-                    .collect(),
-            )
-        })
+    /// Build a version 0 name table.
+    ///
+    /// We write this ourselves (rather than via write-fonts) so that each
+    /// string is encoded for its platform; records in unsupported encodings
+    /// are skipped. Returns an error if there are too many records, or too
+    /// much string data, for the table's 16-bit offsets and lengths.
+    pub(crate) fn build(&self) -> Result<Option<Vec<u8>>, BinaryCompilationError> {
+        if self.records.is_empty() {
+            return Ok(None);
+        }
+        let mut records = self
+            .records
+            .iter()
+            .filter_map(|(id, spec)| {
+                let key = (spec.platform_id, spec.encoding_id, spec.language_id, *id);
+                Some((key, spec.encode()?))
+            })
+            .collect::<Vec<_>>();
+        records.sort_by_key(|(key, _)| *key);
+
+        let to_u16 = |value: usize| {
+            let too_large = BinaryCompilationError::TableTooLarge(Tag::new(b"name"));
+            u16::try_from(value).map_err(|_| too_large)
+        };
+        let storage_offset = 6 + records.len() * 12;
+        let mut data = Vec::with_capacity(storage_offset);
+        for field in [0, records.len(), storage_offset] {
+            data.extend(to_u16(field)?.to_be_bytes());
+        }
+        // identical strings share storage
+        let mut storage = Vec::new();
+        let mut offsets = HashMap::new();
+        for ((platform_id, encoding_id, language_id, name_id), bytes) in &records {
+            let offset = *offsets.entry(bytes).or_insert_with(|| {
+                storage.extend_from_slice(bytes);
+                storage.len() - bytes.len()
+            });
+            for field in [*platform_id, *encoding_id, *language_id, *name_id] {
+                data.extend(field.to_be_bytes());
+            }
+            data.extend(to_u16(bytes.len())?.to_be_bytes());
+            data.extend(to_u16(offset)?.to_be_bytes());
+        }
+        data.extend(storage);
+        Ok(Some(data))
     }
 }
 
 impl NameSpec {
-    fn is_implemented_in_fontations(&self) -> bool {
-        Encoding::new(self.platform_id, self.encoding_id) != Encoding::Unknown
+    /// The bytes of this name in its platform's encoding.
+    ///
+    /// Windows names are encoded as UTF-16BE, and Mac names as MacRoman.
+    /// Returns `None` if the encoding is not supported, or if the string
+    /// contains characters that the encoding cannot represent.
+    pub(crate) fn encode(&self) -> Option<Vec<u8>> {
+        let string = self.decoded_string();
+        match Encoding::new(self.platform_id, self.encoding_id) {
+            Encoding::Utf16Be => Some(string.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+            Encoding::MacRoman => string.chars().map(char_to_mac_roman).collect(),
+            _ => None,
+        }
     }
 
+    /// The text of this name, with quotes removed and escapes decoded.
+    pub fn decoded_string(&self) -> String {
+        parse_string(self.platform_id, self.string.trim_matches('"'))
+    }
+
+    /// Convert this to a name record with the provided name ID.
     //TODO: rename me to build
     pub fn to_otf(&self, name_id: u16) -> write_fonts::tables::name::NameRecord {
-        let string = parse_string(self.platform_id, self.string.trim_matches('"'));
+        let string = self.decoded_string();
         write_fonts::tables::name::NameRecord::new(
             self.platform_id,
             self.encoding_id,
@@ -409,8 +531,8 @@ impl CvParams {
                 names.add_anon_group(&self.feat_ui_tooltip_text_name);
         }
 
-        if !self.samle_text_name.is_empty() {
-            out.sample_text_name_id = names.add_anon_group(&self.samle_text_name);
+        if !self.sample_text_name.is_empty() {
+            out.sample_text_name_id = names.add_anon_group(&self.sample_text_name);
         }
 
         if let Some((first, rest)) = self.param_ui_label_names.split_first() {
@@ -454,9 +576,11 @@ fn parse_win(s: &str) -> String {
             work = &work[pos + 5..];
         } else {
             out_u16.extend(work.encode_utf16());
+            break;
         }
     }
-    String::from_utf16(&out_u16).unwrap()
+    // unpaired surrogates are reported during validation
+    String::from_utf16_lossy(&out_u16)
 }
 
 fn parse_mac(s: &str) -> String {
@@ -504,6 +628,158 @@ impl HeadBuilder {
     }
 }
 
+impl VmtxBuilder {
+    /// Build a VORG table from any `VertOriginY` statements.
+    ///
+    /// The most common origin becomes the default, and only glyphs that
+    /// differ from it get an explicit record.
+    pub(crate) fn build_vorg(&self) -> Option<Vec<u8>> {
+        let origins = self
+            .origins_y
+            .iter()
+            .copied()
+            .collect::<BTreeMap<GlyphId, i16>>();
+        if origins.is_empty() {
+            return None;
+        }
+
+        let mut counts = BTreeMap::new();
+        for value in origins.values() {
+            *counts.entry(*value).or_insert(0_usize) += 1;
+        }
+        // ties are resolved in favour of the smaller value, for determinism
+        let default = counts
+            .iter()
+            .max_by(|(v1, c1), (v2, c2)| c1.cmp(c2).then(v2.cmp(v1)))
+            .map(|(value, _)| *value)
+            .unwrap_or_default();
+        let records = origins
+            .iter()
+            .filter(|(_, value)| **value != default)
+            .collect::<Vec<_>>();
+
+        let mut data = Vec::with_capacity(8 + records.len() * 4);
+        data.extend(1u16.to_be_bytes());
+        data.extend(0u16.to_be_bytes());
+        data.extend(default.to_be_bytes());
+        data.extend((records.len() as u16).to_be_bytes());
+        for (gid, value) in records {
+            data.extend(gid.to_u16().to_be_bytes());
+            data.extend(value.to_be_bytes());
+        }
+        Some(data)
+    }
+
+    /// Build a vmtx table, by applying the FEA's metrics to the font's.
+    ///
+    /// Glyphs that are not mentioned in the FEA keep their metrics. If
+    /// `bake_origins` is `true`, each `VertOriginY` value is converted to a
+    /// top side bearing, which is the origin minus the top of the glyph's
+    /// bounding box. The table has the same number of full metrics as the
+    /// font's, so the font's `vhea` remains valid.
+    ///
+    /// Values that cannot be applied (such as an advance for a glyph without a
+    /// full metric) are reported during compilation, and are ignored here.
+    pub(crate) fn build_vmtx(
+        &self,
+        font_metrics: &VerticalMetrics,
+        glyph_y_max: &BTreeMap<GlyphId, i16>,
+        bake_origins: bool,
+    ) -> Option<Vec<u8>> {
+        if self.advances_y.is_empty() && (!bake_origins || self.origins_y.is_empty()) {
+            return None;
+        }
+
+        let mut metrics = font_metrics.clone();
+        for (gid, advance) in &self.advances_y {
+            if let Some(metric) = metrics.long_metrics.get_mut(gid.to_u16() as usize) {
+                metric.0 = *advance as u16;
+            }
+        }
+        if bake_origins {
+            let n_long = metrics.long_metrics.len();
+            for (gid, origin) in &self.origins_y {
+                let Some(tsb) = glyph_y_max
+                    .get(gid)
+                    .and_then(|y_max| origin.checked_sub(*y_max))
+                else {
+                    continue;
+                };
+                let idx = gid.to_u16() as usize;
+                if let Some(metric) = metrics.long_metrics.get_mut(idx) {
+                    metric.1 = tsb;
+                } else if let Some(short) = metrics.top_side_bearings.get_mut(idx - n_long) {
+                    *short = tsb;
+                }
+            }
+        }
+
+        let mut data = Vec::with_capacity(
+            metrics.long_metrics.len() * 4 + metrics.top_side_bearings.len() * 2,
+        );
+        for (advance, tsb) in metrics.long_metrics {
+            data.extend(advance.to_be_bytes());
+            data.extend(tsb.to_be_bytes());
+        }
+        for tsb in metrics.top_side_bearings {
+            data.extend(tsb.to_be_bytes());
+        }
+        Some(data)
+    }
+}
+
+impl MetaBuilder {
+    /// Parse a comma-separated list of BCP 47 (ScriptLangTag) language tags.
+    ///
+    /// This only checks the basic shape of each tag: one or more subtags of
+    /// one to eight ASCII letters or digits, separated by hyphens.
+    pub(crate) fn parse_language_list(list: &str) -> Result<Vec<String>, String> {
+        list.split(',')
+            .map(str::trim)
+            .map(|tag| {
+                let is_valid = tag.split('-').all(|subtag| {
+                    (1..=8).contains(&subtag.len())
+                        && subtag.bytes().all(|b| b.is_ascii_alphanumeric())
+                });
+                if is_valid {
+                    Ok(tag.to_owned())
+                } else {
+                    Err(format!("invalid language tag '{tag}'"))
+                }
+            })
+            .collect()
+    }
+
+    /// Build the meta table, with a data map for each non-empty list.
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let maps = [
+            (Tag::new(b"dlng"), &self.design_languages),
+            (Tag::new(b"slng"), &self.supported_languages),
+        ]
+        .into_iter()
+        .filter(|(_, languages)| !languages.is_empty())
+        .map(|(tag, languages)| (tag, languages.join(", ")))
+        .collect::<Vec<_>>();
+
+        // version, flags, reserved, dataMapsCount, then a record per map
+        let mut data_offset = 16 + maps.len() as u32 * 12;
+        let mut data = Vec::new();
+        for field in [1, 0, 0, maps.len() as u32] {
+            data.extend(field.to_be_bytes());
+        }
+        for (tag, text) in &maps {
+            data.extend(tag.into_bytes());
+            data.extend(data_offset.to_be_bytes());
+            data.extend((text.len() as u32).to_be_bytes());
+            data_offset += text.len() as u32;
+        }
+        for (_, text) in &maps {
+            data.extend(text.as_bytes());
+        }
+        data
+    }
+}
+
 impl UnicodeRange {
     pub(crate) fn set_bit(&mut self, bit: u8) {
         set_bit_impl(&mut self.0, bit)
@@ -536,7 +812,8 @@ impl Os2Builder {
         let [ul_code_page_range_1, ul_code_page_range_2] = self.code_page_range.0;
         let [ul_unicode_range_1, ul_unicode_range_2, ul_unicode_range_3, ul_unicode_range_4] =
             self.unicode_range.0;
-
+        // fields not set in the FEA are written as zero, matching feaLib
+        let has = |version: u16| self.version >= version;
         write_fonts::tables::os2::Os2 {
             us_weight_class: self.us_weight_class,
             us_width_class: self.us_width_class,
@@ -553,18 +830,19 @@ impl Os2Builder {
             s_typo_line_gap: self.s_typo_line_gap,
             us_win_ascent: self.us_win_ascent,
             us_win_descent: self.us_win_descent,
-            ul_code_page_range_1: Some(ul_code_page_range_1),
-            ul_code_page_range_2: Some(ul_code_page_range_2),
-            sx_height: Some(self.sx_height),
-            s_cap_height: Some(self.s_cap_height),
+            ul_code_page_range_1: has(1).then_some(ul_code_page_range_1),
+            ul_code_page_range_2: has(1).then_some(ul_code_page_range_2),
+            sx_height: has(2).then_some(self.sx_height),
+            s_cap_height: has(2).then_some(self.s_cap_height),
             //TODO: these are defined in fea, but we want them to be present
             //since other v2 fields are? I assume they get overwritten anyway?
-            us_default_char: Some(0),
-            us_max_context: Some(0),
-            us_break_char: Some(0),
-            //TODO: ensure at validation that if one is present, the other is?
-            us_lower_optical_point_size: self.us_lower_optical_point_size,
-            us_upper_optical_point_size: self.us_upper_optical_point_size,
+            us_default_char: has(2).then_some(0),
+            us_max_context: has(2).then_some(0),
+            us_break_char: has(2).then_some(0),
+            us_lower_optical_point_size: has(5)
+                .then(|| self.us_lower_optical_point_size.unwrap_or_default()),
+            us_upper_optical_point_size: has(5)
+                .then(|| self.us_upper_optical_point_size.unwrap_or_default()),
             ..Default::default()
         }
     }
@@ -580,6 +858,7 @@ impl GdefBuilder {
         );
 
         table.mark_glyph_sets_def = self.build_mark_glyph_sets().into();
+        table.item_var_store = self.var_store.clone().into();
         dump_table(&table)
     }
 
@@ -656,6 +935,7 @@ impl GdefBuilder {
             && self.ligature_pos.is_empty()
             && self.mark_attach_class.is_empty()
             && self.mark_glyph_sets.is_empty()
+            && self.var_store.is_none()
     }
 }
 
@@ -712,6 +992,18 @@ fn mac_roman_to_char(inp: u8) -> char {
     }
 }
 
+/// The MacRoman byte for this character, if it has one.
+pub(crate) fn char_to_mac_roman(c: char) -> Option<u8> {
+    if c.is_ascii() {
+        Some(c as u8)
+    } else {
+        MAC_ROMAN_LOOKUP
+            .iter()
+            .position(|x| *x == c)
+            .map(|idx| idx as u8 + 0x80)
+    }
+}
+
 #[rustfmt::skip]
 /// char equivalents of macroman values 0x80 - 0xFF
 static MAC_ROMAN_LOOKUP: &[char] = &[
@@ -724,7 +1016,7 @@ static MAC_ROMAN_LOOKUP: &[char] = &[
     '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑',
     '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø',
     '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«',
-    '»', '…', '\u{ca}', //nbsp
+    '»', '…', '\u{a0}', //nbsp
     'À', 'Ã', 'Õ', 'Œ', 'œ',
     '–', '—', '“', '”', '‘', '’', '÷', '◊',
     'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ',
@@ -750,6 +1042,88 @@ mod tests {
         assert_eq!(mac_roman_to_char(0x8e), 'é');
     }
 
+    fn name_spec(platform_id: u16, encoding_id: u16, string: &str) -> NameSpec {
+        NameSpec {
+            platform_id,
+            encoding_id,
+            language_id: 0,
+            string: format!("\"{string}\""),
+        }
+    }
+
+    #[test]
+    fn encode_names() {
+        let utf16 = [0, b'c', 0, b'a', 0, b'f', 0, 0xe9];
+        assert_eq!(name_spec(3, 1, "café").encode().unwrap(), utf16);
+        assert_eq!(name_spec(3, 1, "caf\\00e9").encode().unwrap(), utf16);
+        assert_eq!(name_spec(1, 0, "café").encode().unwrap(), b"caf\x8e");
+        assert_eq!(name_spec(1, 0, "caf\\8e").encode().unwrap(), b"caf\x8e");
+        assert_eq!(name_spec(1, 0, "a\\cab").encode().unwrap(), b"a\xcab");
+        assert_eq!(name_spec(1, 0, "café").decoded_string(), "café");
+        // not representable in MacRoman
+        assert!(name_spec(1, 0, "Ω ≠ Ж").encode().is_none());
+    }
+
+    #[test]
+    fn decode_escapes() {
+        // escapes are code units on Windows, and MacRoman bytes on Mac
+        let win = name_spec(3, 1, "\\0041\\00e9 \\d83d\\de00!");
+        assert_eq!(win.decoded_string(), "Aé 😀!");
+        let bytes = [0, 0x41, 0, 0xe9, 0, 0x20, 0xd8, 0x3d, 0xde, 0x00, 0, 0x21];
+        assert_eq!(win.encode().unwrap(), bytes);
+        let mac = name_spec(1, 0, "\\41\\8e\\a5x");
+        assert_eq!(mac.decoded_string(), "Aé•x");
+        assert_eq!(mac.encode().unwrap(), b"A\x8e\xa5x");
+    }
+
+    #[test]
+    fn name_table_records() {
+        let mut names = NameBuilder::default();
+        names.add(256, name_spec(3, 1, "Grüße"));
+        names.add(256, name_spec(1, 0, "Grüße"));
+        let data = names.build().unwrap().unwrap();
+        let read_u16 = |offset: usize| u16::from_be_bytes([data[offset], data[offset + 1]]);
+        assert_eq!(read_u16(2), 2, "record count");
+        let storage = read_u16(4) as usize;
+        let strings = (0..2)
+            .map(|i| {
+                let record = 6 + i * 12;
+                let start = storage + read_u16(record + 10) as usize;
+                let len = read_u16(record + 8) as usize;
+                (read_u16(record), &data[start..start + len])
+            })
+            .collect::<Vec<_>>();
+        // records are sorted, so the mac record comes first
+        assert_eq!(strings[0], (1, b"Gr\x9f\xa7e".as_slice()));
+        assert_eq!(strings[1].0, 3);
+        assert_eq!(strings[1].1.len(), 10);
+    }
+
+    #[test]
+    fn name_table_too_large() {
+        // each string is 60000 bytes in UTF-16, so the third starts past the
+        // largest offset
+        let mut names = NameBuilder::default();
+        for c in ['a', 'b'] {
+            names.add(256, name_spec(3, 1, &c.to_string().repeat(30_000)));
+        }
+        assert!(names.build().is_ok());
+        names.add(257, name_spec(3, 1, &"c".repeat(30_000)));
+        assert!(matches!(
+            names.build(),
+            Err(BinaryCompilationError::TableTooLarge(tag)) if tag == Tag::new(b"name")
+        ));
+    }
+
+    #[test]
+    fn meta_language_list() {
+        let list = MetaBuilder::parse_language_list("en-Latn, ja ,zh-Hant-TW").unwrap();
+        assert_eq!(list, ["en-Latn", "ja", "zh-Hant-TW"]);
+        assert!(MetaBuilder::parse_language_list("en_US").is_err());
+        assert!(MetaBuilder::parse_language_list("en,,fr").is_err());
+        assert!(MetaBuilder::parse_language_list("en-toolongsubtag").is_err());
+    }
+
     #[test]
     fn parse_mac_str() {
         let inp = "M\\9fller";
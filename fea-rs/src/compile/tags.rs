@@ -6,14 +6,151 @@ use write_fonts::types::Tag;
 
 pub const AALT: Tag = Tag::new(b"aalt");
 pub const SIZE: Tag = Tag::new(b"size");
+pub const CCMP: Tag = Tag::new(b"ccmp");
+pub const RTLM: Tag = Tag::new(b"rtlm");
+pub const RTLA: Tag = Tag::new(b"rtla");
 pub const LANG_DFLT: Tag = Tag::new(b"dflt");
 pub const SCRIPT_DFLT: Tag = Tag::new(b"DFLT");
 pub const GSUB: Tag = Tag::new(b"GSUB");
 pub const GPOS: Tag = Tag::new(b"GPOS");
+pub const GDEF: Tag = Tag::new(b"GDEF");
 
 pub const WIN_PLATFORM_ID: u16 = 3;
 pub const MAC_PLATFORM_ID: u16 = 1;
 
+/// Registered OpenType feature tags and their names, other than `ssXX` and `cvXX`.
+///
+/// See <https://learn.microsoft.com/en-us/typography/opentype/spec/featuretags>
+const REGISTERED_FEATURES: &[(&[u8; 4], &str)] = &[
+    (b"aalt", "Access All Alternates"),
+    (b"abvf", "Above-base Forms"),
+    (b"abvm", "Above-base Mark Positioning"),
+    (b"abvs", "Above-base Substitutions"),
+    (b"afrc", "Alternative Fractions"),
+    (b"akhn", "Akhand"),
+    (b"apkn", "Kerning for Alternate Proportional Widths"),
+    (b"blwf", "Below-base Forms"),
+    (b"blwm", "Below-base Mark Positioning"),
+    (b"blws", "Below-base Substitutions"),
+    (b"c2pc", "Petite Capitals From Capitals"),
+    (b"c2sc", "Small Capitals From Capitals"),
+    (b"calt", "Contextual Alternates"),
+    (b"case", "Case-Sensitive Forms"),
+    (b"ccmp", "Glyph Composition / Decomposition"),
+    (b"cfar", "Conjunct Form After Ro"),
+    (b"chws", "Contextual Half-width Spacing"),
+    (b"cjct", "Conjunct Forms"),
+    (b"clig", "Contextual Ligatures"),
+    (b"cpct", "Centered CJK Punctuation"),
+    (b"cpsp", "Capital Spacing"),
+    (b"cswh", "Contextual Swash"),
+    (b"curs", "Cursive Positioning"),
+    (b"dist", "Distances"),
+    (b"dlig", "Discretionary Ligatures"),
+    (b"dnom", "Denominators"),
+    (b"dtls", "Dotless Forms"),
+    (b"expt", "Expert Forms"),
+    (b"falt", "Final Glyph on Line Alternates"),
+    (b"fin2", "Terminal Forms #2"),
+    (b"fin3", "Terminal Forms #3"),
+    (b"fina", "Terminal Forms"),
+    (b"flac", "Flattened Accent Forms"),
+    (b"frac", "Fractions"),
+    (b"fwid", "Full Widths"),
+    (b"half", "Half Forms"),
+    (b"haln", "Halant Forms"),
+    (b"halt", "Alternate Half Widths"),
+    (b"hist", "Historical Forms"),
+    (b"hkna", "Horizontal Kana Alternates"),
+    (b"hlig", "Historical Ligatures"),
+    (b"hngl", "Hangul"),
+    (b"hojo", "Hojo Kanji Forms"),
+    (b"hwid", "Half Widths"),
+    (b"init", "Initial Forms"),
+    (b"isol", "Isolated Forms"),
+    (b"ital", "Italics"),
+    (b"jalt", "Justification Alternates"),
+    (b"jp04", "JIS2004 Forms"),
+    (b"jp78", "JIS78 Forms"),
+    (b"jp83", "JIS83 Forms"),
+    (b"jp90", "JIS90 Forms"),
+    (b"kern", "Kerning"),
+    (b"lfbd", "Left Bounds"),
+    (b"liga", "Standard Ligatures"),
+    (b"ljmo", "Leading Jamo Forms"),
+    (b"lnum", "Lining Figures"),
+    (b"locl", "Localized Forms"),
+    (b"ltra", "Left-to-right Alternates"),
+    (b"ltrm", "Left-to-right Mirrored Forms"),
+    (b"mark", "Mark Positioning"),
+    (b"med2", "Medial Forms #2"),
+    (b"medi", "Medial Forms"),
+    (b"mgrk", "Mathematical Greek"),
+    (b"mkmk", "Mark to Mark Positioning"),
+    (b"mset", "Mark Positioning via Substitution"),
+    (b"nalt", "Alternate Annotation Forms"),
+    (b"nlck", "NLC Kanji Forms"),
+    (b"nukt", "Nukta Forms"),
+    (b"numr", "Numerators"),
+    (b"onum", "Oldstyle Figures"),
+    (b"opbd", "Optical Bounds"),
+    (b"ordn", "Ordinals"),
+    (b"ornm", "Ornaments"),
+    (b"palt", "Proportional Alternate Widths"),
+    (b"pcap", "Petite Capitals"),
+    (b"pkna", "Proportional Kana"),
+    (b"pnum", "Proportional Figures"),
+    (b"pref", "Pre-base Forms"),
+    (b"pres", "Pre-base Substitutions"),
+    (b"pstf", "Post-base Forms"),
+    (b"psts", "Post-base Substitutions"),
+    (b"pwid", "Proportional Widths"),
+    (b"qwid", "Quarter Widths"),
+    (b"rand", "Randomize"),
+    (b"rclt", "Required Contextual Alternates"),
+    (b"rkrf", "Rakar Forms"),
+    (b"rlig", "Required Ligatures"),
+    (b"rphf", "Reph Forms"),
+    (b"rtbd", "Right Bounds"),
+    (b"rtla", "Right-to-left Alternates"),
+    (b"rtlm", "Right-to-left Mirrored Forms"),
+    (b"ruby", "Ruby Notation Forms"),
+    (b"rvrn", "Required Variation Alternates"),
+    (b"salt", "Stylistic Alternates"),
+    (b"sinf", "Scientific Inferiors"),
+    (b"size", "Optical Size"),
+    (b"smcp", "Small Capitals"),
+    (b"smpl", "Simplified Forms"),
+    (b"ssty", "Math Script-style Alternates"),
+    (b"stch", "Stretching Glyph Decomposition"),
+    (b"subs", "Subscript"),
+    (b"sups", "Superscript"),
+    (b"swsh", "Swash"),
+    (b"titl", "Titling"),
+    (b"tjmo", "Trailing Jamo Forms"),
+    (b"tnam", "Traditional Name Forms"),
+    (b"tnum", "Tabular Figures"),
+    (b"trad", "Traditional Forms"),
+    (b"twid", "Third Widths"),
+    (b"unic", "Unicase"),
+    (b"valt", "Alternate Vertical Metrics"),
+    (
+        b"vapk",
+        "Kerning for Alternate Proportional Vertical Metrics",
+    ),
+    (b"vatu", "Vattu Variants"),
+    (b"vchw", "Vertical Contextual Half-width Spacing"),
+    (b"vert", "Vertical Alternates"),
+    (b"vhal", "Alternate Vertical Half Metrics"),
+    (b"vjmo", "Vowel Jamo Forms"),
+    (b"vkna", "Vertical Kana Alternates"),
+    (b"vkrn", "Vertical Kerning"),
+    (b"vpal", "Proportional Alternate Vertical Metrics"),
+    (b"vrt2", "Vertical Alternates and Rotation"),
+    (b"vrtr", "Vertical Alternates for Rotation"),
+    (b"zero", "Slashed Zero"),
+];
+
 /// `true` if this tag is ss01-ss20
 pub fn is_stylistic_set(tag: Tag) -> bool {
     is_numbered_tag(tag, b"ss", 1..=20)
@@ -24,6 +161,34 @@ pub fn is_character_variant(tag: Tag) -> bool {
     is_numbered_tag(tag, b"cv", 1..=99)
 }
 
+/// `true` if this is a feature tag registered in the OpenType spec
+pub fn is_registered_feature(tag: Tag) -> bool {
+    is_stylistic_set(tag)
+        || is_character_variant(tag)
+        || REGISTERED_FEATURES
+            .iter()
+            .any(|(registered, _)| **registered == tag.into_bytes())
+}
+
+/// The name of a registered feature, as given in the OpenType spec.
+///
+/// For example, the name of `smcp` is "Small Capitals", and the name of `ss03`
+/// is "Stylistic Set 3".
+pub fn feature_name(tag: Tag) -> Option<String> {
+    let bytes = tag.into_bytes();
+    let number = || std::str::from_utf8(&bytes[2..]).ok()?.parse::<u8>().ok();
+    if is_stylistic_set(tag) {
+        return number().map(|n| format!("Stylistic Set {n}"));
+    }
+    if is_character_variant(tag) {
+        return number().map(|n| format!("Character Variant {n}"));
+    }
+    REGISTERED_FEATURES
+        .iter()
+        .find(|(registered, _)| **registered == bytes)
+        .map(|(_, name)| name.to_string())
+}
+
 fn is_numbered_tag(tag: Tag, prefix: &[u8], range: RangeInclusive<u8>) -> bool {
     let bytes = tag.into_bytes();
     bytes.starts_with(prefix)
@@ -63,4 +228,26 @@ mod tests {
         assert!(!is_character_variant(Tag::new(b"cv1 ")));
         assert!(!is_character_variant(Tag::new(b"cv9f")));
     }
+
+    #[test]
+    fn registered_feature() {
+        assert!(is_registered_feature(Tag::new(b"liga")));
+        assert!(is_registered_feature(Tag::new(b"c2sc")));
+        assert!(is_registered_feature(Tag::new(b"ss05")));
+        assert!(is_registered_feature(Tag::new(b"cv42")));
+        assert!(!is_registered_feature(Tag::new(b"ligs")));
+        assert!(!is_registered_feature(Tag::new(b"ss21")));
+        assert!(!is_registered_feature(Tag::new(b"LIGA")));
+    }
+
+    #[test]
+    fn registered_feature_name() {
+        let name = |tag: &[u8; 4]| feature_name(Tag::new(tag));
+        assert_eq!(name(b"smcp").as_deref(), Some("Small Capitals"));
+        assert_eq!(name(b"zero").as_deref(), Some("Slashed Zero"));
+        assert_eq!(name(b"ss07").as_deref(), Some("Stylistic Set 7"));
+        assert_eq!(name(b"cv42").as_deref(), Some("Character Variant 42"));
+        assert_eq!(name(b"ss21"), None);
+        assert_eq!(name(b"ligs"), None);
+    }
 }
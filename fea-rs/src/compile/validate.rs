@@ -15,7 +15,10 @@ use write_fonts::{read::tables::name::Encoding, types::Tag};
 
 use super::{
     glyph_range,
-    tags::{self, WIN_PLATFORM_ID},
+    opts::{DiagnosticSink, Dialect},
+    tables::{char_to_mac_roman, MetaBuilder},
+    tags::{self, MAC_PLATFORM_ID, WIN_PLATFORM_ID},
+    AxisMap, Opts,
 };
 use crate::{
     parse::SourceMap,
@@ -24,9 +27,45 @@ use crate::{
         Token,
     },
     typed::ContextualRuleNode,
-    Diagnostic, GlyphMap, Kind, NodeOrToken,
+    Diagnostic, DiagnosticKind, GlyphMap, Kind, NodeOrToken, Phase,
 };
 
+pub(crate) static CID_REQUIRES_CID_KEYED_MAP: &str = "CID references require a CID-keyed glyph map";
+
+/// The message for a glyph name that is not in the font.
+///
+/// If `skipped` is true, the message says that the glyph will be removed from
+/// its class. If the glyph map has a similar name, it is suggested.
+pub(crate) fn missing_glyph_message(glyph_map: &GlyphMap, name: &str, skipped: bool) -> String {
+    let problem = if skipped {
+        "glyph not in font, and will be removed from the class"
+    } else {
+        "glyph not in font"
+    };
+    match glyph_map.closest_name(name) {
+        Some(suggestion) => format!("{problem}; did you mean '{suggestion}'?"),
+        None => problem.to_string(),
+    }
+}
+
+/// The message for a CID that is not in a CID-keyed font.
+///
+/// If `skipped` is true, the message says that the glyph will be removed from
+/// its class.
+pub(crate) fn missing_cid_message(glyph_map: &GlyphMap, cid: u16, skipped: bool) -> String {
+    let range = glyph_map.cid_range().expect("cid-keyed");
+    let mut msg = format!(
+        "CID {cid} not in font (valid CIDs are {}..={})",
+        range.start(),
+        range.end()
+    );
+    if skipped {
+        msg.push_str(", and will be removed from the class");
+    }
+    msg
+}
+
+#[derive(Clone)]
 pub struct ValidationCtx<'a> {
     pub errors: Vec<Diagnostic>,
     glyph_map: &'a GlyphMap,
@@ -39,13 +78,22 @@ pub struct ValidationCtx<'a> {
     mark_class_defs: HashSet<SmolStr>,
     mark_class_used: Option<Token>,
     anchor_defs: HashMap<SmolStr, Token>,
+    // all named anchors in the file, so we can identify forward references
+    all_anchor_names: HashSet<SmolStr>,
+    device_defs: HashMap<SmolStr, Token>,
     value_record_defs: HashMap<SmolStr, Token>,
     aalt_referenced_features: HashMap<Tag, typed::Tag>,
     all_features: HashSet<Tag>,
+    condition_sets: HashMap<SmolStr, Token>,
+    ignore_unknown_items: bool,
+    skip_missing_class_glyphs: bool,
+    dialect: Dialect,
+    axis_map: Option<AxisMap>,
+    diagnostic_sink: Option<DiagnosticSink>,
 }
 
 impl<'a> ValidationCtx<'a> {
-    pub(crate) fn new(glyph_map: &'a GlyphMap, source_map: &'a SourceMap) -> Self {
+    pub(crate) fn new(glyph_map: &'a GlyphMap, source_map: &'a SourceMap, opts: &Opts) -> Self {
         ValidationCtx {
             glyph_map,
             source_map,
@@ -57,24 +105,102 @@ impl<'a> ValidationCtx<'a> {
             mark_class_defs: Default::default(),
             mark_class_used: None,
             anchor_defs: Default::default(),
+            all_anchor_names: Default::default(),
+            device_defs: Default::default(),
             value_record_defs: Default::default(),
             aalt_referenced_features: Default::default(),
             all_features: Default::default(),
+            condition_sets: Default::default(),
+            ignore_unknown_items: opts.ignore_unknown_items,
+            skip_missing_class_glyphs: opts.skip_missing_class_glyphs,
+            dialect: opts.dialect,
+            axis_map: opts.axis_map.clone(),
+            diagnostic_sink: opts.diagnostic_sink.clone(),
         }
     }
 
-    fn error(&mut self, range: Range<usize>, message: impl Into<String>) {
+    /// A copy of this context, for validating statements that follow the ones
+    /// we have already seen.
+    ///
+    /// `source_map` is for a tree made by appending to the one we have been
+    /// validating (see `ParseTree::append`), so that the positions we have
+    /// already seen are still valid. The copy has all of our state, but none
+    /// of our diagnostics.
+    pub(crate) fn fork_for_tree<'b>(&self, source_map: &'b SourceMap) -> ValidationCtx<'b>
+    where
+        'a: 'b,
+    {
+        ValidationCtx {
+            source_map,
+            errors: Vec::new(),
+            ..self.clone()
+        }
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if let Some(sink) = &self.diagnostic_sink {
+            sink.send(&diagnostic);
+        }
+        self.errors.push(diagnostic);
+    }
+
+    fn error(&mut self, kind: DiagnosticKind, range: Range<usize>, message: impl Into<String>) {
         let (file, range) = self.source_map.resolve_range(range);
-        self.errors.push(Diagnostic::error(file, range, message));
+        let diagnostic = Diagnostic::error(file, range, message);
+        self.report(diagnostic.in_phase(Phase::Validation).with_kind(kind));
     }
 
-    fn warning(&mut self, range: Range<usize>, message: impl Into<String>) {
+    fn warning(&mut self, kind: DiagnosticKind, range: Range<usize>, message: impl Into<String>) {
         let (file, range) = self.source_map.resolve_range(range);
-        self.errors.push(Diagnostic::warning(file, range, message));
+        let diagnostic = Diagnostic::warning(file, range, message);
+        self.report(diagnostic.in_phase(Phase::Validation).with_kind(kind));
+    }
+
+    /// Report an item we don't understand; this is an error unless
+    /// [`Opts::ignore_unknown_items`] is set.
+    ///
+    /// Loose tokens are what the parser skipped over, and it has already
+    /// reported them, so we only warn about nodes.
+    fn unknown_item(&mut self, item: &NodeOrToken, message: impl Into<String>) {
+        if !self.ignore_unknown_items {
+            self.error(DiagnosticKind::UnknownItem, item.range(), message);
+        } else if !item.is_token() {
+            self.warning(DiagnosticKind::UnknownItem, item.range(), message);
+        }
+    }
+
+    /// Report an error if the variable font extensions to the syntax are not
+    /// allowed by the [`Opts::dialect`]; returns `false` in this case.
+    fn check_variable_syntax(&mut self, range: Range<usize>) -> bool {
+        if self.dialect == Dialect::Variable {
+            return true;
+        }
+        self.error(
+            DiagnosticKind::InvalidStatement,
+            range,
+            "variable syntax requires the Variable dialect",
+        );
+        false
     }
 
     pub(crate) fn validate_root(&mut self, node: &typed::Root) {
-        for item in node.statements() {
+        self.validate_statements(&node.statements().collect::<Vec<_>>());
+        self.finalize();
+    }
+
+    /// Validate some top-level statements.
+    ///
+    /// This can be called more than once, for statements that follow each
+    /// other; [`finalize`](Self::finalize) must be called after the last.
+    pub(crate) fn validate_statements(&mut self, statements: &[&NodeOrToken]) {
+        self.all_anchor_names.extend(
+            statements
+                .iter()
+                .copied()
+                .filter_map(typed::AnchorDef::cast)
+                .map(|anchor_def| anchor_def.name().text.clone()),
+        );
+        for item in statements.iter().copied() {
             if let Some(language_system) = typed::LanguageSystem::cast(item) {
                 self.validate_language_system(&language_system)
             } else if let Some(class_def) = typed::GlyphClassDef::cast(item) {
@@ -83,8 +209,14 @@ impl<'a> ValidationCtx<'a> {
                 self.validate_mark_class_def(&mark_def);
             } else if let Some(anchor_def) = typed::AnchorDef::cast(item) {
                 self.validate_anchor_def(&anchor_def);
+            } else if let Some(device_def) = typed::DeviceDef::cast(item) {
+                self.validate_device_def(&device_def);
             } else if let Some(feature) = typed::Feature::cast(item) {
                 self.validate_feature(&feature);
+            } else if let Some(condition_set) = typed::ConditionSet::cast(item) {
+                self.validate_condition_set(&condition_set);
+            } else if let Some(variation) = typed::FeatureVariation::cast(item) {
+                self.validate_feature_variation(&variation);
             } else if let Some(table) = typed::Table::cast(item) {
                 self.validate_table(&table);
             } else if let Some(lookup) = typed::LookupBlock::cast(item) {
@@ -95,11 +227,10 @@ impl<'a> ValidationCtx<'a> {
                 unimplemented!("anon")
             }
         }
-        self.finalize();
     }
 
     /// perform any analysis required after seeing all items
-    fn finalize(&mut self) {
+    pub(crate) fn finalize(&mut self) {
         self.finalize_aalt();
     }
 
@@ -113,7 +244,11 @@ impl<'a> ValidationCtx<'a> {
             })
             .collect::<Vec<_>>();
         for tag in bad {
-            self.error(tag.range(), "Referenced feature not found.");
+            self.error(
+                DiagnosticKind::UndefinedName,
+                tag.range(),
+                "Referenced feature not found.",
+            );
         }
     }
 
@@ -124,6 +259,7 @@ impl<'a> ValidationCtx<'a> {
         if script.text() == "DFLT" && lang.text() == "dflt" && !self.default_lang_systems.is_empty()
         {
             self.error(
+                DiagnosticKind::InvalidStatement,
                 node.range(),
                 "'DFLT dftl' must be first languagesystem statement",
             );
@@ -132,6 +268,7 @@ impl<'a> ValidationCtx<'a> {
         if script.text() == "DFLT" {
             if self.seen_non_default_script {
                 self.error(
+                    DiagnosticKind::InvalidStatement,
                     script.range(),
                     "languagesystem with 'DFLT' script tag must precede non-'DFLT' languagesystems",
                 );
@@ -145,7 +282,11 @@ impl<'a> ValidationCtx<'a> {
             .default_lang_systems
             .insert((script.text().clone(), lang.text().clone()))
         {
-            self.warning(node.range(), "Duplicate languagesystem definition");
+            self.warning(
+                DiagnosticKind::DuplicateDefinition,
+                node.range(),
+                "Duplicate languagesystem definition",
+            );
         }
     }
 
@@ -155,7 +296,11 @@ impl<'a> ValidationCtx<'a> {
             .glyph_class_defs
             .insert(name.text().to_owned(), name.token().clone())
         {
-            self.warning(name.range(), "duplicate glyph class definition");
+            self.warning(
+                DiagnosticKind::DuplicateDefinition,
+                name.range(),
+                "duplicate glyph class definition",
+            );
             //TODO: use previous span to show previous declaration
             //TODO: have help message
         }
@@ -164,22 +309,53 @@ impl<'a> ValidationCtx<'a> {
         } else if let Some(alias) = node.class_alias() {
             self.validate_glyph_class_ref(&alias, false);
         } else {
-            self.error(node.range(), "unknown parser bug?");
+            self.error(
+                DiagnosticKind::Internal,
+                node.range(),
+                "unknown parser bug?",
+            );
         }
     }
 
     fn validate_anchor_def(&mut self, node: &typed::AnchorDef) {
+        self.validate_anchor(&node.anchor());
         if let Some(_prev) = self
             .anchor_defs
             .insert(node.name().text.clone(), node.name().clone())
         {
-            self.warning(node.name().range(), "duplicate anchor name");
+            self.warning(
+                DiagnosticKind::DuplicateDefinition,
+                node.name().range(),
+                "duplicate anchor name",
+            );
+        }
+    }
+
+    fn validate_device_def(&mut self, node: &typed::DeviceDef) {
+        let device = node.device();
+        if let Some(name) = device.name() {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                name.range(),
+                "device definitions cannot refer to other devices",
+            );
+        }
+        if let Some(_prev) = self
+            .device_defs
+            .insert(node.name().text.clone(), node.name().clone())
+        {
+            self.warning(
+                DiagnosticKind::DuplicateDefinition,
+                node.name().range(),
+                "duplicate device name",
+            );
         }
     }
 
     fn validate_mark_class_def(&mut self, node: &typed::MarkClassDef) {
         if let Some(_use_site) = self.mark_class_used.as_ref() {
             self.error(
+                DiagnosticKind::InvalidStatement,
                 node.keyword().range(),
                 "all markClass definitions must precede any use of a mark class in the file",
             );
@@ -198,7 +374,11 @@ impl<'a> ValidationCtx<'a> {
 
     fn validate_mark_class(&mut self, node: &typed::GlyphClassName) {
         if !self.mark_class_defs.contains(node.text()) {
-            self.error(node.range(), "undefined mark class");
+            self.error(
+                DiagnosticKind::UndefinedName,
+                node.range(),
+                "undefined mark class",
+            );
         }
     }
 
@@ -213,7 +393,12 @@ impl<'a> ValidationCtx<'a> {
             typed::Table::Name(table) => self.validate_name(table),
             typed::Table::Os2(table) => self.validate_os2(table),
             typed::Table::Stat(table) => self.validate_stat(table),
-            _ => self.error(node.tag().range(), "unsupported table type"),
+            typed::Table::Meta(table) => self.validate_meta(table),
+            _ => self.error(
+                DiagnosticKind::Unsupported,
+                node.tag().range(),
+                "unsupported table type",
+            ),
         }
     }
 
@@ -235,6 +420,26 @@ impl<'a> ValidationCtx<'a> {
         }
     }
 
+    fn validate_meta(&mut self, node: &typed::MetaTable) {
+        let mut seen = HashSet::new();
+        for record in node.statements() {
+            let keyword = record.keyword();
+            if !seen.insert(keyword.as_str()) {
+                let message = format!("{} must only be defined once", keyword.as_str());
+                self.error(
+                    DiagnosticKind::DuplicateDefinition,
+                    keyword.range(),
+                    message,
+                );
+            }
+            let value = record.value();
+            let languages = value.as_str().trim_matches('"');
+            if let Err(e) = MetaBuilder::parse_language_list(languages) {
+                self.error(DiagnosticKind::InvalidValue, value.range(), e);
+            }
+        }
+    }
+
     fn validate_os2(&mut self, node: &typed::Os2Table) {
         for item in node.statements() {
             match item {
@@ -242,11 +447,17 @@ impl<'a> ValidationCtx<'a> {
                     Kind::PanoseKw => {
                         for number in item.values() {
                             match number.parse_unsigned() {
-                                None => self.error(number.range(), "expected positive number"),
+                                None => self.error(
+                                    DiagnosticKind::InvalidValue,
+                                    number.range(),
+                                    "expected positive number",
+                                ),
                                 Some(0..=127) => (),
-                                Some(_) => {
-                                    self.error(number.range(), "expected value in range 0..128")
-                                }
+                                Some(_) => self.error(
+                                    DiagnosticKind::InvalidValue,
+                                    number.range(),
+                                    "expected value in range 0..128",
+                                ),
                             }
                         }
                     }
@@ -254,6 +465,7 @@ impl<'a> ValidationCtx<'a> {
                         for number in item.values() {
                             if !(0..128).contains(&number.parse_signed()) {
                                 self.error(
+                                    DiagnosticKind::InvalidValue,
                                     number.range(),
                                     "expected value in unicode character range 0..=127",
                                 );
@@ -267,7 +479,11 @@ impl<'a> ValidationCtx<'a> {
                             )
                             .is_none()
                             {
-                                self.error(number.range(), "not a valid code page");
+                                self.error(
+                                    DiagnosticKind::InvalidValue,
+                                    number.range(),
+                                    "not a valid code page",
+                                );
                             }
                         }
                     }
@@ -279,6 +495,7 @@ impl<'a> ValidationCtx<'a> {
                         Ok(raw_val) => {
                             if let Err((cls, sub)) = validate_os2_family_class(raw_val) {
                                 self.warning(
+                                    DiagnosticKind::InvalidValue,
                                     val.range(),
                                     format!(
                                         "Class {cls}, subclass {sub} is not a known sFamilyClass"
@@ -286,27 +503,39 @@ impl<'a> ValidationCtx<'a> {
                                 )
                             }
                         }
-                        Err(e) => self.error(val.range(), e),
+                        Err(e) => self.error(DiagnosticKind::InvalidValue, val.range(), e),
                     };
                 }
                 typed::Os2TableItem::Metric(i) => {
                     if matches!(i.keyword().kind, Kind::WinAscentKw | Kind::WinDescentKw) {
                         let val = i.metric();
                         if val.parse().is_negative() {
-                            self.error(val.range(), "expected positive number");
+                            self.error(
+                                DiagnosticKind::InvalidValue,
+                                val.range(),
+                                "expected positive number",
+                            );
                         }
                     }
                 }
                 typed::Os2TableItem::Number(item) => {
                     let val = item.number();
                     if val.parse_unsigned().is_none() {
-                        self.error(val.range(), "expected positive number");
+                        self.error(
+                            DiagnosticKind::InvalidValue,
+                            val.range(),
+                            "expected positive number",
+                        );
                     }
                 }
                 typed::Os2TableItem::Vendor(item) => {
                     let val = item.value();
                     if let Err(e) = Tag::from_str(val.as_str().trim_matches('"')) {
-                        self.error(val.range(), format!("invalid tag: '{}'", e));
+                        self.error(
+                            DiagnosticKind::InvalidValue,
+                            val.range(),
+                            format!("invalid tag: '{}'", e),
+                        );
                     }
                 }
             }
@@ -319,7 +548,11 @@ impl<'a> ValidationCtx<'a> {
             match item {
                 typed::StatTableItem::ElidedFallbackName(_) => {
                     if seen_fallback_name {
-                        self.error(item.range(), "fallback name must only be defined once");
+                        self.error(
+                            DiagnosticKind::DuplicateDefinition,
+                            item.range(),
+                            "fallback name must only be defined once",
+                        );
                     }
                     seen_fallback_name = true;
                 }
@@ -327,6 +560,7 @@ impl<'a> ValidationCtx<'a> {
                     let mut seen_location_format = None;
                     for item in axis.statements() {
                         if let typed::StatAxisValueItem::Location(loc) = item {
+                            self.validate_stat_location_range(&loc);
                             let format = match loc.value() {
                                 typed::LocationValue::Value(_) => 'a',
                                 typed::LocationValue::MinMax { .. } => 'b',
@@ -335,8 +569,8 @@ impl<'a> ValidationCtx<'a> {
                             let prev_format = seen_location_format.replace(format);
                             match (prev_format, format) {
                                 (Some('a'), 'a') => (),
-                                (Some(_), 'a') => self.error(loc.range(), "multiple location statements, but previous statement was not format 'a'"),
-                                (Some(_), 'b' | 'c') => self.error(loc.range(),format!("location statement format '{}' must be only statement", format)),
+                                (Some(_), 'a') => self.error(DiagnosticKind::InvalidStatement, loc.range(), "multiple location statements, but previous statement was not format 'a'"),
+                                (Some(_), 'b' | 'c') => self.error(DiagnosticKind::InvalidStatement, loc.range(),format!("location statement format '{}' must be only statement", format)),
                                 _ => (),
                             }
                         }
@@ -347,17 +581,31 @@ impl<'a> ValidationCtx<'a> {
         }
         if !seen_fallback_name {
             self.error(
+                DiagnosticKind::InvalidStatement,
                 node.tag().range(),
                 "STAT table must include 'ElidedFallbackName' or 'ElidedFallbackNameID'",
             );
         }
     }
 
+    /// For a format 2 (range) location, ensure that `min <= nominal <= max`.
+    fn validate_stat_location_range(&mut self, loc: &typed::StatAxisLocation) {
+        let typed::LocationValue::MinMax { nominal, min, max } = loc.value() else { return };
+        let (nominal, min, max) = (nominal.parse(), min.parse(), max.parse());
+        if !(min..=max).contains(&nominal) {
+            self.error(
+                DiagnosticKind::InvalidValue,
+                loc.range(),
+                format!("nominal value {nominal} is outside of the range {min}..={max}"),
+            );
+        }
+    }
+
     fn validate_name(&mut self, node: &typed::NameTable) {
         for record in node.statements() {
             let name_id = record.name_id();
             if let Err(e) = name_id.parse() {
-                self.error(name_id.range(), e);
+                self.error(DiagnosticKind::InvalidValue, name_id.range(), e);
             }
             self.validate_name_spec(&record.entry());
         }
@@ -367,28 +615,47 @@ impl<'a> ValidationCtx<'a> {
         let mut platform = None;
         if let Some(id) = spec.platform_id() {
             match id.parse() {
-                Err(e) => self.error(id.range(), e),
+                Err(e) => self.error(DiagnosticKind::InvalidValue, id.range(), e),
                 Ok(n @ 1 | n @ 3) => platform = Some(n),
-                Ok(_) => self.error(id.range(), "platform id must be one of '1' or '3'"),
+                Ok(_) => self.error(
+                    DiagnosticKind::InvalidValue,
+                    id.range(),
+                    "platform id must be one of '1' or '3'",
+                ),
             }
         };
 
         let platform = platform.unwrap_or(WIN_PLATFORM_ID);
 
         if let Err((range, err)) = validate_name_string_encoding(platform, spec.string()) {
-            self.error(range, err);
+            self.error(DiagnosticKind::InvalidValue, range, err);
+        } else if platform == WIN_PLATFORM_ID {
+            if let Err((range, err)) = validate_utf16_surrogates(spec.string()) {
+                self.error(DiagnosticKind::InvalidValue, range, err);
+            }
+        }
+        let encoding = spec
+            .platform_and_language_ids()
+            .map(|(encoding, _)| encoding.parse().ok())
+            .unwrap_or(Some(0));
+        if platform == MAC_PLATFORM_ID && encoding == Some(0) {
+            if let Err((range, err)) = validate_mac_roman_chars(spec.string()) {
+                self.error(DiagnosticKind::InvalidValue, range, err);
+            }
         }
         if let Some((platspec, language)) = spec.platform_and_language_ids() {
             match (platspec.parse(), language.parse()) {
-                (Ok(a), Ok(_)) if Encoding::new(platform, a) == Encoding::Unknown => {
-                    self.warning(spec.range(), "character encoding unsupported")
-                }
+                (Ok(a), Ok(_)) if Encoding::new(platform, a) == Encoding::Unknown => self.warning(
+                    DiagnosticKind::Unsupported,
+                    spec.range(),
+                    "character encoding unsupported",
+                ),
                 (a, b) => {
                     if let Err(e) = a {
-                        self.error(platspec.range(), e);
+                        self.error(DiagnosticKind::InvalidValue, platspec.range(), e);
                     }
                     if let Err(e) = b {
-                        self.error(language.range(), e);
+                        self.error(DiagnosticKind::InvalidValue, language.range(), e);
                     }
                 }
             };
@@ -419,7 +686,11 @@ impl<'a> ValidationCtx<'a> {
                     self.validate_glyph_or_class(&node.target());
                     for idx in node.indices() {
                         if idx.parse_unsigned().is_none() {
-                            self.error(idx.range(), "contourpoint indexes must be non-negative");
+                            self.error(
+                                DiagnosticKind::InvalidValue,
+                                idx.range(),
+                                "contourpoint indexes must be non-negative",
+                            );
                         }
                     }
                 }
@@ -430,7 +701,11 @@ impl<'a> ValidationCtx<'a> {
                     if let typed::LigatureCaretValue::Pos(node) = node.values() {
                         for idx in node.values() {
                             if idx.parse_unsigned().is_none() {
-                                self.error(idx.range(), "contourpoint index must be non-negative");
+                                self.error(
+                                    DiagnosticKind::InvalidValue,
+                                    idx.range(),
+                                    "contourpoint index must be non-negative",
+                                );
                             }
                         }
                     }
@@ -443,17 +718,26 @@ impl<'a> ValidationCtx<'a> {
         let mut prev = None;
         for statement in node.statements() {
             if let Some(prev) = prev.replace(statement.range()) {
-                self.warning(prev, "FontRevision overwritten by subsequent statement");
+                self.warning(
+                    DiagnosticKind::NoEffect,
+                    prev,
+                    "FontRevision overwritten by subsequent statement; the last value is used",
+                );
             }
             let value = statement.value();
             let (int, fract) = value.text().split_once('.').expect("checked at parse time");
             if int.parse::<i16>().is_err() {
                 let start = value.range().start;
-                self.error(start..start + int.len(), "value exceeds 16bit limit");
+                self.error(
+                    DiagnosticKind::InvalidValue,
+                    start..start + int.len(),
+                    "value exceeds 16bit limit",
+                );
             }
             if fract.len() != 3 {
                 let start = value.range().start + int.len();
                 self.warning(
+                    DiagnosticKind::InvalidValue,
                     start..start + fract.len(),
                     "version number should have exactly three decimal places",
                 );
@@ -489,7 +773,15 @@ impl<'a> ValidationCtx<'a> {
             self.validate_character_variant_items(&mut statement_iter);
         }
 
-        for item in statement_iter {
+        self.validate_feature_statements(statement_iter, tag_raw);
+    }
+
+    fn validate_feature_statements<'b>(
+        &mut self,
+        statements: impl Iterator<Item = &'b NodeOrToken>,
+        tag_raw: Tag,
+    ) {
+        for item in statements {
             if item.kind() == Kind::ScriptNode
                 || item.kind() == Kind::LanguageNode
                 || item.kind() == Kind::SubtableNode
@@ -504,27 +796,112 @@ impl<'a> ValidationCtx<'a> {
             } else if let Some(node) = typed::GsubStatement::cast(item) {
                 self.validate_gsub_statement(&node);
             } else if let Some(node) = typed::GposStatement::cast(item) {
-                self.validate_gpos_statement(&node);
+                self.validate_gpos_statement_in_feature(&node, tag_raw);
             } else if let Some(node) = typed::GlyphClassDef::cast(item) {
                 self.validate_glyph_class_def(&node);
             } else if let Some(node) = typed::MarkClassDef::cast(item) {
                 self.validate_mark_class_def(&node);
             } else if let Some(_node) = typed::FeatureNames::cast(item) {
-                self.warning(item.range(), "Only one featureNames block is allowed, it must preceed all rules, and it is only valid in features ss01-ss20");
+                self.warning(DiagnosticKind::InvalidStatement, item.range(), "Only one featureNames block is allowed, it must preceed all rules, and it is only valid in features ss01-ss20");
             } else if let Some(node) = typed::FeatureRef::cast(item) {
                 self.error(
+                    DiagnosticKind::InvalidStatement,
                     node.keyword().range(),
                     "feature reference only valid in 'aalt' feature",
                 );
             } else {
-                self.error(
-                    item.range(),
-                    format!("unhandled item '{}' in feature", item.kind()),
-                );
+                self.unknown_item(item, format!("unhandled item '{}' in feature", item.kind()));
             }
         }
     }
 
+    fn validate_condition_set(&mut self, node: &typed::ConditionSet) {
+        if !self.check_variable_syntax(node.keyword().range()) {
+            return;
+        }
+        let label = node.label();
+        if let Some(_prev) = self
+            .condition_sets
+            .insert(label.text.clone(), label.clone())
+        {
+            self.error(
+                DiagnosticKind::DuplicateDefinition,
+                label.range(),
+                format!(
+                    "A conditionset named '{}' has already been defined",
+                    label.text
+                ),
+            );
+        }
+
+        let Some(axis_map) = self.axis_map.as_ref() else {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                label.range(),
+                "conditionsets require an axis map",
+            );
+            return;
+        };
+        let mut errors = Vec::new();
+        let mut seen_axes = HashSet::new();
+        for condition in node.conditions() {
+            let tag = condition.tag();
+            let Some(axis) = axis_map.get(tag.to_raw()) else {
+                let msg = format!("axis '{}' is not in the axis map", tag.text());
+                errors.push((tag.range(), msg));
+                continue;
+            };
+            if !seen_axes.insert(axis.tag) {
+                let msg = format!("axis '{}' appears more than once", tag.text());
+                errors.push((tag.range(), msg));
+            }
+            let (min, max) = (condition.min(), condition.max());
+            for value in [&min, &max] {
+                let coord = value.parse();
+                if !axis.contains(coord) {
+                    let msg = format!(
+                        "{coord} is outside the range of axis '{}' ({}..={})",
+                        axis.tag, axis.min, axis.max
+                    );
+                    errors.push((value.range(), msg));
+                }
+            }
+            if min.parse() > max.parse() {
+                let msg = "condition minimum is greater than its maximum";
+                errors.push((condition.range(), msg.into()));
+            }
+        }
+        for (range, msg) in errors {
+            self.error(DiagnosticKind::InvalidValue, range, msg);
+        }
+    }
+
+    fn validate_feature_variation(&mut self, node: &typed::FeatureVariation) {
+        if !self.check_variable_syntax(node.keyword().range()) {
+            return;
+        }
+        let tag = node.tag();
+        let tag_raw = tag.to_raw();
+        if tag_raw == tags::AALT || tag_raw == tags::SIZE {
+            return self.error(
+                DiagnosticKind::InvalidStatement,
+                tag.range(),
+                format!("'{tag_raw}' feature cannot be used in a variation block"),
+            );
+        }
+        self.all_features.insert(tag_raw);
+
+        let condition_set = node.condition_set();
+        if !self.condition_sets.contains_key(&condition_set.text) {
+            self.error(
+                DiagnosticKind::UndefinedName,
+                condition_set.range(),
+                format!("conditionset '{}' is not defined", condition_set.text),
+            );
+        }
+        self.validate_feature_statements(node.statements(), tag_raw);
+    }
+
     fn validate_stylistic_set_items<'b>(
         &mut self,
         iter: &mut impl Iterator<Item = &'b NodeOrToken>,
@@ -551,9 +928,20 @@ impl<'a> ValidationCtx<'a> {
                 Kind::ParamUiLabelNameIdKw,
             ] {
                 if !node.iter().any(|x| x.kind() == kind) {
-                    self.warning(node.keyword().range(), format!("missing '{kind}' node"));
+                    self.warning(
+                        DiagnosticKind::InvalidStatement,
+                        node.keyword().range(),
+                        format!("missing '{kind}' node"),
+                    );
                 }
             }
+            let names = node
+                .iter()
+                .filter_map(typed::CvParametersName::cast)
+                .collect::<Vec<_>>();
+            for spec in names.iter().flat_map(|name| name.statements()) {
+                self.validate_name_spec(&spec);
+            }
 
             iter.next();
         }
@@ -567,6 +955,7 @@ impl<'a> ValidationCtx<'a> {
                         self.validate_gsub_statement(&node)
                     }
                     _ => self.error(
+                        DiagnosticKind::InvalidStatement,
                         node.range(),
                         "only Single and Alternate rules allowed in aalt feature",
                     ),
@@ -576,10 +965,15 @@ impl<'a> ValidationCtx<'a> {
                 let range = tag.range();
                 let raw_tag = tag.to_raw();
                 if self.aalt_referenced_features.insert(raw_tag, tag).is_some() {
-                    self.warning(range, "feature already declared")
+                    self.warning(
+                        DiagnosticKind::DuplicateDefinition,
+                        range,
+                        "feature already declared",
+                    )
                 }
             } else if !item.kind().is_trivia() {
                 self.error(
+                    DiagnosticKind::InvalidStatement,
                     item.range(),
                     "aalt can only contain feature names and single or alternate sub rules.",
                 );
@@ -594,6 +988,7 @@ impl<'a> ValidationCtx<'a> {
             if let Some(node) = typed::Parameters::cast(item) {
                 if param.is_some() {
                     self.error(
+                        DiagnosticKind::InvalidStatement,
                         node.range(),
                         "size feature can have only one 'parameters' statement",
                     );
@@ -604,6 +999,7 @@ impl<'a> ValidationCtx<'a> {
                 menu_name_count += 1;
             } else if !item.kind().is_trivia() {
                 self.error(
+                    DiagnosticKind::InvalidStatement,
                     item.range(),
                     "size can only contain feature names and single or alternate sub rules.",
                 );
@@ -612,6 +1008,7 @@ impl<'a> ValidationCtx<'a> {
 
         match param {
             None => self.error(
+                DiagnosticKind::InvalidStatement,
                 node.tag().range(),
                 "size feature must include a 'parameters' statement",
             ),
@@ -623,6 +1020,7 @@ impl<'a> ValidationCtx<'a> {
                 {
                     //TODO: better diagnostics
                     self.error(
+                        DiagnosticKind::InvalidStatement,
                         param.range(),
                         "if subfamily is omitted, there must be no 'sizemenuname' statements",
                     );
@@ -635,6 +1033,7 @@ impl<'a> ValidationCtx<'a> {
         let name = node.label();
         if in_feature == Some(tags::AALT) || in_feature == Some(tags::SIZE) {
             self.error(
+                DiagnosticKind::InvalidStatement,
                 name.range(),
                 format!(
                     "lookups are not allowed in '{}' feature",
@@ -646,6 +1045,7 @@ impl<'a> ValidationCtx<'a> {
         if let Some(_prev) = self.lookup_defs.insert(name.text.clone(), name.clone()) {
             //TODO: annotate with previous location
             self.error(
+                DiagnosticKind::DuplicateDefinition,
                 name.range(),
                 format!("A lookup named '{}' has already been defined", name.text),
             );
@@ -654,6 +1054,7 @@ impl<'a> ValidationCtx<'a> {
             if item.kind().is_rule() {
                 match kind {
                     Some(kind) if kind != item.kind() => self.error(
+                        DiagnosticKind::InvalidStatement,
                         item.range(),
                         format!(
                             "multiple rule types in lookup block (saw '{}' after '{}')",
@@ -667,6 +1068,7 @@ impl<'a> ValidationCtx<'a> {
             if item.kind() == Kind::ScriptNode || item.kind() == Kind::LanguageNode {
                 if in_feature.is_none() {
                     self.error(
+                        DiagnosticKind::InvalidStatement,
                         item.range(),
                         "script and language statements not allowed in standalone lookup blocks",
                     );
@@ -677,6 +1079,7 @@ impl<'a> ValidationCtx<'a> {
                 if in_feature.is_none() {
                     //TODO: verify that this is accurate
                     self.warning(
+                        DiagnosticKind::NoEffect,
                         node.range(),
                         "lookup reference outside of feature is ignored",
                     );
@@ -684,12 +1087,14 @@ impl<'a> ValidationCtx<'a> {
                 self.validate_lookup_ref(&node);
             } else if let Some(node) = typed::LookupBlock::cast(item) {
                 self.error(
+                    DiagnosticKind::InvalidStatement,
                     node.keyword().range(),
                     "lookup blocks cannot contain other blocks",
                 );
             } else if let Some(node) = typed::LookupFlag::cast(item) {
                 if kind.is_some() {
                     self.error(
+                        DiagnosticKind::InvalidStatement,
                         node.range(),
                         "all rules in named lookup must have same lookup flags",
                     );
@@ -698,20 +1103,35 @@ impl<'a> ValidationCtx<'a> {
             } else if let Some(node) = typed::GsubStatement::cast(item) {
                 self.validate_gsub_statement(&node);
             } else if let Some(node) = typed::GposStatement::cast(item) {
-                self.validate_gpos_statement(&node);
+                match in_feature {
+                    Some(tag) => self.validate_gpos_statement_in_feature(&node, tag),
+                    None => self.validate_gpos_statement(&node),
+                }
             } else if let Some(node) = typed::GlyphClassDef::cast(item) {
                 self.validate_glyph_class_def(&node);
             } else if let Some(node) = typed::MarkClassDef::cast(item) {
                 self.validate_mark_class_def(&node);
             } else {
-                self.error(
-                    item.range(),
+                self.unknown_item(
+                    item,
                     format!("unhandled item {} in lookup block", item.kind()),
                 );
             }
         }
     }
 
+    fn validate_gpos_statement_in_feature(&mut self, node: &typed::GposStatement, feature: Tag) {
+        // ccmp runs before other features, and is only for (de)composition
+        if feature == tags::CCMP {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                node.range(),
+                "'ccmp' feature can only contain substitution rules",
+            );
+        }
+        self.validate_gpos_statement(node);
+    }
+
     fn validate_gpos_statement(&mut self, node: &typed::GposStatement) {
         match node {
             typed::GposStatement::Type1(rule) => {
@@ -739,9 +1159,11 @@ impl<'a> ValidationCtx<'a> {
                     self.validate_anchor(&mark.anchor());
                     match mark.mark_class_name() {
                         Some(name) => self.validate_mark_class(&name),
-                        None => {
-                            self.error(mark.range(), "mark-to-base attachments should not be null")
-                        }
+                        None => self.error(
+                            DiagnosticKind::InvalidStatement,
+                            mark.range(),
+                            "mark-to-base attachments should not be null",
+                        ),
                     }
                 }
             }
@@ -757,6 +1179,7 @@ impl<'a> ValidationCtx<'a> {
                             None => {
                                 if anchor.null().is_none() {
                                     self.error(
+                                        DiagnosticKind::InvalidStatement,
                                         anchor.range(),
                                         "non-NULL anchor must specify mark class",
                                     );
@@ -773,9 +1196,11 @@ impl<'a> ValidationCtx<'a> {
                     self.validate_anchor(&mark.anchor());
                     match mark.mark_class_name() {
                         Some(name) => self.validate_mark_class(&name),
-                        None => {
-                            self.error(mark.range(), "mark-to-mark attachments should not be null")
-                        }
+                        None => self.error(
+                            DiagnosticKind::InvalidStatement,
+                            mark.range(),
+                            "mark-to-mark attachments should not be null",
+                        ),
                     }
                 }
             }
@@ -806,6 +1231,7 @@ impl<'a> ValidationCtx<'a> {
                 self.validate_lookup_ref(&lookup);
                 if seen_inline {
                     self.error(
+                        DiagnosticKind::InvalidStatement,
                         lookup.range(),
                         "rule cannot have both explicit lookups and inline position values",
                     );
@@ -815,6 +1241,7 @@ impl<'a> ValidationCtx<'a> {
             if let Some(value) = item.valuerecord() {
                 if seen_lookup {
                     self.error(
+                        DiagnosticKind::InvalidStatement,
                         value.range(),
                         "rule cannot have both inline rules and explicit lookups",
                     );
@@ -830,8 +1257,14 @@ impl<'a> ValidationCtx<'a> {
             typed::GsubStatement::Type1(rule) => {
                 //TODO: ensure equal lengths, other requirements
                 self.validate_glyph_or_class(&rule.target());
-                if let Some(replacement) = rule.replacement() {
-                    self.validate_glyph_or_class(&replacement);
+                match rule.replacement() {
+                    Some(replacement) => self.validate_glyph_or_class(&replacement),
+                    // this compiles the same as 'by NULL', but is more likely a typo
+                    None => self.warning(
+                        DiagnosticKind::InvalidStatement,
+                        rule.range(),
+                        "substitution has no replacement; use 'by NULL' to delete glyphs",
+                    ),
                 }
             }
             typed::GsubStatement::Type2(rule) => {
@@ -843,7 +1276,11 @@ impl<'a> ValidationCtx<'a> {
                 }
                 if count < 2 {
                     let range = range_for_iter(rule.replacement()).unwrap_or_else(|| rule.range());
-                    self.error(range, "sequence must contain at least two items");
+                    self.error(
+                        DiagnosticKind::InvalidStatement,
+                        range,
+                        "sequence must contain at least two items",
+                    );
                 }
             }
             typed::GsubStatement::Type3(rule) => {
@@ -858,7 +1295,11 @@ impl<'a> ValidationCtx<'a> {
                 }
                 if count < 2 {
                     let range = range_for_iter(rule.target()).unwrap_or_else(|| rule.range());
-                    self.error(range, "sequence must contain at least two items");
+                    self.error(
+                        DiagnosticKind::InvalidStatement,
+                        range,
+                        "sequence must contain at least two items",
+                    );
                 }
                 self.validate_glyph(&rule.replacement());
             }
@@ -893,6 +1334,7 @@ impl<'a> ValidationCtx<'a> {
                     let target = item.target();
                     if i == 0 && inline_class_sub && !target.is_class() {
                         self.error(
+                            DiagnosticKind::InvalidStatement,
                             input_seq.range(),
                             "if replacing by glyph class, input sequence must be a single glyph class",
                         );
@@ -901,6 +1343,7 @@ impl<'a> ValidationCtx<'a> {
                     for lookup in item.lookups() {
                         if has_inline_rule {
                             self.error(
+                                DiagnosticKind::InvalidStatement,
                                 lookup.range(),
                                 "named lookup not allowed in statement that includes inline rule",
                             );
@@ -917,6 +1360,7 @@ impl<'a> ValidationCtx<'a> {
                 for (i, item) in rule.input().items().enumerate() {
                     if i > 0 {
                         self.error(
+                            DiagnosticKind::InvalidStatement,
                             item.range(),
                             "rsub rules can have only one item in the input sequence",
                         );
@@ -925,7 +1369,7 @@ impl<'a> ValidationCtx<'a> {
                         self.validate_glyph_or_class(&target);
                         input_class = item.target().is_class();
                         if let Some(lookup) = item.lookups().next() {
-                            self.error(lookup.range(), "explicit lookups in rsub rules are not supported, although they should be. Please file an issue at https://github.com/cmyr/fea-rs/issues");
+                            self.error(DiagnosticKind::Unsupported, lookup.range(), "explicit lookups in rsub rules are not supported, although they should be. Please file an issue at https://github.com/cmyr/fea-rs/issues");
                         }
                     }
                 }
@@ -934,7 +1378,11 @@ impl<'a> ValidationCtx<'a> {
                         debug_assert!(inline.replacement_glyphs().next().is_none());
                         self.validate_glyph_class(&class, true);
                         if !input_class {
-                            self.error(class.range(), "class can only substitute another class");
+                            self.error(
+                                DiagnosticKind::InvalidStatement,
+                                class.range(),
+                                "class can only substitute another class",
+                            );
                         }
                     } else if let Some(glyph) = inline.replacement_glyphs().next() {
                         self.validate_glyph(&glyph);
@@ -957,7 +1405,11 @@ impl<'a> ValidationCtx<'a> {
     fn validate_lookupflag(&mut self, node: &typed::LookupFlag) {
         if let Some(number) = node.number() {
             if number.text().parse::<u16>().is_err() {
-                self.error(number.range(), "value must be a positive 16 bit integer");
+                self.error(
+                    DiagnosticKind::InvalidValue,
+                    number.range(),
+                    "value must be a positive 16 bit integer",
+                );
             }
             return;
         }
@@ -977,13 +1429,15 @@ impl<'a> ValidationCtx<'a> {
                 Kind::IgnoreLigaturesKw if !ignore_lig => ignore_lig = true,
                 Kind::IgnoreMarksKw if !ignore_marks => ignore_marks = true,
 
-                //FIXME: we are not enforcing some requirements here. in particular,
-                // The glyph sets of the referenced classes must not overlap, and the MarkAttachmentType statement can reference at most 15 different classes.
+                // The glyph sets of the referenced classes must not overlap, and
+                // there can be at most 15 different classes; we check these
+                // during compilation, once the classes are resolved.
                 Kind::MarkAttachmentTypeKw if !mark_set => {
                     mark_set = true;
                     match iter.next().and_then(typed::GlyphClass::cast) {
                         Some(node) => self.validate_glyph_class(&node, true),
                         None => self.error(
+                            DiagnosticKind::InvalidStatement,
                             next.range(),
                             "MarkAttachmentType should be followed by glyph class",
                         ),
@@ -994,6 +1448,7 @@ impl<'a> ValidationCtx<'a> {
                     match iter.next().and_then(typed::GlyphClass::cast) {
                         Some(node) => self.validate_glyph_class(&node, true),
                         None => self.error(
+                            DiagnosticKind::InvalidStatement,
                             next.range(),
                             "MarkAttachmentType should be followed by glyph class",
                         ),
@@ -1004,19 +1459,25 @@ impl<'a> ValidationCtx<'a> {
                 | Kind::IgnoreMarksKw
                 | Kind::IgnoreLigaturesKw
                 | Kind::MarkAttachmentTypeKw
-                | Kind::UseMarkFilteringSetKw => {
-                    self.error(next.range(), "duplicate value in lookupflag")
-                }
+                | Kind::UseMarkFilteringSetKw => self.error(
+                    DiagnosticKind::InvalidValue,
+                    next.range(),
+                    "duplicate value in lookupflag",
+                ),
 
-                _ => self.error(next.range(), "invalid lookupflag value"),
+                _ => self.error(
+                    DiagnosticKind::InvalidValue,
+                    next.range(),
+                    "invalid lookupflag value",
+                ),
             }
         }
     }
 
     fn validate_glyph_or_class(&mut self, node: &typed::GlyphOrClass) {
         match node {
-            typed::GlyphOrClass::Glyph(name) => self.validate_glyph_name(name),
-            typed::GlyphOrClass::Cid(cid) => self.validate_cid(cid),
+            typed::GlyphOrClass::Glyph(name) => self.validate_glyph_name(name, false),
+            typed::GlyphOrClass::Cid(cid) => self.validate_cid(cid, false),
             typed::GlyphOrClass::Class(class) => self.validate_glyph_class_literal(class, true),
             typed::GlyphOrClass::NamedClass(name) => self.validate_glyph_class_ref(name, true),
             typed::GlyphOrClass::Null(_) => (),
@@ -1025,8 +1486,8 @@ impl<'a> ValidationCtx<'a> {
 
     fn validate_glyph(&mut self, node: &typed::Glyph) {
         match node {
-            typed::Glyph::Named(name) => self.validate_glyph_name(name),
-            typed::Glyph::Cid(cid) => self.validate_cid(cid),
+            typed::Glyph::Named(name) => self.validate_glyph_name(name, false),
+            typed::Glyph::Cid(cid) => self.validate_cid(cid, false),
             typed::Glyph::Null(_) => (),
         }
     }
@@ -1049,9 +1510,9 @@ impl<'a> ValidationCtx<'a> {
     ) {
         for item in node.items() {
             if let Some(id) = typed::GlyphName::cast(item) {
-                self.validate_glyph_name(&id);
+                self.validate_glyph_name(&id, true);
             } else if let Some(id) = typed::Cid::cast(item) {
-                self.validate_cid(&id);
+                self.validate_cid(&id, true);
             } else if let Some(range) = typed::GlyphRange::cast(item) {
                 self.validate_glyph_range(&range);
             } else if let Some(alias) = typed::GlyphClassName::cast(item) {
@@ -1061,20 +1522,46 @@ impl<'a> ValidationCtx<'a> {
                 && item.kind() != Kind::Ident
                 && item.kind() != Kind::GlyphNameOrRange
             {
-                self.warning(item.range(), format!("unexpected item {}", item.kind()));
+                self.warning(
+                    DiagnosticKind::UnknownItem,
+                    item.range(),
+                    format!("unexpected item {}", item.kind()),
+                );
             }
         }
     }
 
-    fn validate_glyph_name(&mut self, name: &typed::GlyphName) {
+    fn validate_glyph_name(&mut self, name: &typed::GlyphName, in_class: bool) {
         if self.glyph_map.get(name.text()).is_none() {
-            self.error(name.range(), "glyph not in font");
+            let skip = in_class && self.skip_missing_class_glyphs;
+            let msg = missing_glyph_message(self.glyph_map, name.text(), skip);
+            self.report_missing_glyph(name.range(), msg, skip);
+        }
+    }
+
+    /// Report a glyph that is not in the font.
+    ///
+    /// This is an error, unless `skip` is true, which means that the glyph is
+    /// in a class and [`Opts::skip_missing_class_glyphs`] is set.
+    fn report_missing_glyph(&mut self, range: Range<usize>, message: String, skip: bool) {
+        if skip {
+            self.warning(DiagnosticKind::UndefinedGlyph, range, message);
+        } else {
+            self.error(DiagnosticKind::UndefinedGlyph, range, message);
         }
     }
 
-    fn validate_cid(&mut self, cid: &typed::Cid) {
-        if self.glyph_map.get(&cid.parse()).is_none() {
-            self.error(cid.range(), "CID not in font");
+    fn validate_cid(&mut self, cid: &typed::Cid, in_class: bool) {
+        if !self.glyph_map.is_cid_keyed() {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                cid.range(),
+                CID_REQUIRES_CID_KEYED_MAP,
+            );
+        } else if self.glyph_map.get(&cid.parse()).is_none() {
+            let skip = in_class && self.skip_missing_class_glyphs;
+            let msg = missing_cid_message(self.glyph_map, cid.parse(), skip);
+            self.report_missing_glyph(cid.range(), msg, skip);
         }
     }
 
@@ -1083,13 +1570,21 @@ impl<'a> ValidationCtx<'a> {
             return;
         }
         if !self.glyph_class_defs.contains_key(node.text()) {
-            self.error(node.range(), "undefined glyph class");
+            self.error(
+                DiagnosticKind::UndefinedGlyphClass,
+                node.range(),
+                "undefined glyph class",
+            );
         }
     }
 
     fn validate_lookup_ref(&mut self, node: &typed::LookupRef) {
         if !self.lookup_defs.contains_key(&node.label().text) {
-            self.error(node.label().range(), "lookup is not defined");
+            self.error(
+                DiagnosticKind::UndefinedName,
+                node.label().range(),
+                "lookup is not defined",
+            );
         }
     }
 
@@ -1098,47 +1593,186 @@ impl<'a> ValidationCtx<'a> {
         let end = range.end();
 
         match (start.kind, end.kind) {
+            (Kind::Cid, Kind::Cid) if !self.glyph_map.is_cid_keyed() => {
+                self.error(
+                    DiagnosticKind::InvalidStatement,
+                    range.range(),
+                    CID_REQUIRES_CID_KEYED_MAP,
+                );
+            }
             (Kind::Cid, Kind::Cid) => {
                 if let Err(err) = glyph_range::cid(start, end, |cid| {
                     if self.glyph_map.get(&cid).is_none() {
                         // this is techincally allowed, but we error for now
                         self.warning(
+                            DiagnosticKind::UndefinedGlyph,
                             range.range(),
                             format!("Range member '{}' does not exist in font", cid),
                         );
                     }
                 }) {
-                    self.error(range.range(), err);
+                    self.error(DiagnosticKind::InvalidValue, range.range(), err);
                 }
             }
             (Kind::GlyphName, Kind::GlyphName) => {
                 if let Err(err) = glyph_range::named(start, end, |name| {
                     if self.glyph_map.get(name).is_none() {
                         self.warning(
+                            DiagnosticKind::UndefinedGlyph,
                             range.range(),
                             format!("Range member '{}' does not exist in font", name),
                         );
                     }
                 }) {
-                    self.error(range.range(), err);
+                    self.error(DiagnosticKind::InvalidValue, range.range(), err);
                 }
             }
-            (_, _) => self.error(range.range(), "Invalid types in glyph range"),
+            (_, _) => self.error(
+                DiagnosticKind::InvalidValue,
+                range.range(),
+                "Invalid types in glyph range",
+            ),
         }
     }
 
     fn validate_value_record(&mut self, node: &typed::ValueRecord) {
         if let Some(name) = node.named() {
             if !self.value_record_defs.contains_key(&name.text) {
-                self.error(name.range(), "undefined value record name");
+                self.error(
+                    DiagnosticKind::UndefinedName,
+                    name.range(),
+                    "undefined value record name",
+                );
+            }
+        }
+        let placement = node.placement().into_iter().flatten();
+        for metric in node.advance().into_iter().chain(placement) {
+            if let typed::Scalar::Variable(metric) = metric {
+                self.validate_variable_metric(&metric);
+            }
+        }
+        for device in node.device().into_iter().flatten() {
+            self.validate_device(&device);
+        }
+        if let Some((metrics, devices)) = node.placement().zip(node.device()) {
+            for (metric, device) in metrics.iter().zip(devices.iter()) {
+                if matches!(metric, typed::Scalar::Variable(_)) {
+                    self.validate_variable_device(device);
+                }
             }
         }
     }
 
+    /// A variable metric has its own device table, so it can't have another.
+    fn validate_variable_device(&mut self, device: &typed::Device) {
+        if device.null().is_none() {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                device.range(),
+                "a variable metric cannot also have a device",
+            );
+        }
+    }
+
+    fn validate_device(&mut self, device: &typed::Device) {
+        if let Some(name) = device.name() {
+            if !self.device_defs.contains_key(&name.text) {
+                self.error(
+                    DiagnosticKind::UndefinedName,
+                    name.range(),
+                    format!("device '{}' is not defined", name.text),
+                );
+            }
+        }
+    }
+
+    fn validate_variable_metric(&mut self, metric: &typed::VariableMetric) {
+        if !self.check_variable_syntax(metric.range()) {
+            return;
+        }
+        let Some(axis_map) = self.axis_map.as_ref() else {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                metric.range(),
+                "variable metrics require an axis map",
+            );
+            return;
+        };
+        let mut errors = Vec::new();
+        let mut has_default = false;
+        for value in metric.location_values() {
+            let mut is_default = true;
+            for item in value.location().items() {
+                let tag = item.axis();
+                let Some(axis) = axis_map.get(tag.to_raw()) else {
+                    let msg = format!("axis '{}' is not in the axis map", tag.text());
+                    errors.push((tag.range(), msg));
+                    continue;
+                };
+                let coord = item.value().parse();
+                if !axis.contains(coord) {
+                    let msg = format!(
+                        "{coord} is outside the range of axis '{}' ({}..={})",
+                        axis.tag, axis.min, axis.max
+                    );
+                    errors.push((item.value().range(), msg));
+                }
+                is_default &= coord == axis.default;
+            }
+            has_default |= is_default;
+        }
+        if !has_default {
+            let msg = "variable metric must include a value at the default location";
+            errors.push((metric.range(), msg.into()));
+        }
+        for (range, msg) in errors {
+            self.error(DiagnosticKind::InvalidValue, range, msg);
+        }
+    }
+
     fn validate_anchor(&mut self, anchor: &typed::Anchor) {
+        let mut is_variable = [false; 2];
+        if let Some((x, y)) = anchor.coords() {
+            for (coord, is_variable) in [x, y].into_iter().zip(is_variable.iter_mut()) {
+                if let typed::Scalar::Variable(metric) = coord {
+                    self.validate_variable_metric(&metric);
+                    *is_variable = true;
+                }
+            }
+        }
+        if let Some((x_device, y_device)) = anchor.devices() {
+            self.validate_device(&x_device);
+            self.validate_device(&y_device);
+            for (device, is_variable) in [x_device, y_device].iter().zip(is_variable) {
+                if is_variable {
+                    self.validate_variable_device(device);
+                }
+            }
+        }
+        let point = anchor.contourpoint();
+        if let Some(point) = point.filter(|_| is_variable.contains(&true)) {
+            self.error(
+                DiagnosticKind::InvalidStatement,
+                point.range(),
+                "an anchor with a variable coordinate cannot have a contourpoint",
+            );
+        }
         if let Some(name) = anchor.name() {
-            if !self.anchor_defs.contains_key(&name.text) {
-                self.error(name.range(), "undefined anchor name");
+            if self.anchor_defs.contains_key(&name.text) {
+                return;
+            }
+            if self.all_anchor_names.contains(&name.text) {
+                self.error(
+                    DiagnosticKind::InvalidStatement,
+                    name.range(),
+                    format!("anchor '{}' is defined after its use here", name.text),
+                );
+            } else {
+                self.error(
+                    DiagnosticKind::UndefinedName,
+                    name.range(),
+                    format!("anchor '{}' is not defined", name.text),
+                );
             }
         }
     }
@@ -1216,6 +1850,62 @@ fn validate_name_string_encoding(
     Ok(())
 }
 
+/// Ensure that surrogates in Windows escape sequences form valid pairs.
+///
+/// This assumes the escape sequences are otherwise well-formed.
+fn validate_utf16_surrogates(string: &Token) -> Result<(), (Range<usize>, String)> {
+    let text = string.as_str();
+    let token_start = string.range().start;
+    let unpaired = |pos: usize| {
+        let start = token_start + pos;
+        (
+            start..start + 5,
+            "unpaired surrogate in escape sequence".to_string(),
+        )
+    };
+    let mut high_surrogate = None;
+    let mut pos = 0;
+    while pos < text.len() {
+        if text.as_bytes()[pos] != b'\\' {
+            if let Some(start) = high_surrogate.take() {
+                return Err(unpaired(start));
+            }
+            pos += 1;
+            continue;
+        }
+        let unit = u16::from_str_radix(&text[pos + 1..pos + 5], 16).expect("already validated");
+        match (high_surrogate.take(), unit) {
+            (None, 0xD800..=0xDBFF) => high_surrogate = Some(pos),
+            (Some(_), 0xDC00..=0xDFFF) => (),
+            (Some(start), _) => return Err(unpaired(start)),
+            (None, 0xDC00..=0xDFFF) => return Err(unpaired(pos)),
+            (None, _) => (),
+        }
+        pos += 5;
+    }
+    Ok(())
+}
+
+/// Ensure that the characters in a Mac name string can be encoded as MacRoman.
+///
+/// Escape sequences are MacRoman bytes, and are checked separately.
+fn validate_mac_roman_chars(string: &Token) -> Result<(), (Range<usize>, String)> {
+    let token_start = string.range().start;
+    let mut chars = string.as_str().char_indices();
+    while let Some((pos, c)) = chars.next() {
+        if c == '\\' {
+            chars.nth(1);
+        } else if char_to_mac_roman(c).is_none() {
+            let start = token_start + pos;
+            return Err((
+                start..start + c.len_utf8(),
+                format!("'{c}' cannot be encoded in MacRoman"),
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// adapted from <https://learn.microsoft.com/en-us/typography/opentype/spec/ibmfc>
 fn validate_os2_family_class(raw: u16) -> Result<u16, (u8, u8)> {
     let [cls, subcls] = raw.to_be_bytes();
@@ -1237,7 +1927,28 @@ fn validate_os2_family_class(raw: u16) -> Result<u16, (u8, u8)> {
 
 #[cfg(test)]
 mod tests {
+    use std::{ffi::OsStr, sync::Arc};
+
     use super::*;
+    use crate::parse::SourceLoadError;
+
+    /// Parse and validate, returning the text of any diagnostics.
+    fn validate_messages(fea: &str) -> Vec<String> {
+        validate_messages_with_opts(fea, &Opts::new())
+    }
+
+    fn validate_messages_with_opts(fea: &str, opts: &Opts) -> Vec<String> {
+        let glyph_map = crate::util::ttx::make_glyph_map();
+        let fea: Arc<str> = fea.into();
+        let resolver = move |_: &OsStr| -> Result<Arc<str>, SourceLoadError> { Ok(fea.clone()) };
+        let (tree, errs) =
+            crate::parse::parse_root("test.fea".into(), Some(&glyph_map), resolver).unwrap();
+        assert!(errs.is_empty(), "{errs:?}");
+        super::super::validate(&tree, &glyph_map, opts)
+            .iter()
+            .map(|diag| diag.text().to_owned())
+            .collect()
+    }
 
     #[test]
     fn os2_family_class() {
@@ -1247,4 +1958,255 @@ mod tests {
         assert!(validate_os2_family_class(0x0203).is_err());
         assert!(validate_os2_family_class(0x0600).is_err());
     }
+
+    #[test]
+    fn anchor_forward_reference() {
+        let fea = "\
+        feature test {
+            pos cursive a <anchor ENTRY> <anchor NULL>;
+        } test;
+        anchorDef 120 0 ENTRY;";
+        assert_eq!(
+            validate_messages(fea),
+            ["anchor 'ENTRY' is defined after its use here"]
+        );
+    }
+
+    #[test]
+    fn cid_with_name_keyed_map() {
+        let fea = "feature test { sub \\510 by a; } test;";
+        assert_eq!(
+            validate_messages(fea),
+            ["CID references require a CID-keyed glyph map"]
+        );
+    }
+
+    #[test]
+    fn gpos_in_ccmp() {
+        let fea = "\
+        feature ccmp {
+            sub f i by f_i;
+            pos a 10;
+            lookup inner { pos b 10; } inner;
+        } ccmp;";
+        assert_eq!(
+            validate_messages(fea),
+            [
+                "'ccmp' feature can only contain substitution rules",
+                "'ccmp' feature can only contain substitution rules"
+            ]
+        );
+    }
+
+    #[test]
+    fn anchor_not_defined() {
+        let fea = "\
+        feature test {
+            pos cursive a <anchor ENTRY> <anchor NULL>;
+        } test;";
+        assert_eq!(validate_messages(fea), ["anchor 'ENTRY' is not defined"]);
+    }
+
+    #[test]
+    fn variable_metric_locations() {
+        let fea = "\
+        feature kern {
+            pos a (wght=400:0 wght=1000:-60 wdth=100:10);
+            pos b (wght=900:-60);
+        } kern;";
+        let errs = validate_messages(fea);
+        assert_eq!(errs, ["variable metrics require an axis map"; 2]);
+
+        let opts = Opts {
+            axis_map: Some(AxisMap::new([crate::compile::VariationAxis::new(
+                Tag::new(b"wght"),
+                100.,
+                400.,
+                900.,
+            )])),
+            ..Opts::new()
+        };
+        let errs = validate_messages_with_opts(fea, &opts);
+        assert_eq!(
+            errs,
+            [
+                "1000 is outside the range of axis 'wght' (100..=900)",
+                "axis 'wdth' is not in the axis map",
+                "variable metric must include a value at the default location",
+            ]
+        );
+    }
+
+    #[test]
+    fn condition_sets() {
+        let fea = "\
+        conditionset heavy {
+            wght 700 900;
+        } heavy;
+        variation rvrn heavy {
+            sub a by b;
+        } rvrn;";
+        let errs = validate_messages(fea);
+        assert_eq!(errs, ["conditionsets require an axis map"]);
+
+        let fea = "\
+        conditionset bad {
+            wght 800 700;
+            wdth 50 100;
+            wght 100 1000;
+        } bad;
+        variation rvrn heavy {
+            sub a by b;
+        } rvrn;";
+        let opts = Opts {
+            axis_map: Some(AxisMap::new([crate::compile::VariationAxis::new(
+                Tag::new(b"wght"),
+                100.,
+                400.,
+                900.,
+            )])),
+            ..Opts::new()
+        };
+        let errs = validate_messages_with_opts(fea, &opts);
+        assert_eq!(
+            errs,
+            [
+                "condition minimum is greater than its maximum",
+                "axis 'wdth' is not in the axis map",
+                "axis 'wght' appears more than once",
+                "1000 is outside the range of axis 'wght' (100..=900)",
+                "conditionset 'heavy' is not defined",
+            ]
+        );
+    }
+
+    #[test]
+    fn classic_dialect() {
+        let fea = "\
+        conditionset heavy {
+            wght 700 900;
+        } heavy;
+        variation rvrn heavy {
+            sub a by b;
+        } rvrn;
+        feature kern {
+            pos a (wght=400:0 wght=900:-60);
+            pos cursive b <anchor (wght=400:0 wght=900:10) 0> <anchor NULL>;
+        } kern;";
+        let opts = Opts::new().dialect(Dialect::Classic);
+        let errs = validate_messages_with_opts(fea, &opts);
+        assert_eq!(errs, ["variable syntax requires the Variable dialect"; 4]);
+
+        let fea = "feature kern { pos a b -20; } kern;";
+        assert!(validate_messages_with_opts(fea, &opts).is_empty());
+    }
+
+    #[test]
+    fn implicit_deletion() {
+        let fea = "feature test { sub a by NULL; sub [b c] by NULL; } test;";
+        assert!(validate_messages(fea).is_empty());
+        let fea = "feature test { sub a; sub [b c]; } test;";
+        assert_eq!(
+            validate_messages(fea),
+            ["substitution has no replacement; use 'by NULL' to delete glyphs"; 2]
+        );
+    }
+
+    #[test]
+    fn device_definitions() {
+        let fea = "\
+        deviceDef <device 11 -1> FIX;
+        deviceDef <device FIX> ALIAS;
+        feature kern {
+            pos a <0 0 -10 0 <device NULL> <device NULL> <device FIX> <device NULL>>;
+            pos b <0 0 -10 0 <device NULL> <device NULL> <device NOPE> <device NULL>>;
+            pos cursive c <anchor 0 0 <device FIX> <device NOPE>> <anchor NULL>;
+        } kern;";
+        let errs = validate_messages(fea);
+        assert_eq!(
+            errs,
+            [
+                "device definitions cannot refer to other devices",
+                "device 'NOPE' is not defined",
+                "device 'NOPE' is not defined",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicate_font_revision() {
+        let fea =
+            "table head { FontRevision 1.000; FontRevision 1.100; FontRevision 2.000; } head;";
+        let messages = validate_messages(fea);
+        assert_eq!(messages.len(), 2, "{messages:?}");
+        assert!(messages
+            .iter()
+            .all(|msg| msg.ends_with("the last value is used")));
+    }
+
+    #[test]
+    fn mac_name_encoding() {
+        let messages = validate_messages("table name { nameid 9 1 \"Ren\\8ee Lefèvre\"; } name;");
+        assert!(messages.is_empty(), "{messages:?}");
+        let messages = validate_messages("table name { nameid 9 \"Жо\"; } name;");
+        assert!(messages.is_empty(), "{messages:?}");
+        let messages = validate_messages("table name { nameid 9 1 \"Жо\"; } name;");
+        assert_eq!(messages, ["'Ж' cannot be encoded in MacRoman"]);
+    }
+
+    #[test]
+    fn name_escapes() {
+        let messages = validate_messages("table name { nameid 9 \"\\d83d\\de00 \\00e9\"; } name;");
+        assert!(messages.is_empty(), "{messages:?}");
+        let messages = validate_messages("table name { nameid 9 \"\\d83d!\"; } name;");
+        assert_eq!(messages, ["unpaired surrogate in escape sequence"]);
+
+        let fea = "feature ss01 { featureNames { name \"Caf\\0e9\"; }; sub a by b; } ss01;";
+        let messages = validate_messages(fea);
+        assert_eq!(
+            messages,
+            ["windows escape sequences must be four hex digits long"]
+        );
+        let fea = "feature size { parameters 10.0 3 80 139; sizemenuname 1 \"Caf\\8\"; } size;";
+        let messages = validate_messages(fea);
+        assert_eq!(
+            messages,
+            ["mac escape sequences must be two hex digits long"]
+        );
+        let fea = "\
+        feature cv01 {
+            cvParameters {
+                FeatUILabelNameID { name \"\\de00\"; };
+                FeatUITooltipTextNameID { name \"Tooltip\"; };
+                SampleTextNameID { name \"Sample\"; };
+                ParamUILabelNameID { name \"Param\"; };
+            };
+            sub a by b;
+        } cv01;";
+        let messages = validate_messages(fea);
+        assert_eq!(messages, ["unpaired surrogate in escape sequence"]);
+    }
+
+    #[test]
+    fn stat_range_order() {
+        let stat = |location: &str| {
+            format!(
+                "table STAT {{
+                    ElidedFallbackName {{ name \"Regular\"; }};
+                    DesignAxis wght 0 {{ name \"Weight\"; }};
+                    AxisValue {{ location wght {location}; name \"Bold\"; }};
+                }} STAT;"
+            )
+        };
+        assert!(validate_messages(&stat("700 600 800")).is_empty());
+        assert!(validate_messages(&stat("700 700 700")).is_empty());
+        assert_eq!(
+            validate_messages(&stat("700 750 800")),
+            ["nominal value 700 is outside of the range 750..=800"]
+        );
+        assert_eq!(
+            validate_messages(&stat("700 600 650")),
+            ["nominal value 700 is outside of the range 600..=650"]
+        );
+    }
 }
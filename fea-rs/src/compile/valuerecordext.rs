@@ -1,6 +1,12 @@
 //! Extra helper methods on ValueRecord
 
-use write_fonts::tables::gpos::{ValueFormat, ValueRecord};
+use write_fonts::{
+    tables::{
+        gpos::{ValueFormat, ValueRecord},
+        layout::{DeltaFormat, Device},
+    },
+    NullableOffsetMarker,
+};
 
 pub(crate) trait ValueRecordExt {
     fn clear_zeros(self) -> Self;
@@ -9,23 +15,24 @@ pub(crate) trait ValueRecordExt {
 }
 
 impl ValueRecordExt for ValueRecord {
+    /// Remove fields that are zero, unless they are variable.
     fn clear_zeros(mut self) -> Self {
-        if self.x_placement == Some(0) {
-            self.x_placement = None;
+        let is_variable = |device: &NullableOffsetMarker<Device>| {
+            device
+                .as_ref()
+                .map(|device| device.delta_format == DeltaFormat::VariationIndex)
+                .unwrap_or(false)
+        };
+        for (value, device) in [
+            (&mut self.x_placement, &self.x_placement_device),
+            (&mut self.y_placement, &self.y_placement_device),
+            (&mut self.x_advance, &self.x_advance_device),
+            (&mut self.y_advance, &self.y_advance_device),
+        ] {
+            if *value == Some(0) && !is_variable(device) {
+                *value = None;
+            }
         }
-
-        if self.y_placement == Some(0) {
-            self.y_placement = None;
-        }
-
-        if self.x_advance == Some(0) {
-            self.x_advance = None;
-        }
-
-        if self.y_advance == Some(0) {
-            self.y_advance = None;
-        }
-
         self
     }
 
@@ -0,0 +1,822 @@
+//! Building the item variation store for variable metrics
+//!
+//! This follows fonttools (`varLib.models` and `varLib.varStore`) closely, so
+//! that we produce the same deltas, regions and store layout.
+
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BTreeSet, HashMap},
+};
+
+use write_fonts::{
+    tables::{
+        layout::{DeltaFormat, Device},
+        variations::{
+            ItemVariationData, ItemVariationStore, RegionAxisCoordinates, VariationRegion,
+            VariationRegionList,
+        },
+    },
+    types::{F2Dot14, Tag},
+};
+
+use super::AxisMap;
+
+/// The outer and inner index used for deltas that are all zero.
+const NO_VARIATION_INDEX: (u16, u16) = (0xFFFF, 0xFFFF);
+
+/// A location in normalized coordinates; axes at their default are omitted.
+type Location = BTreeMap<Tag, f64>;
+
+/// The region where a delta applies, as (start, peak, end) on each axis.
+///
+/// Axes that are not included do not affect the region.
+pub(crate) type Support = BTreeMap<Tag, (f64, f64, f64)>;
+
+/// A metric with different values at different locations.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct VariableValue {
+    /// The value at the default location
+    pub default: i16,
+    /// The change from the default in each region.
+    ///
+    /// This is empty if the value is the same at every location.
+    pub deltas: Vec<(Support, i32)>,
+}
+
+/// Computes the deltas that reproduce values at a set of master locations.
+struct VariationModel {
+    /// The master locations, sorted so that each only depends on those before it
+    locations: Vec<Location>,
+    supports: Vec<Support>,
+    /// For each master, the weight of each earlier master's delta at its location
+    delta_weights: Vec<Vec<(usize, f64)>>,
+    /// For each sorted master, its index in the original list
+    reverse_mapping: Vec<usize>,
+}
+
+/// Collects the deltas of variable metrics, to build an item variation store.
+#[derive(Clone, Debug)]
+pub(crate) struct VariationStoreBuilder {
+    axes: Vec<Tag>,
+    regions: Vec<Vec<(f64, f64, f64)>>,
+    region_ids: HashMap<Vec<[u64; 3]>, u16>,
+    // rows are grouped by the regions they use, in order of first use
+    var_datas: Vec<(Vec<u16>, Vec<Vec<i32>>)>,
+    var_data_ids: HashMap<Vec<u16>, usize>,
+}
+
+/// A finished item variation store, and the location of each set of deltas.
+#[derive(Clone, Debug)]
+pub(crate) struct VariationStore {
+    axes: Vec<Tag>,
+    region_ids: HashMap<Vec<[u64; 3]>, u16>,
+    n_regions: usize,
+    indices: HashMap<Vec<i32>, (u16, u16)>,
+    table: Option<ItemVariationStore>,
+}
+
+/// A set of rows that can be stored with the same column sizes.
+///
+/// For each column, `chars` has bit 0 set if any row has a non-zero value,
+/// bit 1 if a value needs more than one byte, and bits 2 and 3 if it needs
+/// more than two.
+struct Encoding {
+    chars: Vec<u8>,
+    width: usize,
+    overhead: usize,
+    items: BTreeSet<Vec<i32>>,
+    best_new_width: Option<usize>,
+}
+
+impl VariableValue {
+    /// Resolve a metric from its values at some locations.
+    ///
+    /// Locations are in user coordinates, and axes that are not included are
+    /// at their default. Returns `None` if an axis is not in the axis map, or
+    /// if there is no value at the default location.
+    pub(crate) fn new(axis_map: &AxisMap, masters: &[(Vec<(Tag, f32)>, i16)]) -> Option<Self> {
+        let mut locations: Vec<Location> = Vec::new();
+        let mut values = Vec::new();
+        for (location, value) in masters {
+            let location = location
+                .iter()
+                .map(|(tag, coord)| Some((*tag, axis_map.normalize(*tag, *coord)? as f64)))
+                .collect::<Option<Location>>()?
+                .into_iter()
+                .filter(|(_, coord)| *coord != 0.0)
+                .collect::<Location>();
+            // a later value for the same location replaces the earlier one
+            match locations.iter().position(|prev| *prev == location) {
+                Some(idx) => values[idx] = *value,
+                None => {
+                    locations.push(location);
+                    values.push(*value);
+                }
+            }
+        }
+
+        let default = values[locations.iter().position(Location::is_empty)?];
+        if values.iter().all(|value| *value == default) {
+            return Some(VariableValue {
+                default,
+                deltas: Vec::new(),
+            });
+        }
+        let model = VariationModel::new(locations);
+        let values = values.into_iter().map(f64::from).collect::<Vec<_>>();
+        let deltas = model.deltas(&values);
+        // the first master is at the default location, and its 'delta' is
+        // the default value.
+        let deltas = model
+            .supports
+            .into_iter()
+            .zip(deltas)
+            .skip(1)
+            .map(|(support, delta)| (support, round_ties_even(delta)))
+            .collect();
+        Some(VariableValue { default, deltas })
+    }
+}
+
+impl VariationModel {
+    /// Create a model for some locations, which must include the default.
+    fn new(locations: Vec<Location>) -> Self {
+        // the points on each axis where there is a master on that axis alone
+        let mut axis_points: HashMap<Tag, Vec<f64>> = HashMap::new();
+        for location in locations.iter().filter(|loc| loc.len() == 1) {
+            for (axis, value) in location {
+                axis_points
+                    .entry(*axis)
+                    .or_insert_with(|| vec![0.0])
+                    .push(*value);
+            }
+        }
+        let sort_key = |location: &Location| {
+            let on_point_axes = location
+                .iter()
+                .filter(|(axis, value)| {
+                    axis_points
+                        .get(*axis)
+                        .map(|points| points.contains(*value))
+                        .unwrap_or(false)
+                })
+                .count();
+            let axes = location.keys().copied().collect::<Vec<_>>();
+            (location.len(), Reverse(on_point_axes), axes)
+        };
+        let signs = |loc: &Location| loc.values().map(|v| v.signum()).collect::<Vec<_>>();
+        let magnitudes = |loc: &Location| loc.values().map(|v| v.abs()).collect::<Vec<_>>();
+
+        let mut reverse_mapping = (0..locations.len()).collect::<Vec<_>>();
+        reverse_mapping.sort_by(|a, b| {
+            let (a, b) = (&locations[*a], &locations[*b]);
+            sort_key(a)
+                .cmp(&sort_key(b))
+                .then_with(|| signs(a).partial_cmp(&signs(b)).unwrap())
+                .then_with(|| magnitudes(a).partial_cmp(&magnitudes(b)).unwrap())
+        });
+        let locations = reverse_mapping
+            .iter()
+            .map(|idx| locations[*idx].clone())
+            .collect::<Vec<_>>();
+
+        let supports = master_supports(&locations);
+        let delta_weights = locations
+            .iter()
+            .enumerate()
+            .map(|(i, location)| {
+                supports[..i]
+                    .iter()
+                    .enumerate()
+                    .map(|(j, support)| (j, support_scalar(location, support)))
+                    .filter(|(_, scalar)| *scalar != 0.0)
+                    .collect()
+            })
+            .collect();
+
+        VariationModel {
+            locations,
+            supports,
+            delta_weights,
+            reverse_mapping,
+        }
+    }
+
+    /// The delta for each master, given the values at the original locations.
+    fn deltas(&self, values: &[f64]) -> Vec<f64> {
+        debug_assert_eq!(values.len(), self.locations.len());
+        let mut out: Vec<f64> = Vec::with_capacity(values.len());
+        for (i, weights) in self.delta_weights.iter().enumerate() {
+            let mut delta = values[self.reverse_mapping[i]];
+            for (j, weight) in weights {
+                delta -= out[*j] * weight;
+            }
+            out.push(delta);
+        }
+        out
+    }
+}
+
+/// Compute the region of influence of each of a sorted list of masters.
+///
+/// Each region starts out reaching from the default to the furthest master on
+/// each axis, and is then cut short at earlier masters that are inside it.
+fn master_supports(locations: &[Location]) -> Vec<Support> {
+    let mut min_values: HashMap<Tag, f64> = HashMap::new();
+    let mut max_values: HashMap<Tag, f64> = HashMap::new();
+    for (axis, value) in locations.iter().flatten() {
+        let min = min_values.entry(*axis).or_insert(*value);
+        *min = min.min(*value);
+        let max = max_values.entry(*axis).or_insert(*value);
+        *max = max.max(*value);
+    }
+
+    let mut supports: Vec<Support> = Vec::with_capacity(locations.len());
+    for location in locations {
+        let mut region = location
+            .iter()
+            .map(|(axis, value)| {
+                let triple = if *value > 0.0 {
+                    (0.0, *value, max_values[axis])
+                } else {
+                    (min_values[axis], *value, 0.0)
+                };
+                (*axis, triple)
+            })
+            .collect::<Support>();
+
+        for prev in &supports {
+            // masters on other axes don't participate
+            if !prev.keys().eq(region.keys()) {
+                continue;
+            }
+            // and neither do masters outside of the current region
+            let is_relevant = region.iter().all(|(axis, (lower, peak, upper))| {
+                let prev_peak = prev[axis].1;
+                prev_peak == *peak || (*lower < prev_peak && prev_peak < *upper)
+            });
+            if !is_relevant {
+                continue;
+            }
+
+            // split the region in whichever direction has the largest ratio
+            let mut best_axes = Vec::new();
+            let mut best_ratio = -1.0;
+            for (axis, (_, value, _)) in prev {
+                let (lower, peak, upper) = region[axis];
+                let (triple, ratio) = match value.partial_cmp(&peak) {
+                    Some(Ordering::Less) => {
+                        ((*value, peak, upper), (value - peak) / (lower - peak))
+                    }
+                    Some(Ordering::Greater) => {
+                        ((lower, peak, *value), (value - peak) / (upper - peak))
+                    }
+                    // can't split the region in this direction
+                    _ => continue,
+                };
+                if ratio > best_ratio {
+                    best_axes.clear();
+                    best_ratio = ratio;
+                }
+                if ratio == best_ratio {
+                    best_axes.push((*axis, triple));
+                }
+            }
+            region.extend(best_axes);
+        }
+        supports.push(region);
+    }
+    supports
+}
+
+/// The weight of a delta with this support at this location.
+fn support_scalar(location: &Location, support: &Support) -> f64 {
+    let mut scalar = 1.0;
+    for (axis, &(lower, peak, upper)) in support {
+        if peak == 0.0 || lower > peak || peak > upper || (lower < 0.0 && upper > 0.0) {
+            continue;
+        }
+        let value = location.get(axis).copied().unwrap_or(0.0);
+        if value == peak {
+            continue;
+        }
+        if value <= lower || upper <= value {
+            return 0.0;
+        }
+        if value < peak {
+            scalar *= (value - lower) / (peak - lower);
+        } else {
+            scalar *= (value - upper) / (peak - upper);
+        }
+    }
+    scalar
+}
+
+/// Round to the nearest integer, and to the even one when halfway (like python).
+fn round_ties_even(value: f64) -> i32 {
+    let rounded = value.round();
+    if (value - value.trunc()).abs() == 0.5 {
+        (2.0 * (value / 2.0).round()) as i32
+    } else {
+        rounded as i32
+    }
+}
+
+impl VariationStoreBuilder {
+    /// Create a builder for a font with these axes.
+    pub(crate) fn new(axis_map: &AxisMap) -> Self {
+        VariationStoreBuilder {
+            axes: axis_map.axes().map(|axis| axis.tag).collect(),
+            regions: Vec::new(),
+            region_ids: HashMap::new(),
+            var_datas: Vec::new(),
+            var_data_ids: HashMap::new(),
+        }
+    }
+
+    /// Add the deltas of a variable value.
+    pub(crate) fn add(&mut self, value: &VariableValue) {
+        if value.deltas.is_empty() {
+            return;
+        }
+        let mut region_ids = Vec::with_capacity(value.deltas.len());
+        let mut row = Vec::with_capacity(value.deltas.len());
+        for (support, delta) in &value.deltas {
+            let region = region_for_support(&self.axes, support);
+            let next_id = self.regions.len() as u16;
+            let id = *self
+                .region_ids
+                .entry(region_key(&region))
+                .or_insert(next_id);
+            if id == next_id {
+                self.regions.push(region);
+            }
+            region_ids.push(id);
+            row.push(*delta);
+        }
+
+        let next_id = self.var_datas.len();
+        let id = *self
+            .var_data_ids
+            .entry(region_ids.clone())
+            .or_insert(next_id);
+        if id == next_id {
+            self.var_datas.push((region_ids, Vec::new()));
+        }
+        let items = &mut self.var_datas[id].1;
+        if !items.contains(&row) {
+            items.push(row);
+        }
+    }
+
+    /// Build the store, choosing a compact layout for the deltas.
+    pub(crate) fn build(self) -> VariationStore {
+        let n_regions = self.regions.len();
+        let mut encodings: Vec<Encoding> = Vec::new();
+        let mut encoding_ids = HashMap::new();
+        let mut has_items = false;
+        for (region_ids, items) in &self.var_datas {
+            for item in items {
+                has_items = true;
+                let row = full_row(
+                    n_regions,
+                    region_ids.iter().copied().zip(item.iter().copied()),
+                );
+                // rows of zeros use NO_VARIATION_INDEX, and aren't stored
+                if row.iter().all(|delta| *delta == 0) {
+                    continue;
+                }
+                let chars = row_characteristics(&row);
+                let id = *encoding_ids.entry(chars.clone()).or_insert_with(|| {
+                    encodings.push(Encoding::new(chars));
+                    encodings.len() - 1
+                });
+                encodings[id].items.insert(row);
+            }
+        }
+
+        let encodings = merge_encodings(encodings);
+        let mut indices = HashMap::new();
+        let mut var_datas = Vec::with_capacity(encodings.len());
+        for (outer, encoding) in encodings.into_iter().enumerate() {
+            for (inner, item) in encoding.items.iter().enumerate() {
+                indices.insert(item.clone(), (outer as u16, inner as u16));
+            }
+            var_datas.push(build_var_data(&encoding.items, n_regions));
+        }
+
+        // drop the regions that only have zero deltas
+        let used_regions = var_datas
+            .iter()
+            .flat_map(|data| data.region_indexes.iter().copied())
+            .collect::<BTreeSet<_>>();
+        let region_map = used_regions
+            .iter()
+            .enumerate()
+            .map(|(new, old)| (*old, new as u16))
+            .collect::<HashMap<_, _>>();
+        for data in var_datas.iter_mut() {
+            for region in data.region_indexes.iter_mut() {
+                *region = region_map[&*region];
+            }
+        }
+        let regions = used_regions
+            .into_iter()
+            .map(|idx| {
+                let coords = self.regions[idx as usize]
+                    .iter()
+                    .map(|(start, peak, end)| {
+                        RegionAxisCoordinates::new(
+                            F2Dot14::from_f32(*start as f32),
+                            F2Dot14::from_f32(*peak as f32),
+                            F2Dot14::from_f32(*end as f32),
+                        )
+                    })
+                    .collect();
+                VariationRegion::new(coords)
+            })
+            .collect();
+        let var_datas = var_datas.into_iter().map(Some).collect();
+        let table = has_items
+            .then(|| ItemVariationStore::new(1, VariationRegionList::new(regions), var_datas));
+        VariationStore {
+            axes: self.axes,
+            region_ids: self.region_ids,
+            n_regions,
+            indices,
+            table,
+        }
+    }
+}
+
+/// The coordinates of a region on every axis, in the order of the axis map.
+fn region_for_support(axes: &[Tag], support: &Support) -> Vec<(f64, f64, f64)> {
+    axes.iter()
+        .map(|axis| support.get(axis).copied().unwrap_or_default())
+        .collect()
+}
+
+fn region_key(region: &[(f64, f64, f64)]) -> Vec<[u64; 3]> {
+    region
+        .iter()
+        .map(|(start, peak, end)| [start.to_bits(), peak.to_bits(), end.to_bits()])
+        .collect()
+}
+
+/// A row with a column for every region.
+fn full_row(n_regions: usize, deltas: impl Iterator<Item = (u16, i32)>) -> Vec<i32> {
+    let mut row = vec![0; n_regions];
+    for (region, delta) in deltas {
+        row[region as usize] += delta;
+    }
+    row
+}
+
+fn row_characteristics(row: &[i32]) -> Vec<u8> {
+    let is_long = |value: &i32| i16::try_from(*value).is_err();
+    if row.iter().any(is_long) {
+        // only two and four byte values are possible
+        row.iter()
+            .map(|value| match value {
+                0 => 0,
+                v if is_long(v) => 0b1111,
+                _ => 0b0011,
+            })
+            .collect()
+    } else {
+        row.iter()
+            .map(|value| match value {
+                0 => 0,
+                v if i8::try_from(*v).is_ok() => 0b0001,
+                _ => 0b0011,
+            })
+            .collect()
+    }
+}
+
+impl Encoding {
+    fn new(chars: Vec<u8>) -> Self {
+        let width = chars.iter().map(|c| c.count_ones() as usize).sum();
+        let overhead = characteristic_overhead(&chars);
+        Encoding {
+            chars,
+            width,
+            overhead,
+            items: BTreeSet::new(),
+            best_new_width: None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// The most bytes per row that we could add while still gaining by
+    /// merging into another encoding.
+    fn room(&self) -> usize {
+        ((self.overhead - 1) / self.len()).saturating_sub(self.width)
+    }
+
+    /// The most bytes we could gain by merging into another encoding.
+    fn gain(&self) -> usize {
+        self.overhead.saturating_sub(self.len())
+    }
+
+    fn can_encode(&self, chars: &[u8]) -> bool {
+        self.chars
+            .iter()
+            .zip(chars)
+            .all(|(ours, theirs)| theirs & !ours == 0)
+    }
+
+    fn find_best_new_encoding(&mut self, done_by_width: &BTreeMap<usize, Vec<Encoding>>) {
+        self.best_new_width = None;
+        for new_width in self.width + 1..=self.width + self.room() {
+            self.best_new_width = done_by_width
+                .get(&new_width)
+                .into_iter()
+                .flatten()
+                .any(|encoding| encoding.can_encode(&self.chars))
+                .then_some(new_width);
+        }
+    }
+
+    /// The bytes we gain by moving our rows to our best new encoding.
+    fn separate_gain(&self) -> isize {
+        self.best_new_width
+            .map(|width| self.overhead as isize - ((width - self.width) * self.len()) as isize)
+            .unwrap_or(0)
+    }
+
+    fn sort_key_cmp(&self, other: &Encoding) -> Ordering {
+        // chars are compared as a number, where the first column is the
+        // least significant
+        self.width
+            .cmp(&other.width)
+            .then_with(|| self.chars.iter().rev().cmp(other.chars.iter().rev()))
+    }
+}
+
+/// The bytes needed to store an encoding as a separate `ItemVariationData`.
+fn characteristic_overhead(chars: &[u8]) -> usize {
+    6 + 2 * chars.iter().filter(|c| **c != 0).count()
+}
+
+fn combine_chars(one: &[u8], two: &[u8]) -> Vec<u8> {
+    one.iter().zip(two).map(|(one, two)| one | two).collect()
+}
+
+/// Merge encodings where this saves space, and sort the result.
+fn merge_encodings(mut encodings: Vec<Encoding>) -> Vec<Encoding> {
+    encodings.sort_by_key(|encoding| Reverse(encoding.len()));
+    let mut done_by_width: BTreeMap<usize, Vec<Encoding>> = BTreeMap::new();
+    let mut todo = Vec::new();
+    for encoding in encodings {
+        if encoding.gain() == 0 {
+            done_by_width
+                .entry(encoding.width)
+                .or_default()
+                .push(encoding);
+        } else {
+            todo.push(encoding);
+        }
+    }
+
+    todo.sort_by_key(Encoding::room);
+    for encoding in todo.iter_mut() {
+        encoding.find_best_new_encoding(&done_by_width);
+    }
+
+    // merge each encoding with another if that gains more than moving each of
+    // them to their best new encoding.
+    while let Some(encoding) = todo.pop() {
+        let mut best_idx = None;
+        for (i, other) in todo.iter().enumerate() {
+            let combined_chars = combine_chars(&encoding.chars, &other.chars);
+            let combined_width: usize =
+                combined_chars.iter().map(|c| c.count_ones() as usize).sum();
+            let combined_gain = (encoding.overhead + other.overhead) as isize
+                - characteristic_overhead(&combined_chars) as isize
+                - ((combined_width - encoding.width) * encoding.len()) as isize
+                - ((combined_width - other.width) * other.len()) as isize;
+            if combined_gain > encoding.separate_gain() + other.separate_gain() {
+                best_idx = Some(i);
+            }
+        }
+
+        match best_idx {
+            None => done_by_width
+                .entry(encoding.width)
+                .or_default()
+                .push(encoding),
+            Some(idx) => {
+                let other = todo.remove(idx);
+                let mut combined = Encoding::new(combine_chars(&encoding.chars, &other.chars));
+                combined.items.extend(encoding.items);
+                combined.items.extend(other.items);
+                combined.find_best_new_encoding(&done_by_width);
+                todo.push(combined);
+            }
+        }
+    }
+
+    let mut encodings = done_by_width.into_values().flatten().collect::<Vec<_>>();
+    encodings.sort_by(Encoding::sort_key_cmp);
+    encodings
+}
+
+/// Build an `ItemVariationData` for some rows with a value for every region.
+///
+/// Unused regions are dropped, and the regions with larger deltas are moved
+/// to the front.
+fn build_var_data(items: &BTreeSet<Vec<i32>>, n_regions: usize) -> ItemVariationData {
+    let byte_len = |value: i32| match value {
+        0 => 0,
+        v if i8::try_from(v).is_ok() => 1,
+        v if i16::try_from(v).is_ok() => 2,
+        _ => 4,
+    };
+    let mut column_lens = vec![0; n_regions];
+    for item in items {
+        for (len, value) in column_lens.iter_mut().zip(item) {
+            *len = byte_len(*value).max(*len);
+        }
+    }
+    let lens = &column_lens;
+    let columns = [4, 2, 1]
+        .into_iter()
+        .flat_map(|len| (0..n_regions).filter(move |col| lens[*col] == len))
+        .collect::<Vec<_>>();
+    let long_words = column_lens.contains(&4);
+    let (word_count, word_len) = if long_words {
+        (column_lens.iter().filter(|len| **len == 4).count(), 4)
+    } else {
+        (column_lens.iter().filter(|len| **len == 2).count(), 2)
+    };
+
+    let mut delta_sets = Vec::new();
+    for item in items {
+        for (i, col) in columns.iter().enumerate() {
+            let value = item[*col];
+            match (i < word_count, word_len) {
+                (true, 4) => delta_sets.extend(value.to_be_bytes()),
+                (true, _) | (false, 4) => delta_sets.extend((value as i16).to_be_bytes()),
+                (false, _) => delta_sets.extend((value as i8).to_be_bytes()),
+            }
+        }
+    }
+    let word_delta_count = word_count as u16 | if long_words { 0x8000 } else { 0 };
+    ItemVariationData::new(
+        items.len() as u16,
+        word_delta_count,
+        columns.into_iter().map(|col| col as u16).collect(),
+        delta_sets,
+    )
+}
+
+impl VariationStore {
+    /// A device table with the location of this value's deltas in the store.
+    ///
+    /// Returns `None` if the value does not vary.
+    pub(crate) fn variation_index(&self, value: &VariableValue) -> Option<Device> {
+        if value.deltas.is_empty() {
+            return None;
+        }
+        let deltas = value
+            .deltas
+            .iter()
+            .map(|(support, delta)| {
+                let region = region_for_support(&self.axes, support);
+                Some((*self.region_ids.get(&region_key(&region))?, *delta))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let row = full_row(self.n_regions, deltas.into_iter());
+        let (outer, inner) = if row.iter().all(|delta| *delta == 0) {
+            NO_VARIATION_INDEX
+        } else {
+            *self.indices.get(&row)?
+        };
+        // a VariationIndex table has the same layout as a device table
+        Some(Device {
+            start_size: outer,
+            end_size: inner,
+            delta_format: DeltaFormat::VariationIndex,
+            delta_value: Vec::new(),
+        })
+    }
+
+    /// The `ItemVariationStore` table, or `None` if no values vary.
+    pub(crate) fn table(&self) -> Option<ItemVariationStore> {
+        self.table.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile::VariationAxis;
+
+    fn axis_map() -> AxisMap {
+        AxisMap::new([
+            VariationAxis::new(Tag::new(b"wght"), 200., 200., 1000.),
+            VariationAxis::new(Tag::new(b"wdth"), 100., 100., 200.),
+        ])
+    }
+
+    /// Create a value from locations like `"wght=900 wdth=150"`.
+    fn value(masters: &[(&str, i16)]) -> VariableValue {
+        let masters = masters
+            .iter()
+            .map(|(loc, value)| {
+                let loc = loc
+                    .split_whitespace()
+                    .map(|item| {
+                        let (tag, coord) = item.split_once('=').unwrap();
+                        let tag = Tag::new(tag.as_bytes().try_into().unwrap());
+                        (tag, coord.parse().unwrap())
+                    })
+                    .collect();
+                (loc, *value)
+            })
+            .collect::<Vec<_>>();
+        VariableValue::new(&axis_map(), &masters).unwrap()
+    }
+
+    #[test]
+    fn deltas() {
+        let value = value(&[
+            ("wght=200", 12),
+            ("wght=900", 22),
+            ("wdth=150 wght=900", 42),
+        ]);
+        assert_eq!(value.default, 12);
+        let wght = (0.0, 0.875, 0.875);
+        let wdth = (0.0, 0.5, 0.5);
+        let deltas = value
+            .deltas
+            .iter()
+            .map(|(support, delta)| (support.values().copied().collect::<Vec<_>>(), *delta))
+            .collect::<Vec<_>>();
+        // wdth sorts before wght
+        assert_eq!(deltas, [(vec![wght], 10), (vec![wdth, wght], 20)]);
+    }
+
+    #[test]
+    fn intermediate_master() {
+        let value = value(&[("wght=200", 0), ("wght=600", 30), ("wght=1000", 40)]);
+        let supports = value
+            .deltas
+            .iter()
+            .map(|(support, delta)| (support[&Tag::new(b"wght")], *delta))
+            .collect::<Vec<_>>();
+        // the master at 600 is at 0.5, and the region of the master at 1.0
+        // starts there
+        assert_eq!(supports, [((0.0, 0.5, 1.0), 30), ((0.5, 1.0, 1.0), 40)]);
+    }
+
+    #[test]
+    fn does_not_vary() {
+        let value = value(&[("wght=200", 5), ("wght=900", 5)]);
+        assert_eq!(value.default, 5);
+        assert!(value.deltas.is_empty());
+    }
+
+    #[test]
+    fn build_store() {
+        let one = value(&[("wght=200", 0), ("wght=1000", 10)]);
+        let two = value(&[("wght=200", 0), ("wght=1000", -300)]);
+        let same = value(&[("wght=200", 1), ("wght=1000", 11)]);
+        let mut builder = VariationStoreBuilder::new(&axis_map());
+        for value in [&one, &two, &same] {
+            builder.add(value);
+        }
+        let store = builder.build();
+        let index = |value| {
+            let device = store.variation_index(value).unwrap();
+            assert_eq!(device.delta_format, DeltaFormat::VariationIndex);
+            (device.start_size, device.end_size)
+        };
+        // a second subtable would cost more than storing 10 in two bytes;
+        // rows are sorted
+        assert_eq!(index(&two), (0, 0));
+        assert_eq!(index(&one), (0, 1));
+        assert_eq!(index(&same), (0, 1));
+
+        let table = store.table().unwrap();
+        assert_eq!(table.variation_region_list.variation_regions.len(), 1);
+        let data = table
+            .item_variation_datas
+            .iter()
+            .map(|data| data.as_ref().unwrap())
+            .map(|data| {
+                (
+                    data.item_count,
+                    data.word_delta_count,
+                    data.delta_sets.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        let [hi, lo] = (-300i16).to_be_bytes();
+        assert_eq!(data, [(2, 1, vec![hi, lo, 0, 10])]);
+    }
+}
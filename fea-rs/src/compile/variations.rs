@@ -0,0 +1,139 @@
+//! Information about the axes of a variable font
+
+use std::collections::BTreeMap;
+
+use write_fonts::types::Tag;
+
+use super::lookups::{FeatureKey, LookupId};
+
+/// A variation axis, with coordinates in user space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariationAxis {
+    /// The axis tag, e.g. `wght`
+    pub tag: Tag,
+    /// The minimum value on this axis
+    pub min: f32,
+    /// The default value on this axis
+    pub default: f32,
+    /// The maximum value on this axis
+    pub max: f32,
+}
+
+/// The axes of a variable font.
+///
+/// This is used to resolve locations in the variable extensions to the FEA
+/// syntax, such as the variable metric `(wght=200:-20 wght=900:-60)`.
+/// Locations are written in user coordinates, and are normalized using the
+/// range of the axis; `avar` mappings are not applied.
+///
+/// The axes should be in the same order as in the font's `fvar` table, since
+/// feature variation conditions refer to axes by index.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AxisMap {
+    axes: Vec<VariationAxis>,
+}
+
+/// A range on a single axis, in normalized coordinates.
+///
+/// This is one of the conditions in a `conditionset`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AxisCondition {
+    /// The index of the axis in the [`AxisMap`]
+    pub axis_index: u16,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// The lookups to use for some features when a set of conditions is met.
+///
+/// This is the result of compiling the `variation` blocks that refer to a
+/// given `conditionset`.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct FeatureVariation {
+    pub conditions: Vec<AxisCondition>,
+    pub features: BTreeMap<FeatureKey, Vec<LookupId>>,
+}
+
+impl VariationAxis {
+    /// Create a new axis.
+    pub fn new(tag: Tag, min: f32, default: f32, max: f32) -> Self {
+        VariationAxis {
+            tag,
+            min,
+            default,
+            max,
+        }
+    }
+
+    /// `true` if `value` is within the range of this axis.
+    pub fn contains(&self, value: f32) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+
+    /// Normalize a user coordinate on this axis to the range `-1.0..=1.0`.
+    ///
+    /// Values outside of the axis range are clamped.
+    pub fn normalize(&self, value: f32) -> f32 {
+        let value = value.clamp(self.min, self.max);
+        if value < self.default {
+            (value - self.default) / (self.default - self.min)
+        } else if value > self.default {
+            (value - self.default) / (self.max - self.default)
+        } else {
+            0.0
+        }
+    }
+}
+
+impl AxisMap {
+    /// Create a new `AxisMap` from a list of axes.
+    pub fn new(axes: impl IntoIterator<Item = VariationAxis>) -> Self {
+        AxisMap {
+            axes: axes.into_iter().collect(),
+        }
+    }
+
+    /// The axis with the given tag, if it exists.
+    pub fn get(&self, tag: Tag) -> Option<&VariationAxis> {
+        self.axes.iter().find(|axis| axis.tag == tag)
+    }
+
+    /// The position of the axis with the given tag.
+    ///
+    /// Axes are expected to be in the same order as in the font's `fvar` table.
+    pub(crate) fn axis_index(&self, tag: Tag) -> Option<u16> {
+        self.axes
+            .iter()
+            .position(|axis| axis.tag == tag)
+            .map(|idx| idx as u16)
+    }
+
+    /// The axes, in order.
+    pub(crate) fn axes(&self) -> impl Iterator<Item = &VariationAxis> {
+        self.axes.iter()
+    }
+
+    /// Normalize a user coordinate on the axis with the given tag.
+    ///
+    /// Returns `None` if there is no such axis.
+    pub fn normalize(&self, tag: Tag, value: f32) -> Option<f32> {
+        self.get(tag).map(|axis| axis.normalize(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize() {
+        let axes = AxisMap::new([VariationAxis::new(Tag::new(b"wght"), 100., 400., 900.)]);
+        let wght = Tag::new(b"wght");
+        assert_eq!(axes.normalize(wght, 100.), Some(-1.0));
+        assert_eq!(axes.normalize(wght, 250.), Some(-0.5));
+        assert_eq!(axes.normalize(wght, 400.), Some(0.0));
+        assert_eq!(axes.normalize(wght, 650.), Some(0.5));
+        assert_eq!(axes.normalize(wght, 1000.), Some(1.0));
+        assert_eq!(axes.normalize(Tag::new(b"wdth"), 100.), None);
+    }
+}
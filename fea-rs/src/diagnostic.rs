@@ -1,28 +1,86 @@
 //! Reporting errors, warnings, and other information to the user.
 use crate::parse::FileId;
-use std::{convert::TryInto, ops::Range};
+use std::{collections::HashSet, convert::TryInto, ops::Range};
 
 /// A span of a source file.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Span {
     start: u32,
     end: u32,
 }
 
 /// A diagnostic level
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[repr(u8)]
 pub enum Level {
     /// An unrecoverable error
     Error,
     /// A warning: something the user may want to address, but which is non-fatal
     Warning,
-    /// Info. unused?
+    /// Info: something that may be interesting, but that is not a problem
     Info,
 }
 
+/// The stage of compilation that produced a diagnostic.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    /// Parsing, including the resolution of `include` statements
+    #[default]
+    Parse,
+    /// Validation of the parse tree, such as checking that glyphs exist
+    Validation,
+    /// Compilation of the validated tree into tables
+    Compilation,
+}
+
+/// The category of a diagnostic.
+///
+/// This allows tools to filter or handle particular diagnostics without
+/// matching on their text. Each kind has a stable name, returned by
+/// [`DiagnosticKind::code`]; new kinds may be added in the future.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// A diagnostic that does not belong to a more specific category
+    #[default]
+    Other,
+    /// A glyph name, CID, or range member that is not in the glyph map
+    UndefinedGlyph,
+    /// A reference to a glyph class or mark class that has not been defined
+    UndefinedGlyphClass,
+    /// A reference to some other named item (a lookup, anchor, device, value
+    /// record, feature, or conditionset) that has not been defined
+    UndefinedName,
+    /// An item that is defined more than once
+    DuplicateDefinition,
+    /// A statement that is not valid where it appears, or an invalid rule
+    InvalidStatement,
+    /// A value that is out of range or otherwise invalid
+    InvalidValue,
+    /// A statement that is valid, but has no effect on the output
+    NoEffect,
+    /// A glyph class that is defined but never used
+    UnusedGlyphClass,
+    /// A `lookupflag` statement that sets the flags that are already active
+    RedundantLookupFlag,
+    /// A feature tag that is not registered in the OpenType spec
+    UnregisteredFeature,
+    /// A conflict between the glyph classes used by different rules
+    ClassConflict,
+    /// An item or statement that is not understood
+    UnknownItem,
+    /// Syntax that is understood, but not (fully) supported
+    Unsupported,
+    /// Input that exceeds a limit of the compiler or the font format
+    LimitExceeded,
+    /// Default lookups that were excluded from a language system
+    ExcludedLookups,
+    /// An internal error in the compiler
+    Internal,
+}
+
 /// A message, associated with a location in a file.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Message {
     pub text: String,
     pub file: FileId,
@@ -32,12 +90,55 @@ pub struct Message {
 /// A diagnostic, including a message and additional annotations
 //TODO: would this be more useful with additional annotations or a help field?
 //some fancy error reporting crates have these.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Diagnostic {
     /// The main message for this diagnostic
     pub message: Message,
     /// The diagnostic level
     pub level: Level,
+    phase: Phase,
+    kind: DiagnosticKind,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Parse => f.write_str("parse"),
+            Phase::Validation => f.write_str("validation"),
+            Phase::Compilation => f.write_str("compilation"),
+        }
+    }
+}
+
+impl DiagnosticKind {
+    /// A short, stable name for this kind, such as `undefined-glyph`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticKind::Other => "other",
+            DiagnosticKind::UndefinedGlyph => "undefined-glyph",
+            DiagnosticKind::UndefinedGlyphClass => "undefined-glyph-class",
+            DiagnosticKind::UndefinedName => "undefined-name",
+            DiagnosticKind::DuplicateDefinition => "duplicate-definition",
+            DiagnosticKind::InvalidStatement => "invalid-statement",
+            DiagnosticKind::InvalidValue => "invalid-value",
+            DiagnosticKind::NoEffect => "no-effect",
+            DiagnosticKind::UnusedGlyphClass => "unused-glyph-class",
+            DiagnosticKind::RedundantLookupFlag => "redundant-lookupflag",
+            DiagnosticKind::UnregisteredFeature => "unregistered-feature",
+            DiagnosticKind::ClassConflict => "class-conflict",
+            DiagnosticKind::UnknownItem => "unknown-item",
+            DiagnosticKind::Unsupported => "unsupported",
+            DiagnosticKind::LimitExceeded => "limit-exceeded",
+            DiagnosticKind::ExcludedLookups => "excluded-lookups",
+            DiagnosticKind::Internal => "internal",
+        }
+    }
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
 }
 
 impl Span {
@@ -49,6 +150,13 @@ impl Span {
 
 impl Diagnostic {
     /// Create a new diagnostic
+    ///
+    /// The diagnostic is attributed to [`Phase::Parse`], and has the kind
+    /// [`DiagnosticKind::Other`]; use [`in_phase`] and [`with_kind`] to
+    /// change these.
+    ///
+    /// [`in_phase`]: Self::in_phase
+    /// [`with_kind`]: Self::with_kind
     pub fn new(
         level: Level,
         file: FileId,
@@ -65,9 +173,23 @@ impl Diagnostic {
                 file,
             },
             level,
+            phase: Phase::Parse,
+            kind: DiagnosticKind::Other,
         }
     }
 
+    /// Set the phase in which this diagnostic was generated.
+    pub fn in_phase(mut self, phase: Phase) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Set the category of this diagnostic.
+    pub fn with_kind(mut self, kind: DiagnosticKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     /// Create a new error, at the provided location
     pub fn error(file: FileId, span: Range<usize>, message: impl Into<String>) -> Self {
         Diagnostic::new(Level::Error, file, span, message)
@@ -78,6 +200,11 @@ impl Diagnostic {
         Diagnostic::new(Level::Warning, file, span, message)
     }
 
+    /// Create a new informational message, at the provided location
+    pub fn info(file: FileId, span: Range<usize>, message: impl Into<String>) -> Self {
+        Diagnostic::new(Level::Info, file, span, message)
+    }
+
     /// The diagnostic's message text
     pub fn text(&self) -> &str {
         &self.message.text
@@ -88,8 +215,28 @@ impl Diagnostic {
         self.message.span.range()
     }
 
+    /// The phase in which this diagnostic was generated
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// The category of this diagnostic
+    pub fn kind(&self) -> DiagnosticKind {
+        self.kind
+    }
+
     /// `true` if this diagnostic is an error
     pub fn is_error(&self) -> bool {
         matches!(self.level, Level::Error)
     }
 }
+
+/// Remove any diagnostic that is identical to an earlier one.
+///
+/// The same problem can be reported more than once, for instance when a file
+/// is included twice; we only want to show it to the user once. Different
+/// diagnostics at the same location are all kept, in their original order.
+pub(crate) fn remove_duplicates(diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+    diagnostics.retain(|diagnostic| seen.insert(diagnostic.clone()));
+}
@@ -17,6 +17,6 @@ mod tests;
 
 pub use common::{GlyphIdent, GlyphMap, GlyphName};
 pub use compile::Compiler;
-pub use diagnostic::{Diagnostic, Level};
+pub use diagnostic::{Diagnostic, DiagnosticKind, Level, Phase};
 pub use parse::{ParseTree, TokenSet};
 pub use token_tree::{typed, Kind, Node, NodeOrToken, Token};
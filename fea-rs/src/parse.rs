@@ -14,7 +14,7 @@ use std::{ffi::OsString, path::PathBuf, sync::Arc};
 
 pub use lexer::TokenSet;
 pub use source::{FileSystemResolver, SourceLoadError, SourceResolver};
-pub use tree::ParseTree;
+pub use tree::{GlyphToken, ParseTree};
 
 pub(crate) use context::{IncludeStatement, ParseContext};
 pub(crate) use parser::Parser;
@@ -17,7 +17,10 @@ use crate::{
     Diagnostic, GlyphMap, Node,
 };
 
-const MAX_INCLUDE_DEPTH: usize = 50;
+/// The default value of [`Opts::max_include_depth`].
+///
+/// [`Opts::max_include_depth`]: crate::compile::Opts::max_include_depth
+pub(crate) const DEFAULT_MAX_INCLUDE_DEPTH: usize = 50;
 
 /// Oversees parsing, following, resolving and validating input statements.
 ///
@@ -36,7 +39,9 @@ const MAX_INCLUDE_DEPTH: usize = 50;
 /// we avoid it by keeping track of what we've already parsed.
 ///
 /// Once parsing is finished, we use our `IncludeGraph` to validate that there
-/// are no cycles, and that the depth limit is not exceeded.
+/// are no cycles, and that the depth limit is not exceeded. Files past the
+/// depth limit are not included, so the tree can always be built without
+/// unbounded recursion.
 ///
 /// After parsing, you use [`generate_parse_tree`] to validate and assemble
 /// the parsed sources into a single parse tree. This is also where validation
@@ -58,13 +63,14 @@ pub(crate) struct ParseContext {
     sources: Arc<SourceList>,
     parsed_files: HashMap<FileId, (Node, Vec<Diagnostic>)>,
     graph: IncludeGraph,
+    max_include_depth: usize,
 }
 
 /// A simple graph of files and their includes.
 ///
 /// We maintain this in order to validate that the input does not contain
 /// any cyclical include statements, and does not exceed the maximum include
-/// depth of 50.
+/// depth.
 #[derive(Clone, Debug, Default)]
 struct IncludeGraph {
     // source file -> (destination file, span-in-source-for-error)
@@ -76,6 +82,8 @@ pub struct IncludeStatement(pub(crate) typed::Include);
 
 struct IncludeError {
     file: FileId,
+    /// the file named by the problem statement
+    included: FileId,
     /// the index of the problem statement, in the list of that file's includes
     statement_idx: usize,
     range: Range<usize>,
@@ -167,6 +175,7 @@ impl ParseContext {
             sources: sources.into_inner(),
             parsed_files,
             graph: includes,
+            max_include_depth: DEFAULT_MAX_INCLUDE_DEPTH,
         })
     }
 
@@ -174,6 +183,15 @@ impl ParseContext {
         self.root_id
     }
 
+    /// Set the maximum depth of nested include statements.
+    ///
+    /// The root file is at depth 0, and a file it includes is at depth 1. An
+    /// include that would exceed this depth is reported as an error, and the
+    /// file is not included.
+    pub(crate) fn set_max_include_depth(&mut self, depth: usize) {
+        self.max_include_depth = depth;
+    }
+
     /// Construct a `ParseTree`, and return any diagnostics.
     ///
     /// This method also performs validation of include statements.
@@ -184,16 +202,29 @@ impl ParseContext {
             .flat_map(|(_, (_, errs))| errs.iter())
             .cloned()
             .collect::<Vec<_>>();
-        let include_errors = self.graph.validate(self.root_id());
+        let include_errors = self.graph.validate(self.root_id(), self.max_include_depth);
         // record any errors:
         for IncludeError {
-            file, range, kind, ..
+            file,
+            included,
+            range,
+            kind,
+            ..
         } in &include_errors
         {
             // find statement
             let message = match kind {
-                IncludeErrorKind::Cycle => "cyclical include statement",
-                IncludeErrorKind::ToDeep => "exceded maximum include depth",
+                IncludeErrorKind::Cycle => {
+                    let path = self.sources.get(included).map(|src| src.path());
+                    format!(
+                        "cyclical include statement: '{}' is already being included",
+                        path.unwrap_or_default().to_string_lossy()
+                    )
+                }
+                IncludeErrorKind::ToDeep => format!(
+                    "exceeded maximum include depth ({})",
+                    self.max_include_depth
+                ),
             };
             all_errors.push(Diagnostic::error(*file, range.clone(), message));
         }
@@ -203,6 +234,7 @@ impl ParseContext {
         (
             ParseTree {
                 root,
+                root_id: self.root_id,
                 map: Arc::new(map),
                 sources: self.sources,
             },
@@ -269,23 +301,28 @@ impl IncludeGraph {
     /// If the result is non-empty, each returned error should be converted to
     /// d to diagnostics by the caller, and those statements should
     /// not be resolved when building the final tree.
-    fn validate(&self, root: FileId) -> Vec<IncludeError> {
+    fn validate(&self, root: FileId, max_depth: usize) -> Vec<IncludeError> {
         let edges = match self.nodes.get(&root) {
             None => return Vec::new(),
             Some(edges) => edges,
         };
 
         let mut stack = vec![(root, edges, 0_usize)];
-        let mut seen = HashSet::new();
+        // the root counts as seen, so that a cycle back to it is reported at
+        // the statement that closes the cycle, and not at the root's include
+        let mut seen = HashSet::from([root]);
         let mut bad_edges = Vec::new();
 
         while let Some((node, edges, cur_edge)) = stack.pop() {
             if let Some((child, stmt)) = edges.get(cur_edge) {
                 // push parent, advancing idx
                 stack.push((node, edges, cur_edge + 1));
-                if stack.len() >= MAX_INCLUDE_DEPTH - 1 {
+                // the stack holds the child's ancestors, so its length is the
+                // child's depth
+                if stack.len() > max_depth {
                     bad_edges.push(IncludeError {
                         file: node,
+                        included: *child,
                         statement_idx: cur_edge,
                         range: stmt.clone(),
                         kind: IncludeErrorKind::ToDeep,
@@ -302,6 +339,7 @@ impl IncludeGraph {
                     // we have a cycle
                     bad_edges.push(IncludeError {
                         file: node,
+                        included: *child,
                         statement_idx: cur_edge,
                         range: stmt.clone(),
                         kind: IncludeErrorKind::Cycle,
@@ -365,9 +403,19 @@ mod tests {
         graph.add_edge(c, (d, statement.range()));
         graph.add_edge(d, (b, statement.range()));
 
-        let result = graph.validate(a);
+        let result = graph.validate(a, DEFAULT_MAX_INCLUDE_DEPTH);
         assert_eq!(result[0].file, d);
+        assert_eq!(result[0].included, b);
         assert_eq!(result[0].range, 0..18);
+
+        // a cycle that returns to the root
+        let mut graph = IncludeGraph::default();
+        graph.add_edge(a, (b, statement.range()));
+        graph.add_edge(b, (a, statement.range()));
+        let result = graph.validate(a, DEFAULT_MAX_INCLUDE_DEPTH);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].file, b);
+        assert_eq!(result[0].included, a);
     }
 
     #[test]
@@ -385,9 +433,77 @@ mod tests {
             }),
         )
         .unwrap();
+        let bb_id = parse.sources.id_for_path("bb").unwrap();
         let (resolved, errs) = parse.generate_parse_tree();
         assert_eq!(errs.len(), 1);
-        assert_eq!(resolved.root.text_len(), "include(bb);".len());
+        // the error is at the statement that closes the cycle
+        assert_eq!(errs[0].message.file, bb_id);
+        assert_eq!(errs[0].range(), 0.."include(a);".len());
+        assert_eq!(
+            errs[0].message.text,
+            "cyclical include statement: 'a' is already being included"
+        );
+        // 'bb' is included in 'a', but its include of 'a' is skipped
+        assert_eq!(resolved.root.text_len(), "include(a);".len());
+        assert_eq!(resolved.map.resolve_range(0..7), (bb_id, 0..7));
+    }
+
+    #[test]
+    fn include_depth() {
+        // each file 'fN' includes 'fN+1', up to 'f60'
+        let parse_chain = || {
+            ParseContext::parse(
+                "f0".into(),
+                None,
+                Box::new(|path: &OsStr| {
+                    let n: usize = path.to_str().unwrap()[1..].parse().unwrap();
+                    match n {
+                        60 => Ok("".into()),
+                        n => Ok(format!("include(f{});", n + 1).into()),
+                    }
+                }),
+            )
+            .unwrap()
+        };
+
+        let parse = parse_chain();
+        let f50 = parse.sources.id_for_path("f50").unwrap();
+        let (tree, errs) = parse.generate_parse_tree();
+        assert_eq!(errs.len(), 1, "{errs:?}");
+        // 'f50' is at the maximum depth, so cannot include 'f51'
+        assert_eq!(errs[0].message.file, f50);
+        assert_eq!(errs[0].message.text, "exceeded maximum include depth (50)");
+        assert_eq!(tree.root.text_len(), "include(f51);".len());
+
+        let mut parse = parse_chain();
+        parse.set_max_include_depth(60);
+        let (tree, errs) = parse.generate_parse_tree();
+        assert!(errs.is_empty(), "{errs:?}");
+        // every file was included, and only the last one has any text
+        assert_eq!(tree.root.text_len(), 0);
+    }
+
+    #[test]
+    fn included_files() {
+        let parse = ParseContext::parse(
+            "a".into(),
+            None,
+            Box::new(|path: &OsStr| match path.to_str().unwrap() {
+                "a" => Ok("include(c);\ninclude(b);".into()),
+                "b" => Ok("include(c);".into()),
+                "c" => Ok("".into()),
+                _ => Err(SourceLoadError::new(
+                    path.to_owned(),
+                    std::io::Error::new(std::io::ErrorKind::NotFound, "oh no"),
+                )),
+            }),
+        )
+        .unwrap();
+        let (tree, _) = parse.generate_parse_tree();
+        assert_eq!(
+            tree.included_files(),
+            [std::path::PathBuf::from("b"), "c".into()]
+        );
     }
 
     #[test]
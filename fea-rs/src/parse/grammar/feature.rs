@@ -34,6 +34,82 @@ pub(crate) fn feature(parser: &mut Parser) {
     parser.in_node(Kind::FeatureNode, feature_body);
 }
 
+/// A set of conditions on the axes of a variable font, used by `variation` blocks.
+///
+/// ```fea
+/// conditionset heavy {
+///     wght 700 900;
+/// } heavy;
+/// ```
+pub(crate) fn condition_set(parser: &mut Parser) {
+    fn condition(parser: &mut Parser, recovery: TokenSet) {
+        parser.in_node(Kind::ConditionNode, |parser| {
+            parser.expect_tag(recovery);
+            parser.expect_recover(TokenSet::FLOAT_LIKE, recovery);
+            parser.expect_recover(TokenSet::FLOAT_LIKE, recovery);
+            parser.expect_semi();
+        })
+    }
+
+    fn condition_set_body(parser: &mut Parser) {
+        assert!(parser.eat(Kind::ConditionSetKw));
+        parser.expect_remap_recover(TokenSet::IDENT_LIKE, Kind::Label, LABEL_RECOVERY);
+        parser.expect(Kind::LBrace);
+        let recovery = TokenSet::TOP_LEVEL.union(TokenSet::SEMI_RBRACE);
+        while !parser.at_eof() && !parser.matches(0, Kind::RBrace) {
+            if parser.matches(0, TokenSet::TOP_LEVEL) {
+                break;
+            }
+            let start_pos = parser.nth_range(0).start;
+            condition(parser, recovery);
+            if parser.nth_range(0).start == start_pos {
+                parser.err_and_bump("expected axis tag");
+            }
+        }
+        parser.expect_recover(Kind::RBrace, TokenSet::TOP_SEMI);
+        parser.expect_remap_recover(TokenSet::IDENT_LIKE, Kind::Label, TokenSet::TOP_SEMI);
+        parser.expect_semi();
+    }
+
+    parser.in_node(Kind::ConditionSetNode, condition_set_body);
+}
+
+/// A feature block whose lookups are only applied under some conditionset.
+///
+/// ```fea
+/// variation rvrn heavy {
+///     lookup symbols_heavy;
+/// } rvrn;
+/// ```
+pub(crate) fn feature_variation(parser: &mut Parser) {
+    fn feature_variation_body(parser: &mut Parser) {
+        assert!(parser.eat(Kind::VariationKw));
+        let open_tag = parser.expect_tag(LABEL_RECOVERY);
+        parser.expect_remap_recover(TokenSet::IDENT_LIKE, Kind::Label, LABEL_RECOVERY);
+
+        parser.eat(Kind::UseExtensionKw);
+        parser.expect(Kind::LBrace);
+        while !parser.at_eof() && !parser.matches(0, Kind::RBrace) {
+            if !statement(parser, TokenSet::FEATURE_STATEMENT, false) {
+                if let Some(tag) = open_tag.as_ref() {
+                    parser.raw_error(tag.range.clone(), "Variation block is unclosed");
+                }
+                break;
+            }
+        }
+        parser.expect_recover(Kind::RBrace, TokenSet::TOP_SEMI);
+        let close_tag = parser.expect_tag(TokenSet::TOP_LEVEL);
+        if let (Some(open), Some(close)) = (open_tag, close_tag) {
+            if open.tag != close.tag {
+                parser.raw_error(close.range, format!("expected tag '{}'", open.tag));
+            }
+        }
+        parser.expect_semi();
+    }
+
+    parser.in_node(Kind::FeatureVariationNode, feature_variation_body);
+}
+
 pub(crate) fn lookup_block(parser: &mut Parser, recovery: TokenSet) {
     fn lookup_body(parser: &mut Parser, recovery: TokenSet) {
         assert!(parser.eat(Kind::LookupKw));
@@ -123,7 +199,7 @@ fn statement(parser: &mut Parser, recovery: TokenSet, in_lookup: bool) -> bool {
         Kind::FeatureNamesKw => feature_names(parser, recovery),
 
         _ => {
-            parser.err(format!(
+            parser.err_unknown(format!(
                 "'{}' Not valid in a feature block",
                 parser.current_token_text()
             ));
@@ -231,12 +307,14 @@ fn lookupflag(parser: &mut Parser, recovery: TokenSet) {
                 parser.eat_raw();
                 true
             }
-            Kind::MarkAttachmentTypeKw | Kind::UseMarkFilteringSetKw => {
+            kind @ (Kind::MarkAttachmentTypeKw | Kind::UseMarkFilteringSetKw) => {
                 parser.eat_raw();
                 if !parser.eat(Kind::NamedGlyphClass)
                     && !glyph::eat_glyph_class_list(parser, recovery)
                 {
-                    parser.err("lookupflag '{}' must be followed by a glyph class.");
+                    parser.err(format!(
+                        "lookupflag '{kind}' must be followed by a glyph class."
+                    ));
                 }
                 true
             }
@@ -26,8 +26,8 @@ pub(crate) fn anchor(parser: &mut Parser, recovery: TokenSet) -> bool {
         // <metric> metric>
         // <metric> <metric> <contour point>
         // <metric> <metric> <device> <device>
-        parser.expect_remap_recover(Kind::Number, AstKind::Metric, recovery);
-        parser.expect_remap_recover(Kind::Number, AstKind::Metric, recovery);
+        expect_anchor_metric(parser, recovery);
+        expect_anchor_metric(parser, recovery);
         if parser.eat(Kind::ContourpointKw) {
             parser.expect_recover(Kind::Number, recovery);
         } else if eat_device(parser, recovery) {
@@ -43,10 +43,11 @@ pub(crate) fn anchor(parser: &mut Parser, recovery: TokenSet) -> bool {
 // B: <<metric> <metric> <metric> <metric>> (<1 2 -5 242>)
 // C: <<metric> <metric> <metric> <metric> <device> <device> <device> <device>>
 // (<1 2 -5 242 <device 1 2, 3 4> <device NULL> <device 1 1, 2 2> <device NULL>>)
+// where any <metric> may also be a variable metric (see below)
 // return 'true' if we make any progress (this looks like a value record)
 pub(crate) fn eat_value_record(parser: &mut Parser, recovery: TokenSet) -> bool {
     fn value_record_body(parser: &mut Parser, recovery: TokenSet) {
-        if parser.eat(Kind::Number) {
+        if eat_metric(parser, recovery) {
             return;
         }
 
@@ -57,10 +58,10 @@ pub(crate) fn eat_value_record(parser: &mut Parser, recovery: TokenSet) -> bool
             return;
         }
 
-        parser.expect_recover(Kind::Number, recovery);
-        parser.expect_recover(Kind::Number, recovery);
-        parser.expect_recover(Kind::Number, recovery);
-        parser.expect_recover(Kind::Number, recovery);
+        expect_metric(parser, recovery);
+        expect_metric(parser, recovery);
+        expect_metric(parser, recovery);
+        expect_metric(parser, recovery);
         if parser.eat(Kind::RAngle) {
             return;
         }
@@ -72,9 +73,10 @@ pub(crate) fn eat_value_record(parser: &mut Parser, recovery: TokenSet) -> bool
         parser.expect_recover(Kind::RAngle, recovery);
     }
 
+    const RECORD_START: TokenSet = TokenSet::new(&[Kind::Number, Kind::NullKw, Kind::LParen]);
     let looks_like_record = parser.matches(0, Kind::Number)
-        || (parser.matches(0, Kind::LAngle)
-            && parser.matches(1, TokenSet::new(&[Kind::Number, Kind::NullKw])));
+        || parser.matches(0, Kind::LParen)
+        || (parser.matches(0, Kind::LAngle) && parser.matches(1, RECORD_START));
 
     if !looks_like_record {
         return false;
@@ -96,7 +98,66 @@ pub(crate) fn expect_value_record(parser: &mut Parser, recovery: TokenSet) -> bo
     }
 }
 
-fn expect_device(parser: &mut Parser, recovery: TokenSet) -> bool {
+// an anchor coordinate; either a number or a variable metric
+pub(crate) fn expect_anchor_metric(parser: &mut Parser, recovery: TokenSet) -> bool {
+    if parser.matches(0, Kind::LParen) {
+        variable_metric(parser, recovery);
+        return true;
+    }
+    parser.expect_remap_recover(Kind::Number, AstKind::Metric, recovery)
+}
+
+// a number, or a variable metric
+fn eat_metric(parser: &mut Parser, recovery: TokenSet) -> bool {
+    if parser.matches(0, Kind::LParen) {
+        variable_metric(parser, recovery);
+        return true;
+    }
+    parser.eat(Kind::Number)
+}
+
+fn expect_metric(parser: &mut Parser, recovery: TokenSet) -> bool {
+    eat_metric(parser, recovery) || parser.expect_recover(Kind::Number, recovery)
+}
+
+// a metric that varies with location in designspace:
+// (<location>:<number> <location>:<number> ...)
+// where a location is a comma-separated list of <axis tag>=<user coordinate>:
+// (wght=200:-20 wght=900:-60 wght=900,wdth=150:-80)
+fn variable_metric(parser: &mut Parser, recovery: TokenSet) {
+    fn location_spec_item(parser: &mut Parser, recovery: TokenSet) {
+        parser.in_node(AstKind::LocationSpecItemNode, |parser| {
+            parser.expect_tag(recovery);
+            parser.expect_recover(Kind::Eq, recovery);
+            parser.expect_recover(TokenSet::new(&[Kind::Number, Kind::Float]), recovery);
+        })
+    }
+
+    fn location_value(parser: &mut Parser, recovery: TokenSet) {
+        parser.in_node(AstKind::LocationValueNode, |parser| {
+            parser.in_node(AstKind::LocationSpecNode, |parser| {
+                location_spec_item(parser, recovery);
+                while parser.eat(Kind::Comma) {
+                    location_spec_item(parser, recovery);
+                }
+            });
+            parser.expect_recover(Kind::Colon, recovery);
+            parser.expect_recover(Kind::Number, recovery);
+        })
+    }
+
+    let recovery = recovery.union(TokenSet::new(&[Kind::RParen, Kind::RAngle]));
+    parser.in_node(AstKind::VariableMetricNode, |parser| {
+        parser.expect(Kind::LParen);
+        // each item starts with a tag, so we always make progress
+        while parser.matches(0, TokenSet::TAG_LIKE) {
+            location_value(parser, recovery);
+        }
+        parser.expect_recover(Kind::RParen, recovery);
+    })
+}
+
+pub(crate) fn expect_device(parser: &mut Parser, recovery: TokenSet) -> bool {
     let result = eat_device(parser, recovery);
     if !result {
         parser.err_recover("expected device record", recovery);
@@ -109,7 +170,8 @@ fn eat_device(parser: &mut Parser, recovery: TokenSet) -> bool {
         let recovery = recovery.union(TokenSet::new(&[Kind::LAngle, Kind::RAngle, Kind::Comma]));
         parser.expect_recover(Kind::LAngle, recovery);
         parser.expect_recover(Kind::DeviceKw, recovery);
-        if parser.eat(Kind::NullKw) {
+        // 'NULL', or a reference to a named device
+        if parser.eat(Kind::NullKw) || parser.eat_remap(TokenSet::IDENT_LIKE, AstKind::Ident) {
             parser.expect_recover(Kind::RAngle, recovery);
             return;
         }
@@ -194,10 +256,40 @@ mod tests {
 <device 1 2>
 <device 1 2,>
 <device 1 2, 3 4, 5 6, 7 8>
+<device KERN_FIX>
 ";
         let (_out, _, errstr) = debug_parse_output(fea, |parser| {
             expect_device(parser, TokenSet::EMPTY);
         });
         assert!(errstr.is_empty(), "{}", errstr);
     }
+
+    #[test]
+    fn variable_metric_smoke_test() {
+        let fea = "\
+(wght=200:-20 wght=900:-60)
+<0 (wght=200:12 wght=900:22 wdth=150,wght=900:42) 0 (wght=900.5:1)>
+";
+        let (out, _, errstr) = debug_parse_output(fea, |parser| {
+            expect_value_record(parser, TokenSet::EMPTY);
+            expect_value_record(parser, TokenSet::EMPTY);
+        });
+        assert!(errstr.is_empty(), "{}", errstr);
+        let n_variable = out
+            .iter_children()
+            .filter_map(|child| child.as_node())
+            .flat_map(|record| record.iter_children())
+            .filter(|child| child.kind() == AstKind::VariableMetricNode)
+            .count();
+        assert_eq!(n_variable, 3);
+    }
+
+    #[test]
+    fn variable_anchor_smoke_test() {
+        let fea = "<anchor (wght=200:-100 wght=900:-150) 0 contourpoint 2>";
+        let (_out, _, errstr) = debug_parse_output(fea, |parser| {
+            anchor(parser, TokenSet::EMPTY);
+        });
+        assert!(errstr.is_empty(), "{}", errstr);
+    }
 }
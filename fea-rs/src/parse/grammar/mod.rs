@@ -39,10 +39,16 @@ fn top_level_element(parser: &mut Parser) {
         language_system(parser)
     } else if parser.matches(0, Kind::FeatureKw) {
         feature::feature(parser)
+    } else if parser.matches(0, Kind::ConditionSetKw) {
+        feature::condition_set(parser)
+    } else if parser.matches(0, Kind::VariationKw) {
+        feature::feature_variation(parser)
     } else if parser.matches(0, Kind::MarkClassKw) {
         mark_class(parser)
     } else if parser.matches(0, Kind::AnchorDefKw) {
         anchor_def(parser)
+    } else if parser.matches(0, Kind::DeviceDefKw) {
+        device_def(parser)
     } else if parser.matches(0, Kind::AnonKw) {
         anonymous(parser)
     } else if parser.matches(0, Kind::NamedGlyphClass) {
@@ -50,10 +56,11 @@ fn top_level_element(parser: &mut Parser) {
     } else if parser.matches(0, Kind::ValueRecordDefKw) {
         unimplemented!()
     } else {
-        parser.err_and_bump(format!(
+        parser.err_unknown(format!(
             "Unexpected token '{}', expected global keyword.",
             parser.current_token_text()
         ));
+        parser.eat_raw();
         advance_to_top_level(parser);
     }
 }
@@ -189,8 +196,8 @@ fn anchor_def(parser: &mut Parser) {
             let recovery = TokenSet::TOP_LEVEL
                 .union(TokenSet::IDENT_LIKE)
                 .union(TokenSet::new(&[Kind::ContourpointKw, Kind::Semi]));
-            parser.expect_remap_recover(Kind::Number, AstKind::Metric, recovery);
-            parser.expect_remap_recover(Kind::Number, AstKind::Metric, recovery);
+            metrics::expect_anchor_metric(parser, recovery);
+            metrics::expect_anchor_metric(parser, recovery);
             if parser.eat(Kind::ContourpointKw) {
                 parser.expect_recover(Kind::Number, TokenSet::TOP_SEMI);
             }
@@ -202,6 +209,23 @@ fn anchor_def(parser: &mut Parser) {
     parser.in_node(AstKind::AnchorDefNode, anchor_def_body);
 }
 
+/// A named device table, which can be referenced as `<device NAME>`.
+///
+/// ```fea
+/// deviceDef <device 11 -1, 12 -1> KERN_FIX;
+/// ```
+fn device_def(parser: &mut Parser) {
+    fn device_def_body(parser: &mut Parser) {
+        assert!(parser.eat(Kind::DeviceDefKw));
+        let recovery = TokenSet::TOP_LEVEL.union(TokenSet::IDENT_LIKE);
+        metrics::expect_device(parser, recovery);
+        parser.expect_remap_recover(TokenSet::IDENT_LIKE, AstKind::Ident, TokenSet::TOP_SEMI);
+        parser.expect_semi();
+    }
+
+    parser.in_node(AstKind::DeviceDefNode, device_def_body);
+}
+
 fn anonymous(parser: &mut Parser) {
     fn anon_body(parser: &mut Parser) {
         assert!(parser.eat(Kind::AnonKw));
@@ -317,7 +341,7 @@ fn debug_parse_output(
         if !err_str.is_empty() {
             err_str.push('\n');
         }
-        crate::util::highlighting::write_diagnostic(&mut err_str, err, &source, Some(80));
+        crate::util::highlighting::write_diagnostic(&mut err_str, err, &source, Some(80), false);
     }
     (node, errs, err_str)
 }
@@ -332,4 +356,28 @@ mod tests {
         assert!(!errors.is_empty(), "{}", fea);
         assert!(errors.first().unwrap().text().contains("cvParameters"));
     }
+
+    #[test]
+    fn condition_set_and_variation() {
+        let fea = "\
+conditionset heavy {
+    wght 700 900;
+    wdth -0.5 100.5;
+} heavy;
+variation rvrn heavy {
+    sub a by b;
+} rvrn;
+";
+        let (out, _, errstr) = debug_parse_output(fea, root);
+        assert!(errstr.is_empty(), "{}", errstr);
+        let kinds = out
+            .iter_children()
+            .filter(|child| !child.kind().is_trivia())
+            .map(|child| child.kind())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [AstKind::ConditionSetNode, AstKind::FeatureVariationNode]
+        );
+    }
 }
@@ -18,6 +18,7 @@ mod tags {
     pub const STAT: Tag = Tag::new(b"STAT");
     pub const head: Tag = Tag::new(b"head");
     pub const hhea: Tag = Tag::new(b"hhea");
+    pub const meta: Tag = Tag::new(b"meta");
     pub const name: Tag = Tag::new(b"name");
     pub const OS2: Tag = Tag::new(b"OS/2");
     pub const vhea: Tag = Tag::new(b"vhea");
@@ -49,6 +50,7 @@ pub(crate) fn table(parser: &mut Parser) {
         tags::vhea => table_impl(parser, tags::vhea, vhea::table_entry),
         tags::vmtx => table_impl(parser, tags::vmtx, vmtx::table_entry),
         tags::STAT => table_impl(parser, tags::STAT, stat::table_entry),
+        tags::meta => table_impl(parser, tags::meta, meta::table_entry),
         _ => unknown_table(parser, tag.range),
     }
 
@@ -519,6 +521,31 @@ mod stat {
     }
 }
 
+mod meta {
+    use super::*;
+
+    // the meta table is an fea-rs extension, and these are not keywords
+    static META_KEYWORDS: &[&[u8]] = &[b"DesignLanguages", b"SupportedLanguages"];
+
+    pub(crate) fn table_entry(parser: &mut Parser, recovery: TokenSet) {
+        let recovery_semi = recovery.add(Kind::RBrace).union(TokenSet::SEMI);
+        if META_KEYWORDS.contains(&parser.nth_raw(0)) {
+            parser.in_node(AstKind::MetaRecordNode, |parser| {
+                parser.eat_raw();
+                parser.expect_recover(Kind::String, recovery_semi);
+                parser.expect_semi();
+            })
+        } else {
+            parser.err_recover(
+                "expected 'DesignLanguages' or 'SupportedLanguages'",
+                recovery_semi,
+            );
+            parser.eat_until(recovery_semi);
+            parser.eat(Kind::Semi);
+        }
+    }
+}
+
 fn table_kind_for_tag(tag: Tag) -> AstKind {
     match tag {
         tags::head => AstKind::HeadTableNode,
@@ -530,6 +557,7 @@ fn table_kind_for_tag(tag: Tag) -> AstKind {
         tags::vhea => AstKind::VheaTableNode,
         tags::vmtx => AstKind::VmtxTableNode,
         tags::STAT => AstKind::StatTableNode,
+        tags::meta => AstKind::MetaTableNode,
         _ => AstKind::TableNode,
     }
 }
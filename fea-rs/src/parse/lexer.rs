@@ -17,6 +17,7 @@ pub(crate) struct Lexer<'a> {
     pos: usize,
     after_backslash: bool,
     after_l_paren: bool,
+    after_include: bool,
 }
 
 impl<'a> Lexer<'a> {
@@ -26,6 +27,7 @@ impl<'a> Lexer<'a> {
             pos: 0,
             after_backslash: false,
             after_l_paren: false,
+            after_include: false,
         }
     }
 
@@ -67,6 +69,7 @@ impl<'a> Lexer<'a> {
             b']' => Kind::RSquare,
             b'(' => Kind::LParen,
             b')' => Kind::RParen,
+            b':' => Kind::Colon,
             b'<' => Kind::LAngle,
             b'>' => Kind::RAngle,
             b'\'' => Kind::SingleQuote,
@@ -75,7 +78,12 @@ impl<'a> Lexer<'a> {
         };
 
         self.after_backslash = matches!(kind, Kind::Backslash);
-        self.after_l_paren = matches!(kind, Kind::LParen);
+        // parens are only used for paths in include statements; elsewhere they
+        // are used for variable metrics, which are tokenized normally.
+        self.after_l_paren = self.after_include && matches!(kind, Kind::LParen);
+        if !kind.is_trivia() {
+            self.after_include = matches!(kind, Kind::IncludeKw);
+        }
 
         let len = self.pos - start_pos;
         Lexeme { len, kind }
@@ -404,6 +412,21 @@ mod tests {
         assert_eq!(token_strs[16], ";");
     }
 
+    #[test]
+    fn parens_outside_include() {
+        let fea = "include(a.fea) (wght=200:-20)";
+        let tokens = tokenize(fea);
+        let token_strs = debug_tokens2(&tokens, fea);
+        assert_eq!(token_strs[2], "Path");
+        assert_eq!(token_strs[5], "(");
+        assert_eq!(token_strs[6], "ID(wght)");
+        assert_eq!(token_strs[7], "=");
+        assert_eq!(token_strs[8], "NUM(200)");
+        assert_eq!(token_strs[9], ":");
+        assert_eq!(token_strs[10], "NUM(-20)");
+        assert_eq!(token_strs[11], ")");
+    }
+
     #[test]
     fn trivia() {
         let fea = "# OpenType 4.h\n# -@,\nlanguagesystem DFLT cool;";
@@ -49,6 +49,7 @@ pub enum Kind {
     RSquare,
     LParen,
     RParen,
+    Colon,
     LAngle,
     RAngle,
     SingleQuote,
@@ -61,9 +62,12 @@ pub enum Kind {
     LookupKw,
     LanguagesystemKw,
     AnchorDefKw,
+    DeviceDefKw,
     FeatureKw,
     MarkClassKw,
     AnonKw, // 'anon' and 'anonymous'
+    ConditionSetKw,
+    VariationKw,
 
     // other keywords
     AnchorKw,
@@ -185,10 +189,12 @@ impl Kind {
             b"anchor" => Some(Kind::AnchorKw),
             b"anchorDef" => Some(Kind::AnchorDefKw),
             b"anon" | b"anonymous" => Some(Kind::AnonKw),
+            b"conditionset" => Some(Kind::ConditionSetKw),
             b"by" => Some(Kind::ByKw),
             b"contourpoint" => Some(Kind::ContourpointKw),
             b"cursive" => Some(Kind::CursiveKw),
             b"device" => Some(Kind::DeviceKw), //[ Not implemented ];
+            b"deviceDef" => Some(Kind::DeviceDefKw),
             b"enum" | b"enumerate" => Some(Kind::EnumKw),
             b"exclude_dflt" | b"excludeDFLT" => Some(Kind::ExcludeDfltKw),
             b"feature" => Some(Kind::FeatureKw), //(used as a block and as a statement);
@@ -218,6 +224,7 @@ impl Kind {
             b"subtable" => Some(Kind::SubtableKw),
             b"table" => Some(Kind::TableKw),
             b"useExtension" => Some(Kind::UseExtensionKw),
+            b"variation" => Some(Kind::VariationKw),
             b"UseMarkFilteringSet" => Some(Kind::UseMarkFilteringSetKw),
             b"valueRecordDef" => Some(Kind::ValueRecordDefKw),
             b"HorizAxis.BaseScriptList" => Some(Kind::HorizAxisBaseScriptListKw),
@@ -300,6 +307,7 @@ impl Kind {
             Self::RSquare => AstKind::RSquare,
             Self::LParen => AstKind::LParen,
             Self::RParen => AstKind::RParen,
+            Self::Colon => AstKind::Colon,
             Self::LAngle => AstKind::LAngle,
             Self::RAngle => AstKind::RAngle,
             Self::SingleQuote => AstKind::SingleQuote,
@@ -311,9 +319,12 @@ impl Kind {
             Self::LookupKw => AstKind::LookupKw,
             Self::LanguagesystemKw => AstKind::LanguagesystemKw,
             Self::AnchorDefKw => AstKind::AnchorDefKw,
+            Self::DeviceDefKw => AstKind::DeviceDefKw,
             Self::FeatureKw => AstKind::FeatureKw,
             Self::MarkClassKw => AstKind::MarkClassKw,
             Self::AnonKw => AstKind::AnonKw,
+            Self::ConditionSetKw => AstKind::ConditionSetKw,
+            Self::VariationKw => AstKind::VariationKw,
             Self::AnchorKw => AstKind::AnchorKw,
             Self::ByKw => AstKind::ByKw,
             Self::ContourpointKw => AstKind::ContourpointKw,
@@ -424,6 +435,7 @@ impl std::fmt::Display for Kind {
             Self::RSquare => write!(f, "]"),
             Self::LParen => write!(f, "("),
             Self::RParen => write!(f, ")"),
+            Self::Colon => write!(f, ":"),
             Self::LAngle => write!(f, "<"),
             Self::RAngle => write!(f, ">"),
             Self::SingleQuote => write!(f, "'"),
@@ -437,9 +449,12 @@ impl std::fmt::Display for Kind {
             Self::LookupKw => write!(f, "LookupKw"),
             Self::LanguagesystemKw => write!(f, "LanguagesystemKw"),
             Self::AnchorDefKw => write!(f, "AnchorDefKw"),
+            Self::DeviceDefKw => write!(f, "DeviceDefKw"),
             Self::FeatureKw => write!(f, "FeatureKw"),
             Self::MarkClassKw => write!(f, "MarkClassKw"),
             Self::AnonKw => write!(f, "AnonKw"),
+            Self::ConditionSetKw => write!(f, "ConditionSetKw"),
+            Self::VariationKw => write!(f, "VariationKw"),
             Self::AnchorKw => write!(f, "AnchorKw"),
             Self::ByKw => write!(f, "ByKw"),
             Self::ContourpointKw => write!(f, "ContourpointKw"),
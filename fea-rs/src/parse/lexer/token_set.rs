@@ -21,10 +21,13 @@ impl TokenSet {
         Kind::LookupKw,
         Kind::LanguagesystemKw,
         Kind::AnchorDefKw,
+        Kind::DeviceDefKw,
         Kind::FeatureKw,
         Kind::MarkClassKw,
         Kind::AnonKw,
         Kind::NamedGlyphClass,
+        Kind::ConditionSetKw,
+        Kind::VariationKw,
     ]);
 
     /// Tokens that may be a tag.
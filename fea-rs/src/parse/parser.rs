@@ -11,7 +11,7 @@ use super::{
 };
 use crate::token_tree::{AstSink, Kind};
 
-use crate::diagnostic::Diagnostic;
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
 
 const LOOKAHEAD: usize = 4;
 const LOOKAHEAD_MAX: usize = LOOKAHEAD - 1;
@@ -268,6 +268,17 @@ impl<'b, 'a> Parser<'a, 'b> {
         self.sink.error(err);
     }
 
+    /// Write an error for an item we don't understand, do not advance.
+    ///
+    /// These are downgraded to warnings if [`Opts::ignore_unknown_items`] is set.
+    ///
+    /// [`Opts::ignore_unknown_items`]: crate::compile::Opts::ignore_unknown_items
+    pub(crate) fn err_unknown(&mut self, error: impl Into<String>) {
+        let err = Diagnostic::error(FileId::CURRENT_FILE, self.nth_range(0), error)
+            .with_kind(DiagnosticKind::UnknownItem);
+        self.sink.error(err);
+    }
+
     /// Write an error associated *before* the whitespace of the current token.
     ///
     /// In practice this is useful when missing things like semis or braces.
@@ -90,6 +90,19 @@ pub trait SourceResolver {
         path.to_owned()
     }
 
+    /// The locations that were checked when resolving a raw path.
+    ///
+    /// This is used to explain why an include could not be loaded: if a
+    /// source fails to load, these are listed in the error, in order. The
+    /// arguments are the same as for [`resolve_raw_path`].
+    ///
+    /// The default implementation returns an empty list.
+    ///
+    /// [`resolve_raw_path`]: SourceResolver::resolve_raw_path
+    fn searched_locations(&self, _path: &OsStr, _included_from: Option<&OsStr>) -> Vec<OsString> {
+        Vec::new()
+    }
+
     /// If necessary, canonicalize this path.
     ///
     /// There are an unbounded number of ways to represent a given path;
@@ -135,6 +148,7 @@ where
 /// This is the common case.
 pub struct FileSystemResolver {
     project_root: PathBuf,
+    include_dirs: Vec<PathBuf>,
 }
 
 impl FileSystemResolver {
@@ -143,7 +157,22 @@ impl FileSystemResolver {
     /// If compiling from a UFO, the root directory is the UFO directory. In other
     /// cases, it is likely the directory containing the root feature file.
     pub fn new(project_root: PathBuf) -> Self {
-        Self { project_root }
+        Self {
+            project_root,
+            include_dirs: Vec::new(),
+        }
+    }
+
+    /// Provide additional directories to search for included files.
+    ///
+    /// A relative `include` path is first resolved against the project root,
+    /// and then against the directory of the including file. If it is not
+    /// found in either, each of these directories is tried, in order. This is
+    /// similar to the `-I` option of a C compiler, and is intended for sharing
+    /// a library of feature files between projects.
+    pub fn with_include_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.include_dirs = dirs.into_iter().collect();
+        self
     }
 }
 
@@ -157,7 +186,17 @@ impl SourceResolver for FileSystemResolver {
     fn resolve_raw_path(&self, path: &OsStr, included_from: Option<&OsStr>) -> OsString {
         let path = Path::new(path);
         let included_from = included_from.map(Path::new).and_then(Path::parent);
-        util::paths::resolve_path(path, &self.project_root, included_from).into_os_string()
+        util::paths::resolve_path(path, &self.project_root, included_from, &self.include_dirs)
+            .into_os_string()
+    }
+
+    fn searched_locations(&self, path: &OsStr, included_from: Option<&OsStr>) -> Vec<OsString> {
+        let path = Path::new(path);
+        let included_from = included_from.map(Path::new).and_then(Path::parent);
+        util::paths::search_locations(path, &self.project_root, included_from, &self.include_dirs)
+            .into_iter()
+            .map(PathBuf::into_os_string)
+            .collect()
     }
 
     fn canonicalize(&self, path: &OsStr) -> Result<OsString, SourceLoadError> {
@@ -219,6 +258,16 @@ impl Source {
         (offset_idx + 1, offset_in_line)
     }
 
+    /// Compute the zero-based line and UTF-16 column for a given utf-8 offset.
+    ///
+    /// This is the format of positions in the Language Server Protocol.
+    pub(crate) fn utf16_position(&self, offset: usize) -> (usize, usize) {
+        let (line, _) = self.line_col_for_offset(offset);
+        let line_start = self.offset_for_line_number(line);
+        let column = self.contents[line_start..offset].encode_utf16().count();
+        (line - 1, column)
+    }
+
     /// returns the (1-indexed) number and text.
     pub fn line_containing_offset(&self, offset: usize) -> (usize, &str) {
         let offset_idx = match self.line_offsets.binary_search(&offset) {
@@ -264,6 +313,22 @@ impl SourceMap {
         }
     }
 
+    /// Add the entries of `other`, for a tree that starts at `offset`.
+    pub(crate) fn append(&mut self, other: &SourceMap, offset: usize) {
+        for (src, dest) in &other.offsets {
+            self.add_entry(src.start + offset..src.end + offset, *dest);
+        }
+    }
+
+    /// `true` if this range is a single uninterrupted span of one source.
+    ///
+    /// This is `false` if the range contains the contents of an included file.
+    pub(crate) fn is_contiguous(&self, global_range: Range<usize>) -> bool {
+        self.offsets
+            .iter()
+            .any(|(chunk, _)| chunk.start <= global_range.start && global_range.end <= chunk.end)
+    }
+
     /// panics if `global_range` crosses a file barrier?
     pub(crate) fn resolve_range(&self, global_range: Range<usize>) -> (FileId, Range<usize>) {
         // it is hard to imagine more than a couple hundred include statements,
@@ -311,14 +376,18 @@ impl SourceLoader {
         path: &dyn AsRef<OsStr>,
         included_by: Option<FileId>,
     ) -> Result<FileId, SourceLoadError> {
+        let raw_path = path.as_ref();
         let included_by = included_by.map(|id| self.sources.get(&id).unwrap().path.as_os_str());
-        let path = self.resolver.resolve_raw_path(path.as_ref(), included_by);
-        let canonical = self.resolver.canonicalize(&path)?;
+        let path = self.resolver.resolve_raw_path(raw_path, included_by);
+        let add_searched = |err: SourceLoadError| {
+            err.with_searched_locations(&self.resolver.searched_locations(raw_path, included_by))
+        };
+        let canonical = self.resolver.canonicalize(&path).map_err(add_searched)?;
 
         match self.sources.id_for_path(&canonical) {
             Some(id) => Ok(id),
             None => {
-                let source = self.resolver.resolve(&path)?;
+                let source = self.resolver.resolve(&path).map_err(add_searched)?;
                 let id = source.id;
                 self.sources.add(canonical, source);
                 Ok(id)
@@ -336,6 +405,20 @@ impl SourceList {
         self.sources.get(id)
     }
 
+    /// Add the sources from `other` that we don't already have.
+    pub(crate) fn extend(&mut self, other: &SourceList) {
+        for (path, id) in other.iter_paths() {
+            if self.id_for_path(path).is_none() {
+                self.add(path.to_owned(), other.sources[&id].clone());
+            }
+        }
+    }
+
+    /// Iterate over the canonical path and id of each source.
+    pub(crate) fn iter_paths(&self) -> impl Iterator<Item = (&OsStr, FileId)> + '_ {
+        self.ids.iter().map(|(path, id)| (path.as_os_str(), *id))
+    }
+
     fn add(&mut self, canonical_path: OsString, source: Source) {
         self.ids.insert(canonical_path, source.id);
         self.sources.insert(source.id, source);
@@ -344,11 +427,15 @@ impl SourceList {
     /// Generate a string suitable for presenting a [`Diagnostic`] to the user.
     ///
     /// This associates the message with the appropriate source location and
-    /// syntax highlighting.
-    pub(crate) fn format_diagnostic(&self, err: &Diagnostic) -> String {
+    /// syntax highlighting. If `show_kind` is `true`, the diagnostic's
+    /// [`DiagnosticKind`] is included after its level, as in
+    /// `error[undefined-glyph]`.
+    ///
+    /// [`DiagnosticKind`]: crate::DiagnosticKind
+    pub(crate) fn format_diagnostic(&self, err: &Diagnostic, show_kind: bool) -> String {
         let mut s = String::new();
         let source = self.get(&err.message.file).unwrap();
-        crate::util::highlighting::write_diagnostic(&mut s, err, source, None);
+        crate::util::highlighting::write_diagnostic(&mut s, err, source, None, show_kind);
         s
     }
 }
@@ -364,4 +451,18 @@ impl SourceLoadError {
             path,
         }
     }
+
+    /// Add the locations that were searched for this source to the cause.
+    fn with_searched_locations(self, locations: &[OsString]) -> Self {
+        if locations.is_empty() {
+            return self;
+        }
+        let locations = locations
+            .iter()
+            .map(|path| format!("'{}'", Path::new(path).display()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let cause = format!("{} (searched {locations})", self.cause);
+        SourceLoadError::new(self.path, cause)
+    }
 }
@@ -1,10 +1,10 @@
 //! the result of a parsing operation
 
-use std::sync::Arc;
+use std::{ops::Range, path::PathBuf, sync::Arc};
 
 use super::source::Source;
 use super::{FileId, SourceList, SourceMap};
-use crate::{token_tree::typed, Diagnostic, Node};
+use crate::{common::GlyphId, token_tree::typed, Diagnostic, GlyphMap, Kind, Node};
 
 /// A fully parsed feature file, with attached imports and a sourcemap.
 ///
@@ -16,11 +16,25 @@ use crate::{token_tree::typed, Diagnostic, Node};
 #[derive(Clone, Debug)]
 pub struct ParseTree {
     pub(crate) root: Node,
+    pub(crate) root_id: FileId,
     // Arc so we can send across threads
     pub(crate) sources: Arc<SourceList>,
     pub(crate) map: Arc<SourceMap>,
 }
 
+/// A glyph name or CID in the source, and the glyph it refers to.
+///
+/// See [`ParseTree::resolve_glyphs`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GlyphToken {
+    /// The file that contains this token
+    pub file: FileId,
+    /// The range of the token in its file
+    pub range: Range<usize>,
+    /// The glyph this token refers to, or `None` if it is not in the glyph map
+    pub glyph: Option<GlyphId>,
+}
+
 impl ParseTree {
     /// The root node for this parse tree
     pub fn root(&self) -> &Node {
@@ -37,6 +51,41 @@ impl ParseTree {
         &self.map
     }
 
+    /// Combine this tree with another, as if `other` were included at the end
+    /// of our root file.
+    ///
+    /// The nodes of both trees are shared. Ours keep their positions, and so
+    /// anything that refers to a position in this tree is still valid in the
+    /// result; the nodes of `other` follow them.
+    pub(crate) fn append(&self, other: &ParseTree) -> ParseTree {
+        let mut map = SourceMap::clone(&self.map);
+        map.append(&other.map, self.root.text_len());
+        let mut sources = SourceList::clone(&self.sources);
+        sources.extend(&other.sources);
+        ParseTree {
+            root: self.root.append(&other.root),
+            root_id: self.root_id,
+            sources: Arc::new(sources),
+            map: Arc::new(map),
+        }
+    }
+
+    /// The paths of all files included (directly or indirectly) by the root file.
+    ///
+    /// These are the paths used to load each file, after canonicalization by
+    /// the resolver, and are sorted. This is intended for build systems that
+    /// need to know which files a compilation depends on.
+    pub fn included_files(&self) -> Vec<PathBuf> {
+        let mut paths = self
+            .sources
+            .iter_paths()
+            .filter(|(_, id)| *id != self.root_id)
+            .map(|(path, _)| PathBuf::from(path))
+            .collect::<Vec<_>>();
+        paths.sort_unstable();
+        paths
+    }
+
     /// Return the source for this id, if it exists in the source map
     pub fn get_source(&self, id: FileId) -> Option<&Source> {
         self.sources.get(&id)
@@ -47,6 +96,67 @@ impl ParseTree {
     /// This associates the message with the appropriate source location and
     /// syntax highlighting.
     pub fn format_diagnostic(&self, err: &Diagnostic) -> String {
-        self.sources.format_diagnostic(err)
+        self.sources.format_diagnostic(err, false)
+    }
+
+    /// Find the glyph for each glyph name and CID in the tree.
+    ///
+    /// This is intended for editor features such as "go to glyph", and does
+    /// not require the source to be valid or to compile. Tokens that are not
+    /// in the glyph map are included, with no glyph. Glyph ranges are not
+    /// expanded; only their endpoints are included. Tokens are returned in
+    /// the order they appear in the tree.
+    pub fn resolve_glyphs(&self, glyph_map: &GlyphMap) -> Vec<GlyphToken> {
+        self.root
+            .iter_tokens()
+            .filter_map(|token| {
+                let glyph = match token.kind {
+                    Kind::GlyphName => glyph_map.get(token.as_str()),
+                    Kind::Cid => token
+                        .as_str()
+                        .parse::<u16>()
+                        .ok()
+                        .and_then(|cid| glyph_map.get(&cid)),
+                    _ => return None,
+                };
+                let (file, range) = self.map.resolve_range(token.range());
+                Some(GlyphToken { file, range, glyph })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, sync::Arc};
+
+    use crate::{parse::SourceLoadError, GlyphMap, GlyphName};
+
+    #[test]
+    fn resolve_glyphs() {
+        let glyph_map = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(GlyphName::from)
+            .collect::<GlyphMap>();
+        let fea = "@C = [a-c]; feature test { sub a by nope; sub \\b by d; } test;";
+        let resolver = move |_: &OsStr| -> Result<Arc<str>, SourceLoadError> { Ok(fea.into()) };
+        let (tree, _) =
+            crate::parse::parse_root("test.fea".into(), Some(&glyph_map), resolver).unwrap();
+        let resolved = tree
+            .resolve_glyphs(&glyph_map)
+            .into_iter()
+            .map(|token| (&fea[token.range], token.glyph.map(|gid| gid.to_u16())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            resolved,
+            [
+                ("a", Some(0)),
+                ("c", Some(2)),
+                ("a", Some(0)),
+                ("nope", None),
+                ("b", Some(1)),
+                ("d", Some(3)),
+            ]
+        );
     }
 }
@@ -3,7 +3,7 @@
 use std::path::{Path, PathBuf};
 
 use crate::{
-    compile::{error::CompilerError, Compiler, Opts},
+    compile::{error::CompilerError, Compiler, Opts, PostFormat},
     util::ttx::{self as test_utils, Report, TestCase, TestResult},
     GlyphMap, GlyphName,
 };
@@ -21,7 +21,13 @@ static IMPORT_RESOLUTION_TEST: &str = "./test-data/include-resolution-tests/dir1
 #[ignore = "disabled so we can use CI"]
 fn fonttools_tests() -> Result<(), Report> {
     test_utils::assert_has_ttx_executable();
-    test_utils::run_all_tests(FONTTOOLS_TESTS, None).into_error()
+    test_utils::run_all_tests(
+        FONTTOOLS_TESTS,
+        None,
+        test_utils::DEFAULT_TABLES,
+        Default::default(),
+    )
+    .into_error()
 }
 
 #[test]
@@ -37,7 +43,12 @@ fn should_fail() -> Result<(), Report> {
 fn import_resolution() {
     let glyph_map = test_utils::make_glyph_map();
     let path = PathBuf::from(IMPORT_RESOLUTION_TEST);
-    match test_utils::run_test(path, &glyph_map) {
+    match test_utils::run_test(
+        path,
+        &glyph_map,
+        test_utils::DEFAULT_TABLES,
+        Default::default(),
+    ) {
         Ok(_) => (),
         Err(e) => panic!("{:?}", e.reason),
     }
@@ -47,11 +58,14 @@ fn import_resolution() {
 fn should_pass() -> Result<(), Report> {
     let mut results = Vec::new();
     for (glyph_map, tests) in iter_test_groups(GOOD_DIR) {
-        results.extend(
-            tests
-                .into_iter()
-                .map(|path| test_utils::run_test(path, &glyph_map)),
-        );
+        results.extend(tests.into_iter().map(|path| {
+            test_utils::run_test(
+                path,
+                &glyph_map,
+                test_utils::DEFAULT_TABLES,
+                Default::default(),
+            )
+        }));
     }
     test_utils::finalize_results(results).into_error()
 }
@@ -93,7 +107,7 @@ fn run_bad_test(path: PathBuf, map: &GlyphMap) -> Result<PathBuf, TestCase> {
 fn bad_test_body(path: &Path, glyph_map: &GlyphMap) -> Result<(), TestResult> {
     match Compiler::new(path, glyph_map)
         .verbose(std::env::var(crate::util::VERBOSE).is_ok())
-        .with_opts(Opts::new().make_post_table(true))
+        .with_opts(Opts::new().make_post_table(PostFormat::Format2))
         .compile_binary()
     {
         Ok(_) => Err(TestResult::UnexpectedSuccess),
@@ -1,6 +1,12 @@
 use std::fmt::Write;
 
-use std::{cell::Cell, ops::Range, sync::Arc};
+use std::{
+    ops::Range,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
 
 use smol_str::SmolStr;
 
@@ -35,7 +41,7 @@ pub struct Node {
     // NOTE: the absolute position within the tree is not known when the node
     // is created; this is updated (and correct) only when the node has been
     // accessed via a `Cursor`.
-    abs_pos: Cell<u32>,
+    abs_pos: AbsPos,
     text_len: u32,
     /// true if an error was encountered in this node.
     ///
@@ -52,7 +58,7 @@ pub struct Token {
     /// The [`Kind`] of this token
     pub kind: Kind,
     /// The absolute position in the source where this token starts
-    abs_pos: Cell<u32>,
+    abs_pos: AbsPos,
     /// The token text
     pub text: SmolStr,
 }
@@ -66,6 +72,15 @@ pub enum NodeOrToken {
     Token(Token),
 }
 
+/// The absolute position of a node or token, updated as the tree is traversed.
+///
+/// This behaves like a `Cell<u32>`, but it can be shared between threads, so
+/// that separate parts of a tree can be compiled in parallel. A position is
+/// only ever set to the value computed by a cursor from the node's parent,
+/// so relaxed ordering is enough.
+#[derive(Default)]
+struct AbsPos(AtomicU32);
+
 #[derive(Clone, Debug, Default)]
 pub(crate) struct TreeBuilder {
     //TODO: reuse tokens
@@ -249,7 +264,7 @@ impl Node {
             kind,
             text_len,
             rel_pos: 0,
-            abs_pos: Cell::new(0),
+            abs_pos: AbsPos::default(),
             children: children.into(),
             error,
         }
@@ -300,6 +315,15 @@ impl Node {
         edit::apply_edits(self, edits, skip_parent)
     }
 
+    /// Create a new node with the children of this node followed by those of
+    /// `other`.
+    ///
+    /// The children themselves are shared, not copied.
+    pub(crate) fn append(&self, other: &Node) -> Node {
+        let children = self.children.iter().chain(other.children.iter()).cloned();
+        Node::new(self.kind, children.collect(), self.error || other.error)
+    }
+
     fn find_include_nodes(&self, collect: &mut Vec<IncludeStatement>, num: usize) {
         for item in self.iter_children() {
             if let Some(node) = item.as_node() {
@@ -519,7 +543,7 @@ impl Token {
         Token {
             kind,
             text,
-            abs_pos: Cell::new(0),
+            abs_pos: AbsPos::default(),
         }
     }
 
@@ -620,3 +644,45 @@ mod tests {
         crate::assert_eq_str!(SAMPLE_FEA, reconstruct);
     }
 }
+
+impl AbsPos {
+    fn get(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, pos: u32) {
+        self.0.store(pos, Ordering::Relaxed)
+    }
+}
+
+impl Clone for AbsPos {
+    fn clone(&self) -> Self {
+        AbsPos(AtomicU32::new(self.get()))
+    }
+}
+
+impl PartialEq for AbsPos {
+    fn eq(&self, other: &Self) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Eq for AbsPos {}
+
+impl PartialOrd for AbsPos {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AbsPos {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.get().cmp(&other.get())
+    }
+}
+
+impl std::fmt::Debug for AbsPos {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.get().fmt(f)
+    }
+}
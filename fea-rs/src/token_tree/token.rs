@@ -31,6 +31,7 @@ pub enum Kind {
     RSquare,
     LParen,
     RParen,
+    Colon,
     LAngle,
     RAngle,
     SingleQuote,
@@ -43,9 +44,12 @@ pub enum Kind {
     LookupKw,
     LanguagesystemKw,
     AnchorDefKw,
+    DeviceDefKw,
     FeatureKw,
     MarkClassKw,
     AnonKw, // 'anon' and 'anonymous'
+    ConditionSetKw,
+    VariationKw,
 
     // other keywords
     AnchorKw,
@@ -209,11 +213,19 @@ pub enum Kind {
     MarkClassNode,
     AnchorNode,
     DeviceNode,
+    VariableMetricNode,
+    LocationValueNode,
+    LocationSpecNode,
+    LocationSpecItemNode,
     AnchorDefNode,
+    DeviceDefNode,
     AnonBlockNode,
     GlyphClassDefNode,
     LanguageSystemNode,
     FeatureNode,
+    ConditionSetNode,
+    ConditionNode,
+    FeatureVariationNode,
     SizeMenuNameNode,
     ParametersNode,
     ScriptNode,
@@ -256,6 +268,8 @@ pub enum Kind {
     StatAxisValueNode,
     StatAxisValueLocationNode,
     StatAxisValueFlagNode,
+    MetaTableNode,
+    MetaRecordNode,
     CvParamsNameNode,
     AaltFeatureNode,
 }
@@ -314,6 +328,7 @@ impl std::fmt::Display for Kind {
             Self::RSquare => write!(f, "]"),
             Self::LParen => write!(f, "("),
             Self::RParen => write!(f, ")"),
+            Self::Colon => write!(f, ":"),
             Self::LAngle => write!(f, "<"),
             Self::RAngle => write!(f, ">"),
             Self::SingleQuote => write!(f, "'"),
@@ -334,9 +349,12 @@ impl std::fmt::Display for Kind {
             Self::LookupKw => write!(f, "LookupKw"),
             Self::LanguagesystemKw => write!(f, "LanguagesystemKw"),
             Self::AnchorDefKw => write!(f, "AnchorDefKw"),
+            Self::DeviceDefKw => write!(f, "DeviceDefKw"),
             Self::FeatureKw => write!(f, "FeatureKw"),
             Self::MarkClassKw => write!(f, "MarkClassKw"),
             Self::AnonKw => write!(f, "AnonKw"),
+            Self::ConditionSetKw => write!(f, "ConditionSetKw"),
+            Self::VariationKw => write!(f, "VariationKw"),
             Self::AnchorKw => write!(f, "AnchorKw"),
             Self::ByKw => write!(f, "ByKw"),
             Self::ContourpointKw => write!(f, "ContourpointKw"),
@@ -464,12 +482,20 @@ impl std::fmt::Display for Kind {
             Self::IncludeNode => write!(f, "IncludeNode"),
             Self::MarkClassNode => write!(f, "MarkClassNode"),
             Self::AnchorDefNode => write!(f, "AnchorDefNode"),
+            Self::DeviceDefNode => write!(f, "DeviceDefNode"),
             Self::AnchorNode => write!(f, "AnchorNode"),
             Self::DeviceNode => write!(f, "DeviceNode"),
+            Self::VariableMetricNode => write!(f, "VariableMetricNode"),
+            Self::LocationValueNode => write!(f, "LocationValueNode"),
+            Self::LocationSpecNode => write!(f, "LocationSpecNode"),
+            Self::LocationSpecItemNode => write!(f, "LocationSpecItemNode"),
             Self::AnonBlockNode => write!(f, "AnonBlockNode"),
             Self::GlyphClassDefNode => write!(f, "GlyphClassDefNode"),
             Self::LanguageSystemNode => write!(f, "LanguageSystemNode"),
             Self::FeatureNode => write!(f, "FeatureNode"),
+            Self::ConditionSetNode => write!(f, "ConditionSetNode"),
+            Self::ConditionNode => write!(f, "ConditionNode"),
+            Self::FeatureVariationNode => write!(f, "FeatureVariationNode"),
             Self::SizeMenuNameNode => write!(f, "SizeMenuNameNode"),
             Self::ParametersNode => write!(f, "ParametersNode"),
             Self::ScriptNode => write!(f, "ScriptNode"),
@@ -494,6 +520,8 @@ impl std::fmt::Display for Kind {
             Self::StatAxisValueNode => write!(f, "StatAxisValueNode"),
             Self::StatAxisValueLocationNode => write!(f, "StatAxisValueLocationNode"),
             Self::StatAxisValueFlagNode => write!(f, "StatAxisValueFlagNode"),
+            Self::MetaTableNode => write!(f, "MetaTableNode"),
+            Self::MetaRecordNode => write!(f, "MetaRecordNode"),
             Self::VmtxEntryNode => write!(f, "VmtxEntryNode"),
             Self::Os2PanoseNode => write!(f, "Os2PanoseNode"),
             Self::Os2UnicodeRangeNode => write!(f, "Os2UnicodeRangeNode"),
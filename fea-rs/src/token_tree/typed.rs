@@ -203,11 +203,15 @@ ast_node!(GlyphClassDef, Kind::GlyphClassDefNode);
 ast_node!(MarkClassDef, Kind::MarkClassNode);
 ast_node!(Anchor, Kind::AnchorNode);
 ast_node!(AnchorDef, Kind::AnchorDefNode);
+ast_node!(DeviceDef, Kind::DeviceDefNode);
 ast_node!(ValueRecordDef, Kind::ValueRecordDefKw);
 ast_node!(GlyphClassLiteral, Kind::GlyphClass);
 ast_node!(LanguageSystem, Kind::LanguageSystemNode);
 ast_node!(Include, Kind::IncludeNode);
 ast_node!(Feature, Kind::FeatureNode);
+ast_node!(ConditionSet, Kind::ConditionSetNode);
+ast_node!(Condition, Kind::ConditionNode);
+ast_node!(FeatureVariation, Kind::FeatureVariationNode);
 ast_node!(Script, Kind::ScriptNode);
 ast_node!(Language, Kind::LanguageNode);
 ast_node!(LookupFlag, Kind::LookupFlagNode);
@@ -215,6 +219,10 @@ ast_node!(LookupRef, Kind::LookupRefNode);
 ast_node!(LookupBlock, Kind::LookupBlockNode);
 ast_node!(ValueRecord, Kind::ValueRecordNode);
 ast_node!(Device, Kind::DeviceNode);
+ast_node!(VariableMetric, Kind::VariableMetricNode);
+ast_node!(VariableMetricValue, Kind::LocationValueNode);
+ast_node!(LocationSpec, Kind::LocationSpecNode);
+ast_node!(LocationSpecItem, Kind::LocationSpecItemNode);
 ast_node!(SizeMenuName, Kind::SizeMenuNameNode);
 ast_node!(Parameters, Kind::ParametersNode);
 ast_node!(FeatureNames, Kind::FeatureNamesKw);
@@ -231,6 +239,7 @@ ast_node!(Os2Table, Kind::Os2TableNode);
 ast_node!(VheaTable, Kind::VheaTableNode);
 ast_node!(VmtxTable, Kind::VmtxTableNode);
 ast_node!(StatTable, Kind::StatTableNode);
+ast_node!(MetaTable, Kind::MetaTableNode);
 ast_node!(UnimplentedTable, Kind::TableNode);
 
 ast_enum!(Table {
@@ -243,6 +252,7 @@ ast_enum!(Table {
     Vhea(VheaTable),
     Vmtx(VmtxTable),
     Stat(StatTable),
+    Meta(MetaTable),
     Other(UnimplentedTable),
 });
 
@@ -256,6 +266,7 @@ ast_node!(VendorRecord, Kind::Os2VendorNode);
 ast_node!(NameRecord, Kind::NameRecordNode);
 ast_node!(NameSpec, Kind::NameSpecNode);
 ast_node!(VmtxEntry, Kind::VmtxEntryNode);
+ast_node!(MetaRecord, Kind::MetaRecordNode);
 
 ast_enum!(DecOctHex {
     Decimal(Number),
@@ -268,6 +279,13 @@ ast_enum!(FloatLike {
     Number(Number),
 });
 
+// a number (or an anchor coordinate), or a number that varies across designspace
+ast_enum!(Scalar {
+    Number(Number),
+    Metric(Metric),
+    Variable(VariableMetric),
+});
+
 ast_node!(GdefClassDef, Kind::GdefClassDefNode);
 ast_node!(GdefClassDefEntry, Kind::GdefClassDefEntryNode);
 ast_node!(GdefAttach, Kind::GdefAttachNode);
@@ -534,20 +552,13 @@ impl AnchorDef {
 }
 
 impl Anchor {
-    pub(crate) fn coords(&self) -> Option<(Metric, Metric)> {
-        let tokens = self.iter();
-        let mut first = None;
-
-        for token in tokens {
-            if let Some(metric) = Metric::cast(token) {
-                if let Some(prev) = first.take() {
-                    return Some((prev, metric));
-                } else {
-                    first = Some(metric);
-                }
-            }
-        }
-        None
+    pub(crate) fn coords(&self) -> Option<(Scalar, Scalar)> {
+        // the contourpoint is a plain number, and is not a coordinate
+        let mut iter = self
+            .iter()
+            .filter_map(Scalar::cast)
+            .filter(|item| !matches!(item, Scalar::Number(_)));
+        Some((iter.next()?, iter.next()?))
     }
 
     pub(crate) fn contourpoint(&self) -> Option<Number> {
@@ -630,6 +641,56 @@ impl Feature {
     }
 }
 
+impl ConditionSet {
+    pub(crate) fn keyword(&self) -> &Token {
+        self.find_token(Kind::ConditionSetKw).unwrap()
+    }
+
+    pub(crate) fn label(&self) -> &Token {
+        self.find_token(Kind::Label).unwrap()
+    }
+
+    pub(crate) fn conditions(&self) -> impl Iterator<Item = Condition> + '_ {
+        self.iter().filter_map(Condition::cast)
+    }
+}
+
+impl Condition {
+    pub(crate) fn tag(&self) -> Tag {
+        self.iter().find_map(Tag::cast).unwrap()
+    }
+
+    pub(crate) fn min(&self) -> FloatLike {
+        self.iter().find_map(FloatLike::cast).unwrap()
+    }
+
+    pub(crate) fn max(&self) -> FloatLike {
+        self.iter().filter_map(FloatLike::cast).nth(1).unwrap()
+    }
+}
+
+impl FeatureVariation {
+    pub(crate) fn keyword(&self) -> &Token {
+        self.find_token(Kind::VariationKw).unwrap()
+    }
+
+    pub(crate) fn tag(&self) -> Tag {
+        self.iter().find_map(Tag::cast).unwrap()
+    }
+
+    pub(crate) fn condition_set(&self) -> &Token {
+        self.find_token(Kind::Label).unwrap()
+    }
+
+    pub(crate) fn statements(&self) -> impl Iterator<Item = &NodeOrToken> {
+        self.iter()
+            .skip_while(|t| t.kind() != Kind::LBrace)
+            .skip(1)
+            .filter(|t| !t.kind().is_trivia())
+            .take_while(|t| t.kind() != Kind::RBrace)
+    }
+}
+
 impl LookupBlock {
     pub(crate) fn tag(&self) -> &Token {
         self.find_token(Kind::Label).unwrap()
@@ -949,8 +1010,8 @@ impl AnchorMark {
 }
 
 impl ValueRecord {
-    pub(crate) fn advance(&self) -> Option<Number> {
-        self.iter().next().and_then(Number::cast)
+    pub(crate) fn advance(&self) -> Option<Scalar> {
+        self.iter().next().and_then(Scalar::cast)
     }
 
     pub(crate) fn null(&self) -> Option<&Token> {
@@ -964,9 +1025,9 @@ impl ValueRecord {
         self.find_token(Kind::Ident)
     }
 
-    pub(crate) fn placement(&self) -> Option<[Number; 4]> {
-        if self.iter().filter(|t| t.kind() == Kind::Number).count() == 4 {
-            let mut iter = self.iter().filter_map(Number::cast);
+    pub(crate) fn placement(&self) -> Option<[Scalar; 4]> {
+        if self.iter().filter_map(Scalar::cast).count() == 4 {
+            let mut iter = self.iter().filter_map(Scalar::cast);
             return Some([
                 iter.next().unwrap(),
                 iter.next().unwrap(),
@@ -991,8 +1052,55 @@ impl ValueRecord {
     }
 }
 
+impl VariableMetric {
+    pub(crate) fn location_values(&self) -> impl Iterator<Item = VariableMetricValue> + '_ {
+        self.iter().filter_map(VariableMetricValue::cast)
+    }
+}
+
+impl VariableMetricValue {
+    pub(crate) fn location(&self) -> LocationSpec {
+        self.iter().find_map(LocationSpec::cast).unwrap()
+    }
+
+    pub(crate) fn value(&self) -> Number {
+        self.iter().find_map(Number::cast).unwrap()
+    }
+}
+
+impl LocationSpec {
+    pub(crate) fn items(&self) -> impl Iterator<Item = LocationSpecItem> + '_ {
+        self.iter().filter_map(LocationSpecItem::cast)
+    }
+}
+
+impl LocationSpecItem {
+    pub(crate) fn axis(&self) -> Tag {
+        self.iter().find_map(Tag::cast).unwrap()
+    }
+
+    pub(crate) fn value(&self) -> FloatLike {
+        self.iter().find_map(FloatLike::cast).unwrap()
+    }
+}
+
+impl DeviceDef {
+    pub(crate) fn device(&self) -> Device {
+        self.iter().find_map(Device::cast).unwrap()
+    }
+
+    pub(crate) fn name(&self) -> &Token {
+        self.find_token(Kind::Ident).expect("pre-validated")
+    }
+}
+
 impl Device {
-    fn null(&self) -> Option<&Token> {
+    /// The name of the referenced device, for `<device NAME>`
+    pub(crate) fn name(&self) -> Option<&Token> {
+        self.find_token(Kind::Ident)
+    }
+
+    pub(crate) fn null(&self) -> Option<&Token> {
         self.iter()
             .take(4)
             .find(|t| t.kind() == Kind::NullKw)
@@ -1158,6 +1266,27 @@ impl VmtxEntry {
     }
 }
 
+impl MetaTable {
+    pub(crate) fn statements(&self) -> impl Iterator<Item = MetaRecord> + '_ {
+        self.iter().filter_map(MetaRecord::cast)
+    }
+}
+
+impl MetaRecord {
+    pub(crate) fn keyword(&self) -> &Token {
+        self.iter().next().and_then(NodeOrToken::as_token).unwrap()
+    }
+
+    /// `true` if this is a `DesignLanguages` record (as opposed to `SupportedLanguages`)
+    pub(crate) fn is_design_languages(&self) -> bool {
+        self.keyword().as_str() == "DesignLanguages"
+    }
+
+    pub(crate) fn value(&self) -> &Token {
+        self.find_token(Kind::String).unwrap()
+    }
+}
+
 impl MetricRecord {
     pub(crate) fn keyword(&self) -> &Token {
         self.iter().next().and_then(|t| t.as_token()).unwrap()
@@ -1169,6 +1298,10 @@ impl MetricRecord {
 }
 
 impl Os2Table {
+    pub(crate) fn tag(&self) -> Tag {
+        self.iter().find_map(Tag::cast).unwrap()
+    }
+
     pub(crate) fn statements(&self) -> impl Iterator<Item = Os2TableItem> + '_ {
         self.iter().filter_map(Os2TableItem::cast)
     }
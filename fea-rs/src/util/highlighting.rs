@@ -18,9 +18,12 @@ pub fn style_for_kind(kind: Kind) -> Style {
         | Kind::LookupKw
         | Kind::LanguagesystemKw
         | Kind::AnchorDefKw
+        | Kind::DeviceDefKw
         | Kind::FeatureKw
         | Kind::MarkClassKw
         | Kind::AnonKw
+        | Kind::ConditionSetKw
+        | Kind::VariationKw
         | Kind::GlyphClassDefKw => Style::new().fg(Colour::Cyan),
         Kind::NamedGlyphClass => Style::new().fg(Colour::Blue).italic(),
         Kind::LookupflagKw | Kind::ScriptKw | Kind::LanguageKw => Colour::Blue.into(),
@@ -45,8 +48,9 @@ pub(crate) fn write_diagnostic(
     err: &Diagnostic,
     source: &Source,
     line_width: Option<usize>,
+    show_kind: bool,
 ) {
-    write_header(writer, err, source);
+    write_header(writer, err, source, show_kind);
 
     let line_width = line_width.unwrap_or(MAX_PRINT_WIDTH);
     let span = err.message.span.range();
@@ -126,11 +130,16 @@ pub(crate) fn write_diagnostic(
     .unwrap();
 }
 
-fn write_header(writer: &mut impl Write, err: &Diagnostic, source: &Source) {
+fn write_header(writer: &mut impl Write, err: &Diagnostic, source: &Source, show_kind: bool) {
     let color = err.level.color();
     let text = err.level.label();
 
-    write!(writer, "{}{}: {}", color.prefix(), text, color.suffix(),).unwrap();
+    if show_kind {
+        let (prefix, suffix) = (color.prefix(), color.suffix());
+        write!(writer, "{prefix}{text}[{}]: {suffix}", err.kind()).unwrap();
+    } else {
+        write!(writer, "{}{}: {}", color.prefix(), text, color.suffix(),).unwrap();
+    }
 
     writeln!(writer, "{}", &err.message.text).unwrap();
     let (line, column) = source.line_col_for_offset(err.message.span.range().start);
@@ -179,6 +188,6 @@ mod tests {
         let source = Source::new("test", A_BAD_LINE.into());
         let err = Diagnostic::warning(source.id(), 200..220, "bad!");
         let mut write_to = String::new();
-        write_diagnostic(&mut write_to, &err, &source, None);
+        write_diagnostic(&mut write_to, &err, &source, None, false);
     }
 }
@@ -47,24 +47,55 @@ fn rebase_path(path: &Path, base: &Path) -> PathBuf {
 /// Given a relative path, resolve it to a specific path per [the spec][].
 ///
 /// The second argument is the root of the project, and the third argument is the
-/// path to the *including* file, if one exists.
+/// path to the *including* file, if one exists. If the path is not found
+/// relative to either of these, each of `include_dirs` is tried in order.
 ///
 /// [the spec]: http://adobe-type-tools.github.io/afdko/OpenTypeFeatureFileSpecification.html#3-including-files
-pub(crate) fn resolve_path(path: &Path, root: &Path, parent: Option<&Path>) -> PathBuf {
+pub(crate) fn resolve_path(
+    path: &Path,
+    root: &Path,
+    parent: Option<&Path>,
+    include_dirs: &[PathBuf],
+) -> PathBuf {
     if path.is_absolute() {
         log::info!("path {} is absolute", path.display());
         return path.to_path_buf();
     }
 
-    if root.join(path).exists() {
-        return rebase_path(path, root);
-    }
-    if let Some(parent) = parent {
-        if parent.join(path).exists() {
-            return rebase_path(path, parent);
-        }
+    search_dirs(root, parent, include_dirs)
+        .find(|dir| dir.join(path).exists())
+        .map(|dir| rebase_path(path, dir))
+        .unwrap_or_else(|| path.to_owned())
+}
+
+/// The locations that [`resolve_path`] checks for `path`, in order.
+///
+/// This is intended for reporting a path that could not be found. If the
+/// including file is in the project root, that location is only listed once.
+pub(crate) fn search_locations(
+    path: &Path,
+    root: &Path,
+    parent: Option<&Path>,
+    include_dirs: &[PathBuf],
+) -> Vec<PathBuf> {
+    if path.is_absolute() {
+        return vec![path.to_path_buf()];
     }
-    path.to_owned()
+    let mut locations = search_dirs(root, parent, include_dirs)
+        .map(|dir| dir.join(path))
+        .collect::<Vec<_>>();
+    locations.dedup();
+    locations
+}
+
+fn search_dirs<'a>(
+    root: &'a Path,
+    parent: Option<&'a Path>,
+    include_dirs: &'a [PathBuf],
+) -> impl Iterator<Item = &'a Path> {
+    std::iter::once(root)
+        .chain(parent)
+        .chain(include_dirs.iter().map(PathBuf::as_path))
 }
 
 #[cfg(test)]
@@ -96,4 +127,28 @@ mod tests {
             Path::new("font/includes/features.fea")
         );
     }
+
+    #[test]
+    fn search_order() {
+        let include_dirs = [PathBuf::from("lib"), PathBuf::from("/shared/fea")];
+        let path = Path::new("kern.fea");
+        assert_eq!(
+            search_locations(
+                path,
+                Path::new("font"),
+                Some(Path::new("font/inc")),
+                &include_dirs
+            ),
+            [
+                Path::new("font/kern.fea"),
+                Path::new("font/inc/kern.fea"),
+                Path::new("lib/kern.fea"),
+                Path::new("/shared/fea/kern.fea"),
+            ]
+        );
+        assert_eq!(
+            search_locations(path, Path::new("font"), Some(Path::new("font")), &[]),
+            [Path::new("font/kern.fea")]
+        );
+    }
 }
@@ -7,13 +7,14 @@ use std::{
     fmt::{Debug, Display, Write},
     path::{Path, PathBuf},
     process::Command,
-    time::SystemTime,
+    sync::{mpsc, Arc},
+    time::{Duration, SystemTime},
 };
 
 use crate::{
     compile::{
         error::{CompilerError, DiagnosticSet},
-        Compiler, Opts,
+        AxisMap, Compiler, Opts, PostFormat, VariationAxis,
     },
     Diagnostic, GlyphIdent, GlyphMap, GlyphName, ParseTree,
 };
@@ -21,6 +22,7 @@ use crate::{
 use ansi_term::Color;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use write_fonts::types::Tag;
 
 static IGNORED_TESTS: &[&str] = &[
     // ## tests with invalid syntax ## //
@@ -34,8 +36,11 @@ static IGNORED_TESTS: &[&str] = &[
     // # tests of variable syntax extension #
     "variable_bug2772.fea",
     "variable_conditionset.fea",
-    "variable_scalar_anchor.fea",
-    "variable_scalar_valuerecord.fea",
+];
+
+/// The tables that are compared with the fonttools output by default.
+pub const DEFAULT_TABLES: &[&str] = &[
+    "head", "name", "BASE", "GDEF", "GSUB", "GPOS", "OS/2", "STAT", "hhea", "vhea",
 ];
 
 /// An environment variable that can be set to specify where to write generated files.
@@ -43,7 +48,26 @@ static IGNORED_TESTS: &[&str] = &[
 /// This can be set during debugging if you want to inspect the generated files.
 static TEMP_DIR_ENV: &str = "TTX_TEMP_DIR";
 
+/// Settings for running tests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TestOptions {
+    /// Keep the generated fonts and ttx files, and print their paths if the
+    /// test fails.
+    ///
+    /// The files are also kept if the `TTX_TEMP_DIR` environment variable is
+    /// set.
+    pub keep_temp_files: bool,
+    /// Report any test that takes longer than this as [`TestResult::Timeout`].
+    ///
+    /// This is only used by [`run_all_tests`] and [`run_test_with_timeout`].
+    pub timeout: Option<Duration>,
+}
+
 /// The combined results of this set of tests
+///
+/// Reports can be saved as JSON with [`Report::write_json`] and compared with
+/// later runs; the field names of this type, [`TestCase`], and [`TestResult`]
+/// are part of that format and should not be changed.
 #[derive(Default, Serialize, Deserialize)]
 pub struct Report {
     /// All of the test cases for this report
@@ -56,6 +80,7 @@ struct ReportSummary {
     panic: u32,
     parse: u32,
     compile: u32,
+    timeout: u32,
     compare: u32,
     other: u32,
     sum_compare_perc: f64,
@@ -88,6 +113,8 @@ pub enum TestResult {
     CompileFail(String),
     /// Compilation succeeded, but shouldn't have
     UnexpectedSuccess,
+    /// The test did not finish in the allotted time
+    Timeout,
     /// A call to the `ttx` utility failed
     #[allow(missing_docs)]
     TtxFail { code: Option<i32>, std_err: String },
@@ -143,13 +170,21 @@ impl<'a> Filter<'a> {
 ///
 /// `filter` is an optional comma-separated list of strings. If present, only
 /// tests which contain one of the strings in the list will be run.
-pub fn run_all_tests(fonttools_data_dir: impl AsRef<Path>, filter: Option<&String>) -> Report {
-    let glyph_map = make_glyph_map();
+///
+/// `tables` is the list of tables that are dumped and compared; this is
+/// usually [`DEFAULT_TABLES`]. See [`TestOptions`] for the other settings.
+pub fn run_all_tests(
+    fonttools_data_dir: impl AsRef<Path>,
+    filter: Option<&String>,
+    tables: &[&str],
+    options: TestOptions,
+) -> Report {
+    let glyph_map = Arc::new(make_glyph_map());
     let filter = Filter::new(filter);
 
     let result = iter_compile_tests(fonttools_data_dir.as_ref(), filter)
         .par_bridge()
-        .map(|path| run_test(path, &glyph_map))
+        .map(|path| run_test_with_timeout(path, &glyph_map, tables, options))
         .collect::<Vec<_>>();
 
     finalize_results(result)
@@ -218,13 +253,31 @@ pub fn try_parse_file(
 }
 
 /// Run the test case at the provided path.
-pub fn run_test(path: PathBuf, glyph_map: &GlyphMap) -> Result<PathBuf, TestCase> {
+///
+/// Only the tables in `tables` are compared with the expected output.
+///
+/// The compiled font and the ttx output are written to a temporary directory
+/// and deleted afterwards, unless [`TestOptions::keep_temp_files`] is set.
+/// The timeout in `options` is not used; see [`run_test_with_timeout`].
+pub fn run_test(
+    path: PathBuf,
+    glyph_map: &GlyphMap,
+    tables: &[&str],
+    options: TestOptions,
+) -> Result<PathBuf, TestCase> {
     match std::panic::catch_unwind(|| {
-        match Compiler::new(&path, glyph_map)
+        let mut compiler = Compiler::new(&path, glyph_map)
             .verbose(std::env::var(super::VERBOSE).is_ok())
-            .with_opts(Opts::new().make_post_table(true))
-            .compile_binary()
-        {
+            .with_opts(
+                Opts::new()
+                    .make_post_table(PostFormat::Format2)
+                    // the expected output was written without the adjustment
+                    .skip_checksum_adjustment(true),
+            );
+        if is_variable_test(&path) {
+            compiler = compiler.with_axis_map(variable_test_axes());
+        }
+        match compiler.compile_binary() {
             // this means we have a test case that doesn't exist or something weird
             Err(CompilerError::SourceLoad(err)) => panic!("{err}"),
             Err(CompilerError::WriteFail(err)) => panic!("{err}"),
@@ -232,7 +285,7 @@ pub fn run_test(path: PathBuf, glyph_map: &GlyphMap) -> Result<PathBuf, TestCase
             Err(CompilerError::ValidationFail(errs) | CompilerError::CompilationFail(errs)) => {
                 Err(TestResult::CompileFail(errs.to_string()))
             }
-            Ok(result) => compare_ttx(&result, &path),
+            Ok(result) => compare_ttx(&result, &path, tables, options.keep_temp_files),
         }
     }) {
         Err(_) => Err(TestResult::Panic),
@@ -242,6 +295,65 @@ pub fn run_test(path: PathBuf, glyph_map: &GlyphMap) -> Result<PathBuf, TestCase
     .map_err(|reason| TestCase { reason, path })
 }
 
+/// `true` if this is one of the fonttools tests of the variable syntax.
+fn is_variable_test(path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .map(|name| name.starts_with("variable_"))
+        .unwrap_or(false)
+}
+
+/// The axes of the font that fonttools uses for the tests of the variable syntax.
+fn variable_test_axes() -> AxisMap {
+    AxisMap::new([
+        VariationAxis::new(Tag::new(b"wght"), 200., 200., 1000.),
+        VariationAxis::new(Tag::new(b"wdth"), 100., 100., 200.),
+    ])
+}
+
+/// Run the test case at the provided path, giving up after the timeout in
+/// `options`.
+///
+/// This is the same as [`run_test`], except that the test is run on its own
+/// thread, and [`TestResult::Timeout`] is returned if it does not finish in
+/// time. The thread cannot be cancelled; it keeps running in the background
+/// until the test completes, or the process exits. If there is no timeout,
+/// this just calls [`run_test`].
+pub fn run_test_with_timeout(
+    path: PathBuf,
+    glyph_map: &Arc<GlyphMap>,
+    tables: &[&str],
+    options: TestOptions,
+) -> Result<PathBuf, TestCase> {
+    let Some(timeout) = options.timeout else {
+        return run_test(path, glyph_map, tables, options);
+    };
+    let glyph_map = glyph_map.clone();
+    let tables = tables.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+    let test_path = path.clone();
+    run_with_timeout(timeout, move || {
+        let tables = tables.iter().map(String::as_str).collect::<Vec<_>>();
+        run_test(test_path, &glyph_map, &tables, options)
+    })
+    .unwrap_or(Err(TestCase {
+        path,
+        reason: TestResult::Timeout,
+    }))
+}
+
+/// Run `f` on a new thread, returning `None` if it takes longer than `timeout`.
+fn run_with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    f: impl FnOnce() -> T + Send + 'static,
+) -> Option<T> {
+    let (send, recv) = mpsc::channel();
+    std::thread::spawn(move || {
+        // the receiver is gone if we've timed out, which is fine
+        let _ = send.send(f());
+    });
+    recv.recv_timeout(timeout).ok()
+}
+
 /// Convert diagnostics to a printable string
 pub fn stringify_diagnostics(root: &ParseTree, diagnostics: &[Diagnostic]) -> String {
     DiagnosticSet {
@@ -279,22 +391,44 @@ fn get_temp_file_name(in_file: &Path) -> PathBuf {
     Path::new(&format!("{stem}_{millis}")).with_extension("ttf")
 }
 
-fn compare_ttx(font_data: &[u8], fea_path: &Path) -> Result<(), TestResult> {
-    let ttx_path = fea_path.with_extension("ttx");
-    let expected_diff_path = fea_path.with_extension("expected_diff");
+fn compare_ttx(
+    font_data: &[u8],
+    fea_path: &Path,
+    tables: &[&str],
+    keep_temp_files: bool,
+) -> Result<(), TestResult> {
     let temp_path = get_temp_dir().join(get_temp_file_name(fea_path));
+    let ttx_out_path = temp_path.with_extension("ttx");
     std::fs::write(&temp_path, font_data).unwrap();
 
-    const TO_WRITE: &[&str] = &[
-        "head", "name", "BASE", "GDEF", "GSUB", "GPOS", "OS/2", "STAT", "hhea", "vhea",
-    ];
+    let result = compare_ttx_impl(&temp_path, fea_path, tables);
+    if keep_temp_files || std::env::var(TEMP_DIR_ENV).is_ok() {
+        if result.is_err() {
+            eprintln!(
+                "{}: font written to {}, ttx to {}",
+                fea_path.display(),
+                temp_path.display(),
+                ttx_out_path.display()
+            );
+        }
+    } else {
+        // the ttx file won't exist if ttx failed
+        let _ = std::fs::remove_file(&temp_path);
+        let _ = std::fs::remove_file(&ttx_out_path);
+    }
+    result
+}
+
+fn compare_ttx_impl(temp_path: &Path, fea_path: &Path, tables: &[&str]) -> Result<(), TestResult> {
+    let ttx_path = fea_path.with_extension("ttx");
+    let expected_diff_path = fea_path.with_extension("expected_diff");
 
     let mut cmd = Command::new("ttx");
-    for table in TO_WRITE {
+    for table in tables {
         cmd.arg("-t").arg(table);
     }
     let status = cmd
-        .arg(&temp_path)
+        .arg(temp_path)
         .output()
         .unwrap_or_else(|_| panic!("failed to execute for path {}", fea_path.display()));
     if !status.status.success() {
@@ -368,19 +502,43 @@ pub fn compare_to_expected_output(
     }
     Ok(())
 }
-// hacky way to make our ttx output match fonttools'
-fn rewrite_ttx(input: &str) -> String {
-    let mut out = String::with_capacity(input.len());
+/// A step in rewriting ttx output, so that our output can be compared to fonttools'.
+///
+/// Each step should make a single targeted change (possibly only to a single
+/// table) so that it can be tested in isolation.
+trait TtxNormalizer {
+    /// Apply this step to the contents of a ttx file.
+    fn normalize(&self, ttx: &str) -> String;
+}
 
-    for line in input.lines() {
-        if line.starts_with("<ttFont") {
-            out.push_str("<ttFont>\n");
-        } else {
-            out.push_str(line);
-            out.push('\n')
+/// Remove the attributes of the root `<ttFont>` element.
+///
+/// These include the version of fonttools, which varies between machines.
+struct StripTtFontAttributes;
+
+impl TtxNormalizer for StripTtFontAttributes {
+    fn normalize(&self, ttx: &str) -> String {
+        let mut out = String::with_capacity(ttx.len());
+        for line in ttx.lines() {
+            if line.starts_with("<ttFont") {
+                out.push_str("<ttFont>\n");
+            } else {
+                out.push_str(line);
+                out.push('\n')
+            }
         }
+        out
     }
-    out
+}
+
+/// The steps applied, in order, to all ttx output before comparison.
+static TTX_NORMALIZERS: &[&(dyn TtxNormalizer + Sync)] = &[&StripTtFontAttributes];
+
+// hacky way to make our ttx output match fonttools'
+fn rewrite_ttx(input: &str) -> String {
+    TTX_NORMALIZERS
+        .iter()
+        .fold(input.to_owned(), |ttx, step| step.normalize(&ttx))
 }
 
 fn write_lines(f: &mut impl Write, lines: &[&str], line_num: usize, prefix: char) {
@@ -517,6 +675,21 @@ impl Report {
         }
     }
 
+    /// Write this report to `path` as JSON.
+    ///
+    /// The report can be loaded with [`Report::from_json`], for instance to
+    /// compare a later run against it with [`Report::compare_printer`].
+    pub fn write_json(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let json = serde_json::to_vec(self)?;
+        std::fs::write(path, json)
+    }
+
+    /// Load a report previously written with [`Report::write_json`].
+    pub fn from_json(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let json = std::fs::read(path)?;
+        Ok(serde_json::from_slice(&json)?)
+    }
+
     /// Return a type that can print comparison results
     pub fn compare_printer<'a, 'b: 'a>(&'b self, old: &'a Report) -> impl std::fmt::Debug + 'a {
         ReportComparePrinter { old, new: self }
@@ -540,6 +713,7 @@ impl Report {
                 TestResult::Panic => summary.panic += 1,
                 TestResult::ParseFail(_) => summary.parse += 1,
                 TestResult::CompileFail(_) => summary.compile += 1,
+                TestResult::Timeout => summary.timeout += 1,
                 TestResult::UnexpectedSuccess | TestResult::TtxFail { .. } => summary.other += 1,
                 TestResult::CompareFail { diff_percent, .. } => {
                     summary.compare += 1;
@@ -558,6 +732,7 @@ impl TestResult {
             Self::Panic => 2,
             Self::ParseFail(_) => 3,
             Self::CompileFail(_) => 4,
+            Self::Timeout => 5,
             Self::UnexpectedSuccess => 6,
             Self::TtxFail { .. } => 10,
             Self::CompareFail { .. } => 50,
@@ -719,6 +894,7 @@ impl Display for ReasonPrinter<'_> {
             TestResult::UnexpectedSuccess => {
                 write!(f, "{}", Color::Yellow.paint("unexpected success"))
             }
+            TestResult::Timeout => write!(f, "{}", Color::Red.paint("timeout")),
             TestResult::TtxFail { code, std_err } => {
                 write!(f, "ttx failure ({:?}) stderr:\n{}", code, std_err)
             }
@@ -751,7 +927,13 @@ impl Debug for TestResult {
 
 impl ReportSummary {
     fn total_items(&self) -> u32 {
-        self.passed + self.panic + self.parse + self.compile + self.compare + self.other
+        self.passed
+            + self.panic
+            + self.parse
+            + self.compile
+            + self.timeout
+            + self.compare
+            + self.other
     }
 
     fn average_diff_percent(&self) -> f64 {
@@ -768,8 +950,74 @@ impl Display for ReportSummary {
             panic,
             parse,
             compile,
+            timeout,
             ..
         } = self;
-        write!(f, "passed {passed}/{total} tests: ({panic} panics {parse} unparsed {compile} compile) {perc:.2}% avg diff")
+        write!(f, "passed {passed}/{total} tests: ({panic} panics {parse} unparsed {compile} compile {timeout} timeouts) {perc:.2}% avg diff")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout() {
+        // the slow task runs until we release it, after it has timed out
+        let (release, wait) = mpsc::channel::<()>();
+        let slow = move || {
+            let _ = wait.recv();
+        };
+        assert!(run_with_timeout(Duration::from_millis(10), slow).is_none());
+        release.send(()).unwrap();
+        assert_eq!(run_with_timeout(Duration::from_secs(10), || 42), Some(42));
+
+        let report = finalize_results(vec![Err(TestCase {
+            path: "slow.fea".into(),
+            reason: TestResult::Timeout,
+        })]);
+        assert!(report.has_failures());
+        assert_eq!(report.summary().timeout, 1);
+    }
+
+    #[test]
+    fn report_json_round_trip() {
+        let report = Report {
+            results: vec![
+                TestCase {
+                    path: "good.fea".into(),
+                    reason: TestResult::Success,
+                },
+                TestCase {
+                    path: "bad.fea".into(),
+                    reason: TestResult::CompareFail {
+                        expected: "<GSUB/>".into(),
+                        result: "<GPOS/>".into(),
+                        diff_percent: 50.0,
+                    },
+                },
+            ],
+        };
+        let path = temp_dir().join(format!("fea-rs-report-{}.json", std::process::id()));
+        report.write_json(&path).unwrap();
+        let loaded = Report::from_json(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.results.len(), 2);
+        for (loaded, expected) in loaded.results.iter().zip(&report.results) {
+            assert_eq!(loaded.path, expected.path);
+            assert!(loaded.reason == expected.reason);
+        }
+    }
+
+    #[test]
+    fn strip_ttfont_attributes() {
+        let ttx = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                   <ttFont sfntVersion=\"\\x00\\x01\\x00\\x00\" ttLibVersion=\"4.38\">\n\
+                   </ttFont>\n";
+        assert_eq!(
+            StripTtFontAttributes.normalize(ttx),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ttFont>\n</ttFont>\n"
+        );
     }
 }